@@ -0,0 +1,63 @@
+/*
+ * C interface to steamclipconverter (see src/ffi.rs).
+ *
+ * Link against the cdylib (libsteamclipconverter.so / steamclipconverter.dll /
+ * libsteamclipconverter.dylib) or the staticlib built by `cargo build --release`.
+ * Strings are NUL-terminated UTF-8. Functions returning int give 0 on success or a
+ * negative SCC_ERR_* code; scc_last_error() then describes the failure on that thread.
+ */
+#ifndef STEAMCLIPCONVERTER_H
+#define STEAMCLIPCONVERTER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SCC_OK 0
+#define SCC_ERR_ARGUMENT -1
+#define SCC_ERR_SCAN -2
+#define SCC_ERR_INVALID_CLIP -3
+#define SCC_ERR_SPAWN -4
+#define SCC_ERR_FFMPEG -5
+#define SCC_ERR_IO -6
+#define SCC_ERR_CANCELLED -7
+#define SCC_ERR_OTHER -8
+
+/* One clip; the strings are owned by the SccClipList it came from. */
+typedef struct SccClip {
+    const char *dir;
+    const char *date; /* YYYYMMDD */
+    const char *time; /* HHMMSS */
+    uint32_t appid;   /* 0: recorded without a game */
+} SccClip;
+
+typedef struct SccClipList SccClipList;
+
+/* percent is 0..100, or negative when the duration is unknown. */
+typedef void (*SccProgressFn)(float percent, void *user_data);
+
+/* Message for the last failed call on this thread, or NULL. Valid until the next call. */
+const char *scc_last_error(void);
+
+/* SCC_ERR_* code of the last failed call on this thread, or SCC_OK if none failed. */
+int scc_last_error_code(void);
+
+/* Find all fg_* clip folders under root. Returns NULL on failure; scc_last_error_code()
+ * tells why (SCC_ERR_ARGUMENT for a bad root, SCC_ERR_SCAN when it can't be read). */
+SccClipList *scc_scan(const char *root);
+size_t scc_clip_list_len(const SccClipList *list);
+int scc_clip_list_get(const SccClipList *list, size_t index, SccClip *out);
+void scc_clip_list_free(SccClipList *list);
+
+/* Convert one fg_* clip folder into out_path. Blocks; progress may be NULL. */
+int scc_convert(const char *clip_dir, const char *out_path, SccProgressFn progress,
+                void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STEAMCLIPCONVERTER_H */
@@ -0,0 +1,241 @@
+"""Find and convert Steam game recordings, through the library's C API.
+
+The functions and error codes are those of ``include/steamclipconverter.h``; the shared
+library is looked for in ``STEAMCLIPCONVERTER_LIB``, next to this file, then on the
+system's library path.
+"""
+
+import ctypes
+import ctypes.util
+import os
+from dataclasses import dataclass
+from datetime import datetime
+from pathlib import Path
+from typing import Callable, List, Optional, Union
+
+__all__ = [
+    "Clip",
+    "scan",
+    "convert",
+    "Error",
+    "ArgumentError",
+    "ScanError",
+    "InvalidClipError",
+    "SpawnError",
+    "FfmpegError",
+    "IoError",
+    "CancelledError",
+]
+
+
+class Error(Exception):
+    """A failed call; ``code`` is the ``SCC_ERR_*`` value."""
+
+    def __init__(self, message: str, code: int):
+        super().__init__(message)
+        self.code = code
+
+
+class ArgumentError(Error):
+    pass
+
+
+class ScanError(Error):
+    pass
+
+
+class InvalidClipError(Error):
+    pass
+
+
+class SpawnError(Error):
+    pass
+
+
+class FfmpegError(Error):
+    pass
+
+
+class IoError(Error):
+    pass
+
+
+class CancelledError(Error):
+    pass
+
+
+_ERRORS = {
+    -1: ArgumentError,
+    -2: ScanError,
+    -3: InvalidClipError,
+    -4: SpawnError,
+    -5: FfmpegError,
+    -6: IoError,
+    -7: CancelledError,
+}
+
+
+class _SccClip(ctypes.Structure):
+    _fields_ = [
+        ("dir", ctypes.c_char_p),
+        ("date", ctypes.c_char_p),
+        ("time", ctypes.c_char_p),
+        ("appid", ctypes.c_uint32),
+    ]
+
+
+_PROGRESS = ctypes.CFUNCTYPE(None, ctypes.c_float, ctypes.c_void_p)
+
+
+def _library_names() -> List[str]:
+    names = []
+    own = os.environ.get("STEAMCLIPCONVERTER_LIB")
+    if own:
+        names.append(own)
+    here = Path(__file__).resolve().parent
+    for file in (
+        "libsteamclipconverter.so",
+        "libsteamclipconverter.dylib",
+        "steamclipconverter.dll",
+    ):
+        if (here / file).is_file():
+            names.append(str(here / file))
+    found = ctypes.util.find_library("steamclipconverter")
+    if found:
+        names.append(found)
+    return names
+
+
+def _load() -> ctypes.CDLL:
+    names = _library_names()
+    for name in names:
+        try:
+            lib = ctypes.CDLL(name)
+            break
+        except OSError:
+            continue
+    else:
+        raise ImportError(
+            "steamclipconverter shared library not found; build it with `cargo build "
+            "--release` and set STEAMCLIPCONVERTER_LIB or copy it next to this package"
+        )
+    lib.scc_last_error.restype = ctypes.c_char_p
+    lib.scc_last_error.argtypes = []
+    lib.scc_last_error_code.restype = ctypes.c_int
+    lib.scc_last_error_code.argtypes = []
+    lib.scc_scan.restype = ctypes.c_void_p
+    lib.scc_scan.argtypes = [ctypes.c_char_p]
+    lib.scc_clip_list_len.restype = ctypes.c_size_t
+    lib.scc_clip_list_len.argtypes = [ctypes.c_void_p]
+    lib.scc_clip_list_get.restype = ctypes.c_int
+    lib.scc_clip_list_get.argtypes = [
+        ctypes.c_void_p,
+        ctypes.c_size_t,
+        ctypes.POINTER(_SccClip),
+    ]
+    lib.scc_clip_list_free.restype = None
+    lib.scc_clip_list_free.argtypes = [ctypes.c_void_p]
+    lib.scc_convert.restype = ctypes.c_int
+    lib.scc_convert.argtypes = [
+        ctypes.c_char_p,
+        ctypes.c_char_p,
+        _PROGRESS,
+        ctypes.c_void_p,
+    ]
+    return lib
+
+
+_lib = _load()
+
+
+def _error(code: int) -> Error:
+    message = _lib.scc_last_error()
+    text = message.decode("utf-8", "replace") if message else "unknown error"
+    return _ERRORS.get(code, Error)(text, code)
+
+
+def _path(p: Union[str, os.PathLike]) -> bytes:
+    # The file system's own bytes; names that aren't UTF-8 get an ArgumentError from the C side
+    # instead of being mangled into a different path here.
+    return os.fsencode(p)
+
+
+@dataclass(frozen=True)
+class Clip:
+    """One recording folder (``fg_<appid>_<date>_<time>`` or ``bg_...``)."""
+
+    dir: Path
+    appid: int
+    #: YYYYMMDD
+    date: str
+    #: HHMMSS
+    time: str
+
+    @property
+    def kind(self) -> str:
+        """``"clip"`` for a saved clip (fg_*), ``"background"`` for background recording."""
+        return "background" if self.dir.name.startswith("bg_") else "clip"
+
+    @property
+    def recorded(self) -> datetime:
+        """Record start as in the folder name (UTC, without a time zone attached)."""
+        return datetime.strptime(self.date + self.time, "%Y%m%d%H%M%S")
+
+    @property
+    def unknown_app(self) -> bool:
+        """Whether Steam recorded it without a game (appid 0)."""
+        return self.appid == 0
+
+
+def scan(root: Union[str, os.PathLike]) -> List[Clip]:
+    """All recording folders under ``root``, sorted by path."""
+    handle = _lib.scc_scan(_path(root))
+    if not handle:
+        raise _error(_lib.scc_last_error_code())
+    try:
+        clips = []
+        for i in range(_lib.scc_clip_list_len(handle)):
+            raw = _SccClip()
+            code = _lib.scc_clip_list_get(handle, i, ctypes.byref(raw))
+            if code != 0:
+                raise _error(code)
+            clips.append(
+                Clip(
+                    dir=Path(raw.dir.decode("utf-8")),
+                    appid=raw.appid,
+                    date=raw.date.decode("utf-8"),
+                    time=raw.time.decode("utf-8"),
+                )
+            )
+        return clips
+    finally:
+        _lib.scc_clip_list_free(handle)
+
+
+def convert(
+    clip: Union[Clip, str, os.PathLike],
+    out_path: Union[str, os.PathLike],
+    progress: Optional[Callable[[Optional[float]], None]] = None,
+) -> None:
+    """Convert ``clip`` (a :class:`Clip` or its folder) into ``out_path`` by stream copy.
+
+    Blocks until ffmpeg finishes; ``progress`` gets the percentage done, or None while
+    it is unknown. An exception it raises is re-raised once the conversion has ended.
+    """
+    folder = clip.dir if isinstance(clip, Clip) else clip
+    raised: List[BaseException] = []
+
+    def on_progress(percent: float, _user_data: Optional[int]) -> None:
+        if progress is None or raised:
+            return
+        try:
+            progress(percent if percent >= 0 else None)
+        except BaseException as e:  # noqa: BLE001 - can't unwind through the C frame
+            raised.append(e)
+
+    callback = _PROGRESS(on_progress)
+    code = _lib.scc_convert(_path(folder), _path(out_path), callback, None)
+    if raised:
+        raise raised[0]
+    if code != 0:
+        raise _error(code)
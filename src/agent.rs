@@ -0,0 +1,556 @@
+//! `agent` subcommand: run on the recording machine (e.g. a Steam Deck) and offer its clips
+//! to `pull` on another machine over HTTP, announced on the local network with mDNS.
+//!
+//! With `--token`/`--scoped-token`/`--basic-auth` every request needs credentials (see
+//! `auth.rs`), and each endpoint a scope; without them anyone on the network can list and
+//! download clips.
+//!
+//! API (JSON unless noted):
+//! - `GET /api/clips`: every clip with its appid, game, timestamps, readiness and files.
+//! - `GET /api/clips/<id>/checksums`: size and SHA-256 of each file of a clip.
+//! - `GET /api/clips/<id>/files/<path>`: one file of a clip (raw bytes; `Range: bytes=N-`
+//!   resumes an interrupted download).
+//! - `GET /` and `GET /preview/<id>`: HTML pages to watch the raw recordings in a browser.
+//! - `DELETE /api/clips/<id>?sha256=<hex>`: delete the clip if its segment digest (see
+//!   `sha256::tree_sha256`) still matches what the puller converted; needs `--allow-delete`.
+//! - `GET /api/events`: server-sent events as clips appear, settle, are fetched and deleted.
+//! - `GET /stats`: the `stats` aggregates of `--catalog`, for dashboards (e.g. Grafana's
+//!   JSON API data source).
+//! - `GET /api/openapi.yaml`: the full contract (`openapi.yaml` in the repository), which
+//!   `steamclipconverter::client` implements.
+
+use crate::{
+    AppNames,
+    auth::{self, Auth, Scope},
+    catalog::Catalog,
+    duration,
+    events::Bus,
+    host,
+    http::{self, Body, Request, Response},
+    json::{self, Value},
+    maybe_remove_clip_grandparent, mdns, preview, resolve_input_dir, sha256,
+    skip::{self},
+    stats::Stats,
+};
+use clap::{ArgAction, Args};
+use filetime::FileTime;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{Seek, SeekFrom},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+use steamclipconverter::{
+    ClipDir, find_fg_clip_dirs,
+    settle::SettleRules,
+    snapshot::ScanSnapshot,
+    steam::{self, KeepReason},
+};
+
+/// Port the agent listens on unless told otherwise, and `pull` connects to.
+pub const DEFAULT_PORT: u16 = 47800;
+
+/// How often the clip folders are checked for `/api/events`.
+const WATCH_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Args, Debug)]
+pub struct AgentArgs {
+    /// Directory to offer (defaults to <SteamRoot>/userdata with a warning).
+    input: Option<PathBuf>,
+
+    /// Address and port to listen on.
+    #[arg(long, value_name = "ADDR", default_value = "0.0.0.0:47800")]
+    listen: String,
+
+    /// Serve plain HTTP on an address other machines can reach (there is no built-in TLS).
+    #[arg(long, action = ArgAction::SetTrue)]
+    insecure: bool,
+
+    /// Don't announce the agent with mDNS (`pull` then needs --from).
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_mdns: bool,
+
+    /// Let pullers delete clips they converted (after checking the segment digest).
+    #[arg(long, action = ArgAction::SetTrue)]
+    allow_delete: bool,
+
+    /// Also let pullers delete clips under PATH, which isn't one of Steam's gamerecordings
+    /// folders (repeatable), as `--allow-delete-under` does for `--delete-after`.
+    #[arg(long, value_name = "PATH")]
+    allow_delete_under: Vec<PathBuf>,
+
+    /// Folders changed more recently count as still being recorded and aren't offered.
+    #[arg(long, value_name = "DURATION", default_value = "30s", value_parser = duration::parse_duration)]
+    settle_time: Duration,
+
+    /// Require this token (`Authorization: Bearer`, or as the basic-auth password).
+    #[arg(long, value_name = "TOKEN", conflicts_with = "token_file")]
+    token: Option<String>,
+
+    /// Read the token from the first line of FILE (keeps it out of the process list).
+    #[arg(long, value_name = "FILE")]
+    token_file: Option<PathBuf>,
+
+    /// Also accept TOKEN with only SCOPE (read, convert or delete); repeatable.
+    #[arg(long, value_name = "SCOPE:TOKEN", value_parser = auth::parse_scoped_token)]
+    scoped_token: Vec<(Scope, String)>,
+
+    /// Require HTTP basic auth with this user and password.
+    #[arg(long, value_name = "USER:PASSWORD")]
+    basic_auth: Option<String>,
+
+    /// Catalog `/stats` summarizes (defaults to the user catalog).
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+}
+
+struct Agent {
+    input_dir: PathBuf,
+    names: AppNames,
+    rules: SettleRules,
+    allow_delete: bool,
+    allow_delete_under: Vec<PathBuf>,
+    auth: Auth,
+    events: Arc<Bus>,
+    catalog: Option<PathBuf>,
+}
+
+/// One file of a clip folder, as listed to pullers.
+pub struct ClipFile {
+    /// `/`-separated, relative to the fg_* folder.
+    pub path: String,
+    pub size: u64,
+    /// Unix seconds, so the pulled copy settles the same way the original did.
+    pub mtime: i64,
+}
+
+/// Every file of a clip folder, sorted by path.
+pub fn clip_files(dir: &Path) -> Vec<ClipFile> {
+    let mut files = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        for ent in fs::read_dir(&d).into_iter().flatten().flatten() {
+            let p = ent.path();
+            if p.is_dir() {
+                stack.push(p);
+            } else if let Ok(meta) = ent.metadata() {
+                let rel: Vec<String> = p
+                    .strip_prefix(dir)
+                    .unwrap_or(&p)
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                files.push(ClipFile {
+                    path: rel.join("/"),
+                    size: meta.len(),
+                    mtime: FileTime::from_last_modification_time(&meta).unix_seconds(),
+                });
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+/// `YYYY-MM-DD HH:MM:SS` of the clip's folder name.
+fn when(clip: &ClipDir) -> String {
+    let (d, t) = (&clip.date, &clip.time);
+    if d.len() == 8 && t.len() == 6 {
+        format!(
+            "{}-{}-{} {}:{}:{}",
+            &d[..4],
+            &d[4..6],
+            &d[6..],
+            &t[..2],
+            &t[2..4],
+            &t[4..]
+        )
+    } else {
+        format!("{d} {t}")
+    }
+}
+
+fn clip_id(clip: &ClipDir) -> String {
+    clip.dir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+impl Agent {
+    fn clips(&self) -> Vec<ClipDir> {
+        let mut clips =
+            find_fg_clip_dirs(&self.input_dir, &mut ScanSnapshot::default()).unwrap_or_default();
+        clips.sort_by(|a, b| (&a.date, &a.time).cmp(&(&b.date, &b.time)));
+        clips
+    }
+
+    fn find(&self, id: &str) -> Option<ClipDir> {
+        self.clips().into_iter().find(|c| clip_id(c) == id)
+    }
+
+    fn handle(&self, req: &Request) -> Response {
+        let Some(granted) = self.auth.granted(req) else {
+            return Response::error(401, "credentials required")
+                .header("WWW-Authenticate", "Basic realm=\"steamclipconverter\"");
+        };
+        let segments = req.segments();
+        let needed = auth::required(&req.method, &segments);
+        if granted < needed {
+            return Response::error(
+                403,
+                &format!("this token lacks the {} scope", needed.name()),
+            );
+        }
+        match (req.method.as_str(), segments.as_slice()) {
+            ("GET" | "HEAD", ["api", "clips"]) => self.list(),
+            ("GET" | "HEAD", ["api", "openapi.yaml"]) => Response::new(
+                200,
+                "application/yaml",
+                include_bytes!("../openapi.yaml").to_vec(),
+            ),
+            ("GET", ["api", "events"]) => self.events.subscribe(),
+            ("GET" | "HEAD", ["stats"]) => self.stats(),
+            ("GET" | "HEAD", ["api", "clips", id, "checksums"]) => {
+                let resp = self.checksums(id);
+                if resp.status == 200 {
+                    // `pull` asks for these right before it downloads a clip.
+                    self.events.publish(
+                        "fetch",
+                        json::obj([
+                            ("id", (*id).into()),
+                            ("by", req.peer.ip().to_string().into()),
+                        ]),
+                    );
+                }
+                resp
+            }
+            ("GET" | "HEAD", ["api", "clips", id, "files", rest @ ..]) if !rest.is_empty() => {
+                self.file(id, &rest.join("/"), req.range_start())
+            }
+            ("DELETE", ["api", "clips", id]) => self.delete(id, req),
+            ("GET" | "HEAD", []) => self.index(),
+            ("GET" | "HEAD", ["preview", id]) => match self.find(id) {
+                Some(clip) => preview::player(id, &self.title(&clip)),
+                None => Response::error(404, "no such clip"),
+            },
+            _ => Response::error(404, "no such endpoint"),
+        }
+    }
+
+    /// Read afresh on every request, so conversions since the agent started show up.
+    fn stats(&self) -> Response {
+        let Some(path) = &self.catalog else {
+            return Response::error(404, "no catalog location; start the agent with --catalog");
+        };
+        match Catalog::open(path) {
+            Ok(c) => Response::json(200, &Stats::collect(&c).to_json()),
+            Err(e) => Response::error(500, &format!("cannot read catalog: {e}")),
+        }
+    }
+
+    fn game(&self, clip: &ClipDir) -> Option<String> {
+        self.names.resolve(clip.appid)
+    }
+
+    fn title(&self, clip: &ClipDir) -> String {
+        format!(
+            "{} {}",
+            self.game(clip).unwrap_or_else(|| clip.appid.to_string()),
+            when(clip)
+        )
+    }
+
+    fn index(&self) -> Response {
+        let rows: Vec<preview::Row> = self
+            .clips()
+            .iter()
+            .rev()
+            .map(|clip| preview::Row {
+                id: clip_id(clip),
+                game: self.game(clip).unwrap_or_else(|| clip.appid.to_string()),
+                when: when(clip),
+                ready: skip::assess(clip, &self.rules).is_ok(),
+            })
+            .collect();
+        preview::index(&host::machine_tag(), &rows)
+    }
+
+    fn list(&self) -> Response {
+        let clips: Vec<Value> = self
+            .clips()
+            .iter()
+            .map(|clip| {
+                let verdict = skip::assess(clip, &self.rules);
+                let files = clip_files(&clip.dir)
+                    .into_iter()
+                    .map(|f| {
+                        json::obj([
+                            ("path", f.path.into()),
+                            ("size", f.size.into()),
+                            ("mtime", (f.mtime as f64).into()),
+                        ])
+                    })
+                    .collect();
+                json::obj([
+                    ("id", clip_id(clip).into()),
+                    ("appid", clip.appid.into()),
+                    ("game", self.game(clip).into()),
+                    ("date", clip.date.as_str().into()),
+                    ("time", clip.time.as_str().into()),
+                    ("ready", verdict.is_ok().into()),
+                    ("reason", verdict.err().map(|r| r.code()).into()),
+                    ("files", Value::Arr(files)),
+                ])
+            })
+            .collect();
+        Response::json(
+            200,
+            &json::obj([
+                ("agent", "steamclipconverter".into()),
+                ("version", 1u32.into()),
+                ("host", host::machine_tag().into()),
+                ("clips", Value::Arr(clips)),
+            ]),
+        )
+    }
+
+    fn checksums(&self, id: &str) -> Response {
+        let Some(clip) = self.find(id) else {
+            return Response::error(404, "no such clip");
+        };
+        let mut files = Vec::new();
+        for f in clip_files(&clip.dir) {
+            match sha256::file_sha256(&clip.dir.join(&f.path)) {
+                Ok(sum) => files.push(json::obj([
+                    ("path", f.path.into()),
+                    ("size", f.size.into()),
+                    ("sha256", sum.into()),
+                ])),
+                Err(e) => return Response::error(500, &format!("cannot hash {}: {e}", f.path)),
+            }
+        }
+        Response::json(200, &json::obj([("files", Value::Arr(files))]))
+    }
+
+    fn file(&self, id: &str, rel: &str, from: Option<u64>) -> Response {
+        let Some(clip) = self.find(id) else {
+            return Response::error(404, "no such clip");
+        };
+        // Only files listed for the clip are served, so `..` and the like go nowhere.
+        let Some(listed) = clip_files(&clip.dir).into_iter().find(|f| f.path == rel) else {
+            return Response::error(404, "no such file");
+        };
+        let mut file = match File::open(clip.dir.join(rel)) {
+            Ok(f) => f,
+            Err(e) => return Response::error(500, &e.to_string()),
+        };
+        let size = listed.size;
+        let resp = match from {
+            None => Response {
+                status: 200,
+                headers: Vec::new(),
+                body: Body::File { file, len: size },
+            },
+            Some(start) if start < size => {
+                if let Err(e) = file.seek(SeekFrom::Start(start)) {
+                    return Response::error(500, &e.to_string());
+                }
+                Response {
+                    status: 206,
+                    headers: Vec::new(),
+                    body: Body::File {
+                        file,
+                        len: size - start,
+                    },
+                }
+                .header(
+                    "Content-Range",
+                    &format!("bytes {start}-{}/{size}", size - 1),
+                )
+            }
+            Some(_) => {
+                return Response::error(416, "range past the end of the file")
+                    .header("Content-Range", &format!("bytes */{size}"));
+            }
+        };
+        // Lets web players on other origins stream the segments too.
+        resp.header("Content-Type", preview::content_type(rel))
+            .header("Accept-Ranges", "bytes")
+            .header("Access-Control-Allow-Origin", "*")
+    }
+
+    fn delete(&self, id: &str, req: &Request) -> Response {
+        if !self.allow_delete {
+            return Response::error(403, "this agent was started without --allow-delete");
+        }
+        let Some(expected) = req.query("sha256") else {
+            return Response::error(400, "sha256 of the converted segments is required");
+        };
+        let Some(clip) = self.find(id) else {
+            return Response::error(404, "no such clip");
+        };
+        // The guard of --delete-after: the digest match below is the hash check.
+        if steam::delete_check(&clip.dir, &self.allow_delete_under, None)
+            == Err(KeepReason::OutsideRecordings)
+        {
+            return Response::error(
+                403,
+                "not in a Steam gamerecordings folder or under --allow-delete-under",
+            );
+        }
+        match sha256::tree_sha256(&clip.dir) {
+            Ok(d) if d.sha256.eq_ignore_ascii_case(expected) => {}
+            Ok(_) => return Response::error(409, "segments differ from the converted copy"),
+            Err(e) => return Response::error(500, &format!("cannot hash: {e}")),
+        }
+        if let Err(e) = fs::remove_dir_all(&clip.dir) {
+            return Response::error(500, &format!("delete failed: {e}"));
+        }
+        println!(
+            "[del] removed {} (pulled by {} and verified)",
+            clip.dir.display(),
+            req.peer.ip()
+        );
+        maybe_remove_clip_grandparent(&clip);
+        self.events.publish(
+            "deleted",
+            json::obj([("id", id.into()), ("by", req.peer.ip().to_string().into())]),
+        );
+        Response::json(200, &json::obj([("deleted", id.into())]))
+    }
+}
+
+/// Publish clips appearing, becoming ready and disappearing, checking every few seconds.
+fn watch(input_dir: &Path, rules: &SettleRules, events: &Bus) {
+    let mut known: HashMap<String, bool> = HashMap::new();
+    let mut snapshot = ScanSnapshot::default();
+    loop {
+        let clips = find_fg_clip_dirs(input_dir, &mut snapshot).unwrap_or_default();
+        let mut seen = HashMap::new();
+        for clip in &clips {
+            let id = clip_id(clip);
+            let ready = skip::assess(clip, rules).is_ok();
+            match known.get(&id) {
+                None => events.publish(
+                    "clip-added",
+                    json::obj([
+                        ("id", id.as_str().into()),
+                        ("appid", clip.appid.into()),
+                        ("date", clip.date.as_str().into()),
+                        ("time", clip.time.as_str().into()),
+                        ("ready", ready.into()),
+                    ]),
+                ),
+                Some(false) if ready => {
+                    events.publish("clip-ready", json::obj([("id", id.as_str().into())]))
+                }
+                _ => {}
+            }
+            seen.insert(id, ready);
+        }
+        for id in known.keys().filter(|id| !seen.contains_key(*id)) {
+            events.publish("clip-removed", json::obj([("id", id.as_str().into())]));
+        }
+        known = seen;
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+pub fn run(args: AgentArgs) -> i32 {
+    let token = match &args.token_file {
+        Some(path) => match auth::read_token_file(path) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                eprintln!("ERROR: cannot read --token-file: {}", e);
+                return 2;
+            }
+        },
+        None => args.token,
+    };
+    let auth = match Auth::new(token, args.scoped_token, args.basic_auth.as_deref()) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return 2;
+        }
+    };
+    let agent = Agent {
+        input_dir: resolve_input_dir(args.input),
+        names: AppNames::discover(),
+        rules: SettleRules {
+            settle_time: args.settle_time,
+            ..SettleRules::default()
+        },
+        allow_delete: args.allow_delete,
+        allow_delete_under: args.allow_delete_under,
+        auth,
+        events: Arc::new(Bus::default()),
+        catalog: args.catalog.or_else(Catalog::default_path),
+    };
+    let watcher = Arc::clone(&agent.events);
+    let (dir, rules) = (agent.input_dir.clone(), agent.rules);
+    thread::spawn(move || watch(&dir, &rules, &watcher));
+    let listener = match TcpListener::bind(&args.listen) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("ERROR: cannot listen on {}: {}", args.listen, e);
+            return 2;
+        }
+    };
+    let local = listener.local_addr();
+    let port = local.as_ref().map(|a| a.port()).unwrap_or(DEFAULT_PORT);
+    if !args.insecure && local.as_ref().is_ok_and(|a| !a.ip().is_loopback()) {
+        eprintln!(
+            "ERROR: refusing to serve {} over plain HTTP: tokens and clips would cross the network unencrypted. Pass --insecure on a trusted LAN, or listen on a loopback address (e.g. --listen 127.0.0.1:{}) behind a TLS reverse proxy.",
+            args.listen, port
+        );
+        return 2;
+    }
+    if agent.auth.is_open() && local.is_ok_and(|a| !a.ip().is_loopback()) {
+        eprintln!(
+            "[warn] no tokens or --basic-auth: anyone on the network can download{} clips",
+            if agent.allow_delete {
+                " and delete"
+            } else {
+                ""
+            }
+        );
+    }
+    let _announcement = if args.no_mdns {
+        None
+    } else {
+        let name = format!("steamclipconverter on {}", host::machine_tag());
+        match mdns::advertise(&name, port) {
+            Ok(a) => Some(a),
+            Err(e) => {
+                eprintln!(
+                    "[warn] cannot announce with mDNS ({}); pull needs --from",
+                    e
+                );
+                None
+            }
+        }
+    };
+    println!(
+        "[agent] offering clips under {} on port {}{}",
+        agent.input_dir.display(),
+        port,
+        if agent.allow_delete {
+            "; pullers may delete converted clips"
+        } else {
+            ""
+        }
+    );
+    match http::serve(listener, move |req| agent.handle(req)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            1
+        }
+    }
+}
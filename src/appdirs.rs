@@ -0,0 +1,75 @@
+//! Per-user directories for this tool's own state (snapshots, catalog) and config.
+
+use std::path::PathBuf;
+
+/// Cache directory: safe to delete, only makes things faster.
+pub fn user_cache_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        let home = std::env::var("HOME").ok()?;
+        Some(PathBuf::from(format!(
+            "{home}/Library/Caches/steamclipconverter"
+        )))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let local = std::env::var("LOCALAPPDATA").ok()?;
+        Some(PathBuf::from(format!(r"{local}\steamclipconverter\cache")))
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        if let Ok(xdg) = std::env::var("XDG_CACHE_HOME")
+            && !xdg.is_empty()
+        {
+            return Some(PathBuf::from(xdg).join("steamclipconverter"));
+        }
+        let home = std::env::var("HOME").ok()?;
+        Some(PathBuf::from(format!("{home}/.cache/steamclipconverter")))
+    }
+}
+
+/// Data directory: state worth keeping, like the conversion catalog.
+pub fn user_data_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        let home = std::env::var("HOME").ok()?;
+        Some(PathBuf::from(format!(
+            "{home}/Library/Application Support/steamclipconverter"
+        )))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let appdata = std::env::var("APPDATA").ok()?;
+        Some(PathBuf::from(format!(r"{appdata}\steamclipconverter")))
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        if let Ok(xdg) = std::env::var("XDG_DATA_HOME")
+            && !xdg.is_empty()
+        {
+            return Some(PathBuf::from(xdg).join("steamclipconverter"));
+        }
+        let home = std::env::var("HOME").ok()?;
+        Some(PathBuf::from(format!(
+            "{home}/.local/share/steamclipconverter"
+        )))
+    }
+}
+
+/// Config directory: files the user writes, like `config.toml`.
+pub fn user_config_dir() -> Option<PathBuf> {
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    {
+        user_data_dir()
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        if let Ok(xdg) = std::env::var("XDG_CONFIG_HOME")
+            && !xdg.is_empty()
+        {
+            return Some(PathBuf::from(xdg).join("steamclipconverter"));
+        }
+        let home = std::env::var("HOME").ok()?;
+        Some(PathBuf::from(format!("{home}/.config/steamclipconverter")))
+    }
+}
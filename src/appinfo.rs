@@ -0,0 +1,152 @@
+//! Game names from Steam's `appcache/appinfo.vdf`, which keeps the store metadata of every
+//! app the account has seen, installed or not: the name of an uninstalled game is still
+//! there after its appmanifest is gone.
+//!
+//! The file is binary VDF: a header, then one entry per app (appid, size, change info and
+//! hashes, then a key/value tree) until an appid of 0. Since format 29 (2024) keys are
+//! indices into a string table at the end of the file. Only `appinfo/common/name` is read;
+//! an entry that doesn't parse is skipped, not an error.
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+const MAGIC_27: u32 = 0x0756_4427;
+const MAGIC_28: u32 = 0x0756_4428;
+const MAGIC_29: u32 = 0x0756_4429;
+
+/// Names of the apps in the appinfo.vdf at `path`.
+pub fn read_names(path: &Path) -> io::Result<HashMap<u32, String>> {
+    let bytes = fs::read(path)?;
+    parse_names(&bytes)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a Steam appinfo.vdf"))
+}
+
+/// Names in appinfo.vdf contents, or None when the header isn't one this reads.
+pub fn parse_names(bytes: &[u8]) -> Option<HashMap<u32, String>> {
+    let mut r = Reader { bytes, pos: 0 };
+    let magic = r.u32()?;
+    // After the change number: the hash of the text form, and since 28 of the binary form.
+    let entry_header = match magic {
+        MAGIC_27 => 4 + 4 + 8 + 20 + 4,
+        MAGIC_28 | MAGIC_29 => 4 + 4 + 8 + 20 + 4 + 20,
+        _ => return None,
+    };
+    r.u32()?; // universe
+    let keys = if magic == MAGIC_29 {
+        let offset = usize::try_from(r.u64()?).ok()?;
+        Some(string_table(bytes.get(offset..)?)?)
+    } else {
+        None
+    };
+
+    let mut names = HashMap::new();
+    loop {
+        let appid = r.u32()?;
+        if appid == 0 {
+            break;
+        }
+        let size = r.u32()? as usize;
+        let entry = r.take(size)?;
+        let mut e = Reader {
+            bytes: entry.get(entry_header..).unwrap_or_default(),
+            pos: 0,
+        };
+        let mut name = None;
+        if e.section(keys.as_deref(), &mut Vec::new(), &mut name)
+            .is_some()
+            && let Some(name) = name.filter(|n| !n.trim().is_empty())
+        {
+            names.insert(appid, name.trim().to_string());
+        }
+    }
+    Some(names)
+}
+
+/// The key strings of a format 29 file: a count, then that many NUL-terminated strings.
+fn string_table(bytes: &[u8]) -> Option<Vec<String>> {
+    let mut r = Reader { bytes, pos: 0 };
+    let count = r.u32()?;
+    (0..count).map(|_| r.cstr()).collect()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let out = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(out)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn cstr(&mut self) -> Option<String> {
+        let rest = self.bytes.get(self.pos..)?;
+        let len = rest.iter().position(|&b| b == 0)?;
+        self.pos += len + 1;
+        Some(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    fn key(&mut self, keys: Option<&[String]>) -> Option<String> {
+        match keys {
+            Some(keys) => keys.get(self.u32()? as usize).cloned(),
+            None => self.cstr(),
+        }
+    }
+
+    /// Walk the key/values up to the end of the current section, keeping the string at
+    /// `appinfo/common/name` in `name`.
+    fn section(
+        &mut self,
+        keys: Option<&[String]>,
+        path: &mut Vec<String>,
+        name: &mut Option<String>,
+    ) -> Option<()> {
+        loop {
+            let kind = self.u8()?;
+            // End of section (0x0b in some older files).
+            if kind == 0x08 || kind == 0x0b {
+                return Some(());
+            }
+            let key = self.key(keys)?;
+            match kind {
+                0x00 => {
+                    path.push(key);
+                    self.section(keys, path, name)?;
+                    path.pop();
+                }
+                0x01 => {
+                    let value = self.cstr()?;
+                    if key == "name"
+                        && path.len() == 2
+                        && path[0] == "appinfo"
+                        && path[1] == "common"
+                    {
+                        *name = Some(value);
+                    }
+                }
+                // int32, float, pointer, color
+                0x02 | 0x03 | 0x04 | 0x06 => {
+                    self.take(4)?;
+                }
+                // uint64, int64
+                0x07 | 0x0a => {
+                    self.take(8)?;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Converting straight from a backup archive (`--input backup.tar.zst`, `.tar.gz`, `.zip`):
+//! the archive is listed once, and each clip's fg_* folder is extracted to a staging folder
+//! just before it is converted and removed right after, so only a few clips are ever on disk.
+//!
+//! Extraction uses the system `tar` (GNU tar or bsdtar, which picks the compression itself;
+//! zstd archives need `zstd` installed for GNU tar). Zip files go through `unzip` where it
+//! exists and bsdtar otherwise, as on Windows and macOS.
+
+use crate::staged::StagedInput;
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use steamclipconverter::{ClipDir, snapshot::fnv1a64};
+
+/// File names treated as archives rather than a folder to scan.
+const EXTENSIONS: &[&str] = &[
+    ".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.bz2", ".tar.zst", ".tzst", ".zip",
+];
+
+pub fn is_archive(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    path.is_file() && EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+pub struct Archive {
+    path: PathBuf,
+    staging: PathBuf,
+    /// Member paths of the fg_* folders, as stored in the archive.
+    members: Vec<String>,
+}
+
+fn run(cmd: &mut Command) -> io::Result<String> {
+    let out = cmd.output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "{:?} failed: {}",
+            cmd.get_program(),
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// A member path without the `./` tar puts in front when an archive was made from `.`.
+fn tidy(member: &str) -> &str {
+    member.trim_start_matches("./")
+}
+
+/// `unzip` on PATH? (Its absence means the `tar` is bsdtar, or zips can't be read at all.)
+fn has_unzip() -> bool {
+    Command::new("unzip").arg("-v").output().is_ok()
+}
+
+impl Archive {
+    /// List `path` and find the clip folders in it.
+    pub fn open(path: &Path) -> io::Result<Archive> {
+        let path = std::path::absolute(path)?;
+        let is_zip = path.to_string_lossy().to_lowercase().ends_with(".zip");
+        let listing = if is_zip && has_unzip() {
+            run(Command::new("unzip").arg("-Z1").arg(&path))?
+        } else {
+            run(Command::new("tar").arg("-tf").arg(&path))?
+        };
+        let mut members: Vec<String> = listing
+            .lines()
+            .filter_map(|entry| {
+                // Keep the member's own spelling (`./`, separators) so extraction matches it.
+                let parts: Vec<&str> = entry.split('/').collect();
+                let i = parts
+                    .iter()
+                    .position(|p| ClipDir::from_path(Path::new(p)).is_some())?;
+                Some(parts[..=i].join("/"))
+            })
+            .collect();
+        members.sort();
+        members.dedup();
+        let key = fnv1a64(path.to_string_lossy().as_bytes());
+        Ok(Archive {
+            staging: env::temp_dir().join(format!("steamclipconverter-archive-{key:016x}")),
+            path,
+            members,
+        })
+    }
+
+    fn member(&self, clip: &ClipDir) -> Option<&str> {
+        self.members
+            .iter()
+            .find(|m| self.staging.join(m) == clip.dir)
+            .map(String::as_str)
+    }
+}
+
+impl StagedInput for Archive {
+    fn origin(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn clips(&self) -> Vec<ClipDir> {
+        self.members
+            .iter()
+            .filter_map(|m| ClipDir::from_path(&self.staging.join(tidy(m))))
+            .collect()
+    }
+
+    /// Unpack one clip's fg_* folder into the staging folder.
+    fn fetch(&self, clip: &ClipDir) -> io::Result<()> {
+        let member = self
+            .member(clip)
+            .ok_or_else(|| io::Error::other("not a clip of this archive"))?;
+        fs::create_dir_all(&self.staging)?;
+        let is_zip = self.path.to_string_lossy().to_lowercase().ends_with(".zip");
+        if is_zip && has_unzip() {
+            run(Command::new("unzip")
+                .args(["-q", "-o"])
+                .arg(&self.path)
+                .arg(format!("{member}/*"))
+                .arg("-d")
+                .arg(&self.staging))?;
+        } else {
+            run(Command::new("tar")
+                .arg("-xf")
+                .arg(&self.path)
+                .arg("-C")
+                .arg(&self.staging)
+                .arg(member))?;
+        }
+        Ok(())
+    }
+
+    /// Not the staging folder that is about to disappear, but the folder inside the archive.
+    fn source_of(&self, clip: &ClipDir) -> ClipDir {
+        let dir = match self.member(clip) {
+            Some(m) => self.path.join(tidy(m)),
+            None => clip.dir.clone(),
+        };
+        ClipDir {
+            dir,
+            ..clip.clone()
+        }
+    }
+
+    fn release(&self, clip: &ClipDir) {
+        if clip.dir.starts_with(&self.staging) {
+            let _ = fs::remove_dir_all(&clip.dir);
+        }
+    }
+}
+
+impl Drop for Archive {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.staging);
+    }
+}
@@ -0,0 +1,63 @@
+//! Crash-safe file writes: state files and outputs are written under a temporary name,
+//! flushed to disk and renamed over the real one, so a power loss leaves either the old
+//! file or the new one, never half of one.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Replace `path` with `contents`.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let written = File::create(&tmp).and_then(|mut f| {
+        f.write_all(contents.as_ref())?;
+        f.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    commit_synced(&tmp, path)
+}
+
+/// Name a file is written under until it is complete: `Game-....partial.mp4` next to
+/// `path`, keeping the extension so ffmpeg picks the same container.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.partial.{}", ext.to_string_lossy()),
+        None => format!("{stem}.partial"),
+    };
+    path.with_file_name(name)
+}
+
+/// Flush the finished `partial` to disk and move it to `path`.
+pub fn commit(partial: &Path, path: &Path) -> io::Result<()> {
+    File::open(partial)?.sync_all()?;
+    commit_synced(partial, path)
+}
+
+fn commit_synced(tmp: &Path, path: &Path) -> io::Result<()> {
+    fs::rename(tmp, path)?;
+    sync_dir(path.parent().unwrap_or(Path::new(".")));
+    Ok(())
+}
+
+/// Flush a directory entry change (a rename or a new file) to disk. Only Unix can open a
+/// directory for this; elsewhere the rename is left to the filesystem.
+pub fn sync_dir(dir: &Path) {
+    if cfg!(unix) {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        if let Ok(d) = File::open(dir) {
+            let _ = d.sync_all();
+        }
+    }
+}
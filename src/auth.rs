@@ -0,0 +1,276 @@
+//! Who may talk to the agent: tokens (`Authorization: Bearer`, or as the password of HTTP
+//! basic auth so browsers can open the preview pages) and/or a basic-auth user.
+//!
+//! Each token carries a [`Scope`]; `--token` and the basic-auth user get everything.
+
+use clap::ValueEnum;
+use std::{fs, io, path::Path};
+use steamclipconverter::http::{Request, base64_decode};
+
+/// What a token may do; each scope includes the ones before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Scope {
+    /// List clips (the API listing and the index page).
+    Read,
+    /// Also download segments, to convert them elsewhere or preview them.
+    Convert,
+    /// Also delete clips (the agent still needs --allow-delete).
+    Delete,
+}
+
+impl Scope {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Convert => "convert",
+            Scope::Delete => "delete",
+        }
+    }
+}
+
+/// The scope a request needs: listings and stats read, downloads and previews convert,
+/// anything deleting delete.
+pub fn required(method: &str, segments: &[&str]) -> Scope {
+    match (method, segments) {
+        ("DELETE", _) => Scope::Delete,
+        (_, ["api", "clips" | "openapi.yaml" | "events"] | ["stats"] | []) => Scope::Read,
+        _ => Scope::Convert,
+    }
+}
+
+/// `--scoped-token SCOPE:TOKEN`
+pub fn parse_scoped_token(s: &str) -> Result<(Scope, String), String> {
+    let (scope, token) = s
+        .split_once(':')
+        .ok_or("expected SCOPE:TOKEN, e.g. read:abc123")?;
+    let scope = Scope::from_str(scope, true)
+        .map_err(|_| format!("unknown scope {scope:?} (read, convert or delete)"))?;
+    if token.is_empty() {
+        return Err("empty token".into());
+    }
+    Ok((scope, token.to_string()))
+}
+
+#[derive(Default)]
+pub struct Auth {
+    tokens: Vec<(Scope, String)>,
+    basic: Option<(String, String)>,
+}
+
+/// Comparison whose time doesn't depend on where the inputs first differ.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// A token written by hand or generated into a file: the first line, trimmed.
+pub fn read_token_file(path: &Path) -> io::Result<String> {
+    let text = fs::read_to_string(path)?;
+    let token = text.lines().next().unwrap_or("").trim().to_string();
+    if token.is_empty() {
+        return Err(io::Error::other(format!("{} is empty", path.display())));
+    }
+    Ok(token)
+}
+
+impl Auth {
+    /// `token` gets every scope, `scoped` what each names.
+    pub fn new(
+        token: Option<String>,
+        scoped: Vec<(Scope, String)>,
+        basic: Option<&str>,
+    ) -> Result<Auth, String> {
+        let basic = match basic {
+            Some(b) => match b.split_once(':') {
+                Some((u, p)) if !u.is_empty() && !p.is_empty() => Some((u.into(), p.into())),
+                _ => return Err("--basic-auth needs USER:PASSWORD".into()),
+            },
+            None => None,
+        };
+        let mut tokens = scoped;
+        tokens.extend(token.map(|t| (Scope::Delete, t)));
+        Ok(Auth { tokens, basic })
+    }
+
+    /// No credentials configured: every request is let in.
+    pub fn is_open(&self) -> bool {
+        self.tokens.is_empty() && self.basic.is_none()
+    }
+
+    /// Widest scope among the tokens matching `secret`.
+    fn token_scope(&self, secret: &str) -> Option<Scope> {
+        self.tokens
+            .iter()
+            .filter(|(_, t)| same(t, secret))
+            .map(|(scope, _)| *scope)
+            .max()
+    }
+
+    /// What the request's credentials allow; `None` when they are missing or wrong.
+    pub fn granted(&self, req: &Request) -> Option<Scope> {
+        if self.is_open() {
+            return Some(Scope::Delete);
+        }
+        let header = req.header("Authorization")?;
+        let (scheme, cred) = header.trim().split_once(' ').unwrap_or((header, ""));
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return self.token_scope(cred.trim());
+        }
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+        let decoded = base64_decode(cred.trim()).and_then(|b| String::from_utf8(b).ok())?;
+        let (user, pass) = decoded.split_once(':').unwrap_or((&decoded, ""));
+        if self
+            .basic
+            .as_ref()
+            .is_some_and(|(u, p)| same(u, user) & same(p, pass))
+        {
+            return Some(Scope::Delete);
+        }
+        self.token_scope(pass)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use steamclipconverter::http::base64_encode;
+
+    fn request(authorization: Option<String>) -> Request {
+        Request {
+            method: "GET".into(),
+            path: "/".into(),
+            query: Vec::new(),
+            headers: authorization
+                .map(|a| ("authorization".to_string(), a))
+                .into_iter()
+                .collect(),
+            peer: "127.0.0.1:1".parse().unwrap(),
+        }
+    }
+
+    fn bearer(token: &str) -> Request {
+        request(Some(format!("Bearer {token}")))
+    }
+
+    fn basic(user: &str, pass: &str) -> Request {
+        request(Some(format!(
+            "Basic {}",
+            base64_encode(format!("{user}:{pass}").as_bytes())
+        )))
+    }
+
+    fn auth() -> Auth {
+        Auth::new(
+            Some("full".into()),
+            vec![
+                (Scope::Read, "r".into()),
+                (Scope::Convert, "c".into()),
+                (Scope::Delete, "d".into()),
+            ],
+            Some("sam:pw"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn scopes_include_the_ones_before() {
+        assert!(Scope::Read < Scope::Convert && Scope::Convert < Scope::Delete);
+    }
+
+    #[test]
+    fn routes_need_their_scope() {
+        let routes: [(&str, &[&str], Scope); 10] = [
+            ("GET", &[], Scope::Read),
+            ("GET", &["api", "clips"], Scope::Read),
+            ("GET", &["api", "openapi.yaml"], Scope::Read),
+            ("GET", &["api", "events"], Scope::Read),
+            ("HEAD", &["stats"], Scope::Read),
+            (
+                "GET",
+                &["api", "clips", "fg_1_2_3", "checksums"],
+                Scope::Convert,
+            ),
+            (
+                "GET",
+                &["api", "clips", "fg_1_2_3", "files", "a.m4s"],
+                Scope::Convert,
+            ),
+            ("GET", &["preview", "fg_1_2_3"], Scope::Convert),
+            ("GET", &["unknown"], Scope::Convert),
+            ("DELETE", &["api", "clips", "fg_1_2_3"], Scope::Delete),
+        ];
+        let auth = auth();
+        for (method, segments, needed) in routes {
+            assert_eq!(required(method, segments), needed, "{method} {segments:?}");
+            for (token, scope) in [
+                ("r", Scope::Read),
+                ("c", Scope::Convert),
+                ("d", Scope::Delete),
+            ] {
+                let granted = auth.granted(&bearer(token)).unwrap();
+                assert_eq!(granted, scope);
+                assert_eq!(
+                    granted >= needed,
+                    scope >= needed,
+                    "{token} on {method} {segments:?}"
+                );
+            }
+        }
+        // DELETE needs the delete scope whatever the path.
+        assert_eq!(required("DELETE", &["api", "clips"]), Scope::Delete);
+    }
+
+    #[test]
+    fn tokens_and_basic_auth() {
+        let auth = auth();
+        assert_eq!(auth.granted(&bearer("full")), Some(Scope::Delete));
+        assert_eq!(auth.granted(&bearer("nope")), None);
+        assert_eq!(auth.granted(&bearer("")), None);
+        // A prefix of a token is not the token.
+        assert_eq!(auth.granted(&bearer("ful")), None);
+        assert_eq!(auth.granted(&request(None)), None);
+        assert_eq!(auth.granted(&request(Some("Token full".into()))), None);
+        assert_eq!(auth.granted(&basic("sam", "pw")), Some(Scope::Delete));
+        assert_eq!(auth.granted(&basic("sam", "wrong")), None);
+        assert_eq!(auth.granted(&basic("eve", "pw")), None);
+        // Browsers send the token as the password, with any user.
+        assert_eq!(auth.granted(&basic("any", "r")), Some(Scope::Read));
+        assert_eq!(auth.granted(&request(Some("Basic !!".into()))), None);
+    }
+
+    #[test]
+    fn the_widest_matching_scope_wins() {
+        let auth = Auth::new(
+            None,
+            vec![(Scope::Read, "t".into()), (Scope::Convert, "t".into())],
+            None,
+        )
+        .unwrap();
+        assert_eq!(auth.granted(&bearer("t")), Some(Scope::Convert));
+    }
+
+    #[test]
+    fn no_credentials_lets_everything_in() {
+        let open = Auth::new(None, Vec::new(), None).unwrap();
+        assert!(open.is_open());
+        assert_eq!(open.granted(&request(None)), Some(Scope::Delete));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert_eq!(
+            parse_scoped_token("Convert:abc"),
+            Ok((Scope::Convert, "abc".into()))
+        );
+        assert!(parse_scoped_token("admin:abc").is_err());
+        assert!(parse_scoped_token("read:").is_err());
+        assert!(parse_scoped_token("abc").is_err());
+        assert!(Auth::new(None, Vec::new(), Some("sam")).is_err());
+        assert!(Auth::new(None, Vec::new(), Some(":pw")).is_err());
+    }
+}
@@ -0,0 +1,1397 @@
+//! The conversion run itself, as pipeline stages: prepare (settle check, overrides, naming,
+//! probing) → convert (ffmpeg) → finish (file times, catalog, session files,
+//! post-processing, deletion).
+//!
+//! There is no separate upload stage: `upload-s3` post-processing runs in finish, so
+//! `--post-jobs` also bounds how many uploads run at once.
+
+use crate::{
+    AudioChoice, Cli, EncodeArgs, OnExists, app_names,
+    catalog::Catalog,
+    contact_sheet::ContactSheet,
+    encrypt,
+    energy::EnergyReport,
+    events::{self, Bus},
+    host,
+    lease::{Claim, Leases},
+    maybe_remove_clip_grandparent, merge,
+    pipeline::{self, StageLimits},
+    postprocess::{self, Chain},
+    power,
+    progress_bar::{self, ProgressBars},
+    progress_file::ProgressFile,
+    project,
+    queue::{self, Converted, RunQueue},
+    quota, record_output, remux_options_for,
+    report::{self, say},
+    session, sha256,
+    skip::{self, RunSummary, SkipReason},
+    staged::StagedInput,
+    stamp_output,
+    syslog::{Level, SystemLog},
+    tag_output, template, thermal, timeline, timestamp, unclaimed_name, visibility,
+};
+use chrono::{DateTime, Utc};
+use sanitize_filename::sanitize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Instant,
+};
+use steamclipconverter::{
+    ClipDir, Converter, Error, Progress, ProgressCallback,
+    ffmpeg::{Previews, RemuxOptions},
+    json::{self, Value},
+    probe::{probe_duration, probe_streams},
+    settle::SettleRules,
+    steam::{self, AppNames, KeepReason},
+};
+
+/// A clip that passed every check, with everything needed to convert it.
+struct Job {
+    /// Position in the run queue.
+    index: usize,
+    clip: ClipDir,
+    game_name: String,
+    fname: String,
+    target_dir: PathBuf,
+    out_path: PathBuf,
+    start: Option<DateTime<Utc>>,
+    opts: RemuxOptions,
+    /// Size of the fg_* folder, for --make-room bookkeeping.
+    clip_size: Option<u64>,
+    /// `{seq}` given to this recording.
+    seq: Option<u32>,
+    /// --merge: the parts recorded after `clip`, joined into the same output.
+    parts: Vec<ClipDir>,
+}
+
+/// Why a clip gets no job: skipped, or (--on-exists error) failed.
+enum NoJob {
+    Skip(SkipReason),
+    Fail(Error),
+}
+
+impl From<SkipReason> for NoJob {
+    fn from(reason: SkipReason) -> Self {
+        NoJob::Skip(reason)
+    }
+}
+
+/// State shared by the stages of one run.
+pub struct Batch<'a> {
+    cli: &'a Cli,
+    output_dir: PathBuf,
+    /// Clips are fetched from here one at a time (archive or agent input).
+    staged: Option<&'a dyn StagedInput>,
+    /// Game names: local libraries, the input's own Steam folder, name lists.
+    names: AppNames,
+    rules: SettleRules,
+    host_tag: Option<String>,
+    catalog: Mutex<Option<Catalog>>,
+    summary: Mutex<RunSummary>,
+    /// --make-room: (bytes used, target).
+    room: Mutex<Option<(u64, u64)>>,
+    deferred: AtomicBool,
+    /// Serializes session.json / project rewrites between finishing workers.
+    sessions: Mutex<()>,
+    queue: Option<RunQueue>,
+    /// --system-log
+    log: Option<SystemLog>,
+    /// --events
+    events: Option<Arc<Bus>>,
+    /// --progress-file
+    progress: Option<ProgressFile>,
+    /// Drawn on a terminal unless --no-progress.
+    bars: Option<ProgressBars>,
+    /// --contact-sheet
+    sheet: Option<ContactSheet>,
+    /// --share-work
+    leases: Option<Leases>,
+    /// --energy-report
+    energy: Option<EnergyReport>,
+    /// `[[postprocess]]` steps of the config file.
+    postprocess: Chain,
+    /// Highest `{seq}` handed out in this run per (appid, date), on top of the catalog's.
+    seqs: Mutex<HashMap<(u32, String), u32>>,
+    /// Output paths given out in this run, so --on-exists sees clips that would collide
+    /// before either is written.
+    claimed: Mutex<HashSet<PathBuf>>,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(
+        cli: &'a Cli,
+        output_dir: PathBuf,
+        staged: Option<&'a dyn StagedInput>,
+        catalog: Option<Catalog>,
+        summary: RunSummary,
+        room: Option<(u64, u64)>,
+        queue: Option<RunQueue>,
+    ) -> Self {
+        let names = app_names(cli);
+        if names.is_empty() {
+            eprintln!(
+                "[info] no Steam libraries or name list found; games are named by appid (see --app-names)"
+            );
+        }
+        if catalog.is_none() && cli.name_template.as_ref().is_some_and(|t| t.uses("seq")) {
+            eprintln!("[warn] without a catalog, {{seq}} starts again at 001 on every run");
+        }
+        let leases = (cli.share_work && !cli.dry_run)
+            .then(|| Leases::new(&output_dir, cli.lease_time))
+            .and_then(|r| {
+                r.map_err(|e| eprintln!("[warn] cannot share work: {e}; converting alone"))
+                    .ok()
+            });
+        Batch {
+            cli,
+            output_dir,
+            staged,
+            names,
+            rules: SettleRules {
+                settle_time: cli.settle_time,
+                growth_window: cli.settle_growth,
+            },
+            host_tag: match &cli.host {
+                Some(h) => Some(host::tag_from(h)).filter(|t| !t.is_empty()),
+                None => cli.host_suffix.then(host::machine_tag),
+            },
+            catalog: Mutex::new(catalog),
+            summary: Mutex::new(summary),
+            room: Mutex::new(room),
+            deferred: AtomicBool::new(false),
+            sessions: Mutex::new(()),
+            queue,
+            log: cli.system_log.then(SystemLog::open).and_then(|r| {
+                r.map_err(|e| eprintln!("[warn] cannot open the system log: {}", e))
+                    .ok()
+            }),
+            events: cli.events.as_deref().and_then(|addr| {
+                events::serve(addr)
+                    .map_err(|e| eprintln!("[warn] cannot serve events on {}: {}", addr, e))
+                    .ok()
+            }),
+            progress: cli.progress_file.as_deref().map(ProgressFile::new),
+            bars: (progress_bar::enabled(cli.no_progress) && !cli.encode.ffmpeg_stats)
+                .then(ProgressBars::new),
+            sheet: cli.contact_sheet.as_deref().map(ContactSheet::new),
+            leases,
+            energy: (cli.energy_report && !cli.dry_run).then(EnergyReport::start),
+            postprocess: Chain::default(),
+            seqs: Mutex::new(HashMap::new()),
+            claimed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Run `chain` on every output after it is catalogued.
+    pub fn postprocess(self, chain: Chain) -> Self {
+        Batch {
+            postprocess: chain,
+            ..self
+        }
+    }
+
+    /// Work through the run queue `work` (in this order), continuing clips that an
+    /// interrupted run already converted, and return what happened plus the final
+    /// --make-room usage.
+    pub fn run(
+        mut self,
+        work: Vec<(ClipDir, queue::Progress)>,
+        limits: &StageLimits,
+    ) -> (RunSummary, Option<(u64, u64)>) {
+        let (queue_tx, queue_rx) = mpsc::channel();
+        let mut resumed = Vec::new();
+        let mut seq = 0;
+        for (index, (clip, progress)) in work.into_iter().enumerate() {
+            match progress {
+                queue::Progress::Done => {}
+                queue::Progress::Converted(c) if c.out_path.is_file() => {
+                    resumed.push(self.resumed_job(index, clip, c));
+                }
+                // Pending, or converted but the output is gone: (re)do it.
+                _ => {
+                    queue_tx.send((seq, (index, clip))).unwrap();
+                    seq += 1;
+                }
+            }
+        }
+        drop(queue_tx);
+        self.log(
+            Level::Info,
+            &format!(
+                "run started: {} clip(s) into {}",
+                seq + resumed.len(),
+                self.output_dir.display()
+            ),
+        );
+        self.emit(
+            "run-started",
+            json::obj([
+                ("clips", ((seq + resumed.len()) as u64).into()),
+                ("output", self.output_dir.display().to_string().into()),
+            ]),
+        );
+        self.drive(queue_rx, resumed, limits);
+        if let Some(q) = self.queue.take() {
+            q.finish();
+        }
+        self.write_contact_sheet();
+        if let Some(energy) = self.energy.take() {
+            energy.finish();
+        }
+        let summary = self.summary.lock().unwrap();
+        let level = if summary.any_failed() {
+            Level::Warning
+        } else {
+            Level::Info
+        };
+        self.log(level, &format!("run finished: {}", summary.line()));
+        self.emit("run-finished", summary.to_json());
+        drop(summary);
+        if let Some(bus) = &self.events {
+            bus.close();
+        }
+        (
+            self.summary.into_inner().unwrap(),
+            self.room.into_inner().unwrap(),
+        )
+    }
+
+    /// --watch: convert the clips `feed` delivers, one after another as they arrive, until it
+    /// is closed.
+    pub fn watch(self, feed: mpsc::Receiver<ClipDir>, limits: &StageLimits) -> RunSummary {
+        self.log(
+            Level::Info,
+            &format!(
+                "watching for clips to convert into {}",
+                self.output_dir.display()
+            ),
+        );
+        self.emit(
+            "run-started",
+            json::obj([
+                ("clips", 0u64.into()),
+                ("output", self.output_dir.display().to_string().into()),
+            ]),
+        );
+        let (queue_tx, queue_rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (n, clip) in feed.into_iter().enumerate() {
+                if queue_tx.send((n, (n, clip))).is_err() {
+                    break;
+                }
+            }
+        });
+        self.drive(queue_rx, Vec::new(), limits);
+        self.write_contact_sheet();
+        self.summary.into_inner().unwrap()
+    }
+
+    /// --dry-run: plan `clips` exactly as a run would and print what would be converted,
+    /// skipped, overwritten and deleted, without converting, writing or deleting anything.
+    pub fn dry_run(&self, clips: &[ClipDir]) {
+        let cli = self.cli;
+        let (mut convert, mut overwrite, mut skipped, mut failed) = (0, 0, 0, 0);
+        let mut deleted: Vec<PathBuf> = Vec::new();
+        let has_catalog = self.catalog.lock().unwrap().is_some();
+        for (index, clip) in clips.iter().enumerate() {
+            let job = match self.plan(index, clip) {
+                Ok(job) => job,
+                Err(NoJob::Skip(reason)) => {
+                    say!("[plan] skip      {}: {}", clip.dir.display(), reason);
+                    skipped += 1;
+                    continue;
+                }
+                Err(NoJob::Fail(e)) => {
+                    say!("[plan] fail      {}: {}", clip.dir.display(), e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            // ffmpeg is run with -y, so an existing output is replaced (--on-exists overwrite).
+            let verb = if job.out_path.exists() {
+                overwrite += 1;
+                "overwrite"
+            } else {
+                convert += 1;
+                "convert  "
+            };
+            say!(
+                "[plan] {verb} {} -> {}",
+                clip.dir.display(),
+                job.out_path.display()
+            );
+            for part in &job.parts {
+                say!("[plan]   + part {}", part.dir.display());
+            }
+            if !cli.delete_after {
+                continue;
+            }
+            // The run's guard, with what its hash would find: the folder it hashes readable.
+            let hashed_dir = match clip.dir.parent() {
+                Some(video) if !job.parts.is_empty() => video,
+                _ => clip.dir.as_path(),
+            };
+            let hashed = has_catalog.then(|| readable(hashed_dir));
+            for c in std::iter::once(clip).chain(&job.parts) {
+                match steam::delete_check(&c.dir, &cli.allow_delete_under, hashed) {
+                    Ok(()) => {
+                        say!("[plan] delete    {}", c.dir.display());
+                        deleted.push(c.dir.clone());
+                    }
+                    Err(KeepReason::OutsideRecordings) => say!(
+                        "[plan] keep      {}: not in a Steam gamerecordings folder",
+                        c.dir.display()
+                    ),
+                    Err(KeepReason::NotHashed) => say!(
+                        "[plan] keep      {}: its segments can't be read to hash them",
+                        c.dir.display()
+                    ),
+                }
+            }
+        }
+        // clip_* folders whose every recording would go.
+        let mut parents: Vec<&Path> = deleted
+            .iter()
+            .filter_map(|d| d.parent())
+            .filter(|video| video.file_name().is_some_and(|n| n == "video"))
+            .collect();
+        parents.sort();
+        parents.dedup();
+        for video in parents {
+            let emptied = fs::read_dir(video)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|e| e.path().is_dir())
+                .all(|e| deleted.contains(&e.path()));
+            if let Some(clip_dir) = video.parent().filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("clip_"))
+            }) && emptied
+            {
+                say!("[plan] delete    {} (left empty)", clip_dir.display());
+            }
+        }
+        let filtered = {
+            let summary = self.summary.lock().unwrap();
+            summary.skipped_for(&SkipReason::FilteredGame)
+                + summary.skipped_for(&SkipReason::OutsideRange)
+                + summary.skipped_for(&SkipReason::NotMatched)
+        };
+        say!(
+            "\nDry run: {} to convert, {} overwriting an existing file, {} skipped{}{}, {} clip folder(s) to delete. Nothing was changed.",
+            convert,
+            overwrite,
+            skipped,
+            if filtered > 0 {
+                format!(" (and {filtered} filtered out)")
+            } else {
+                String::new()
+            },
+            if failed > 0 {
+                format!(", {failed} failing")
+            } else {
+                String::new()
+            },
+            deleted.len()
+        );
+    }
+
+    /// Run the stages over `queue` (numbered clips), with `resumed` jobs going straight to
+    /// the finish stage; returns once everything queued is done.
+    fn drive(
+        &self,
+        queue: mpsc::Receiver<(usize, (usize, ClipDir))>,
+        resumed: Vec<Job>,
+        limits: &StageLimits,
+    ) {
+        let prepare = |(seq, (index, clip)): (usize, (usize, ClipDir))| {
+            Some((seq, self.prepare(index, clip)))
+        };
+        let workers = limits.convert_workers();
+        // With several conversions at once, each clip's lines are printed together; under
+        // the progress bars too, so they go above them in one piece.
+        let buffered = (workers > 1 || self.bars.is_some()) && !self.cli.encode.ffmpeg_stats;
+        let convert = |job: Job| self.convert(job, buffered);
+        let finish = |job: Job| self.finish(job);
+        thread::scope(|s| {
+            let lookahead = usize::from(limits.probe_jobs);
+            let (prepared_tx, prepared_rx) = mpsc::sync_channel(lookahead);
+            let (ready_tx, ready_rx) = mpsc::sync_channel(0);
+            let (done_tx, done_rx) = mpsc::sync_channel(usize::from(limits.post_jobs));
+            let resume_tx = done_tx.clone();
+            pipeline::stage(s, limits.probe_jobs, queue, prepared_tx, &prepare);
+            pipeline::in_order(s, prepared_rx, ready_tx);
+            pipeline::stage(s, workers, ready_rx, done_tx, &convert);
+            pipeline::sink(s, limits.post_jobs, done_rx, &finish);
+            for job in resumed {
+                say!("[resume] finishing {}", job.out_path.display());
+                let _ = resume_tx.send(job);
+            }
+        });
+    }
+
+    /// A job whose conversion an interrupted run completed; only `finish` is left.
+    fn resumed_job(&self, index: usize, clip: ClipDir, c: Converted) -> Job {
+        self.summary
+            .lock()
+            .unwrap()
+            .converted
+            .push(c.out_path.clone());
+        let parts = if self.cli.merge {
+            merge::later_parts(&clip)
+        } else {
+            Vec::new()
+        };
+        Job {
+            index,
+            clip_size: self.cli.make_room.map(|_| recording_size(&clip, &parts)),
+            parts,
+            clip,
+            game_name: c.game_name,
+            fname: c.fname,
+            target_dir: c.target_dir,
+            out_path: c.out_path,
+            start: c.start,
+            seq: c.seq,
+            opts: RemuxOptions::default(),
+        }
+    }
+
+    /// `{seq}` for `clip`: the number it already has in the catalog when converted before,
+    /// otherwise one more than any recording of the same game and day has had.
+    fn next_seq(&self, clip: &ClipDir) -> u32 {
+        let catalog = self.catalog.lock().unwrap();
+        if let Some(n) = catalog
+            .as_ref()
+            .and_then(|c| c.find_recording(clip.appid, &clip.date, &clip.time))
+            .and_then(|e| e.seq)
+        {
+            return n;
+        }
+        let mut seqs = self.seqs.lock().unwrap();
+        let last = seqs
+            .entry((clip.appid, clip.date.clone()))
+            .or_insert_with(|| {
+                catalog
+                    .as_ref()
+                    .and_then(|c| c.last_seq(clip.appid, &clip.date))
+                    .unwrap_or(0)
+            });
+        *last += 1;
+        *last
+    }
+
+    fn mark_done(&self, index: usize) {
+        if let Some(q) = &self.queue {
+            q.mark_done(index);
+        }
+    }
+
+    fn room_reached(&self) -> bool {
+        matches!(*self.room.lock().unwrap(), Some((used, target)) if used <= target)
+    }
+
+    /// Record a skip decided by a check; the clip needs nothing more from this queue.
+    fn skip(&self, index: usize, clip: &ClipDir, reason: SkipReason) {
+        if reason.is_problem() {
+            self.log(
+                Level::Warning,
+                &format!("skipped {}: {}", clip.dir.display(), reason),
+            );
+        }
+        self.emit_skip(clip, &reason);
+        self.summary.lock().unwrap().skip(clip, reason);
+        self.mark_done(index);
+        self.release(clip);
+    }
+
+    fn fail(&self, index: usize, clip: &ClipDir, err: &Error) {
+        self.log(
+            Level::Error,
+            &format!("failed {}: {}", clip.dir.display(), err),
+        );
+        self.emit(
+            "failed",
+            json::obj([
+                ("clip", clip.dir.display().to_string().into()),
+                ("kind", err.kind().into()),
+                ("error", err.to_string().into()),
+            ]),
+        );
+        self.summary.lock().unwrap().fail(clip, err);
+        self.mark_done(index);
+        self.release(clip);
+    }
+
+    /// Drop a clip's fetched copy (archive or agent input) and its --share-work lease.
+    fn release(&self, clip: &ClipDir) {
+        if let Some(a) = self.staged {
+            a.release(clip);
+        }
+        if let Some(l) = &self.leases {
+            l.release(clip);
+        }
+    }
+
+    fn log(&self, level: Level, msg: &str) {
+        if let Some(log) = &self.log {
+            log.event(level, msg);
+        }
+    }
+
+    fn write_contact_sheet(&self) {
+        let Some(sheet) = &self.sheet else {
+            return;
+        };
+        match sheet.write() {
+            Ok(0) => say!("[sheet] nothing converted, no contact sheet"),
+            Ok(n) => say!("[sheet] wrote {} ({n} clip(s))", sheet.path().display()),
+            Err(e) => eprintln!("[warn] contact sheet: {e}"),
+        }
+    }
+
+    fn emit(&self, kind: &str, data: Value) {
+        if let Some(p) = &self.progress {
+            p.observe(kind, &data);
+        }
+        if let Some(b) = &self.bars {
+            b.observe(kind, &data);
+        }
+        if let Some(bus) = &self.events {
+            bus.publish(kind, data);
+        }
+    }
+
+    fn emit_skip(&self, clip: &ClipDir, reason: &SkipReason) {
+        self.emit(
+            "skipped",
+            json::obj([
+                ("clip", clip.dir.display().to_string().into()),
+                ("reason", reason.code().into()),
+            ]),
+        );
+    }
+
+    fn skip_quietly(&self, clip: &ClipDir, reason: SkipReason) {
+        self.emit_skip(clip, &reason);
+        self.summary.lock().unwrap().skip_quietly(clip, reason);
+        self.release(clip);
+    }
+
+    /// Stage 1: everything up to the ffmpeg call. `None` when the clip is skipped.
+    fn prepare(&self, index: usize, clip: ClipDir) -> Option<Job> {
+        if self.room_reached() {
+            self.skip_quietly(&clip, SkipReason::EnoughRoom);
+            return None;
+        }
+        if self.deferred.load(Ordering::SeqCst) {
+            self.skip_quietly(&clip, SkipReason::DeferredOnBattery);
+            return None;
+        }
+        if let Some(leases) = &self.leases {
+            match leases.claim(&clip) {
+                Ok(Claim::Claimed) => {}
+                Ok(Claim::Held { owner }) => {
+                    self.skip(index, &clip, SkipReason::ClaimedElsewhere(owner));
+                    return None;
+                }
+                Ok(Claim::Done(output)) => {
+                    self.skip(index, &clip, SkipReason::AlreadyConverted(output));
+                    return None;
+                }
+                Err(e) => eprintln!(
+                    "[warn] cannot claim {}: {}; converting it anyway",
+                    clip.dir.display(),
+                    e
+                ),
+            }
+        }
+
+        if let Some(a) = self.staged
+            && let Err(e) = a.fetch(&clip)
+        {
+            let path = a.source_of(&clip).dir;
+            self.fail(index, &clip, &Error::Io { path, source: e });
+            return None;
+        }
+
+        let job = match self.plan(index, &clip) {
+            Ok(job) => job,
+            Err(NoJob::Skip(reason)) => {
+                self.skip(index, &clip, reason);
+                return None;
+            }
+            Err(NoJob::Fail(e)) => {
+                self.fail(index, &clip, &e);
+                return None;
+            }
+        };
+        // A template can put the file in folders of its own below the target.
+        let out_dir = job
+            .target_dir
+            .join(&job.fname)
+            .parent()
+            .map_or_else(|| job.target_dir.clone(), Path::to_path_buf);
+        if let Err(e) = fs::create_dir_all(&out_dir) {
+            self.fail(
+                index,
+                &clip,
+                &Error::Io {
+                    path: out_dir,
+                    source: e,
+                },
+            );
+            return None;
+        }
+        Some(job)
+    }
+
+    /// Everything a run decides about `clip` before converting it (checks, name, output
+    /// path, encode options), without touching any file: shared by conversion and
+    /// --dry-run. `Err` when the clip would be skipped or fail.
+    fn plan(&self, index: usize, clip: &ClipDir) -> Result<Job, NoJob> {
+        let cli = self.cli;
+        let overrides = skip::assess(clip, &self.rules)?;
+        // A merged recording waits until every part is ready.
+        let parts = if cli.merge {
+            merge::later_parts(clip)
+        } else {
+            Vec::new()
+        };
+        for part in &parts {
+            if let Err(reason) = skip::assess(part, &self.rules) {
+                say!("[merge] {}: {}", part.dir.display(), reason);
+                return Err(reason.into());
+            }
+        }
+
+        // In a shared output folder, another machine may have converted this recording.
+        if cli.portable_catalog
+            && let Some(done) = self
+                .catalog
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|c| c.find_recording(clip.appid, &clip.date, &clip.time))
+                .filter(|e| e.output.is_file())
+                .map(|e| e.output.clone())
+        {
+            return Err(SkipReason::AlreadyConverted(done).into());
+        }
+
+        // Resolve game name (best-effort)
+        let resolved = self
+            .names
+            .resolve(clip.appid)
+            .or_else(|| self.staged.and_then(|s| s.game_name(clip)));
+        if resolved.is_none() && cli.strict_names {
+            return Err(SkipReason::UnresolvedName.into());
+        }
+        let game_name = resolved.unwrap_or_else(|| clip.appid.to_string());
+
+        let start = timestamp::record_start(clip, cli.timestamp_source);
+        // Filename: GameName-YYYYMMDD-HHMMSS[-Title].mp4  (sanitize for safety)
+        let title_suffix = overrides
+            .title
+            .as_ref()
+            .map(|t| format!("-{}", sanitize(t)))
+            .unwrap_or_default();
+        let host_suffix = self
+            .host_tag
+            .as_ref()
+            .map(|h| format!("-{h}"))
+            .unwrap_or_default();
+        let seq = cli
+            .name_template
+            .as_ref()
+            .filter(|t| t.uses("seq"))
+            .map(|_| self.next_seq(clip));
+        let mut fname = match &cli.name_template {
+            Some(t) => {
+                let vars = template::Vars {
+                    game: sanitize(&game_name),
+                    appid: clip.appid,
+                    date: clip.date.clone(),
+                    time: clip.time.clone(),
+                    title: overrides.title.as_deref().map(sanitize),
+                    host: sanitize(self.host_tag.clone().unwrap_or_else(host::machine_tag)),
+                    start,
+                    duration: if t.uses("duration") {
+                        clip_duration(clip)
+                    } else {
+                        None
+                    },
+                    user: t
+                        .uses("user")
+                        .then(|| template::persona_name(&clip.dir))
+                        .flatten()
+                        .map(sanitize),
+                    seq,
+                    index: Some(index + 1),
+                };
+                t.render(&vars, cli.encode.container().extension())
+                    .to_string_lossy()
+                    .into_owned()
+            }
+            None => format!(
+                "{}-{}-{}{}{}.{}",
+                sanitize(&game_name),
+                clip.date,
+                clip.time,
+                host_suffix,
+                title_suffix,
+                cli.encode.container().extension()
+            ),
+        };
+        let target_dir = match (cli.layout, start) {
+            (session::Layout::Sessions, Some(t)) => {
+                session::session_dir(&self.output_dir, &game_name, t)
+            }
+            _ => self.output_dir.clone(),
+        };
+        if cli.portable_catalog
+            && let Some(cat) = self.catalog.lock().unwrap().as_ref()
+        {
+            fname = unclaimed_name(cat, &target_dir, &fname, clip);
+        }
+        let mut out_path = target_dir.join(&fname);
+        {
+            let mut claimed = self.claimed.lock().unwrap();
+            let taken = |p: &Path| p.exists() || claimed.contains(p);
+            // Overwriting is for files of earlier runs; two clips of this one converting onto
+            // the same file would clobber each other, so the later one is renamed.
+            let on_exists = match cli.on_exists {
+                OnExists::Overwrite if claimed.contains(&out_path) => OnExists::Rename,
+                policy => policy,
+            };
+            if taken(&out_path) {
+                match on_exists {
+                    OnExists::Overwrite => {}
+                    OnExists::Skip => return Err(SkipReason::AlreadyConverted(out_path).into()),
+                    OnExists::Error => {
+                        return Err(NoJob::Fail(Error::Io {
+                            path: out_path,
+                            source: io::Error::new(
+                                io::ErrorKind::AlreadyExists,
+                                "output already exists (--on-exists error)",
+                            ),
+                        }));
+                    }
+                    OnExists::Rename => {
+                        let (stem, ext) = fname.rsplit_once('.').unwrap_or((&fname, ""));
+                        let renamed = (1..)
+                            .map(|n| match ext {
+                                "" => format!("{stem}-{n}"),
+                                ext => format!("{stem}-{n}.{ext}"),
+                            })
+                            .find(|f| !taken(&target_dir.join(f)))
+                            .expect("some suffix is free");
+                        fname = renamed;
+                        out_path = target_dir.join(&fname);
+                    }
+                }
+            }
+            claimed.insert(out_path.clone());
+        }
+
+        let mut encode = cli.encode.clone();
+        if overrides.preset.is_some() {
+            // The clip's own preset replaces --encode as well.
+            encode.target_device = overrides.preset;
+            encode.encode = None;
+        }
+        let on_battery = cli.power_policy == power::PowerPolicy::LowPower
+            && power::power_source() == power::PowerSource::Battery;
+        // What shapes the output itself can't be dropped like the quality settings below;
+        // such a clip waits for AC instead.
+        let transcoding = [
+            ("--blur", !encode.blur.is_empty()),
+            ("--prepend", encode.prepend.is_some()),
+            ("--append", encode.append.is_some()),
+            ("--watermark", encode.watermark.is_some()),
+            ("--container", !encode.container().copies_streams()),
+            ("--fps", encode.fps.is_some()),
+            ("--scale", encode.scale.is_some()),
+            ("--mute-voice-channel", encode.mute_voice_channel.is_some()),
+            ("--thumbnails", cli.thumbnails),
+        ]
+        .into_iter()
+        .find(|(_, set)| *set);
+        if on_battery && let Some((flag, _)) = transcoding {
+            say!(
+                "[power] on battery: {} needs {flag}, which transcodes; leaving it for AC power",
+                clip.dir.display()
+            );
+            self.claimed.lock().unwrap().remove(&out_path);
+            return Err(SkipReason::DeferredOnBattery.into());
+        }
+        let mut opts = if on_battery {
+            // Low-power profile: labels are free, but nothing gets transcoded.
+            say!("[power] on battery: stream copy only, 1 thread");
+            let enc = EncodeArgs {
+                target_device: None,
+                encode: None,
+                audio: match encode.audio {
+                    // Mixing transcodes the audio.
+                    AudioChoice::Mix => AudioChoice::Game,
+                    choice => choice,
+                },
+                downmix: None,
+                fix_drift: false,
+                ..encode
+            };
+            RemuxOptions {
+                threads: Some(1),
+                ..remux_options_for(&enc, clip)
+            }
+        } else {
+            remux_options_for(&encode, clip)
+        };
+        // A clip's own [trim] replaces --start-offset/--end-offset as a whole.
+        if overrides.trim_start.is_some() || overrides.trim_end.is_some() {
+            opts.trim_start = overrides.trim_start;
+            opts.trim_end = overrides.trim_end;
+        }
+        opts.title = overrides.title.clone();
+        tag_output(&mut opts, clip, &game_name, start);
+        opts.previews = cli.thumbnails.then(|| Previews {
+            poster_at: cli.thumbnail_at,
+            sheet: cli.thumbnail_sheet.unwrap_or(0),
+            animated: cli.thumbnail_preview,
+            duration: None,
+        });
+
+        let clip_size = cli.make_room.map(|_| recording_size(clip, &parts));
+        Ok(Job {
+            index,
+            clip: clip.clone(),
+            game_name,
+            fname,
+            target_dir,
+            out_path,
+            start,
+            opts,
+            clip_size,
+            seq,
+            parts,
+        })
+    }
+
+    /// Checks made right before a conversion starts: --make-room, power and heat.
+    fn may_start(&self, clip: &ClipDir) -> bool {
+        if self.room_reached() {
+            self.skip_quietly(clip, SkipReason::EnoughRoom);
+            return false;
+        }
+        if self.deferred.load(Ordering::SeqCst) {
+            self.skip_quietly(clip, SkipReason::DeferredOnBattery);
+            return false;
+        }
+        if self.cli.power_policy == power::PowerPolicy::Defer
+            && power::power_source() == power::PowerSource::Battery
+        {
+            if !self.deferred.swap(true, Ordering::SeqCst) {
+                say!(
+                    "[defer] running on battery; leaving the remaining clips for when AC power is back \
+                     (--power-policy low-power or ignore to convert anyway)"
+                );
+            }
+            self.skip_quietly(clip, SkipReason::DeferredOnBattery);
+            return false;
+        }
+        if let Some(max) = self.cli.max_temp {
+            thermal::cool_down(max);
+        }
+        true
+    }
+
+    /// Stage 2: run ffmpeg. Passes the job on only if the output was written. When `buffered`,
+    /// the clip's lines and ffmpeg's messages are held back and printed in one piece.
+    fn convert(&self, job: Job, buffered: bool) -> Option<Job> {
+        if !self.may_start(&job.clip) {
+            return None;
+        }
+        let clip = &job.clip;
+        let mut out = ClipLog::new(buffered);
+        out.line(format!(
+            "== {} (appid={}, start={} {}) ==",
+            clip.dir.display(),
+            clip.appid,
+            clip.date,
+            clip.time
+        ));
+        out.line(format!("converting to {}", job.out_path.display()));
+        let clip_name = clip.dir.display().to_string();
+        self.emit(
+            "convert-started",
+            json::obj([
+                ("clip", clip_name.as_str().into()),
+                ("appid", clip.appid.into()),
+                ("game", job.game_name.as_str().into()),
+                ("output", job.out_path.display().to_string().into()),
+            ]),
+        );
+
+        // Remux from the local MPD (ffmpeg, or the native backend).
+        let mut last_percent = None;
+        let mut converter = Converter::new()
+            .options(job.opts.clone())
+            .backend(self.cli.encode.backend);
+        if let Some(buf) = &out.ffmpeg {
+            converter = converter.capture_stderr(Arc::clone(buf));
+        }
+        if let Some(remote) = &self.cli.encode.remote {
+            converter = converter.remote(remote.clone());
+        }
+        let cpu_time = Arc::new(Mutex::new(None));
+        if self.energy.is_some() {
+            converter = converter.cpu_time(Arc::clone(&cpu_time));
+        }
+        let started = Instant::now();
+        let mut on_progress = |p: &Progress| {
+            let percent = p.percent.map(|x| x.floor() as u32);
+            let watched = self.events.is_some() || self.progress.is_some() || self.bars.is_some();
+            if watched && percent.is_some() && percent != last_percent {
+                last_percent = percent;
+                self.emit(
+                    "progress",
+                    json::obj([
+                        ("clip", clip_name.as_str().into()),
+                        ("percent", percent.into()),
+                        ("eta_secs", p.eta.map(|d| d.as_secs()).into()),
+                    ]),
+                );
+            }
+        };
+        let result = if job.parts.is_empty() {
+            converter.convert(clip, &job.out_path, &mut on_progress)
+        } else {
+            convert_parts(&converter, &job, &mut out, &mut on_progress)
+        };
+        match result {
+            Ok(()) => {
+                out.line(format!("[ok] wrote {}", job.out_path.display()));
+                if let Some(energy) = &self.energy {
+                    let cpu = *cpu_time.lock().unwrap();
+                    out.line(energy.clip(&clip.dir, cpu, started.elapsed()));
+                }
+                out.flush();
+                self.emit(
+                    "converted",
+                    json::obj([
+                        ("clip", clip_name.as_str().into()),
+                        ("output", job.out_path.display().to_string().into()),
+                    ]),
+                );
+                self.summary
+                    .lock()
+                    .unwrap()
+                    .converted
+                    .push(job.out_path.clone());
+                if let Some(q) = &self.queue {
+                    q.mark_converted(
+                        job.index,
+                        &Converted {
+                            out_path: job.out_path.clone(),
+                            game_name: job.game_name.clone(),
+                            fname: job.fname.clone(),
+                            target_dir: job.target_dir.clone(),
+                            start: job.start,
+                            seq: job.seq,
+                        },
+                    );
+                }
+                Some(job)
+            }
+            Err(e) => {
+                out.flush();
+                self.fail(job.index, clip, &e);
+                None
+            }
+        }
+    }
+
+    /// Stage 3: everything after a successful conversion.
+    fn finish(&self, job: Job) {
+        let cli = self.cli;
+        let Job {
+            index,
+            clip,
+            game_name,
+            mut fname,
+            target_dir,
+            mut out_path,
+            start,
+            clip_size,
+            seq,
+            parts,
+            ..
+        } = job;
+
+        // --encrypt before anything records, publishes or uploads the output. The plaintext's
+        // duration is kept, since the encrypted file can't be probed.
+        let mut encrypted = None;
+        if let Some(enc) = encrypt::Encryption::from_specs(&cli.encrypt) {
+            let duration = probe_duration(&out_path).ok();
+            match enc.encrypt(&out_path) {
+                Ok(p) => {
+                    say!("[encrypt] {}", p.display());
+                    let mut summary = self.summary.lock().unwrap();
+                    if let Some(c) = summary.converted.iter_mut().find(|c| **c == out_path) {
+                        *c = p.clone();
+                    }
+                    drop(summary);
+                    fname = format!("{fname}.{}", encrypt::EXT);
+                    out_path = p;
+                    encrypted = Some((enc, duration));
+                }
+                Err(e) => {
+                    // Never leave a plaintext copy where an encrypted one was asked for.
+                    let _ = fs::remove_file(&out_path);
+                    self.summary
+                        .lock()
+                        .unwrap()
+                        .converted
+                        .retain(|c| *c != out_path);
+                    let err = Error::Io {
+                        path: out_path,
+                        source: e,
+                    };
+                    self.fail(index, &clip, &err);
+                    return;
+                }
+            }
+        }
+
+        // Set file times to the record start time (compact Chrono parse).
+        // Other clips are still converting: fail this one instead of ending the run.
+        if let Err(e) = stamp_output(&out_path, &clip, start) {
+            self.summary
+                .lock()
+                .unwrap()
+                .converted
+                .retain(|c| *c != out_path);
+            self.fail(index, &clip, &e);
+            return;
+        }
+
+        // Before deleting, fingerprint the segments so later audits can tell whether
+        // the source was intact when it went.
+        let has_catalog = self.catalog.lock().unwrap().is_some();
+        // Gone already when a resumed run had deleted it just before being interrupted.
+        let source_present = clip.dir.is_dir();
+        // An agent checks this digest against its own copy before deleting it.
+        let remote_delete = self.staged.is_some_and(|s| s.deletes_source());
+        let mut source_digest = None;
+        // A merged recording is hashed as its whole video/ folder, every part included.
+        let hashed_dir = match clip.dir.parent() {
+            Some(video) if !parts.is_empty() => video,
+            _ => clip.dir.as_path(),
+        };
+        if (cli.delete_after && has_catalog || remote_delete) && source_present {
+            match sha256::tree_sha256(hashed_dir) {
+                Ok(d) => source_digest = Some(d),
+                Err(e) => eprintln!("[warn] cannot hash {}: {}", hashed_dir.display(), e),
+            }
+        }
+        let hashed = source_digest.is_some();
+        let recorded = match self.catalog.lock().unwrap().as_mut() {
+            Some(cat) => {
+                let source = match self.staged {
+                    Some(a) => a.source_of(&clip),
+                    None => clip.clone(),
+                };
+                record_output(
+                    cat,
+                    &source,
+                    &out_path,
+                    &game_name,
+                    source_digest.clone(),
+                    seq,
+                    encrypted.as_ref().map(|(enc, d)| (enc, *d)),
+                )
+            }
+            None => false,
+        };
+
+        if cli.layout == session::Layout::Sessions
+            && let Some(start) = start
+        {
+            let duration = probe_duration(&out_path).ok();
+            let item = session::Item::Clip {
+                file: fname,
+                start,
+                duration,
+                visibility: visibility::detect(&clip),
+                markers: timeline::markers(&clip, duration),
+            };
+            let _guard = self.sessions.lock().unwrap();
+            if let Err(e) = session::record(&target_dir, clip.appid, &game_name, item) {
+                eprintln!("[warn] cannot update {}: {}", session::SIDECAR, e);
+            } else if let Some(format) = cli.export_project {
+                let title = format!(
+                    "{} {}",
+                    game_name,
+                    target_dir.file_name().unwrap_or_default().to_string_lossy()
+                );
+                if let Err(e) = project::write(&target_dir, &title, format) {
+                    eprintln!("[warn] cannot write project file: {}", e);
+                }
+            }
+        }
+
+        let output = postprocess::Output {
+            path: &out_path,
+            clip: &clip,
+            game: &game_name,
+        };
+        if let Err(e) = self.postprocess.run(&output) {
+            eprintln!("[warn] post-processing {}: {}", out_path.display(), e);
+            self.log(
+                Level::Warning,
+                &format!("post-processing {} failed: {}", out_path.display(), e),
+            );
+        }
+
+        // Delete-after semantics
+        let mut deleted = false;
+        if cli.delete_after && source_present {
+            let check = steam::delete_check(
+                &clip.dir,
+                &cli.allow_delete_under,
+                has_catalog.then_some(hashed && recorded),
+            );
+            if check == Err(KeepReason::OutsideRecordings) {
+                say!(
+                    "[keep] {}: not in a Steam gamerecordings folder, not deleting",
+                    clip.dir.display()
+                );
+                self.log(
+                    Level::Warning,
+                    &format!(
+                        "kept {}: outside Steam's recording folders and --allow-delete-under",
+                        clip.dir.display()
+                    ),
+                );
+            } else if check == Err(KeepReason::NotHashed) {
+                say!(
+                    "[keep] {}: its segment hash isn't in the catalog, not deleting",
+                    clip.dir.display()
+                );
+                self.log(
+                    Level::Warning,
+                    &format!(
+                        "kept {}: its segment hash isn't in the catalog",
+                        clip.dir.display()
+                    ),
+                );
+            } else if std::iter::once(&clip)
+                .chain(&parts)
+                .all(|c| self.delete_source(c, &out_path))
+            {
+                deleted = true;
+                maybe_remove_clip_grandparent(&clip);
+                if let (Some((used, _)), Some(size)) =
+                    (self.room.lock().unwrap().as_mut(), clip_size)
+                {
+                    *used = used.saturating_sub(size);
+                }
+            }
+        }
+        if remote_delete && let Some(staged) = self.staged {
+            match &source_digest {
+                Some(d) if recorded || !has_catalog => staged.finished(&clip, d),
+                _ => say!(
+                    "[keep] {}: not verified, leaving it on the agent",
+                    clip.dir.display()
+                ),
+            }
+        }
+        // An encrypted output can't be read back for a frame.
+        if let Some(sheet) = &self.sheet
+            && encrypted.is_none()
+        {
+            sheet.add(&out_path, &game_name, start, probe_duration(&out_path).ok());
+        }
+        if report::json() {
+            let duration = match &encrypted {
+                Some((_, d)) => *d,
+                None => probe_duration(&out_path).ok(),
+            };
+            report::converted(&clip, &game_name, &out_path, duration, deleted);
+        }
+        if let Some(l) = &self.leases {
+            l.done(&clip, &out_path);
+        }
+        self.mark_done(index);
+        self.release(&clip);
+    }
+
+    /// Remove a converted clip folder for --delete-after; false if that failed.
+    fn delete_source(&self, clip: &ClipDir, out_path: &Path) -> bool {
+        if let Err(e) = fs::remove_dir_all(&clip.dir) {
+            eprintln!("[warn] delete failed for {}: {}", clip.dir.display(), e);
+            self.log(
+                Level::Error,
+                &format!("delete failed for {}: {}", clip.dir.display(), e),
+            );
+            return false;
+        }
+        say!("[del] removed {}", clip.dir.display());
+        self.emit(
+            "deleted",
+            json::obj([("clip", clip.dir.display().to_string().into())]),
+        );
+        self.log(
+            Level::Info,
+            &format!(
+                "deleted {} (converted to {})",
+                clip.dir.display(),
+                out_path.display()
+            ),
+        );
+        true
+    }
+}
+
+/// --merge: convert each part of the recording to a temporary file, then join them into
+/// the job's output. The first part keeps the trim start, the last one the trim end.
+fn convert_parts(
+    converter: &Converter,
+    job: &Job,
+    out: &mut ClipLog,
+    progress: &mut dyn ProgressCallback,
+) -> Result<(), Error> {
+    let all: Vec<&ClipDir> = std::iter::once(&job.clip).chain(&job.parts).collect();
+    let last = all.len() - 1;
+    let mut files = Vec::new();
+    let mut result = Ok(());
+    for (i, part) in all.iter().enumerate() {
+        let mut opts = job.opts.clone();
+        if i > 0 {
+            opts.trim_start = None;
+        }
+        if i < last {
+            opts.trim_end = None;
+        }
+        let tmp = merge::part_path(&job.out_path, i);
+        out.line(format!(
+            "[merge] part {}/{}: {}",
+            i + 1,
+            all.len(),
+            part.dir.display()
+        ));
+        result = converter
+            .clone()
+            .options(opts)
+            .convert(part, &tmp, &mut *progress);
+        files.push(tmp);
+        if result.is_err() {
+            break;
+        }
+    }
+    if result.is_ok() {
+        result = merge::concat(&files, &job.out_path, &job.opts);
+    }
+    for f in &files {
+        let _ = fs::remove_file(f);
+    }
+    result
+}
+
+/// Size of a recording's folders, all its parts included.
+fn recording_size(clip: &ClipDir, parts: &[ClipDir]) -> u64 {
+    std::iter::once(clip)
+        .chain(parts)
+        .map(|c| quota::tree_size(&c.dir))
+        .sum()
+}
+
+/// Whether every file below `dir` opens, as `sha256::tree_sha256` needs, without reading
+/// them through.
+fn readable(dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().all(|e| {
+        let p = e.path();
+        if p.is_dir() {
+            readable(&p)
+        } else {
+            fs::File::open(&p).is_ok()
+        }
+    })
+}
+
+/// Length of the recording in seconds: its longest stream, as ffprobe reports it.
+fn clip_duration(clip: &ClipDir) -> Option<f64> {
+    probe_streams(&clip.dir)
+        .ok()?
+        .iter()
+        .filter_map(|s| s.duration)
+        .reduce(f64::max)
+}
+
+/// The output of one clip's conversion: printed as it comes, or held back and printed in one
+/// piece (ffmpeg's messages included) so clips converting side by side don't interleave.
+struct ClipLog {
+    lines: Option<String>,
+    ffmpeg: Option<Arc<Mutex<Vec<u8>>>>,
+}
+
+impl ClipLog {
+    fn new(buffered: bool) -> ClipLog {
+        ClipLog {
+            lines: buffered.then(String::new),
+            ffmpeg: buffered.then(|| Arc::new(Mutex::new(Vec::new()))),
+        }
+    }
+
+    fn line(&mut self, line: String) {
+        match &mut self.lines {
+            Some(buf) => {
+                buf.push_str(&line);
+                buf.push('\n');
+            }
+            None => say!("{line}"),
+        }
+    }
+
+    fn flush(&mut self) {
+        if progress_bar::active() {
+            let mut text = self.lines.take().unwrap_or_default();
+            if let Some(ffmpeg) = self.ffmpeg.take() {
+                text.push_str(&String::from_utf8_lossy(&ffmpeg.lock().unwrap()));
+            }
+            progress_bar::above(&text);
+            return;
+        }
+        if let Some(buf) = self.lines.take() {
+            let mut stdout: Box<dyn Write> = if report::json() {
+                Box::new(io::stderr().lock())
+            } else {
+                Box::new(io::stdout().lock())
+            };
+            let _ = stdout.write_all(buf.as_bytes());
+            let _ = stdout.flush();
+        }
+        if let Some(ffmpeg) = self.ffmpeg.take() {
+            let msgs = std::mem::take(&mut *ffmpeg.lock().unwrap());
+            if !msgs.is_empty() {
+                let _ = io::stderr().lock().write_all(&msgs);
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! `bench` subcommand: convert one clip repeatedly with different settings and report timings.
+
+use clap::Args;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use steamclipconverter::{
+    Backend,
+    ffmpeg::{RemuxOptions, remux_command},
+    native,
+};
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// The fg_* clip folder (containing session.mpd) to benchmark with.
+    clip: PathBuf,
+
+    /// Runs per combination; the median is reported.
+    #[arg(long, default_value_t = 3)]
+    runs: u32,
+
+    /// Comma-separated ffmpeg thread counts to try (0 = let ffmpeg decide).
+    #[arg(long, value_delimiter = ',', default_value = "0")]
+    threads: Vec<u32>,
+
+    /// Comma-separated video encoders to try; `copy` is the default stream copy.
+    #[arg(long, value_delimiter = ',', default_value = "copy")]
+    encoder: Vec<String>,
+
+    /// Comma-separated backends to try (`ffmpeg`, `native`). The native remuxer only
+    /// copies, and takes no thread count: it runs once per `copy` encoder.
+    #[arg(long, value_delimiter = ',', default_value = "ffmpeg", value_parser = Backend::parse)]
+    backends: Vec<Backend>,
+}
+
+struct BenchResult {
+    backend: &'static str,
+    encoder: String,
+    threads: u32,
+    median: Duration,
+    failures: u32,
+}
+
+pub fn run(args: BenchArgs) -> i32 {
+    if !args.clip.join("session.mpd").is_file() {
+        eprintln!(
+            "ERROR: {} does not contain a session.mpd",
+            args.clip.display()
+        );
+        return 2;
+    }
+    let runs = args.runs.max(1);
+    let input_bytes = dir_size(&args.clip);
+
+    let scratch = env::temp_dir().join(format!("steamclipconverter-bench-{}", std::process::id()));
+    if let Err(e) = fs::create_dir_all(&scratch) {
+        eprintln!("ERROR: cannot create {}: {}", scratch.display(), e);
+        return 2;
+    }
+    let out_path = scratch.join("bench.mp4");
+
+    println!(
+        "Benchmarking {} ({:.1} MB), {} run(s) per combination.",
+        args.clip.display(),
+        mb(input_bytes),
+        runs
+    );
+
+    let mut results = Vec::new();
+    for &backend in &args.backends {
+        let name = match backend {
+            Backend::Ffmpeg => "ffmpeg",
+            Backend::Native => "native",
+        };
+        for encoder in &args.encoder {
+            // Native ignores the thread count; one pass is enough.
+            let threads = match backend {
+                Backend::Ffmpeg => &args.threads[..],
+                Backend::Native => &[0],
+            };
+            for &threads in threads {
+                let opts = RemuxOptions {
+                    video_encoder: (encoder != "copy").then(|| encoder.clone()),
+                    threads: (threads != 0).then_some(threads),
+                    ..Default::default()
+                };
+                if backend == Backend::Native
+                    && let Some(what) = native::unsupported(&opts)
+                {
+                    println!("  {name:<7}{encoder:<12} skipped: {what} needs ffmpeg");
+                    continue;
+                }
+                let mut times = Vec::new();
+                let mut failures = 0;
+                for _ in 0..runs {
+                    let started = Instant::now();
+                    let status = match backend {
+                        Backend::Ffmpeg => remux_command(&args.clip, &out_path, &opts)
+                            .status()
+                            .map(|s| s.success().then_some(()).ok_or(s.to_string())),
+                        Backend::Native => {
+                            Ok(
+                                native::remux(&args.clip, &out_path, &opts, &mut |_, _| true)
+                                    .map_err(|e| e.to_string()),
+                            )
+                        }
+                    };
+                    match status {
+                        Ok(Ok(())) => times.push(started.elapsed()),
+                        Ok(Err(e)) => {
+                            eprintln!("[fail] {name} {encoder} threads={threads}: {e}");
+                            failures += 1;
+                        }
+                        Err(e) => {
+                            eprintln!("[fail] launching ffmpeg: {}", e);
+                            let _ = fs::remove_dir_all(&scratch);
+                            return 1;
+                        }
+                    }
+                }
+                times.sort();
+                let median = times.get(times.len() / 2).copied().unwrap_or_default();
+                println!(
+                    "  {:<7}{:<12} threads={:<3} {}",
+                    name,
+                    encoder,
+                    threads,
+                    describe(median, input_bytes, failures, runs)
+                );
+                results.push(BenchResult {
+                    backend: name,
+                    encoder: encoder.clone(),
+                    threads,
+                    median,
+                    failures,
+                });
+            }
+        }
+    }
+    let _ = fs::remove_dir_all(&scratch);
+
+    results.retain(|r| r.failures < runs);
+    results.sort_by_key(|r| r.median);
+    match results.first() {
+        Some(best) => {
+            println!(
+                "\nFastest: backend={} encoder={} threads={} ({:.2}s, {:.1} MB/s)",
+                best.backend,
+                best.encoder,
+                best.threads,
+                best.median.as_secs_f64(),
+                throughput(input_bytes, best.median)
+            );
+            0
+        }
+        None => {
+            eprintln!("\nEvery combination failed.");
+            1
+        }
+    }
+}
+
+fn describe(median: Duration, input_bytes: u64, failures: u32, runs: u32) -> String {
+    if failures == runs {
+        return "failed".to_string();
+    }
+    let mut s = format!(
+        "{:>7.2}s  {:>8.1} MB/s",
+        median.as_secs_f64(),
+        throughput(input_bytes, median)
+    );
+    if failures > 0 {
+        s.push_str(&format!("  ({failures} failed run(s))"));
+    }
+    s
+}
+
+fn throughput(bytes: u64, took: Duration) -> f64 {
+    let secs = took.as_secs_f64();
+    if secs > 0.0 { mb(bytes) / secs } else { 0.0 }
+}
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// Total size of the regular files directly inside `dir` (clip folders are flat).
+pub fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|rd| {
+            rd.flatten()
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
@@ -0,0 +1,501 @@
+//! Conversion catalog: one JSON record per converted output (JSON Lines file).
+//!
+//! Records are only ever appended; when the same output shows up more than once, the latest
+//! record wins. Each append is flushed to disk before the run moves on, and rewrites go
+//! through a temporary file, so a power loss can at worst tear the last line, which
+//! `catalog repair` sets aside.
+
+use crate::{
+    json,
+    json::Value,
+    schema::{self, Migration},
+    sha256::{TreeDigest, file_sha256},
+    visibility::Visibility,
+};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use steamclipconverter::{appdirs, atomic, probe::probe_duration};
+
+const SCHEMA_KIND: &str = "steamclipconverter.catalog";
+
+/// File name of a portable catalog, kept in the output directory itself. Outputs in it are
+/// stored relative to that directory, so machines mounting the share at different paths
+/// (a desktop's `Z:\clips`, a Deck's `/mnt/nas/clips`) read the same records.
+pub const PORTABLE_FILE: &str = "steamclipconverter-catalog.jsonl";
+/// v1: bare records (no header). v2: header line first.
+const SCHEMA_VERSION: u32 = 2;
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    // Records are unchanged; v2 only adds the header line, written by `rewrite_migrated`.
+    apply: |records| records,
+}];
+
+/// What we know about one converted file.
+#[derive(Clone, Debug, Default)]
+pub struct CatalogEntry {
+    /// The fg_* folder the output was made from.
+    pub source: PathBuf,
+    /// Absolute path of the written file.
+    pub output: PathBuf,
+    pub appid: u32,
+    pub game: String,
+    pub date: String, // YYYYMMDD
+    pub time: String, // HHMMSS
+    pub size: u64,
+    /// Seconds, as ffprobe reported when the record was made (absent in older records).
+    pub duration: Option<f64>,
+    pub sha256: String,
+    /// RFC 3339 UTC timestamp of the conversion.
+    pub converted_at: String,
+    /// Steam's private/unlisted marking, if the clip had one. Anything that publishes
+    /// outputs must not make such a clip more visible than this.
+    pub visibility: Option<Visibility>,
+    /// Digest of the source segment set, taken just before `--delete-after` removed it.
+    pub source_digest: Option<TreeDigest>,
+    /// Outcome of `review`: "kept" or "deleted".
+    pub review: Option<String>,
+    /// Free-form tags given during `review`.
+    pub tags: Vec<String>,
+    /// `{seq}` of `--name-template`: the take number within the game and recording day.
+    pub seq: Option<u32>,
+    /// `--encrypt`: the age recipients the output was encrypted to (empty if it wasn't).
+    pub recipients: Vec<String>,
+}
+
+impl CatalogEntry {
+    /// Record for a freshly written output, hashing and probing the file.
+    pub fn for_output(
+        source: &Path,
+        output: &Path,
+        appid: u32,
+        game: &str,
+        date: &str,
+        time: &str,
+    ) -> io::Result<Self> {
+        let output = fs::canonicalize(output)?;
+        Ok(CatalogEntry {
+            source: fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf()),
+            size: fs::metadata(&output)?.len(),
+            duration: probe_duration(&output).ok(),
+            sha256: file_sha256(&output)?,
+            output,
+            appid,
+            game: game.to_string(),
+            date: date.to_string(),
+            time: time.to_string(),
+            converted_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            visibility: None,
+            source_digest: None,
+            review: None,
+            tags: Vec::new(),
+            seq: None,
+            recipients: Vec::new(),
+        })
+    }
+
+    /// The file was thrown away in `review`; nothing should bring it back.
+    pub fn review_deleted(&self) -> bool {
+        self.review.as_deref() == Some("deleted")
+    }
+
+    /// Serialize; with `base`, outputs under it are written relative to it.
+    fn to_json(&self, base: Option<&Path>) -> Value {
+        let output = base
+            .and_then(|b| self.output.strip_prefix(b).ok())
+            .unwrap_or(&self.output);
+        json::obj([
+            ("source", self.source.to_string_lossy().as_ref().into()),
+            ("output", output.to_string_lossy().as_ref().into()),
+            ("appid", self.appid.into()),
+            ("game", self.game.as_str().into()),
+            ("date", self.date.as_str().into()),
+            ("time", self.time.as_str().into()),
+            ("size", self.size.into()),
+            ("duration", self.duration.into()),
+            ("sha256", self.sha256.as_str().into()),
+            ("converted_at", self.converted_at.as_str().into()),
+            ("visibility", self.visibility.map(Visibility::as_str).into()),
+            (
+                "source_sha256",
+                self.source_digest
+                    .as_ref()
+                    .map(|d| d.sha256.as_str())
+                    .into(),
+            ),
+            (
+                "source_files",
+                self.source_digest.as_ref().map(|d| d.files).into(),
+            ),
+            ("review", self.review.as_deref().into()),
+            (
+                "tags",
+                Value::Arr(self.tags.iter().map(|t| t.as_str().into()).collect()),
+            ),
+            ("seq", self.seq.map(u64::from).into()),
+            (
+                "encryption",
+                (!self.recipients.is_empty()).then_some("age").into(),
+            ),
+            (
+                "recipients",
+                Value::Arr(self.recipients.iter().map(|r| r.as_str().into()).collect()),
+            ),
+        ])
+    }
+
+    /// Parse a record; relative outputs are resolved against `dir`, the catalog's folder.
+    fn from_json(v: &Value, dir: &Path) -> Option<Self> {
+        let output = v.get("output")?.as_str()?;
+        Some(CatalogEntry {
+            source: PathBuf::from(v.str_field("source")),
+            output: dir.join(output),
+            appid: v.get("appid").and_then(Value::as_u64).unwrap_or(0) as u32,
+            game: v.str_field("game").to_string(),
+            date: v.str_field("date").to_string(),
+            time: v.str_field("time").to_string(),
+            size: v.get("size").and_then(Value::as_u64).unwrap_or(0),
+            duration: v.get("duration").and_then(Value::as_f64),
+            sha256: v.str_field("sha256").to_string(),
+            converted_at: v.str_field("converted_at").to_string(),
+            visibility: Visibility::parse(v.str_field("visibility")),
+            source_digest: v
+                .get("source_sha256")
+                .and_then(Value::as_str)
+                .map(|h| TreeDigest {
+                    sha256: h.to_string(),
+                    files: v.get("source_files").and_then(Value::as_u64).unwrap_or(0),
+                }),
+            review: v.get("review").and_then(Value::as_str).map(str::to_string),
+            tags: match v.get("tags") {
+                Some(Value::Arr(tags)) => tags
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect(),
+                _ => Vec::new(),
+            },
+            seq: v.get("seq").and_then(Value::as_u64).map(|n| n as u32),
+            recipients: match v.get("recipients") {
+                Some(Value::Arr(rs)) => rs
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect(),
+                _ => Vec::new(),
+            },
+        })
+    }
+}
+
+pub struct Catalog {
+    path: PathBuf,
+    entries: Vec<CatalogEntry>,
+    /// Set for portable catalogs: the (canonical) folder outputs are stored relative to.
+    base: Option<PathBuf>,
+}
+
+impl Catalog {
+    /// `<data dir>/catalog.jsonl`
+    pub fn default_path() -> Option<PathBuf> {
+        Some(appdirs::user_data_dir()?.join("catalog.jsonl"))
+    }
+
+    /// Load the catalog at `path`; a missing file is an empty catalog.
+    ///
+    /// Catalogs written by older releases are migrated in place (keeping a `.bak` copy);
+    /// catalogs from newer releases are refused with `InvalidData`.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let txt = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut lines = txt.lines().filter(|l| !l.trim().is_empty()).peekable();
+        let header = lines.peek().and_then(|l| json::parse(l).ok());
+        let version = match header
+            .as_ref()
+            .and_then(|h| schema::header_version(SCHEMA_KIND, h))
+        {
+            Some(v) => {
+                lines.next();
+                v
+            }
+            // Headerless files predate versioning; an empty file is simply current.
+            None if txt.trim().is_empty() => SCHEMA_VERSION,
+            None => 1,
+        };
+
+        let mut records = Vec::new();
+        let mut unreadable = Vec::new();
+        for line in lines {
+            match json::parse(line) {
+                Ok(v) => records.push(v),
+                Err(_) => unreadable.push(line.to_string()),
+            }
+        }
+        if !unreadable.is_empty() {
+            eprintln!(
+                "[warn] {}: ignoring {} unreadable catalog record(s)",
+                path.display(),
+                unreadable.len()
+            );
+        }
+
+        let records = schema::migrate(SCHEMA_KIND, version, SCHEMA_VERSION, MIGRATIONS, records)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if version < SCHEMA_VERSION {
+            rewrite_migrated(path, &records, &unreadable)?;
+            eprintln!(
+                "[info] migrated catalog {} from schema v{} to v{}",
+                path.display(),
+                version,
+                SCHEMA_VERSION
+            );
+        }
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let portable = path.file_name().is_some_and(|n| n == PORTABLE_FILE);
+        Ok(Catalog {
+            path: path.to_path_buf(),
+            entries: records
+                .iter()
+                .filter_map(|r| CatalogEntry::from_json(r, &dir))
+                .collect(),
+            base: portable.then_some(dir),
+        })
+    }
+
+    /// Latest record of the recording `appid`/`date`/`time`, whichever machine converted it.
+    pub fn find_recording(&self, appid: u32, date: &str, time: &str) -> Option<&CatalogEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.appid == appid && e.date == date && e.time == time)
+    }
+
+    /// Highest `{seq}` given to a recording of `appid` on `date`, counting every record so
+    /// numbers of deleted outputs aren't handed out again.
+    pub fn last_seq(&self, appid: u32, date: &str) -> Option<u32> {
+        self.entries
+            .iter()
+            .filter(|e| e.appid == appid && e.date == date)
+            .filter_map(|e| e.seq)
+            .max()
+    }
+
+    /// Latest record per output, in first-seen order.
+    pub fn entries(&self) -> Vec<&CatalogEntry> {
+        let mut index: HashMap<&Path, usize> = HashMap::new();
+        let mut out: Vec<&CatalogEntry> = Vec::new();
+        for e in &self.entries {
+            match index.get(e.output.as_path()) {
+                Some(&i) => out[i] = e,
+                None => {
+                    index.insert(&e.output, out.len());
+                    out.push(e);
+                }
+            }
+        }
+        out
+    }
+
+    /// Latest record for an output file.
+    pub fn find_output(&self, output: &Path) -> Option<&CatalogEntry> {
+        let output = fs::canonicalize(output).unwrap_or_else(|_| output.to_path_buf());
+        self.entries.iter().rev().find(|e| e.output == output)
+    }
+
+    /// Append a record and persist it.
+    pub fn append(&mut self, entry: CatalogEntry) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut f = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        if f.metadata()?.len() == 0 {
+            writeln!(f, "{}", schema::header(SCHEMA_KIND, SCHEMA_VERSION))?;
+        } else {
+            // A line torn by a crash must not swallow the new record too.
+            let mut last = [0u8];
+            f.seek(SeekFrom::End(-1))?;
+            f.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                writeln!(f)?;
+            }
+        }
+        writeln!(f, "{}", entry.to_json(self.base.as_deref()))?;
+        f.sync_data()?;
+        self.entries.push(entry);
+        Ok(())
+    }
+}
+
+/// Replace `path` with the migrated records, keeping the original as `<name>.bak`.
+fn rewrite_migrated(path: &Path, records: &[Value], unreadable: &[String]) -> io::Result<()> {
+    let mut out = format!("{}\n", schema::header(SCHEMA_KIND, SCHEMA_VERSION));
+    for r in records {
+        out.push_str(&r.to_string());
+        out.push('\n');
+    }
+    // Lines we couldn't parse are carried over verbatim rather than dropped.
+    for line in unreadable {
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    let mut bak = path.as_os_str().to_owned();
+    bak.push(".bak");
+    fs::copy(path, &bak)?;
+    atomic::write(path, out)
+}
+
+/// What `repair` did to a catalog file.
+pub struct Repaired {
+    pub records: usize,
+    /// Lines that weren't JSON (torn writes), now in `<name>.bad`.
+    pub damaged: usize,
+    /// The file didn't end with a newline.
+    pub truncated: bool,
+}
+
+/// Set aside the lines of the catalog at `path` that can't be read (appending them to
+/// `<name>.bad`, so nothing is lost) and rewrite it with the rest. A file that needs
+/// nothing is left untouched.
+pub fn repair(path: &Path) -> io::Result<Repaired> {
+    let txt = fs::read_to_string(path)?;
+    let (mut kept, mut bad) = (String::new(), String::new());
+    let (mut records, mut damaged) = (0, 0);
+    for line in txt.lines().filter(|l| !l.trim().is_empty()) {
+        match json::parse(line) {
+            Ok(v) => {
+                if schema::header_version(SCHEMA_KIND, &v).is_none() {
+                    records += 1;
+                }
+                kept.push_str(line);
+                kept.push('\n');
+            }
+            Err(_) => {
+                damaged += 1;
+                bad.push_str(line);
+                bad.push('\n');
+            }
+        }
+    }
+    let truncated = !txt.is_empty() && !txt.ends_with('\n');
+    if damaged > 0 {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".bad");
+        let mut f = OpenOptions::new().create(true).append(true).open(name)?;
+        f.write_all(bad.as_bytes())?;
+        f.sync_all()?;
+    }
+    if damaged > 0 || truncated {
+        atomic::write(path, kept)?;
+    }
+    Ok(Repaired {
+        records,
+        damaged,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scc-catalog-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A v1 catalog: records without a header line, the first as the earliest releases
+    /// wrote them (no duration, digest, review or tags), then a damaged line.
+    const V1: &str = concat!(
+        r#"{"source":"/rec/fg_570_20250101_100000","output":"/out/Dota 2-20250101-100000.mp4","appid":570,"game":"Dota 2","date":"20250101","time":"100000","size":1234,"sha256":"ab12","converted_at":"2025-01-01T10:05:00Z"}"#,
+        "\n",
+        r#"{"source":"/rec/fg_570_20250102_100000","output":"/out/b.mp4","appid":570,"game":"Dota 2","date":"20250102","time":"100000","size":99,"duration":61.5,"sha256":"cd34","converted_at":"2025-01-02T10:05:00Z","visibility":"private","source_sha256":"ef56","source_files":12,"review":"kept","tags":["ace"],"seq":2}"#,
+        "\n",
+        "{not json\n",
+    );
+
+    #[test]
+    fn v1_is_migrated_to_v2_with_its_fields() {
+        let dir = scratch("v1");
+        let path = dir.join("catalog.jsonl");
+        fs::write(&path, V1).unwrap();
+        let cat = Catalog::open(&path).unwrap();
+
+        let old = cat.find_recording(570, "20250101", "100000").unwrap();
+        assert_eq!(old.output, Path::new("/out/Dota 2-20250101-100000.mp4"));
+        assert_eq!(old.source, Path::new("/rec/fg_570_20250101_100000"));
+        assert_eq!((old.size, old.sha256.as_str()), (1234, "ab12"));
+        assert_eq!(old.duration, None);
+        assert_eq!(old.visibility, None);
+        assert!(old.source_digest.is_none() && old.review.is_none() && old.tags.is_empty());
+        assert_eq!(old.seq, None);
+
+        let new = cat.find_recording(570, "20250102", "100000").unwrap();
+        assert_eq!(new.duration, Some(61.5));
+        assert_eq!(new.visibility, Some(Visibility::Private));
+        assert_eq!(
+            new.source_digest,
+            Some(TreeDigest {
+                sha256: "ef56".into(),
+                files: 12
+            })
+        );
+        assert_eq!(new.review.as_deref(), Some("kept"));
+        assert_eq!(new.tags, ["ace"]);
+        assert_eq!(new.seq, Some(2));
+
+        // Rewritten with the v2 header, the damaged line kept, the original backed up.
+        let text = fs::read_to_string(&path).unwrap();
+        let mut lines = text.lines();
+        let header = json::parse(lines.next().unwrap()).unwrap();
+        assert_eq!(schema::header_version(SCHEMA_KIND, &header), Some(2));
+        assert_eq!(lines.clone().count(), 3);
+        assert_eq!(lines.last(), Some("{not json"));
+        assert_eq!(
+            fs::read_to_string(dir.join("catalog.jsonl.bak")).unwrap(),
+            V1
+        );
+
+        // Opening the migrated file changes nothing more.
+        let _ = fs::remove_file(dir.join("catalog.jsonl.bak"));
+        assert_eq!(Catalog::open(&path).unwrap().entries().len(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+        assert!(!dir.join("catalog.jsonl.bak").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_file_is_current_and_newer_versions_are_refused() {
+        let dir = scratch("versions");
+        let path = dir.join("catalog.jsonl");
+        fs::write(&path, "").unwrap();
+        assert!(Catalog::open(&path).unwrap().entries().is_empty());
+        assert!(!dir.join("catalog.jsonl.bak").exists());
+
+        let v3 = format!(
+            "{}\n{}\n",
+            schema::header(SCHEMA_KIND, SCHEMA_VERSION + 1),
+            r#"{"output":"/out/a.mp4"}"#
+        );
+        fs::write(&path, &v3).unwrap();
+        let err = Catalog::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read_to_string(&path).unwrap(), v3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+//! Client for the agent's HTTP API (`steamclipconverter agent`), following the contract in
+//! `openapi.yaml` at the root of the repository: one method per operation, one type per
+//! schema. `pull` uses it too.
+//!
+//! ```no_run
+//! use steamclipconverter::client::Client;
+//!
+//! let agent = Client::new("deck.local:47800").with_token("s3cret");
+//! for clip in agent.list_clips()?.clips {
+//!     println!("{} ready={}", clip.id, clip.ready);
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::{
+    http::{self, ClientResponse, percent_encode},
+    json::Value,
+};
+use std::io::{self, Read};
+
+/// `ClipList`
+#[derive(Clone, Debug)]
+pub struct ClipList {
+    pub agent: String,
+    pub version: u64,
+    pub host: String,
+    pub clips: Vec<Clip>,
+}
+
+/// `Clip`
+#[derive(Clone, Debug)]
+pub struct Clip {
+    /// Folder name, `fg_<appid>_<YYYYMMDD>_<HHMMSS>`.
+    pub id: String,
+    pub appid: u32,
+    pub game: Option<String>,
+    /// YYYYMMDD
+    pub date: String,
+    /// HHMMSS
+    pub time: String,
+    pub ready: bool,
+    /// Skip code when not ready.
+    pub reason: Option<String>,
+    pub files: Vec<ClipFile>,
+}
+
+/// `ClipFile`
+#[derive(Clone, Debug)]
+pub struct ClipFile {
+    /// `/`-separated, relative to the clip folder.
+    pub path: String,
+    pub size: u64,
+    /// Unix seconds.
+    pub mtime: i64,
+}
+
+/// One entry of `ChecksumList`.
+#[derive(Clone, Debug)]
+pub struct FileChecksum {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// A file being downloaded by [`Client::download_file`].
+pub struct Download {
+    /// Whether the agent honoured `from` (206); otherwise the body starts at byte 0.
+    pub partial: bool,
+    pub body: Box<dyn Read + Send>,
+}
+
+/// Talks to one agent.
+#[derive(Clone, Debug)]
+pub struct Client {
+    authority: String,
+    credentials: Option<String>,
+}
+
+fn size(v: &Value, key: &str) -> u64 {
+    v.get(key).and_then(Value::as_u64).unwrap_or(0)
+}
+
+fn items<'v>(v: &'v Value, key: &str) -> &'v [Value] {
+    v.get(key).and_then(Value::as_arr).unwrap_or(&[])
+}
+
+fn clip_from(c: &Value) -> Clip {
+    Clip {
+        id: c.str_field("id").to_string(),
+        appid: u32::try_from(size(c, "appid")).unwrap_or(0),
+        game: c.get("game").and_then(Value::as_str).map(str::to_string),
+        date: c.str_field("date").to_string(),
+        time: c.str_field("time").to_string(),
+        ready: c.get("ready") == Some(&Value::Bool(true)),
+        reason: c.get("reason").and_then(Value::as_str).map(str::to_string),
+        files: items(c, "files")
+            .iter()
+            .map(|f| ClipFile {
+                path: f.str_field("path").to_string(),
+                size: size(f, "size"),
+                mtime: f.get("mtime").and_then(Value::as_f64).unwrap_or(0.0) as i64,
+            })
+            .collect(),
+    }
+}
+
+impl Client {
+    /// `authority` is `host:port`.
+    pub fn new(authority: impl Into<String>) -> Client {
+        Client {
+            authority: authority.into(),
+            credentials: None,
+        }
+    }
+
+    /// Send `Authorization: Bearer <token>`.
+    pub fn with_token(mut self, token: &str) -> Client {
+        self.credentials = Some(format!("Bearer {token}"));
+        self
+    }
+
+    /// Send HTTP basic auth.
+    pub fn with_basic_auth(mut self, user: &str, password: &str) -> Client {
+        let pair = format!("{user}:{password}");
+        self.credentials = Some(format!("Basic {}", http::base64_encode(pair.as_bytes())));
+        self
+    }
+
+    pub fn authority(&self) -> &str {
+        &self.authority
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, String)],
+    ) -> io::Result<ClientResponse> {
+        let mut headers = headers.to_vec();
+        if let Some(c) = &self.credentials {
+            headers.push(("Authorization", c.clone()));
+        }
+        let resp = http::request(&self.authority, method, path, &headers)?;
+        if resp.status == 401 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the agent wants credentials",
+            ));
+        }
+        Ok(resp)
+    }
+
+    /// `listClips`
+    pub fn list_clips(&self) -> io::Result<ClipList> {
+        let v = self.call("GET", "/api/clips", &[])?.json()?;
+        if v.str_field("agent") != "steamclipconverter" {
+            return Err(io::Error::other(format!(
+                "{} is not a steamclipconverter agent",
+                self.authority
+            )));
+        }
+        Ok(ClipList {
+            agent: v.str_field("agent").to_string(),
+            version: size(&v, "version"),
+            host: v.str_field("host").to_string(),
+            clips: items(&v, "clips").iter().map(clip_from).collect(),
+        })
+    }
+
+    /// `clipChecksums`
+    pub fn clip_checksums(&self, id: &str) -> io::Result<Vec<FileChecksum>> {
+        let path = format!("/api/clips/{}/checksums", percent_encode(id));
+        let v = self.call("GET", &path, &[])?.json()?;
+        Ok(items(&v, "files")
+            .iter()
+            .map(|f| FileChecksum {
+                path: f.str_field("path").to_string(),
+                size: size(f, "size"),
+                sha256: f.str_field("sha256").to_string(),
+            })
+            .collect())
+    }
+
+    /// `downloadFile`, from byte `from` on (0 for the whole file).
+    pub fn download_file(&self, id: &str, path: &str, from: u64) -> io::Result<Download> {
+        let url = format!(
+            "/api/clips/{}/files/{}",
+            percent_encode(id),
+            path.split('/')
+                .map(percent_encode)
+                .collect::<Vec<_>>()
+                .join("/")
+        );
+        let range = [("Range", format!("bytes={from}-"))];
+        let headers: &[(&str, String)] = if from > 0 { &range } else { &[] };
+        let resp = self.call("GET", &url, headers)?;
+        match resp.status {
+            200 | 206 => Ok(Download {
+                partial: resp.status == 206,
+                body: Box::new(resp.body),
+            }),
+            status => Err(resp
+                .json()
+                .err()
+                .unwrap_or_else(|| io::Error::other(format!("HTTP {status}")))),
+        }
+    }
+
+    /// `deleteClip`
+    pub fn delete_clip(&self, id: &str, sha256: &str) -> io::Result<()> {
+        let path = format!(
+            "/api/clips/{}?sha256={}",
+            percent_encode(id),
+            percent_encode(sha256)
+        );
+        self.call("DELETE", &path, &[])?.json().map(|_| ())
+    }
+
+    /// `openApi`: the contract this client follows, as served by the agent.
+    pub fn open_api(&self) -> io::Result<String> {
+        let resp = self.call("GET", "/api/openapi.yaml", &[])?;
+        if resp.status != 200 {
+            return Err(io::Error::other(format!("HTTP {}", resp.status)));
+        }
+        resp.text()
+    }
+}
@@ -0,0 +1,60 @@
+//! `config.toml` in the per-user config directory (or `--config`): settings that don't fit
+//! on a command line. For now that is the `[[postprocess]]` chain (see `postprocess.rs`).
+
+use crate::{json::Value, postprocess::Chain, toml};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use steamclipconverter::appdirs;
+
+pub const FILE_NAME: &str = "config.toml";
+
+#[derive(Default)]
+pub struct Config {
+    pub postprocess: Chain,
+    /// Player for `--play` (`player = "mpv"`).
+    pub player: Option<String>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        appdirs::user_config_dir().map(|d| d.join(FILE_NAME))
+    }
+
+    /// Read `path`, or the default location when None. Only a missing default file is
+    /// fine (an empty config); a missing `--config` is an error.
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let (path, explicit) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(p) => (p, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !explicit => {
+                return Ok(Config::default());
+            }
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let at = |e: String| format!("{}: {}", path.display(), e);
+        let doc = toml::parse(&text).map_err(at)?;
+        let Value::Obj(fields) = &doc else {
+            return Ok(Config::default());
+        };
+
+        let mut config = Config::default();
+        for (key, v) in fields {
+            match (key.as_str(), v) {
+                ("postprocess", Value::Arr(tables)) => {
+                    config.postprocess = Chain::from_config(tables).map_err(at)?
+                }
+                ("player", Value::Str(p)) => config.player = Some(p.clone()),
+                (k, _) => return Err(at(format!("unsupported or mistyped key '{k}'"))),
+            }
+        }
+        Ok(config)
+    }
+}
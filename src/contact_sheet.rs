@@ -0,0 +1,163 @@
+//! `--contact-sheet`: one image of the run's new clips, a frame of each in a grid under a
+//! label of its game, record date and length, as a quick look at what a night of
+//! playing produced.
+//!
+//! Frames are taken from the outputs when the run is done, a tenth of the way in, and put
+//! together with ffmpeg's `tile` filter. The labels need an ffmpeg with `drawtext`
+//! (libfreetype); without it the sheet is made unlabelled.
+
+use chrono::{DateTime, Local, Utc};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+use steamclipconverter::ffmpeg::file_arg;
+
+/// Size of one frame on the sheet; frames of another shape are letterboxed.
+const TILE_W: u32 = 320;
+const TILE_H: u32 = 180;
+/// Frames per row, fewer when the run converted fewer clips.
+const COLUMNS: usize = 4;
+
+struct Tile {
+    video: PathBuf,
+    label: String,
+    start: Option<DateTime<Utc>>,
+    duration: Option<f64>,
+}
+
+pub struct ContactSheet {
+    path: PathBuf,
+    tiles: Mutex<Vec<Tile>>,
+}
+
+impl ContactSheet {
+    pub fn new(path: &Path) -> ContactSheet {
+        ContactSheet {
+            path: path.to_path_buf(),
+            tiles: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Put the converted `video` on the sheet.
+    pub fn add(
+        &self,
+        video: &Path,
+        game: &str,
+        start: Option<DateTime<Utc>>,
+        duration: Option<f64>,
+    ) {
+        let mut label = game.to_string();
+        if let Some(start) = start {
+            label.push_str(
+                &start
+                    .with_timezone(&Local)
+                    .format("  %Y-%m-%d %H:%M")
+                    .to_string(),
+            );
+        }
+        if let Some(secs) = duration {
+            let s = secs.round() as u64;
+            label.push_str(&format!("  {}:{:02}", s / 60, s % 60));
+        }
+        self.tiles.lock().unwrap().push(Tile {
+            video: video.to_path_buf(),
+            label,
+            start,
+            duration,
+        });
+    }
+
+    /// Render the sheet, in the order the clips were recorded. Ok(0) when the run
+    /// converted nothing and there is no sheet to make.
+    pub fn write(&self) -> Result<usize, String> {
+        let mut tiles = std::mem::take(&mut *self.tiles.lock().unwrap());
+        if tiles.is_empty() {
+            return Ok(0);
+        }
+        tiles.sort_by_key(|t| t.start);
+        let work =
+            std::env::temp_dir().join(format!("steamclipconverter-sheet-{}", std::process::id()));
+        fs::create_dir_all(&work).map_err(|e| format!("cannot create {}: {e}", work.display()))?;
+        let result = render(&tiles, &work, &self.path);
+        let _ = fs::remove_dir_all(&work);
+        result.map(|()| tiles.len())
+    }
+}
+
+fn render(tiles: &[Tile], work: &Path, sheet: &Path) -> Result<(), String> {
+    let mut labelled = true;
+    for (i, tile) in tiles.iter().enumerate() {
+        let name = format!("{i:04}");
+        fs::write(work.join(format!("{name}.txt")), &tile.label)
+            .map_err(|e| format!("cannot write label: {e}"))?;
+        let at = tile.duration.map_or(1.0, |d| d / 10.0);
+        let frame = |label: bool| {
+            let mut vf = format!(
+                "scale={TILE_W}:{TILE_H}:force_original_aspect_ratio=decrease,\
+                 pad={TILE_W}:{TILE_H}:(ow-iw)/2:(oh-ih)/2"
+            );
+            if label {
+                // Read from a file next to the frame, so nothing in it needs escaping.
+                vf.push_str(&format!(
+                    ",drawtext=textfile={name}.txt:expansion=none:fontsize=14:fontcolor=white:\
+                     box=1:boxcolor=black@0.6:boxborderw=4:x=6:y=h-th-8"
+                ));
+            }
+            let mut cmd = Command::new("ffmpeg");
+            cmd.current_dir(work)
+                .args(["-hide_banner", "-loglevel", "error", "-y"])
+                .args(["-ss", &format!("{at:.3}")])
+                .arg("-i")
+                .arg(file_arg(
+                    &std::path::absolute(&tile.video).unwrap_or(tile.video.clone()),
+                ))
+                .args(["-frames:v", "1", "-vf", &vf])
+                .arg(format!("{name}.png"));
+            ffmpeg(cmd)
+        };
+        if labelled && frame(true).is_err() {
+            // Most likely an ffmpeg without drawtext: carry on without labels.
+            labelled = false;
+        }
+        if !labelled {
+            frame(false)
+                .map_err(|e| format!("cannot take a frame of {}: {e}", tile.video.display()))?;
+        }
+    }
+
+    let columns = tiles.len().min(COLUMNS);
+    let rows = tiles.len().div_ceil(columns);
+    let mut cmd = Command::new("ffmpeg");
+    cmd.current_dir(work)
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(["-framerate", "1", "-i", "%04d.png"])
+        .args([
+            "-vf",
+            &format!("tile={columns}x{rows}:padding=4:margin=4"),
+            "-frames:v",
+            "1",
+        ])
+        .arg(file_arg(
+            &std::path::absolute(sheet).unwrap_or(sheet.to_path_buf()),
+        ));
+    ffmpeg(cmd)
+}
+
+fn ffmpeg(mut cmd: Command) -> Result<(), String> {
+    let out = cmd
+        .output()
+        .map_err(|e| format!("cannot run ffmpeg: {e}"))?;
+    if out.status.success() {
+        Ok(())
+    } else {
+        let err = String::from_utf8_lossy(&out.stderr);
+        Err(err.lines().next().unwrap_or("ffmpeg failed").to_string())
+    }
+}
@@ -0,0 +1,594 @@
+//! Converting clips, with cancellation and progress reporting for embedders: one at a time
+//! with `Converter::convert`, or a whole scan with `Converter::convert_all`, which names the
+//! outputs, applies the filters and can delete what it converted, like the CLI does.
+
+use crate::{
+    ClipDir, Error, atomic,
+    ffmpeg::{RemuxOptions, remux_command},
+    native,
+    probe::probe_duration,
+    sha256::{TreeDigest, tree_sha256},
+    ssh::SshRemote,
+    steam::{self, KeepReason},
+    usage,
+};
+use sanitize_filename::sanitize;
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How many trailing ffmpeg stderr lines are kept in `Error::Ffmpeg`.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Shared flag an embedding application flips to stop a running conversion.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// What writes the output: ffmpeg, or the built-in remuxer that needs no external program
+/// but only copies streams.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Ffmpeg,
+    Native,
+}
+
+impl Backend {
+    /// `ffmpeg` or `native`; used as the clap value parser of `--backend`.
+    pub fn parse(s: &str) -> Result<Backend, String> {
+        match s {
+            "ffmpeg" => Ok(Backend::Ffmpeg),
+            "native" => Ok(Backend::Native),
+            _ => Err(format!("unknown backend '{s}' (ffmpeg, native)")),
+        }
+    }
+}
+
+/// What the converter is doing right now.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading the MPD to learn the clip's duration.
+    Probing,
+    /// The output is being written.
+    Remuxing,
+    /// Output written; conversion done.
+    Finished,
+}
+
+/// One progress update.
+#[derive(Clone, Debug)]
+pub struct Progress {
+    pub phase: Phase,
+    /// 0–100, when the clip duration is known.
+    pub percent: Option<f32>,
+    /// Estimated time left in the current clip.
+    pub eta: Option<Duration>,
+}
+
+/// Receives progress updates; implemented for any `FnMut(&Progress)`.
+pub trait ProgressCallback {
+    fn on_progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> ProgressCallback for F {
+    fn on_progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// What happened to one clip during `Converter::convert_all`.
+#[derive(Debug)]
+pub enum ClipEvent<'a> {
+    /// The clip is about to be converted to `output`.
+    Started {
+        clip: &'a ClipDir,
+        output: &'a Path,
+    },
+    Progress {
+        clip: &'a ClipDir,
+        progress: &'a Progress,
+    },
+    Converted {
+        clip: &'a ClipDir,
+        output: &'a Path,
+    },
+    /// Not converted: `filtered` (game id or unknown game) or `exists` (output already there).
+    Skipped {
+        clip: &'a ClipDir,
+        reason: &'static str,
+    },
+    Failed {
+        clip: &'a ClipDir,
+        error: &'a Error,
+    },
+    /// A folder removed by `delete_after`: the `fg_*` folder, with the hash of its segments
+    /// taken just before (to record what the output was made from), then possibly its
+    /// `clip_*` parent.
+    Deleted {
+        clip: &'a ClipDir,
+        path: &'a Path,
+        digest: Option<&'a TreeDigest>,
+    },
+    /// Converted, but not deleted by `delete_after` for this reason.
+    Kept {
+        clip: &'a ClipDir,
+        reason: KeepReason,
+    },
+}
+
+/// Receives per-clip events; implemented for any `FnMut(&ClipEvent)`.
+pub trait ClipCallback {
+    fn on_clip(&mut self, event: &ClipEvent);
+}
+
+impl<F: FnMut(&ClipEvent)> ClipCallback for F {
+    fn on_clip(&mut self, event: &ClipEvent) {
+        self(event)
+    }
+}
+
+/// Counts returned by `Converter::convert_all`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub converted: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Converts clips with a fixed set of ffmpeg options.
+#[derive(Clone, Debug, Default)]
+pub struct Converter {
+    options: RemuxOptions,
+    backend: Backend,
+    cancel: Option<CancellationToken>,
+    stderr: Option<Arc<Mutex<Vec<u8>>>>,
+    cpu_time: Option<Arc<Mutex<Option<Duration>>>>,
+    output_dir: Option<PathBuf>,
+    delete_after: bool,
+    allow_delete_under: Vec<PathBuf>,
+    game_ids: Vec<u32>,
+    skip_unknown_app: bool,
+    steamapps_roots: Option<Vec<PathBuf>>,
+    remote: Option<SshRemote>,
+}
+
+impl Converter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How streams are copied/transcoded (default: stream copy).
+    pub fn options(mut self, options: RemuxOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Who writes the output (default: ffmpeg).
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Run ffmpeg on this machine instead, copying each clip there and the output back.
+    pub fn remote(mut self, remote: SshRemote) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Stop (and clean up) as soon as `token` is cancelled.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Collect ffmpeg's messages into `buf` instead of passing them through to stderr, e.g.
+    /// to print them with the clip they belong to when several convert at once.
+    pub fn capture_stderr(mut self, buf: Arc<Mutex<Vec<u8>>>) -> Self {
+        self.stderr = Some(buf);
+        self
+    }
+
+    /// Add the CPU time (user + system) each ffmpeg run takes to `total`. Left alone where it
+    /// can't be measured: the native backend, a `remote` ffmpeg, platforms without a way to
+    /// read it.
+    pub fn cpu_time(mut self, total: Arc<Mutex<Option<Duration>>>) -> Self {
+        self.cpu_time = Some(total);
+        self
+    }
+
+    /// Where `convert_all` writes its outputs (default: the current directory).
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Have `convert_all` delete each clip folder it converted successfully, and its
+    /// `clip_*` parent once nothing else is left in it. Only folders inside Steam's
+    /// `gamerecordings` (or `allow_delete_under`) whose segments could be hashed go; the
+    /// others are reported as `Kept`.
+    pub fn delete_after(mut self, yes: bool) -> Self {
+        self.delete_after = yes;
+        self
+    }
+
+    /// Folders outside Steam's `gamerecordings` that `delete_after` may delete from too.
+    pub fn allow_delete_under(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.allow_delete_under = dirs;
+        self
+    }
+
+    /// Only convert clips of these AppIDs (default: all).
+    pub fn game_ids(mut self, ids: impl IntoIterator<Item = u32>) -> Self {
+        self.game_ids = ids.into_iter().collect();
+        self
+    }
+
+    /// Leave out clips recorded without a game.
+    pub fn skip_unknown_app(mut self, yes: bool) -> Self {
+        self.skip_unknown_app = yes;
+        self
+    }
+
+    /// Where game names are looked up (default: `steam::AppNames::discover()`, this
+    /// machine's libraries and `appnames.txt`).
+    pub fn steamapps_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.steamapps_roots = Some(roots);
+        self
+    }
+
+    /// Convert every clip that passes the filters to `<output_dir>/<Game>-YYYYMMDD-HHMMSS.mp4`
+    /// (the AppID when the name is unknown), reporting each step to `on_clip`. Outputs that
+    /// already exist are skipped, and a failed clip doesn't stop the others; only
+    /// cancellation does.
+    pub fn convert_all(&self, clips: &[ClipDir], on_clip: &mut dyn ClipCallback) -> Summary {
+        let names = match &self.steamapps_roots {
+            Some(roots) => steam::AppNames::new(roots.clone()),
+            None => steam::AppNames::discover(),
+        };
+        let out_dir = self
+            .output_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("."));
+        let mut summary = Summary::default();
+        for clip in clips {
+            if self.is_cancelled() {
+                break;
+            }
+            let reason = if (!self.game_ids.is_empty() && !self.game_ids.contains(&clip.appid))
+                || (self.skip_unknown_app && clip.is_unknown_app())
+            {
+                Some("filtered")
+            } else {
+                None
+            };
+            let game = names
+                .resolve(clip.appid)
+                .unwrap_or_else(|| clip.appid.to_string());
+            let output = out_dir.join(format!(
+                "{}-{}-{}.{}",
+                sanitize(&game),
+                clip.date,
+                clip.time,
+                self.options.container.extension()
+            ));
+            let reason = reason.or(output.exists().then_some("exists"));
+            if let Some(reason) = reason {
+                summary.skipped += 1;
+                on_clip.on_clip(&ClipEvent::Skipped { clip, reason });
+                continue;
+            }
+
+            on_clip.on_clip(&ClipEvent::Started {
+                clip,
+                output: &output,
+            });
+            let result = self.convert(clip, &output, &mut |progress: &Progress| {
+                on_clip.on_clip(&ClipEvent::Progress { clip, progress })
+            });
+            if let Err(error) = result {
+                summary.failed += 1;
+                on_clip.on_clip(&ClipEvent::Failed {
+                    clip,
+                    error: &error,
+                });
+                continue;
+            }
+            summary.converted += 1;
+            on_clip.on_clip(&ClipEvent::Converted {
+                clip,
+                output: &output,
+            });
+
+            if self.delete_after {
+                let digest = tree_sha256(&clip.dir).ok();
+                if let Err(reason) =
+                    steam::delete_check(&clip.dir, &self.allow_delete_under, Some(digest.is_some()))
+                {
+                    on_clip.on_clip(&ClipEvent::Kept { clip, reason });
+                    continue;
+                }
+                let removed = fs::remove_dir_all(&clip.dir)
+                    .map_err(|e| Error::io(&clip.dir, e))
+                    .and_then(|()| {
+                        on_clip.on_clip(&ClipEvent::Deleted {
+                            clip,
+                            path: &clip.dir,
+                            digest: digest.as_ref(),
+                        });
+                        steam::remove_clip_parent(clip).map_err(|e| Error::io(&clip.dir, e))
+                    });
+                match removed {
+                    Ok(Some(parent)) => on_clip.on_clip(&ClipEvent::Deleted {
+                        clip,
+                        path: &parent,
+                        digest: None,
+                    }),
+                    Ok(None) => {}
+                    Err(error) => on_clip.on_clip(&ClipEvent::Failed {
+                        clip,
+                        error: &error,
+                    }),
+                }
+            }
+        }
+        summary
+    }
+
+    /// Convert `clip` to `out_path`, reporting progress as the output is written.
+    ///
+    /// The file is written as `<name>.partial.<ext>` and renamed once complete, so an
+    /// interrupted conversion never leaves a truncated file under the output's name.
+    pub fn convert(
+        &self,
+        clip: &ClipDir,
+        out_path: &Path,
+        progress: &mut dyn ProgressCallback,
+    ) -> Result<(), Error> {
+        let partial = atomic::partial_path(out_path);
+        // Previews are written under the partial name too, and renamed with the output.
+        let previews: Vec<(PathBuf, PathBuf)> = match &self.options.previews {
+            Some(p) => p
+                .paths(&partial)
+                .into_iter()
+                .zip(p.paths(out_path))
+                .collect(),
+            None => Vec::new(),
+        };
+        let result = self
+            .write(clip, &partial, progress)
+            .and_then(|()| atomic::commit(&partial, out_path).map_err(|e| Error::io(out_path, e)));
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        for (written, path) in previews {
+            // A clip shorter than the poster time has no poster.
+            if result.is_err() || !written.is_file() {
+                let _ = fs::remove_file(&written);
+            } else if let Err(e) = atomic::commit(&written, &path) {
+                let _ = fs::remove_file(&written);
+                return Err(Error::io(&path, e));
+            }
+        }
+        result
+    }
+
+    fn write(
+        &self,
+        clip: &ClipDir,
+        out_path: &Path,
+        progress: &mut dyn ProgressCallback,
+    ) -> Result<(), Error> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let mpd = clip.dir.join("session.mpd");
+        if !mpd.is_file() {
+            return Err(Error::InvalidClip {
+                path: clip.dir.clone(),
+                reason: "missing session.mpd".into(),
+            });
+        }
+        if self.backend == Backend::Native {
+            return self.convert_native(clip, out_path, progress);
+        }
+        progress.on_progress(&Progress {
+            phase: Phase::Probing,
+            percent: None,
+            eta: None,
+        });
+        // Progress is best-effort: without a duration we still report the phase.
+        let total = probe_duration(&mpd)
+            .ok()
+            .map(|d| {
+                let end = self.options.trim_end.map_or(d, |e| e.min(d));
+                end - self.options.trim_start.unwrap_or(0.0)
+            })
+            .filter(|d| *d > 0.0);
+
+        let mut opts = self.options.clone();
+        opts.progress_pipe = true;
+        if let Some(p) = &mut opts.previews {
+            p.duration = p.duration.or(total);
+        }
+        // On a remote machine the output is written in the work folder and fetched at the end.
+        let remote = match &self.remote {
+            Some(r) => Some((r, r.upload(&clip.dir)?)),
+            None => None,
+        };
+        let remote_name = format!(
+            "out.{}",
+            out_path.extension().unwrap_or_default().to_string_lossy()
+        );
+        let mut command = match &remote {
+            Some((r, work)) => r.command(
+                work,
+                &remux_command(Path::new(&work.path), Path::new(&remote_name), &opts),
+            ),
+            None => remux_command(&clip.dir, out_path, &opts),
+        };
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|source| Error::Spawn {
+                program: "ffmpeg".into(),
+                source,
+            })?;
+
+        // Pass ffmpeg's messages through as they come (or into the capture buffer), keeping
+        // the tail for the error value. Bytes are forwarded unchanged so a `-stats` line can
+        // redraw itself with `\r`.
+        let mut stderr = child.stderr.take().expect("piped stderr");
+        let capture = self.stderr.clone();
+        let stderr_tail = thread::spawn(move || {
+            let mut tail: VecDeque<String> = VecDeque::new();
+            let mut line = Vec::new();
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = stderr.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                match &capture {
+                    Some(c) => c.lock().unwrap().extend_from_slice(&buf[..n]),
+                    None => {
+                        let mut out = io::stderr().lock();
+                        let _ = out.write_all(&buf[..n]);
+                        let _ = out.flush();
+                    }
+                }
+                for &b in &buf[..n] {
+                    match b {
+                        // A redrawn stats line replaces the previous one.
+                        b'\r' => line.clear(),
+                        b'\n' => {
+                            if tail.len() == STDERR_TAIL_LINES {
+                                tail.pop_front();
+                            }
+                            tail.push_back(String::from_utf8_lossy(&line).into_owned());
+                            line.clear();
+                        }
+                        b => line.push(b),
+                    }
+                }
+            }
+            Vec::from(tail).join("\n")
+        });
+
+        let started = Instant::now();
+        let stdout = child.stdout.take().expect("piped stdout");
+        for line in BufReader::new(stdout).lines() {
+            if self.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                let _ = fs::remove_file(out_path);
+                return Err(Error::Cancelled);
+            }
+            let Ok(line) = line else { break };
+            // `out_time_us` is the position written so far, in microseconds.
+            if let Some(us) = line.strip_prefix("out_time_us=")
+                && let Ok(us) = us.trim().parse::<f64>()
+            {
+                let (percent, eta) = estimate(us / 1e6, total, started.elapsed());
+                progress.on_progress(&Progress {
+                    phase: Phase::Remuxing,
+                    percent,
+                    eta,
+                });
+            }
+        }
+
+        let (status, cpu) = usage::wait(&mut child).map_err(|e| Error::io(out_path, e))?;
+        // Over ssh, only ssh itself ran here.
+        if let (Some(total), Some(cpu), None) = (&self.cpu_time, cpu, &remote) {
+            let mut total = total.lock().unwrap();
+            *total = Some(total.unwrap_or_default() + cpu);
+        }
+        let stderr = stderr_tail.join().unwrap_or_default();
+        if self.is_cancelled() {
+            let _ = fs::remove_file(out_path);
+            return Err(Error::Cancelled);
+        }
+        if !status.success() {
+            return Err(Error::Ffmpeg {
+                exit: status.code(),
+                stderr,
+            });
+        }
+        if let Some((r, work)) = &remote {
+            r.download(work, &remote_name, out_path)?;
+        }
+        progress.on_progress(&Progress {
+            phase: Phase::Finished,
+            percent: Some(100.0),
+            eta: Some(Duration::ZERO),
+        });
+        Ok(())
+    }
+
+    /// The native backend: progress is the share of sample bytes copied.
+    fn convert_native(
+        &self,
+        clip: &ClipDir,
+        out_path: &Path,
+        progress: &mut dyn ProgressCallback,
+    ) -> Result<(), Error> {
+        let started = Instant::now();
+        native::remux(&clip.dir, out_path, &self.options, &mut |done, total| {
+            let (percent, eta) = estimate(done as f64, Some(total as f64), started.elapsed());
+            progress.on_progress(&Progress {
+                phase: Phase::Remuxing,
+                percent,
+                eta,
+            });
+            !self.is_cancelled()
+        })?;
+        progress.on_progress(&Progress {
+            phase: Phase::Finished,
+            percent: Some(100.0),
+            eta: Some(Duration::ZERO),
+        });
+        Ok(())
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|c| c.is_cancelled())
+    }
+}
+
+/// Percent done and remaining time, extrapolated from the speed so far.
+fn estimate(
+    done_secs: f64,
+    total: Option<f64>,
+    elapsed: Duration,
+) -> (Option<f32>, Option<Duration>) {
+    let Some(total) = total else {
+        return (None, None);
+    };
+    let frac = (done_secs / total).clamp(0.0, 1.0);
+    let eta = (frac > 0.0).then(|| elapsed.mul_f64((1.0 - frac) / frac));
+    (Some((frac * 100.0) as f32), eta)
+}
@@ -0,0 +1,182 @@
+//! Handing outputs to the desktop: `--copy-to-clipboard`, `--reveal` and `--play`, for the
+//! "convert, then drag into Discord" flow (and `review`'s player).
+//!
+//! Everything goes through the platform's own tools (`osascript`/`pbcopy` and `open` on
+//! macOS, PowerShell and `explorer` on Windows, `wl-copy`/`xclip`/`xsel` and `xdg-open`
+//! elsewhere), so nothing is linked against a GUI toolkit.
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Copy `paths` to the clipboard: the files themselves when `as_file` (so they paste into
+/// chat apps and file managers), else the paths as text, one per line. Returns which of the
+/// two was copied.
+pub fn copy_to_clipboard(paths: &[PathBuf], as_file: bool) -> io::Result<&'static str> {
+    let paths = paths
+        .iter()
+        .map(std::path::absolute)
+        .collect::<io::Result<Vec<_>>>()?;
+    let texts: Vec<String> = paths
+        .iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    let what = if as_file { "file(s)" } else { "path(s)" };
+    if cfg!(target_os = "macos") {
+        if as_file {
+            let files: Vec<String> = texts
+                .iter()
+                .map(|t| {
+                    let escaped = t.replace('\\', "\\\\").replace('"', "\\\"");
+                    format!("POSIX file \"{escaped}\"")
+                })
+                .collect();
+            let script = match files.as_slice() {
+                [one] => format!("set the clipboard to ({one})"),
+                many => format!("set the clipboard to {{{}}}", many.join(", ")),
+            };
+            run_tool(Command::new("osascript").args(["-e", &script]), None)?;
+        } else {
+            run_tool(&mut Command::new("pbcopy"), Some(&texts.join("\n")))?;
+        }
+        return Ok(what);
+    }
+    if cfg!(target_os = "windows") {
+        let quoted: Vec<String> = texts
+            .iter()
+            .map(|t| format!("'{}'", t.replace('\'', "''")))
+            .collect();
+        let command = if as_file {
+            format!("Set-Clipboard -LiteralPath {}", quoted.join(","))
+        } else {
+            format!("Set-Clipboard -Value {}", quoted.join(","))
+        };
+        run_tool(
+            Command::new("powershell").args(["-NoProfile", "-Command", &command]),
+            None,
+        )?;
+        return Ok(what);
+    }
+
+    // Linux and the BSDs: files are copied as a `text/uri-list`, which file managers and
+    // chat apps paste as the files.
+    let (data, kind) = if as_file {
+        let uris: String = texts
+            .iter()
+            .map(|t| format!("file://{}\r\n", percent_encode_path(t)))
+            .collect();
+        (uris, Some("text/uri-list"))
+    } else {
+        (texts.join("\n"), None)
+    };
+    let mut tools: Vec<Command> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        let mut c = Command::new("wl-copy");
+        if let Some(k) = kind {
+            c.args(["--type", k]);
+        }
+        tools.push(c);
+    }
+    let mut c = Command::new("xclip");
+    c.args(["-selection", "clipboard"]);
+    if let Some(k) = kind {
+        c.args(["-t", k]);
+    }
+    tools.push(c);
+    if kind.is_none() {
+        let mut c = Command::new("xsel");
+        c.args(["--clipboard", "--input"]);
+        tools.push(c);
+    }
+    let mut last = io::Error::new(io::ErrorKind::NotFound, "no clipboard tool");
+    for mut tool in tools {
+        match run_tool(&mut tool, Some(&data)) {
+            Ok(()) => return Ok(what),
+            Err(e) => last = e,
+        }
+    }
+    Err(io::Error::new(
+        last.kind(),
+        format!("{last} (install wl-clipboard or xclip)"),
+    ))
+}
+
+/// Open the folder containing `file` in the file manager, with the file selected where the
+/// platform allows it (Finder, Explorer).
+pub fn reveal(file: &Path) -> io::Result<()> {
+    let file = std::path::absolute(file)?;
+    if cfg!(target_os = "macos") {
+        return run_tool(Command::new("open").arg("-R").arg(&file), None);
+    }
+    if cfg!(target_os = "windows") {
+        // Explorer exits with 1 even when it opened the window, so only spawning is checked.
+        let mut arg = std::ffi::OsString::from("/select,");
+        arg.push(&file);
+        return Command::new("explorer").arg(arg).spawn().map(drop);
+    }
+    let dir = file.parent().unwrap_or(&file);
+    run_tool(Command::new("xdg-open").arg(dir), None)
+}
+
+/// Start `player` (or the system default for the file type) on `file`, without waiting for
+/// it to close.
+pub fn open_in_player(file: &Path, player: Option<&str>) -> io::Result<()> {
+    let mut cmd = match player {
+        Some(p) => Command::new(p),
+        None if cfg!(target_os = "macos") => Command::new("open"),
+        None if cfg!(target_os = "windows") => {
+            let mut c = Command::new("cmd");
+            c.args(["/C", "start", ""]);
+            c
+        }
+        None => Command::new("xdg-open"),
+    };
+    cmd.arg(file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+/// Run a desktop tool, feeding it `input` on stdin.
+fn run_tool(cmd: &mut Command, input: Option<&str>) -> io::Result<()> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("{program}: {e}")))?;
+    if let Some(data) = input {
+        child
+            .stdin
+            .take()
+            .expect("piped stdin")
+            .write_all(data.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("{program} exited with {status}")));
+    }
+    Ok(())
+}
+
+/// Escape what can't appear in a `file://` URI path.
+fn percent_encode_path(path: &str) -> String {
+    let mut out = String::new();
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
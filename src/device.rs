@@ -0,0 +1,57 @@
+//! `--target-device` profiles: copy what the device can play, transcode the rest.
+
+use clap::ValueEnum;
+use steamclipconverter::{ffmpeg::RemuxOptions, probe::StreamInfo};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TargetDevice {
+    /// Browsers: H.264 (8-bit 4:2:0) + AAC/MP3.
+    Web,
+    /// iPhone/iPad/Apple TV: H.264 or HEVC + AAC/AC-3.
+    Ios,
+    /// Smart TVs and streaming sticks: H.264 or HEVC + AAC/AC-3.
+    Tv,
+}
+
+impl TargetDevice {
+    fn video_ok(self, s: &StreamInfo) -> bool {
+        // 10-bit / 4:4:4 captures break most hardware decoders even when the codec matches.
+        let pix_ok = s.pix_fmt.is_empty() || s.pix_fmt == "yuv420p" || s.pix_fmt == "yuvj420p";
+        match self {
+            TargetDevice::Web => s.codec_name == "h264" && pix_ok,
+            TargetDevice::Ios | TargetDevice::Tv => {
+                matches!(s.codec_name.as_str(), "h264" | "hevc") && pix_ok
+            }
+        }
+    }
+
+    fn audio_ok(self, s: &StreamInfo) -> bool {
+        match self {
+            TargetDevice::Web => matches!(s.codec_name.as_str(), "aac" | "mp3"),
+            TargetDevice::Ios | TargetDevice::Tv => {
+                matches!(s.codec_name.as_str(), "aac" | "ac3" | "eac3")
+            }
+        }
+    }
+
+    /// Decide copy vs transcode for each stream kind based on the probed source streams.
+    pub fn plan(self, streams: &[StreamInfo]) -> RemuxOptions {
+        let mut opts = RemuxOptions::default();
+
+        if let Some(v) = streams.iter().find(|s| s.codec_type == "video")
+            && !self.video_ok(v)
+        {
+            opts.video_encoder = Some("libx264".into());
+            opts.video_args = ["-preset", "medium", "-crf", "20", "-pix_fmt", "yuv420p"]
+                .map(String::from)
+                .to_vec();
+        }
+        if let Some(a) = streams.iter().find(|s| s.codec_type == "audio")
+            && !self.audio_ok(a)
+        {
+            opts.audio_encoder = Some("aac".into());
+            opts.audio_args = ["-b:a", "192k"].map(String::from).to_vec();
+        }
+        opts
+    }
+}
@@ -0,0 +1,53 @@
+//! Human-friendly durations for flags: `90`, `30s`, `5m`, `1h30m`, `2d`, and positions in a
+//! clip: `90.5`, `1:30`, `00:01:30`, `1m30s`.
+
+use std::time::Duration;
+
+/// Parse a duration; a bare number means seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    let mut total = 0u64;
+    let mut num = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            _ => return Err(format!("invalid duration '{s}' (use e.g. 30s, 5m, 1h30m)")),
+        };
+        let n: u64 = num
+            .parse()
+            .map_err(|_| format!("invalid duration '{s}' (number missing before '{c}')"))?;
+        total += n * unit;
+        num.clear();
+    }
+    if !num.is_empty() {
+        return Err(format!("invalid duration '{s}' (unit missing after {num})"));
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// Seconds into a clip, from `[[H:]M:]S[.fff]` or a duration like `45s`/`1m30s`.
+pub fn parse_offset(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let clock = s.split(':').try_fold(0.0, |acc: f64, part| {
+        part.parse::<f64>().ok().map(|n| acc * 60.0 + n)
+    });
+    match clock {
+        Some(secs) if secs.is_finite() && secs >= 0.0 => Ok(secs),
+        Some(_) => Err(format!(
+            "invalid time '{s}' (use e.g. 90, 1:30, 00:01:30 or 1m30s)"
+        )),
+        None => parse_duration(s)
+            .map(|d| d.as_secs_f64())
+            .map_err(|_| format!("invalid time '{s}' (use e.g. 90, 1:30, 00:01:30 or 1m30s)")),
+    }
+}
@@ -0,0 +1,91 @@
+//! `--encrypt age:<recipient>`: encrypt each output for off-site storage, before
+//! post-processing uploads it anywhere.
+//!
+//! Encryption is done by the `age` tool (https://age-encryption.org), the same way uploads
+//! go through the AWS CLI: the file becomes `<name>.mp4.age`, readable only with one of the
+//! recipients' identities (`age -d -i key.txt`), and the plaintext is removed. The catalog
+//! records the scheme and the recipients, which are public keys and so safe to keep.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Extension added to encrypted outputs.
+pub const EXT: &str = "age";
+
+/// The recipients outputs are encrypted to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Encryption {
+    pub recipients: Vec<String>,
+}
+
+impl Encryption {
+    /// Gather the repeated `--encrypt` values; None when there are none.
+    pub fn from_specs(specs: &[Recipient]) -> Option<Encryption> {
+        (!specs.is_empty()).then(|| Encryption {
+            recipients: specs.iter().map(|r| r.0.clone()).collect(),
+        })
+    }
+
+    /// Encrypt `path` to `<path>.age` and remove the plaintext; returns the new path. On
+    /// failure the plaintext is kept and any partial output removed.
+    pub fn encrypt(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut out = path.as_os_str().to_owned();
+        out.push(".");
+        out.push(EXT);
+        let out = PathBuf::from(out);
+        let mut cmd = Command::new("age");
+        cmd.arg("--encrypt");
+        for r in &self.recipients {
+            cmd.arg("--recipient").arg(r);
+        }
+        let result = cmd
+            .arg("--output")
+            .arg(&out)
+            .arg(path)
+            .stdin(Stdio::null())
+            .output();
+        let failed = match result {
+            Ok(o) if o.status.success() => None,
+            Ok(o) => Some(io::Error::other(format!(
+                "age failed: {}",
+                String::from_utf8_lossy(&o.stderr).trim()
+            ))),
+            Err(e) => Some(io::Error::new(
+                e.kind(),
+                format!("cannot run age: {e} (install it from https://age-encryption.org)"),
+            )),
+        };
+        if let Some(e) = failed {
+            let _ = fs::remove_file(&out);
+            return Err(e);
+        }
+        fs::remove_file(path)?;
+        Ok(out)
+    }
+}
+
+/// One `--encrypt` value: `age:<recipient>`, where the recipient is an age public key
+/// (`age1...`) or an SSH public key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recipient(String);
+
+impl Recipient {
+    pub fn parse(s: &str) -> Result<Recipient, String> {
+        let Some((scheme, key)) = s.split_once(':') else {
+            return Err(format!("expected age:<recipient>, got '{s}'"));
+        };
+        if scheme != "age" {
+            return Err(format!(
+                "unknown encryption scheme '{scheme}' (only age is supported)"
+            ));
+        }
+        let key = key.trim();
+        if key.is_empty() {
+            return Err("age: needs a recipient, e.g. age:age1...".into());
+        }
+        Ok(Recipient(key.to_string()))
+    }
+}
@@ -0,0 +1,319 @@
+//! `--energy-report`: how much work a run took, to compare encoders (software x265 against
+//! NVENC, say) on more than speed.
+//!
+//! Every clip's ffmpeg CPU time is measured exactly; energy where the machine has a meter:
+//! the CPU package's RAPL counters on Linux (readable by root only on most kernels),
+//! `powermetrics` on macOS (run as root) and `nvidia-smi` for NVIDIA cards. Meters see the
+//! whole machine, so a run's energy includes whatever else was running; it is split between
+//! the clips by their CPU time, or for the GPU by the time they took.
+
+use crate::report::say;
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often the RAPL counters are read, well within the time they take to wrap around.
+const RAPL_POLL: Duration = Duration::from_secs(10);
+/// Sample interval of `powermetrics` and `nvidia-smi`, in ms.
+const SAMPLE_MS: u32 = 1000;
+
+pub struct EnergyReport {
+    meters: Vec<Meter>,
+    started: Instant,
+    clips: Mutex<Vec<ClipUsage>>,
+}
+
+struct ClipUsage {
+    clip: PathBuf,
+    /// None where it couldn't be measured (native backend, --remote).
+    cpu: Option<Duration>,
+    wall: Duration,
+}
+
+struct Meter {
+    label: &'static str,
+    /// How the meter's energy is shared out between clips.
+    by_cpu: bool,
+    source: Source,
+}
+
+enum Source {
+    Rapl(Arc<Mutex<Vec<Zone>>>),
+    /// A tool printing power readings; joules and the number of readings so far.
+    Sampler(Child, Arc<Mutex<(f64, u32)>>),
+}
+
+/// A top-level RAPL zone (one per CPU package).
+struct Zone {
+    energy: PathBuf,
+    /// Where the µJ counter wraps around.
+    range: u64,
+    last: u64,
+    total_uj: u64,
+}
+
+impl EnergyReport {
+    /// Start the meters this machine has, saying which.
+    pub fn start() -> EnergyReport {
+        let mut meters = Vec::new();
+        if let Some(zones) = rapl_zones() {
+            meters.push(Meter {
+                label: "CPU package (RAPL)",
+                by_cpu: true,
+                source: Source::Rapl(zones),
+            });
+        }
+        if cfg!(target_os = "macos")
+            && let Some(source) = sampler(
+                Command::new("powermetrics").args([
+                    "--samplers",
+                    "cpu_power",
+                    "-i",
+                    &SAMPLE_MS.to_string(),
+                ]),
+                powermetrics_watts,
+            )
+        {
+            meters.push(Meter {
+                label: "CPU and GPU (powermetrics)",
+                by_cpu: true,
+                source,
+            });
+        }
+        if let Some(source) = sampler(
+            Command::new("nvidia-smi").args([
+                "--query-gpu=power.draw",
+                "--format=csv,noheader,nounits",
+                &format!("--loop-ms={SAMPLE_MS}"),
+            ]),
+            |line| line.trim().parse().ok(),
+        ) {
+            meters.push(Meter {
+                label: "NVIDIA GPU (nvidia-smi)",
+                by_cpu: false,
+                source,
+            });
+        }
+        EnergyReport {
+            meters,
+            started: Instant::now(),
+            clips: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record a converted clip; returns the line to print with it.
+    pub fn clip(&self, clip: &Path, cpu: Option<Duration>, wall: Duration) -> String {
+        self.clips.lock().unwrap().push(ClipUsage {
+            clip: clip.to_path_buf(),
+            cpu,
+            wall,
+        });
+        match cpu {
+            Some(cpu) => format!(
+                "[energy] ffmpeg used {:.1}s of CPU time in {:.1}s",
+                cpu.as_secs_f64(),
+                wall.as_secs_f64()
+            ),
+            None => format!(
+                "[energy] took {:.1}s (CPU time not measurable here)",
+                wall.as_secs_f64()
+            ),
+        }
+    }
+
+    /// Stop the meters and print the run's totals and each clip's share.
+    pub fn finish(mut self) {
+        let elapsed = self.started.elapsed();
+        let clips = self.clips.into_inner().unwrap();
+        let cpu: Duration = clips.iter().filter_map(|c| c.cpu).sum();
+        let wall: Duration = clips.iter().map(|c| c.wall).sum();
+        say!(
+            "[energy] {} clip(s): {:.1}s of ffmpeg CPU time in {:.1}s ({:.1} cores busy on average)",
+            clips.len(),
+            cpu.as_secs_f64(),
+            elapsed.as_secs_f64(),
+            cpu.as_secs_f64() / elapsed.as_secs_f64().max(0.001)
+        );
+        let mut totals = Vec::new();
+        for meter in &mut self.meters {
+            match meter.joules() {
+                Some(j) => {
+                    say!(
+                        "[energy] {}: {} ({:.0} W on average)",
+                        meter.label,
+                        joules(j),
+                        j / elapsed.as_secs_f64().max(0.001)
+                    );
+                    totals.push((meter.by_cpu, j));
+                }
+                None => say!("[energy] {}: no readings", meter.label),
+            }
+        }
+        if self.meters.is_empty() {
+            say!(
+                "[energy] no energy meter here (RAPL needs root on Linux, powermetrics on macOS \
+                 too); CPU time only"
+            );
+        }
+        for c in &clips {
+            let share = |by_cpu: bool| {
+                if by_cpu {
+                    c.cpu.unwrap_or_default().as_secs_f64() / cpu.as_secs_f64()
+                } else {
+                    c.wall.as_secs_f64() / wall.as_secs_f64()
+                }
+            };
+            let energy: f64 = totals
+                .iter()
+                .map(|&(by_cpu, j)| j * share(by_cpu))
+                .filter(|j| j.is_finite())
+                .sum();
+            let cpu = match c.cpu {
+                Some(cpu) => format!("{:.1}s CPU", cpu.as_secs_f64()),
+                None => "CPU time unknown".into(),
+            };
+            let energy = if totals.is_empty() {
+                String::new()
+            } else {
+                format!(", ~{}", joules(energy))
+            };
+            say!("[energy]   {}: {cpu}{energy}", c.clip.display());
+        }
+    }
+}
+
+impl Meter {
+    /// Energy since the start, None if the meter never gave a reading.
+    fn joules(&mut self) -> Option<f64> {
+        match &mut self.source {
+            Source::Rapl(zones) => {
+                let mut zones = zones.lock().unwrap();
+                sample(&mut zones);
+                Some(zones.iter().map(|z| z.total_uj).sum::<u64>() as f64 / 1e6)
+            }
+            Source::Sampler(child, readings) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                let (j, n) = *readings.lock().unwrap();
+                (n > 0).then_some(j)
+            }
+        }
+    }
+}
+
+fn joules(j: f64) -> String {
+    if j >= 1e6 {
+        format!("{:.2} MJ ({:.0} Wh)", j / 1e6, j / 3600.0)
+    } else if j >= 1e3 {
+        format!("{:.1} kJ ({:.1} Wh)", j / 1e3, j / 3600.0)
+    } else {
+        format!("{j:.0} J")
+    }
+}
+
+/// The packages' RAPL zones, read from now on; None without RAPL or without permission.
+fn rapl_zones() -> Option<Arc<Mutex<Vec<Zone>>>> {
+    let read = |p: &Path| -> Option<u64> { fs::read_to_string(p).ok()?.trim().parse().ok() };
+    let mut zones = Vec::new();
+    for ent in fs::read_dir("/sys/class/powercap").ok()?.flatten() {
+        let name = ent.file_name().to_string_lossy().into_owned();
+        // `intel-rapl:0` is a package, `intel-rapl:0:0` a part of it already counted there.
+        if !name.starts_with("intel-rapl:") || name.matches(':').count() != 1 {
+            continue;
+        }
+        let dir = ent.path();
+        let energy = dir.join("energy_uj");
+        if let (Some(last), Some(range)) = (read(&energy), read(&dir.join("max_energy_range_uj"))) {
+            zones.push(Zone {
+                energy,
+                range,
+                last,
+                total_uj: 0,
+            });
+        }
+    }
+    if zones.is_empty() {
+        return None;
+    }
+    let zones = Arc::new(Mutex::new(zones));
+    let polled: Weak<Mutex<Vec<Zone>>> = Arc::downgrade(&zones);
+    thread::spawn(move || {
+        loop {
+            thread::sleep(RAPL_POLL);
+            let Some(zones) = polled.upgrade() else { break };
+            sample(&mut zones.lock().unwrap());
+        }
+    });
+    Some(zones)
+}
+
+/// Add what each counter went up since the last reading.
+fn sample(zones: &mut [Zone]) {
+    for z in zones {
+        let Some(now) = fs::read_to_string(&z.energy)
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+        else {
+            continue;
+        };
+        z.total_uj += if now >= z.last {
+            now - z.last
+        } else {
+            z.range - z.last + now
+        };
+        z.last = now;
+    }
+}
+
+/// Run `cmd`, adding up the watts `parse` finds in its lines, each for the time since the
+/// one before (at most a sample interval).
+fn sampler(cmd: &mut Command, parse: fn(&str) -> Option<f64>) -> Option<Source> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let stdout = child.stdout.take()?;
+    let readings = Arc::new(Mutex::new((0.0, 0)));
+    let summed = Arc::clone(&readings);
+    let interval = f64::from(SAMPLE_MS) / 1000.0;
+    thread::spawn(move || {
+        let mut last = Instant::now();
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(watts) = parse(&line) {
+                let secs = last.elapsed().as_secs_f64().min(interval);
+                last = Instant::now();
+                let mut r = summed.lock().unwrap();
+                r.0 += watts * secs;
+                r.1 += 1;
+            }
+        }
+    });
+    Some(Source::Sampler(child, readings))
+}
+
+/// `Combined Power (CPU + GPU + ANE): 1234 mW` on Apple silicon, `Intel energy model derived
+/// package power (CPUs+GT+SA): 12.34W` on Intel Macs.
+fn powermetrics_watts(line: &str) -> Option<f64> {
+    let (what, value) = line.split_once(':')?;
+    let value = value.trim();
+    if what.starts_with("Combined Power") {
+        value
+            .strip_suffix("mW")?
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(|mw| mw / 1000.0)
+    } else if what.contains("package power") {
+        value.strip_suffix('W')?.trim().parse().ok()
+    } else {
+        None
+    }
+}
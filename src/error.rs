@@ -0,0 +1,87 @@
+//! Error type shared by the library API.
+
+use std::{fmt, io, path::PathBuf};
+
+/// Everything that can go wrong finding or converting clips, by kind, so callers can branch
+/// on the failure instead of matching message strings.
+#[derive(Debug)]
+pub enum Error {
+    /// The scan root couldn't be read.
+    Scan { path: PathBuf, source: io::Error },
+    /// No game name could be found for an appid.
+    NameResolution { appid: u32 },
+    /// A clip folder is unusable (missing session.mpd, unparsable timestamp, ...).
+    InvalidClip { path: PathBuf, reason: String },
+    /// An external program (ffmpeg/ffprobe) couldn't be started.
+    Spawn { program: String, source: io::Error },
+    /// ffmpeg ran but failed; `stderr` holds the tail of what it printed.
+    Ffmpeg { exit: Option<i32>, stderr: String },
+    /// The chosen backend can't do what was asked (e.g. transcoding without ffmpeg).
+    Unsupported { reason: String },
+    /// Copying to or from the `--remote` machine failed.
+    Remote { host: String, reason: String },
+    /// Filesystem operation failed on `path`.
+    Io { path: PathBuf, source: io::Error },
+    /// The operation was cancelled through a `CancellationToken`.
+    Cancelled,
+}
+
+impl Error {
+    /// Stable machine-readable name of the failure kind (used in JSON output).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Scan { .. } => "scan",
+            Error::NameResolution { .. } => "name_resolution",
+            Error::InvalidClip { .. } => "invalid_clip",
+            Error::Spawn { .. } => "spawn",
+            Error::Ffmpeg { .. } => "ffmpeg",
+            Error::Unsupported { .. } => "unsupported",
+            Error::Remote { .. } => "remote",
+            Error::Io { .. } => "io",
+            Error::Cancelled => "cancelled",
+        }
+    }
+
+    pub(crate) fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Scan { path, source } => write!(f, "cannot scan {}: {}", path.display(), source),
+            Error::NameResolution { appid } => write!(f, "no game name found for appid {appid}"),
+            Error::InvalidClip { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            Error::Spawn { program, source } => write!(f, "launching {program}: {source}"),
+            Error::Ffmpeg { exit, stderr } => {
+                match exit {
+                    Some(code) => write!(f, "ffmpeg exited with status {code}")?,
+                    None => write!(f, "ffmpeg was terminated by a signal")?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+            Error::Unsupported { reason } => write!(f, "{reason}"),
+            Error::Remote { host, reason } => write!(f, "{host}: {reason}"),
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Scan { source, .. } | Error::Spawn { source, .. } | Error::Io { source, .. } => {
+                Some(source)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Live events as server-sent events (`text/event-stream`), for dashboards and frontends
+//! that would otherwise poll: the run's (`--events ADDR`, `GET /events`) and the agent's
+//! (`GET /api/events`).
+//!
+//! Each message is `event: <kind>` with a JSON object as `data`. A run sends `run-started`,
+//! `convert-started`, `progress` (whole percents), `converted`, `skipped`, `failed`,
+//! `deleted` and `run-finished`; an agent sends `clip-added`, `clip-ready`, `clip-removed`,
+//! `fetch` and `deleted`.
+
+use std::{
+    io::{self, Read},
+    net::TcpListener,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use steamclipconverter::{
+    http::{self, Body, Response},
+    json::Value,
+};
+
+/// Comment lines sent this often keep proxies from closing quiet streams.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Default)]
+pub struct Bus {
+    subscribers: Mutex<Vec<Sender<String>>>,
+    next_id: AtomicU64,
+    /// Streams still being written, so `close` can let them send the last events.
+    open_streams: Arc<AtomicUsize>,
+}
+
+struct Subscription {
+    rx: Receiver<String>,
+    pending: Vec<u8>,
+    pos: usize,
+    open_streams: Arc<AtomicUsize>,
+}
+
+impl Read for Subscription {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.pending.len() {
+            self.pending = match self.rx.recv_timeout(KEEPALIVE) {
+                Ok(msg) => msg.into_bytes(),
+                Err(RecvTimeoutError::Timeout) => b": keepalive\n\n".to_vec(),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            };
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.pending.len() - self.pos);
+        buf[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.open_streams.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Bus {
+    pub fn publish(&self, kind: &str, data: Value) {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let msg = format!("id: {id}\nevent: {kind}\ndata: {data}\n\n");
+        // Subscribers that went away are dropped here.
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send(msg.clone()).is_ok());
+    }
+
+    /// A response streaming every event published from now on.
+    pub fn subscribe(&self) -> Response {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        self.open_streams.fetch_add(1, Ordering::SeqCst);
+        let sub = Subscription {
+            rx,
+            pending: b": connected\n\n".to_vec(),
+            pos: 0,
+            open_streams: Arc::clone(&self.open_streams),
+        };
+        Response {
+            status: 200,
+            headers: Vec::new(),
+            body: Body::Stream(Box::new(sub)),
+        }
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .header("Access-Control-Allow-Origin", "*")
+    }
+
+    /// End every stream once it has sent what was published, waiting up to a second.
+    pub fn close(&self) {
+        self.subscribers.lock().unwrap().clear();
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while self.open_streams.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+/// Serve a run's events on `addr` at `GET /events`.
+pub fn serve(addr: &str) -> io::Result<Arc<Bus>> {
+    let listener = TcpListener::bind(addr)?;
+    let bus = Arc::new(Bus::default());
+    let server_bus = Arc::clone(&bus);
+    thread::spawn(move || {
+        http::serve(listener, move |req| match req.segments().as_slice() {
+            ["events"] => server_bus.subscribe(),
+            _ => Response::error(404, "events are at /events"),
+        })
+    });
+    Ok(bus)
+}
@@ -0,0 +1,195 @@
+//! `export-raw` subcommand: pack unconverted clip folders into one archive with an index, to
+//! move them to another machine (e.g. from a Steam Deck to a desktop that transcodes faster)
+//! and convert them there straight from the archive with `--input bundle.tar.zst`.
+
+use crate::{
+    AppNames, Kinds, duration, host,
+    json::{self, Value},
+    quota, resolve_input_dir, skip,
+    timestamp::{self, TimestampSource},
+};
+use chrono::{SecondsFormat, Utc};
+use clap::{ArgAction, Args};
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+use steamclipconverter::{ClipDir, find_fg_clip_dirs, settle::SettleRules, snapshot::ScanSnapshot};
+
+/// Index written at the root of every bundle.
+const MANIFEST: &str = "steamclipconverter-bundle.json";
+const KIND: &str = "steamclipconverter.bundle";
+
+#[derive(Args, Debug)]
+pub struct ExportRawArgs {
+    /// Directory to search (defaults to <SteamRoot>/userdata with a warning).
+    input: Option<PathBuf>,
+
+    /// Archive to write; the compression follows the extension (.tar, .tar.gz, .tar.zst, ...).
+    #[arg(long, value_name = "FILE")]
+    to: PathBuf,
+
+    /// Restrict to specific appids; repeatable.
+    #[arg(long = "gameId", action = ArgAction::Append)]
+    game_ids: Vec<u32>,
+
+    /// Leave out clips recorded without a game (appid 0).
+    #[arg(long, action = ArgAction::SetTrue)]
+    skip_unknown_app: bool,
+
+    /// Clips (fg_*), background recordings (bg_*), or all.
+    #[arg(long, value_enum, default_value_t = Kinds::Clips)]
+    kind: Kinds,
+
+    /// Only clips recorded this long ago or more recently.
+    #[arg(long, value_name = "DURATION", value_parser = duration::parse_duration)]
+    since: Option<Duration>,
+
+    /// Folders changed more recently count as still being recorded and are left out.
+    #[arg(long, value_name = "DURATION", default_value = "30s", value_parser = duration::parse_duration)]
+    settle_time: Duration,
+}
+
+/// What to pack for a clip: its whole clip_* folder (timelines, thumbnails) when it has one,
+/// else just the fg_* (or bg_*) folder.
+fn packed_root(clip: &ClipDir) -> &Path {
+    match clip.dir.parent().and_then(Path::parent) {
+        Some(grand)
+            if grand
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("clip_")) =>
+        {
+            grand
+        }
+        _ => &clip.dir,
+    }
+}
+
+/// `/`-separated path of `p` below `base`.
+fn member(base: &Path, p: &Path) -> String {
+    let rel = p.strip_prefix(base).unwrap_or(p);
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn run(args: ExportRawArgs) -> i32 {
+    let input_dir = resolve_input_dir(args.input.clone());
+    let mut clips = match find_fg_clip_dirs(&input_dir, &mut ScanSnapshot::load(&input_dir)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ERROR[find]: {}", e);
+            return 1;
+        }
+    };
+    clips.sort_by(|a, b| a.dir.cmp(&b.dir));
+    let games: HashSet<u32> = args.game_ids.iter().copied().collect();
+    let rules = SettleRules {
+        settle_time: args.settle_time,
+        ..SettleRules::default()
+    };
+    let cutoff = args
+        .since
+        .map(|d| Utc::now() - chrono::Duration::from_std(d).unwrap_or_default());
+    clips.retain(|c| {
+        args.kind.includes(c.kind())
+            && (games.is_empty() || games.contains(&c.appid))
+            && !(args.skip_unknown_app && c.is_unknown_app())
+            && cutoff.is_none_or(|cut| {
+                timestamp::record_start(c, TimestampSource::Folder).is_some_and(|t| t >= cut)
+            })
+    });
+    clips.retain(|c| match skip::assess(c, &rules) {
+        Ok(_) => true,
+        Err(reason) => {
+            eprintln!("[skip] {}: {}", c.dir.display(), reason);
+            false
+        }
+    });
+    if clips.is_empty() {
+        println!("No clips to export.");
+        return 0;
+    }
+
+    let names = AppNames::discover();
+    let mut roots: Vec<String> = Vec::new();
+    let mut entries = Vec::new();
+    let mut total = 0;
+    for clip in &clips {
+        let bytes = quota::tree_size(&clip.dir);
+        total += bytes;
+        let root = member(&input_dir, packed_root(clip));
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+        entries.push(json::obj([
+            ("path", member(&input_dir, &clip.dir).into()),
+            ("appid", clip.appid.into()),
+            (
+                "game",
+                names
+                    .resolve(clip.appid)
+                    .unwrap_or_else(|| clip.appid.to_string())
+                    .into(),
+            ),
+            ("date", clip.date.as_str().into()),
+            ("time", clip.time.as_str().into()),
+            ("bytes", bytes.into()),
+        ]));
+    }
+    let manifest = json::obj([
+        ("kind", KIND.into()),
+        ("version", 1u32.into()),
+        (
+            "created",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true).into(),
+        ),
+        ("host", host::machine_tag().into()),
+        ("clips", Value::Arr(entries)),
+    ]);
+
+    let staging = env::temp_dir().join(format!("steamclipconverter-bundle-{}", std::process::id()));
+    if let Err(e) = fs::create_dir_all(&staging)
+        .and_then(|()| fs::write(staging.join(MANIFEST), format!("{manifest}\n")))
+    {
+        eprintln!("ERROR: cannot write the bundle index: {}", e);
+        return 1;
+    }
+    // -a picks gzip/xz/zstd from the archive's extension (GNU tar and bsdtar alike).
+    let status = Command::new("tar")
+        .arg("-caf")
+        .arg(&args.to)
+        .arg("-C")
+        .arg(&staging)
+        .arg(MANIFEST)
+        .arg("-C")
+        .arg(&input_dir)
+        .args(&roots)
+        .status();
+    let _ = fs::remove_dir_all(&staging);
+    match status {
+        Ok(s) if s.success() => {
+            println!(
+                "[bundle] wrote {}: {} clip(s), {} before compression",
+                args.to.display(),
+                clips.len(),
+                quota::format_size(total)
+            );
+            0
+        }
+        Ok(s) => {
+            eprintln!("ERROR: tar failed ({})", s);
+            let _ = fs::remove_file(&args.to);
+            1
+        }
+        Err(e) => {
+            eprintln!("ERROR: cannot run tar: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! C ABI for embedding the scanner and converter in non-Rust apps (C#, C++, ...).
+//!
+//! The matching declarations live in `include/steamclipconverter.h`. All strings crossing the
+//! boundary are NUL-terminated UTF-8. Functions return 0 on success or a negative `SCC_ERR_*`
+//! code; `scc_last_error` then describes what went wrong on the calling thread.
+
+use crate::{ClipDir, Converter, Error, Progress, find_fg_clip_dirs, snapshot::ScanSnapshot};
+use std::{
+    cell::{Cell, RefCell},
+    ffi::{CStr, CString, c_char, c_int, c_void},
+    path::{Path, PathBuf},
+    ptr,
+};
+
+pub const SCC_OK: c_int = 0;
+pub const SCC_ERR_ARGUMENT: c_int = -1;
+pub const SCC_ERR_SCAN: c_int = -2;
+pub const SCC_ERR_INVALID_CLIP: c_int = -3;
+pub const SCC_ERR_SPAWN: c_int = -4;
+pub const SCC_ERR_FFMPEG: c_int = -5;
+pub const SCC_ERR_IO: c_int = -6;
+pub const SCC_ERR_CANCELLED: c_int = -7;
+pub const SCC_ERR_OTHER: c_int = -8;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+    static LAST_CODE: Cell<c_int> = const { Cell::new(SCC_OK) };
+}
+
+/// Record `code` and `msg` as this thread's last error and return `code`.
+fn set_last_error(code: c_int, msg: impl Into<String>) -> c_int {
+    // Interior NULs can't be represented; drop them rather than losing the whole message.
+    let msg: String = msg.into().replace('\0', "");
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(msg).ok());
+    LAST_CODE.with(|c| c.set(code));
+    code
+}
+
+fn fail(err: &Error) -> c_int {
+    let code = match err {
+        Error::Scan { .. } => SCC_ERR_SCAN,
+        Error::InvalidClip { .. } => SCC_ERR_INVALID_CLIP,
+        Error::Spawn { .. } => SCC_ERR_SPAWN,
+        Error::Ffmpeg { .. } => SCC_ERR_FFMPEG,
+        Error::Io { .. } => SCC_ERR_IO,
+        Error::Cancelled => SCC_ERR_CANCELLED,
+        _ => SCC_ERR_OTHER,
+    };
+    set_last_error(code, err.to_string())
+}
+
+/// Borrow a C string argument as a path, recording an error when it is null or not UTF-8.
+unsafe fn path_arg<'a>(p: *const c_char, what: &str) -> Option<&'a Path> {
+    if p.is_null() {
+        set_last_error(SCC_ERR_ARGUMENT, format!("{what} is null"));
+        return None;
+    }
+    // SAFETY: the caller guarantees `p` points to a NUL-terminated string.
+    match unsafe { CStr::from_ptr(p) }.to_str() {
+        Ok(s) => Some(Path::new(s)),
+        Err(_) => {
+            set_last_error(SCC_ERR_ARGUMENT, format!("{what} is not valid UTF-8"));
+            None
+        }
+    }
+}
+
+/// One clip as seen from C. The strings are owned by the list it came from.
+#[repr(C)]
+pub struct SccClip {
+    pub dir: *const c_char,
+    pub date: *const c_char,
+    pub time: *const c_char,
+    pub appid: u32,
+}
+
+/// Opaque result of [`scc_scan`]; release with [`scc_clip_list_free`].
+pub struct SccClipList {
+    clips: Vec<(CString, CString, CString, u32)>,
+}
+
+/// Message for the last failed call on this thread, or null. Valid until the next call.
+#[unsafe(no_mangle)]
+pub extern "C" fn scc_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Code of the last failed call on this thread (`SCC_OK` if none has failed), e.g. to tell
+/// why [`scc_scan`] returned null.
+#[unsafe(no_mangle)]
+pub extern "C" fn scc_last_error_code() -> c_int {
+    LAST_CODE.with(Cell::get)
+}
+
+/// Find all clip folders under `root`. Returns null on failure, with the reason in
+/// [`scc_last_error_code`] and [`scc_last_error`].
+///
+/// # Safety
+/// `root` must be null or a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn scc_scan(root: *const c_char) -> *mut SccClipList {
+    let Some(root) = (unsafe { path_arg(root, "root") }) else {
+        return ptr::null_mut();
+    };
+    let mut clips = match find_fg_clip_dirs(root, &mut ScanSnapshot::default()) {
+        Ok(c) => c,
+        Err(e) => {
+            fail(&e);
+            return ptr::null_mut();
+        }
+    };
+    clips.sort_by(|a, b| a.dir.cmp(&b.dir));
+    let cstr = |s: &str| CString::new(s.replace('\0', "")).unwrap_or_default();
+    let clips = clips
+        .iter()
+        .map(|c| {
+            let dir = cstr(&c.dir.to_string_lossy());
+            (dir, cstr(&c.date), cstr(&c.time), c.appid)
+        })
+        .collect();
+    Box::into_raw(Box::new(SccClipList { clips }))
+}
+
+/// Number of clips in `list` (0 for null).
+///
+/// # Safety
+/// `list` must be null or a pointer returned by [`scc_scan`] that has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn scc_clip_list_len(list: *const SccClipList) -> usize {
+    // SAFETY: guaranteed by the caller.
+    unsafe { list.as_ref() }.map_or(0, |l| l.clips.len())
+}
+
+/// Fill `out` with clip `index` of `list`.
+///
+/// # Safety
+/// `list` must be a live pointer from [`scc_scan`] and `out` must point to writable memory for
+/// one `SccClip`. The strings stay valid until the list is freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn scc_clip_list_get(
+    list: *const SccClipList,
+    index: usize,
+    out: *mut SccClip,
+) -> c_int {
+    // SAFETY: guaranteed by the caller.
+    let Some(list) = (unsafe { list.as_ref() }) else {
+        return set_last_error(SCC_ERR_ARGUMENT, "list is null");
+    };
+    let Some((dir, date, time, appid)) = list.clips.get(index) else {
+        return set_last_error(SCC_ERR_ARGUMENT, format!("index {index} out of range"));
+    };
+    if out.is_null() {
+        return set_last_error(SCC_ERR_ARGUMENT, "out is null");
+    }
+    // SAFETY: `out` is non-null and writable per the contract above.
+    unsafe {
+        out.write(SccClip {
+            dir: dir.as_ptr(),
+            date: date.as_ptr(),
+            time: time.as_ptr(),
+            appid: *appid,
+        })
+    };
+    SCC_OK
+}
+
+/// Release a list returned by [`scc_scan`]. Null is ignored.
+///
+/// # Safety
+/// `list` must be null or a pointer from [`scc_scan`] that has not already been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn scc_clip_list_free(list: *mut SccClipList) {
+    if !list.is_null() {
+        // SAFETY: the pointer came from Box::into_raw in scc_scan.
+        drop(unsafe { Box::from_raw(list) });
+    }
+}
+
+/// Called with the percentage done (or a negative value when unknown) and the caller's
+/// `user_data`.
+pub type SccProgressFn = Option<extern "C" fn(percent: f32, user_data: *mut c_void)>;
+
+/// Convert the clip folder `clip_dir` (an `fg_<appid>_<date>_<time>` folder) into `out_path`
+/// by stream copy. Blocks until ffmpeg finishes.
+///
+/// # Safety
+/// `clip_dir` and `out_path` must be valid NUL-terminated strings. `user_data` is passed to
+/// `progress` untouched.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn scc_convert(
+    clip_dir: *const c_char,
+    out_path: *const c_char,
+    progress: SccProgressFn,
+    user_data: *mut c_void,
+) -> c_int {
+    let (Some(dir), Some(out)) = (unsafe { path_arg(clip_dir, "clip_dir") }, unsafe {
+        path_arg(out_path, "out_path")
+    }) else {
+        return SCC_ERR_ARGUMENT;
+    };
+    let Some(clip) = ClipDir::from_path(dir) else {
+        return fail(&Error::InvalidClip {
+            path: PathBuf::from(dir),
+            reason: "folder name is not fg_<appid>_<YYYYMMDD>_<HHMMSS>".into(),
+        });
+    };
+    let mut on_progress = |p: &Progress| {
+        if let Some(cb) = progress {
+            cb(p.percent.unwrap_or(-1.0), user_data);
+        }
+    };
+    match Converter::new().convert(&clip, out, &mut on_progress) {
+        Ok(()) => SCC_OK,
+        Err(e) => fail(&e),
+    }
+}
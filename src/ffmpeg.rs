@@ -0,0 +1,757 @@
+//! Building the ffmpeg invocation for one clip folder.
+
+use crate::Error;
+use std::{
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Knobs that change how a clip is handed to ffmpeg. The default is a plain stream copy.
+#[derive(Clone, Debug, Default)]
+pub struct RemuxOptions {
+    /// Video encoder to use instead of stream copy (e.g. `libx264`).
+    pub video_encoder: Option<String>,
+    /// Extra arguments for the video encoder (`-crf 20`, `-pix_fmt yuv420p`, ...).
+    pub video_args: Vec<String>,
+    /// Arguments before the input, such as the device of a hardware encoder.
+    pub input_args: Vec<String>,
+    /// Audio encoder to use instead of stream copy (e.g. `aac`).
+    pub audio_encoder: Option<String>,
+    /// Extra arguments for the audio encoder.
+    pub audio_args: Vec<String>,
+    /// `-threads N`; `None` lets ffmpeg decide.
+    pub threads: Option<u32>,
+    /// Title/language tags for each output audio stream, in output order.
+    pub audio_labels: Vec<TrackLabel>,
+    /// Which of the recording's audio streams go into the output.
+    pub audio: AudioSelect,
+    /// Emit machine-readable `-progress` key=value lines on stdout.
+    pub progress_pipe: bool,
+    /// ffmpeg's `-loglevel`; `None` means `error` (only problems are printed).
+    pub loglevel: Option<String>,
+    /// Let ffmpeg print its own `-stats` progress line on stderr.
+    pub stats: bool,
+    /// Only keep the part of the clip between these offsets (seconds from its start).
+    pub trim_start: Option<f64>,
+    pub trim_end: Option<f64>,
+    /// Container title tag.
+    pub title: Option<String>,
+    /// Further container tags, as (key, value): `date`, `creation_time` (ISO 8601, UTC)
+    /// and keys of this tool's own such as `steam_appid`. MP4 only keeps keys outside its
+    /// standard set with `use_metadata_tags`, which is then added to the movflags.
+    pub tags: Vec<(String, String)>,
+    /// Branding: videos joined before/after the recording and an image overlaid on the
+    /// recording's bottom-right corner. Any of them re-encodes video and audio. Paths must
+    /// be absolute (ffmpeg runs in the clip folder).
+    pub intro: Option<PathBuf>,
+    pub outro: Option<PathBuf>,
+    pub watermark: Option<PathBuf>,
+    /// Areas of the recording to blur (a webcam corner, a name in the HUD), in recording
+    /// pixels. Re-encodes video.
+    pub blur: Vec<Region>,
+    /// `-movflags` of the MP4 muxer; `None` means `+faststart` (the index is moved to the
+    /// front once the file is written, which writes it twice), empty means none.
+    pub movflags: Option<String>,
+    /// Format of the output file, which must have its extension.
+    pub container: Container,
+    /// Frame rate and width to bring the video to (the height follows the aspect ratio).
+    /// Re-encodes video; GIFs default to [`GIF_FPS`] and [`GIF_WIDTH`].
+    pub fps: Option<u32>,
+    pub width: Option<u32>,
+    /// Images of the clip written by the same ffmpeg run, next to the output.
+    pub previews: Option<Previews>,
+}
+
+/// Poster frame, contact sheet and animated preview, made from the recording while it is
+/// converted instead of by reading the output again. Unlike the output they show the
+/// recording as it is (no intro, outro or watermark), blurred like it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Previews {
+    /// Seconds into the (trimmed) clip of the poster frame, `<name>.jpg`.
+    pub poster_at: f64,
+    /// Frames on the contact sheet, `<name>.sheet.jpg`; 0 for none.
+    pub sheet: u32,
+    /// Also write `<name>.preview.gif`, a few seconds from the poster frame on.
+    pub animated: bool,
+    /// Length of the (trimmed) clip, to spread the sheet's frames over; the converter fills
+    /// it in from its probe.
+    pub duration: Option<f64>,
+}
+
+/// Width of the poster frame in pixels.
+const POSTER_WIDTH: u32 = 640;
+/// Width of a contact sheet frame, and the frames per row.
+const SHEET_TILE_WIDTH: u32 = 320;
+const SHEET_COLUMNS: u32 = 4;
+/// Length, frame rate and width of the animated preview.
+const ANIMATED_SECS: f64 = 3.0;
+const ANIMATED_FPS: u32 = 10;
+const ANIMATED_WIDTH: u32 = 320;
+
+impl Previews {
+    /// The files written for the output `out`: the poster, then the sheet and the
+    /// animation when asked for.
+    pub fn paths(&self, out: &Path) -> Vec<PathBuf> {
+        let mut paths = vec![out.with_extension("jpg")];
+        if self.sheet > 0 {
+            paths.push(out.with_extension("sheet.jpg"));
+        }
+        if self.animated {
+            paths.push(out.with_extension("preview.gif"));
+        }
+        paths
+    }
+
+    /// Output options and file of each preview of `out`, with `blur` applied to the video.
+    fn args(&self, out: &Path, blur: &[Region]) -> Vec<OsString> {
+        // The blur as a simple filtergraph, ahead of each preview's own filters.
+        let video = |chain: String| {
+            if blur.is_empty() {
+                return chain;
+            }
+            let (mut graph, video) = blur_graph(blur, "in");
+            graph.push(format!("[{video}]{chain}"));
+            graph.join(";")
+        };
+        let mut outputs: Vec<(Vec<String>, PathBuf)> = Vec::new();
+        let poster_at = format!("{:.3}", self.poster_at);
+        outputs.push((
+            vec![
+                "-ss".into(),
+                poster_at.clone(),
+                "-frames:v".into(),
+                "1".into(),
+                "-vf".into(),
+                video(format!("scale={POSTER_WIDTH}:-2")),
+            ],
+            out.with_extension("jpg"),
+        ));
+        if self.sheet > 0 {
+            let rows = self.sheet.div_ceil(SHEET_COLUMNS);
+            let columns = self.sheet.min(SHEET_COLUMNS);
+            // Frames spread over the clip, or one every ten seconds of a clip of no known
+            // length; the sheet is written once it is full or the clip ends.
+            let rate = match self.duration {
+                Some(d) if d > 0.0 => format!("{}/{d:.3}", self.sheet),
+                _ => "1/10".to_string(),
+            };
+            outputs.push((
+                vec![
+                    "-frames:v".into(),
+                    "1".into(),
+                    "-vf".into(),
+                    video(format!(
+                        "fps={rate},scale={SHEET_TILE_WIDTH}:-2,tile={columns}x{rows}"
+                    )),
+                ],
+                out.with_extension("sheet.jpg"),
+            ));
+        }
+        if self.animated {
+            outputs.push((
+                vec![
+                    "-ss".into(),
+                    poster_at,
+                    "-t".into(),
+                    ANIMATED_SECS.to_string(),
+                    "-vf".into(),
+                    video(format!(
+                        "fps={ANIMATED_FPS},scale={ANIMATED_WIDTH}:-2:flags=lanczos,\
+                         split[preview_a][preview_b];[preview_a]palettegen[preview_p];\
+                         [preview_b][preview_p]paletteuse"
+                    )),
+                    "-loop".into(),
+                    "0".into(),
+                ],
+                out.with_extension("preview.gif"),
+            ));
+        }
+        let mut args = Vec::new();
+        for (options, path) in outputs {
+            args.extend(["-map", "0:v:0"].map(OsString::from));
+            args.extend(options.into_iter().map(OsString::from));
+            // One image under the given name, not a numbered sequence.
+            if path.extension().is_some_and(|e| e == "jpg") {
+                args.extend(["-update", "1"].map(OsString::from));
+            }
+            args.push(file_arg(&path));
+        }
+        args
+    }
+}
+
+/// Tags the MP4 muxer writes without `use_metadata_tags`, of those this tool sets.
+const MP4_STANDARD_TAGS: &[&str] = &["title", "date", "creation_time"];
+
+/// GIF frame rate when none is given.
+pub const GIF_FPS: u32 = 15;
+/// GIF width when none is given, in pixels.
+pub const GIF_WIDTH: u32 = 480;
+
+/// The output file format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Container {
+    /// MP4, with the index moved to the front (see [`RemuxOptions::movflags`]).
+    #[default]
+    Mp4,
+    /// Matroska: takes any codec, so streams are copied whatever Steam recorded.
+    Mkv,
+    /// WebM for browsers and chat apps: VP9 video (or AV1 when that encoder is chosen) and
+    /// Opus audio, always encoded.
+    Webm,
+    /// An animated GIF without sound, built from a palette of the clip's own colours.
+    Gif,
+}
+
+impl Container {
+    /// `mp4`, `mkv`, `webm` or `gif`; used as the clap value parser of `--container`.
+    pub fn parse(s: &str) -> Result<Container, String> {
+        match s {
+            "mp4" => Ok(Container::Mp4),
+            "mkv" => Ok(Container::Mkv),
+            "webm" => Ok(Container::Webm),
+            "gif" => Ok(Container::Gif),
+            _ => Err(format!("unknown container '{s}' (mp4, mkv, webm, gif)")),
+        }
+    }
+
+    /// File extension, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "mkv",
+            Container::Webm => "webm",
+            Container::Gif => "gif",
+        }
+    }
+
+    /// ffmpeg's muxer for it.
+    pub fn muxer(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "matroska",
+            Container::Webm => "webm",
+            Container::Gif => "gif",
+        }
+    }
+
+    /// Whether Steam's H.264/HEVC and AAC can be copied into it as they are.
+    pub fn copies_streams(self) -> bool {
+        matches!(self, Container::Mp4 | Container::Mkv)
+    }
+
+    /// The video encoder used when the video is re-encoded and none is chosen.
+    pub fn video_encoder(self) -> &'static str {
+        match self {
+            Container::Mp4 | Container::Mkv => "libx264",
+            Container::Webm => "libvpx-vp9",
+            Container::Gif => "gif",
+        }
+    }
+
+    /// Arguments for [`video_encoder`](Self::video_encoder) when it is picked by default:
+    /// constant quality for VP9, whose default is a low fixed bitrate.
+    fn video_defaults(self) -> &'static [&'static str] {
+        match self {
+            Container::Webm => &[
+                "-crf",
+                "32",
+                "-b:v",
+                "0",
+                "-row-mt",
+                "1",
+                "-deadline",
+                "good",
+                "-cpu-used",
+                "4",
+            ],
+            _ => &[],
+        }
+    }
+}
+
+/// The audio streams of the output, by position among the recording's audio streams
+/// (Steam writes the game audio first, then the microphone).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioSelect {
+    /// One stream; left out when the recording doesn't have it.
+    Track(u32),
+    /// Every stream, as tracks of their own.
+    All,
+    /// The first N streams mixed down into one track, which has to be encoded.
+    Mix(u32),
+    /// None: a silent video.
+    Silent,
+}
+
+impl Default for AudioSelect {
+    fn default() -> Self {
+        AudioSelect::Track(0)
+    }
+}
+
+impl AudioSelect {
+    /// `amix` of the streams into `[mix]`, for [`AudioSelect::Mix`].
+    fn mix_graph(self) -> Option<String> {
+        let AudioSelect::Mix(n) = self else {
+            return None;
+        };
+        let inputs: String = (0..n).map(|i| format!("[0:a:{i}]")).collect();
+        Some(format!(
+            "{inputs}amix=inputs={n}:duration=longest:normalize=0[mix]"
+        ))
+    }
+
+    /// `-map` arguments for the audio, after any [`mix_graph`](Self::mix_graph).
+    fn maps(self) -> Vec<String> {
+        let spec = match self {
+            AudioSelect::Track(i) => format!("0:a:{i}?"),
+            AudioSelect::All => "0:a?".to_string(),
+            AudioSelect::Mix(_) => "[mix]".to_string(),
+            AudioSelect::Silent => return Vec::new(),
+        };
+        vec!["-map".to_string(), spec]
+    }
+}
+
+/// A rectangle of the frame: `x,y,w,h` from the top-left corner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Region {
+    /// Smallest side accepted, so the blur radius fits the chroma planes too.
+    const MIN_SIDE: u32 = 8;
+
+    /// Parse `x,y,w,h`; used as the clap value parser of `--blur`.
+    pub fn parse(s: &str) -> Result<Region, String> {
+        let nums: Vec<u32> = s
+            .split(',')
+            .map(|n| n.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("expected x,y,w,h in pixels, got '{s}'"))?;
+        let [x, y, w, h] = nums[..] else {
+            return Err(format!("expected x,y,w,h in pixels, got '{s}'"));
+        };
+        if w < Self::MIN_SIDE || h < Self::MIN_SIDE {
+            return Err(format!(
+                "a blur region must be at least {0}x{0} pixels",
+                Self::MIN_SIDE
+            ));
+        }
+        Ok(Region { x, y, w, h })
+    }
+}
+
+/// Metadata shown by players instead of "Track 1/Track 2".
+#[derive(Clone, Debug)]
+pub struct TrackLabel {
+    pub title: String,
+    /// ISO 639-2 code (`eng`, `jpn`, `und`, ...).
+    pub language: String,
+}
+
+/// Conventional titles for Steam's audio streams, by position: game audio first, then the mic.
+pub fn default_audio_title(index: usize) -> String {
+    match index {
+        0 => "Game".to_string(),
+        1 => "Microphone".to_string(),
+        n => format!("Audio {}", n + 1),
+    }
+}
+
+impl RemuxOptions {
+    /// The `-movflags` value to pass, if any; only MP4 has them.
+    pub fn movflags(&self) -> Option<String> {
+        if self.container != Container::Mp4 {
+            return None;
+        }
+        let mut flags = match self.movflags.as_deref() {
+            None => "+faststart",
+            Some(flags) => flags,
+        }
+        .to_string();
+        if self
+            .tags
+            .iter()
+            .any(|(k, _)| !MP4_STANDARD_TAGS.contains(&k.as_str()))
+            && !flags.contains("use_metadata_tags")
+        {
+            flags.push_str("+use_metadata_tags");
+        }
+        (!flags.is_empty()).then_some(flags)
+    }
+
+    /// True when neither stream is re-encoded.
+    pub fn is_copy(&self) -> bool {
+        self.video_encoder.is_none()
+            && self.audio_encoder.is_none()
+            && !self.is_branded()
+            && self.blur.is_empty()
+            && self.finishing_graph("0:v:0").is_none()
+            && self.container.copies_streams()
+    }
+
+    /// The audio that goes into the output: none in a GIF.
+    fn audio(&self) -> AudioSelect {
+        match self.container {
+            Container::Gif => AudioSelect::Silent,
+            _ => self.audio,
+        }
+    }
+
+    /// The audio encoder: Opus in WebM, which takes no AAC.
+    fn audio_encoder(&self) -> Option<&str> {
+        match self.container {
+            Container::Webm => Some("libopus"),
+            _ => self.audio_encoder.as_deref(),
+        }
+    }
+
+    /// Filters bringing the video `[input]` to `[v]` at the last: the frame rate and width,
+    /// and for a GIF its palette (made from the clip, so colours don't band). None when the
+    /// video needs none of them.
+    fn finishing_graph(&self, input: &str) -> Option<String> {
+        let gif = self.container == Container::Gif;
+        let mut chain = Vec::new();
+        if let Some(fps) = self.fps.or(gif.then_some(GIF_FPS)) {
+            chain.push(format!("fps={fps}"));
+        }
+        // -2 keeps the height even, which 4:2:0 encoders need.
+        if let Some(width) = self.width.or(gif.then_some(GIF_WIDTH)) {
+            chain.push(format!("scale={width}:-2:flags=lanczos"));
+        }
+        if chain.is_empty() {
+            return None;
+        }
+        let chain = chain.join(",");
+        Some(if gif {
+            format!(
+                "[{input}]{chain},split[gif_a][gif_b];[gif_a]palettegen[gif_p];\
+                 [gif_b][gif_p]paletteuse[v]"
+            )
+        } else {
+            format!("[{input}]{chain}[v]")
+        })
+    }
+
+    /// An intro, outro or watermark is set, so the streams go through a filter graph.
+    pub fn is_branded(&self) -> bool {
+        self.intro.is_some() || self.outro.is_some() || self.watermark.is_some()
+    }
+}
+
+/// A path as an ffmpeg file argument. The `file:` prefix stops names containing `:` from
+/// being taken as a protocol, and the path stays an `OsStr`, so non-UTF-8 (Unix) or
+/// non-ASCII (Windows, passed as UTF-16) names reach ffmpeg unchanged.
+pub fn file_arg(path: &Path) -> OsString {
+    let mut arg = OsString::from("file:");
+    arg.push(path.as_os_str());
+    arg
+}
+
+/// ffmpeg command that reads `<clip_dir>/session.mpd` and writes `out_path`.
+pub fn remux_command(clip_dir: &Path, out_path: &Path, opts: &RemuxOptions) -> Command {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.current_dir(clip_dir) // MPD uses relative paths
+        .args(["-hide_banner", "-y"])
+        .args(["-loglevel", opts.loglevel.as_deref().unwrap_or("error")]);
+    if opts.progress_pipe {
+        cmd.args(["-progress", "pipe:1"]);
+    }
+    cmd.arg(if opts.stats { "-stats" } else { "-nostats" });
+    cmd.args(&opts.input_args);
+    // As input options, so `-to` is measured from the clip start like `-ss`.
+    if let Some(t) = opts.trim_start {
+        cmd.args(["-ss", &t.to_string()]);
+    }
+    if let Some(t) = opts.trim_end {
+        cmd.args(["-to", &t.to_string()]);
+    }
+    cmd.args(["-i", "session.mpd"]);
+    let audio = opts.audio();
+    let filtered = !opts.blur.is_empty() || opts.finishing_graph("0:v:0").is_some();
+    if opts.is_branded() {
+        add_branding(&mut cmd, opts);
+    } else if filtered {
+        let (mut graph, video) = blur_graph(&opts.blur, "0:v:0");
+        graph.push(
+            opts.finishing_graph(&video)
+                .unwrap_or_else(|| format!("[{video}]null[v]")),
+        );
+        graph.extend(audio.mix_graph());
+        cmd.args(["-filter_complex", &graph.join(";")])
+            .args(["-map", "[v]"])
+            .args(audio.maps());
+    } else {
+        if let Some(mix) = audio.mix_graph() {
+            cmd.args(["-filter_complex", &mix]);
+        }
+        cmd.args(["-map", "0:v:0"]).args(audio.maps());
+    }
+    if let Some(n) = opts.threads {
+        cmd.args(["-threads", &n.to_string()]);
+    }
+    // A chosen encoder comes with its own arguments; the container's default with its.
+    let video_encoder = |default: &'static str| match &opts.video_encoder {
+        Some(encoder) => (encoder.as_str(), opts.video_args.clone()),
+        None if default == "copy" => (default, Vec::new()),
+        None => {
+            let mut args: Vec<String> = opts
+                .container
+                .video_defaults()
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            args.extend(opts.video_args.iter().cloned());
+            (default, args)
+        }
+    };
+    if opts.is_branded() {
+        let (encoder, args) = video_encoder(opts.container.video_encoder());
+        cmd.args(["-c:v", encoder])
+            .args(args)
+            .args(["-c:a", opts.audio_encoder().unwrap_or("aac")])
+            .args(&opts.audio_args);
+    } else if opts.is_copy() {
+        cmd.args(["-c", "copy"]);
+    } else {
+        let (encoder, args) = video_encoder(if filtered || !opts.container.copies_streams() {
+            opts.container.video_encoder()
+        } else {
+            "copy"
+        });
+        cmd.args(["-c:v", encoder]).args(args);
+        if audio == AudioSelect::Silent {
+            cmd.arg("-an");
+        } else {
+            cmd.args(["-c:a", opts.audio_encoder().unwrap_or("copy")])
+                .args(&opts.audio_args);
+        }
+    }
+    for (i, label) in opts.audio_labels.iter().enumerate() {
+        let spec = format!("-metadata:s:a:{i}");
+        cmd.args([&spec, &format!("title={}", label.title)])
+            .args([&spec, &format!("language={}", label.language)]);
+    }
+    if let Some(title) = &opts.title {
+        cmd.args(["-metadata", &format!("title={title}")]);
+    }
+    for (key, value) in &opts.tags {
+        cmd.args(["-metadata", &format!("{key}={value}")]);
+    }
+    if let Some(flags) = opts.movflags() {
+        cmd.args(["-movflags", &flags]);
+    }
+    // Play in a loop, as GIFs shared in chats are expected to.
+    if opts.container == Container::Gif {
+        cmd.args(["-loop", "0"]);
+    }
+    cmd.arg(file_arg(out_path));
+    if let Some(previews) = &opts.previews {
+        cmd.args(previews.args(out_path, &opts.blur));
+    }
+    cmd
+}
+
+/// Filters blurring `regions` of the video `[input]`, and the label of the result. Each
+/// region is cut out, box-blurred and laid back over the frame.
+fn blur_graph(regions: &[Region], input: &str) -> (Vec<String>, String) {
+    let mut graph = Vec::new();
+    let mut video = input.to_string();
+    for (i, r) in regions.iter().enumerate() {
+        // boxblur needs the radius within half of the smallest (chroma) plane side.
+        let radius = (r.w.min(r.h) / 4).clamp(1, 20);
+        graph.push(format!("[{video}]split[blur_base{i}][blur_src{i}]"));
+        graph.push(format!(
+            "[blur_src{i}]crop={}:{}:{}:{},boxblur={radius}:2[blur_area{i}]",
+            r.w, r.h, r.x, r.y
+        ));
+        graph.push(format!(
+            "[blur_base{i}][blur_area{i}]overlay={}:{}[blurred{i}]",
+            r.x, r.y
+        ));
+        video = format!("blurred{i}");
+    }
+    (graph, video)
+}
+
+/// Inputs and filter graph for intro/outro/watermark, after any --blur. Intros and outros are scaled to the
+/// recording's size and must have an audio track (concat needs one per segment). The
+/// recording's audio is the [`AudioSelect::Track`] chosen, or its first stream. The joined
+/// audio is always mapped, so this doesn't work for a GIF.
+fn add_branding(cmd: &mut Command, opts: &RemuxOptions) {
+    let mut next_input = 1;
+    let mut input = |cmd: &mut Command, path: &Path, image: bool| {
+        if image {
+            cmd.args(["-loop", "1"]);
+        }
+        cmd.arg("-i").arg(file_arg(path));
+        next_input += 1;
+        next_input - 1
+    };
+    let intro = opts.intro.as_deref().map(|p| input(cmd, p, false));
+    let outro = opts.outro.as_deref().map(|p| input(cmd, p, false));
+    let watermark = opts.watermark.as_deref().map(|p| input(cmd, p, true));
+
+    let (mut graph, video) = blur_graph(&opts.blur, "0:v:0");
+    graph.push(match watermark {
+        // shortest=1: the looped image would otherwise never end.
+        Some(w) => format!(
+            "[{video}][{w}:v]overlay=W-w-24:H-h-24:shortest=1,format=yuv420p,setsar=1[main]"
+        ),
+        None => format!("[{video}]format=yuv420p,setsar=1[main]"),
+    });
+    let track = match opts.audio {
+        AudioSelect::Track(i) => i,
+        _ => 0,
+    };
+    graph.push(format!("[0:a:{track}]aresample=48000[main_a]"));
+
+    let mut parts = Vec::new();
+    let mut reference = "main".to_string();
+    for (name, idx) in [("intro", intro), ("outro", outro)] {
+        let Some(i) = idx else { continue };
+        graph.push(format!(
+            "[{i}:v:0][{reference}]scale2ref[{name}_s][{name}_ref]"
+        ));
+        graph.push(format!("[{name}_s]format=yuv420p,setsar=1[{name}]"));
+        graph.push(format!("[{i}:a:0]aresample=48000[{name}_a]"));
+        reference = format!("{name}_ref");
+        parts.push(name);
+    }
+
+    if parts.is_empty() {
+        graph.push(format!("[{reference}]null[joined]"));
+        graph.push("[main_a]anull[a]".into());
+    } else {
+        let mut order = Vec::new();
+        if intro.is_some() {
+            order.push("[intro][intro_a]".to_string());
+        }
+        order.push(format!("[{reference}][main_a]"));
+        if outro.is_some() {
+            order.push("[outro][outro_a]".to_string());
+        }
+        graph.push(format!(
+            "{}concat=n={}:v=1:a=1[joined][a]",
+            order.concat(),
+            order.len()
+        ));
+    }
+    graph.push(
+        opts.finishing_graph("joined")
+            .unwrap_or_else(|| "[joined]null[v]".into()),
+    );
+    cmd.args(["-filter_complex", &graph.join(";")])
+        .args(["-map", "[v]", "-map", "[a]"]);
+}
+
+/// Kinds of ffmpeg component a conversion can depend on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentKind {
+    Demuxer,
+    Muxer,
+    Encoder,
+}
+
+impl ComponentKind {
+    /// The ffmpeg option that lists every component of this kind.
+    fn list_flag(self) -> &'static str {
+        match self {
+            ComponentKind::Demuxer => "-demuxers",
+            ComponentKind::Muxer => "-muxers",
+            ComponentKind::Encoder => "-encoders",
+        }
+    }
+}
+
+/// One demuxer, muxer or encoder by its ffmpeg name (`dash`, `mp4`, `libx264`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Component {
+    pub kind: ComponentKind,
+    pub name: String,
+}
+
+impl Component {
+    pub fn new(kind: ComponentKind, name: &str) -> Self {
+        Component {
+            kind,
+            name: name.to_string(),
+        }
+    }
+
+    /// What every conversion into `container` needs: reading session.mpd and writing the
+    /// file.
+    pub fn remux(container: Container) -> Vec<Component> {
+        vec![
+            Component::new(ComponentKind::Demuxer, "dash"),
+            Component::new(ComponentKind::Muxer, container.muxer()),
+        ]
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ComponentKind::Demuxer => "demuxer",
+            ComponentKind::Muxer => "muxer",
+            ComponentKind::Encoder => "encoder",
+        };
+        write!(f, "{} {}", self.name, kind)
+    }
+}
+
+/// The components in `required` that the `ffmpeg` on PATH was built without, found by asking
+/// it for its `-demuxers`/`-muxers`/`-encoders` lists.
+pub fn missing_components(required: &[Component]) -> Result<Vec<Component>, Error> {
+    let mut missing = Vec::new();
+    for kind in [
+        ComponentKind::Demuxer,
+        ComponentKind::Muxer,
+        ComponentKind::Encoder,
+    ] {
+        if !required.iter().any(|c| c.kind == kind) {
+            continue;
+        }
+        let out = Command::new("ffmpeg")
+            .args(["-hide_banner", kind.list_flag()])
+            .output()
+            .map_err(|source| Error::Spawn {
+                program: "ffmpeg".into(),
+                source,
+            })?;
+        if !out.status.success() {
+            return Err(Error::Ffmpeg {
+                exit: out.status.code(),
+                stderr: String::from_utf8_lossy(&out.stderr).into_owned(),
+            });
+        }
+        let available = parse_component_list(&String::from_utf8_lossy(&out.stdout));
+        missing.extend(
+            required
+                .iter()
+                .filter(|c| c.kind == kind && !available.contains(&c.name))
+                .cloned(),
+        );
+    }
+    Ok(missing)
+}
+
+/// Names from an ffmpeg component listing: a legend, a `--` (or `------`) line, then one
+/// ` <flags> <name[,alias...]> <description>` line per component.
+fn parse_component_list(txt: &str) -> Vec<String> {
+    txt.lines()
+        .skip_while(|l| !l.trim_start().starts_with("--"))
+        .skip(1)
+        .filter_map(|l| l.split_whitespace().nth(1))
+        .flat_map(|names| names.split(','))
+        .map(str::to_string)
+        .collect()
+}
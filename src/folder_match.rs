@@ -0,0 +1,52 @@
+//! `--match`: pick clips by their folder name, e.g. `--match 'fg_294100_202508*'` for one
+//! game's clips of August 2025, without working out --since/--until and --gameId.
+
+use crate::glob_match;
+use regex::Regex;
+use std::path::Path;
+use steamclipconverter::ClipDir;
+
+/// A glob of a whole folder name (`*` any run of characters, `?` any one), or with `re:` a
+/// regex found anywhere in it.
+#[derive(Clone, Debug)]
+pub enum FolderPattern {
+    Glob(Vec<char>),
+    Regex(Regex),
+}
+
+impl FolderPattern {
+    /// Used as the clap value parser of --match.
+    pub fn parse(s: &str) -> Result<FolderPattern, String> {
+        match s.strip_prefix("re:") {
+            Some(re) => Regex::new(re)
+                .map(FolderPattern::Regex)
+                .map_err(|e| format!("bad regex '{re}': {e}")),
+            None if s.is_empty() => Err("empty pattern".into()),
+            None => Ok(FolderPattern::Glob(s.chars().collect())),
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        match self {
+            FolderPattern::Glob(p) => glob_match(p, &name.chars().collect::<Vec<_>>()),
+            FolderPattern::Regex(re) => re.is_match(name),
+        }
+    }
+}
+
+/// Whether `clip` passes the --match `patterns` (none passes all): one of them matches its
+/// `fg_*`/`bg_*` folder or the `clip_*` folder around it.
+pub fn matches(patterns: &[FolderPattern], clip: &ClipDir) -> bool {
+    let name = |p: Option<&Path>| {
+        p.and_then(Path::file_name)
+            .and_then(|n| n.to_str())
+            .map(str::to_string)
+    };
+    let own = name(Some(&clip.dir));
+    // <clip_*>/video/<fg_*>
+    let outer = name(clip.dir.parent().and_then(Path::parent)).filter(|n| n.starts_with("clip_"));
+    patterns.is_empty()
+        || patterns
+            .iter()
+            .any(|p| own.iter().chain(outer.iter()).any(|n| p.matches_name(n)))
+}
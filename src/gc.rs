@@ -0,0 +1,84 @@
+//! `--gc-empty`: tidy the recording tree after a batch, so Steam's recording manager
+//! doesn't trip over half-deleted clips.
+//!
+//! Only Steam's own folders are touched: `video/` and `timelines/` directly under a
+//! `gamerecordings/` or `clip_*` folder are removed once empty, and a `clip_*` folder with no
+//! `fg_*` recording left in its `video/` is removed with its leftovers (thumbnails,
+//! timelines, metadata).
+
+use crate::report::say;
+use regex::Regex;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    let mut out: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    out.sort();
+    out
+}
+
+fn name(p: &Path) -> &str {
+    p.file_name().and_then(|n| n.to_str()).unwrap_or("")
+}
+
+fn is_empty_dir(p: &Path) -> bool {
+    fs::read_dir(p).is_ok_and(|mut it| it.next().is_none())
+}
+
+/// A clip (`fg_*`) or background recording (`bg_*`) folder.
+fn is_recording(name: &str) -> bool {
+    name.starts_with("fg_") || name.starts_with("bg_")
+}
+
+/// Does this clip_* folder still hold a recording?
+fn has_recording(clip: &Path) -> bool {
+    subdirs(&clip.join("video"))
+        .iter()
+        .any(|d| is_recording(name(d)))
+}
+
+/// Sweep `root`; returns how many folders were removed.
+pub fn sweep(root: &Path) -> usize {
+    let clip_re = Regex::new(r"^clip_\d+_\d{8}_\d{6}$").unwrap();
+    let mut removed = 0;
+    visit(root, &clip_re, &mut removed);
+    removed
+}
+
+fn visit(dir: &Path, clip_re: &Regex, removed: &mut usize) {
+    let steam_parent = name(dir) == "gamerecordings" || clip_re.is_match(name(dir));
+    for sub in subdirs(dir) {
+        let n = name(&sub);
+        if is_recording(n) {
+            continue; // recordings are never touched here
+        }
+        if clip_re.is_match(n) && !has_recording(&sub) {
+            match fs::remove_dir_all(&sub) {
+                Ok(()) => {
+                    say!("[gc] removed {}", sub.display());
+                    *removed += 1;
+                }
+                Err(e) => eprintln!("[warn] gc failed for {}: {}", sub.display(), e),
+            }
+            continue;
+        }
+        visit(&sub, clip_re, removed);
+        if steam_parent && matches!(n, "video" | "timelines") && is_empty_dir(&sub) {
+            match fs::remove_dir(&sub) {
+                Ok(()) => {
+                    say!("[gc] removed empty {}", sub.display());
+                    *removed += 1;
+                }
+                Err(e) => eprintln!("[warn] gc failed for {}: {}", sub.display(), e),
+            }
+        }
+    }
+}
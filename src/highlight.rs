@@ -0,0 +1,276 @@
+//! `highlight` subcommand: a highlight reel cut from the moments Steam marked on its
+//! timeline (kills, achievements, bookmarks), joined with crossfades.
+//!
+//! Works on an output folder written with `--layout sessions`, whose session.json files
+//! carry each clip's markers; the originals aren't needed.
+
+use crate::{
+    duration,
+    json::{self, Value},
+    session::{self, Item},
+};
+use chrono::{DateTime, Utc};
+use clap::{ArgAction, Args};
+use sanitize_filename::sanitize;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+use steamclipconverter::ffmpeg::file_arg;
+
+#[derive(Args, Debug)]
+pub struct HighlightArgs {
+    /// Session-layout output folder to take clips from (defaults to the current directory).
+    dir: Option<PathBuf>,
+
+    /// Where to write the reel(s) (defaults to the current directory).
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// One reel per game instead of a single one for everything.
+    #[arg(long, action = ArgAction::SetTrue)]
+    per_game: bool,
+
+    /// Longest a reel may get; the most recent moments are kept.
+    #[arg(long, value_name = "DURATION", default_value = "3m", value_parser = duration::parse_duration)]
+    max_length: Duration,
+
+    /// Only use clips recorded this long ago or more recently.
+    #[arg(long, value_name = "DURATION", default_value = "7d", value_parser = duration::parse_duration)]
+    since: Duration,
+
+    /// How much to include before each marker.
+    #[arg(long, value_name = "DURATION", default_value = "8s", value_parser = duration::parse_duration)]
+    before: Duration,
+
+    /// How much to include after each marker.
+    #[arg(long, value_name = "DURATION", default_value = "4s", value_parser = duration::parse_duration)]
+    after: Duration,
+
+    /// Crossfade between segments, in seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 0.5)]
+    crossfade: f64,
+}
+
+/// A piece of one converted clip.
+#[derive(Clone, Debug)]
+struct Segment {
+    file: PathBuf,
+    /// When the segment starts in real time (ordering across clips).
+    at: DateTime<Utc>,
+    start: f64,
+    len: f64,
+}
+
+/// Every session.json below `dir`.
+fn sidecars(dir: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        for ent in fs::read_dir(&d).into_iter().flatten().flatten() {
+            let p = ent.path();
+            if p.is_dir() {
+                stack.push(p);
+            } else if ent.file_name() == session::SIDECAR {
+                out.push(d.clone());
+            }
+        }
+    }
+    out.sort();
+    out
+}
+
+/// Marker windows of one clip, merged where they overlap.
+fn windows(markers: &[f64], duration: f64, before: f64, after: f64) -> Vec<(f64, f64)> {
+    let mut out: Vec<(f64, f64)> = Vec::new();
+    for &m in markers {
+        let (s, e) = ((m - before).max(0.0), (m + after).min(duration));
+        if e <= s {
+            continue;
+        }
+        match out.last_mut() {
+            Some(last) if s <= last.1 => last.1 = last.1.max(e),
+            _ => out.push((s, e)),
+        }
+    }
+    out
+}
+
+/// Segments per reel name: the game, or "All" without --per-game.
+fn collect(args: &HighlightArgs, dir: &Path) -> BTreeMap<String, Vec<Segment>> {
+    let cutoff = Utc::now() - chrono::Duration::from_std(args.since).unwrap_or_default();
+    let mut reels: BTreeMap<String, Vec<Segment>> = BTreeMap::new();
+    for session_dir in sidecars(dir) {
+        let game = fs::read_to_string(session_dir.join(session::SIDECAR))
+            .ok()
+            .and_then(|t| json::parse(&t).ok())
+            .and_then(|v| v.get("game").and_then(Value::as_str).map(str::to_string))
+            .unwrap_or_else(|| "Unknown".into());
+        let items = match session::read_items(&session_dir) {
+            Ok(items) => items,
+            Err(e) => {
+                eprintln!("[warn] {}: {}", session_dir.display(), e);
+                continue;
+            }
+        };
+        for item in items {
+            let Item::Clip {
+                file,
+                start,
+                duration: Some(duration),
+                markers,
+                ..
+            } = item
+            else {
+                continue;
+            };
+            let path = session_dir.join(&file);
+            if start < cutoff || markers.is_empty() || !path.is_file() {
+                continue;
+            }
+            let offsets: Vec<f64> = markers.iter().map(|m| m.offset).collect();
+            let key = if args.per_game {
+                game.clone()
+            } else {
+                "All".into()
+            };
+            let ws = windows(
+                &offsets,
+                duration,
+                args.before.as_secs_f64(),
+                args.after.as_secs_f64(),
+            );
+            reels
+                .entry(key)
+                .or_default()
+                .extend(ws.into_iter().map(|(s, e)| Segment {
+                    file: path.clone(),
+                    at: start + chrono::Duration::milliseconds((s * 1000.0) as i64),
+                    start: s,
+                    len: e - s,
+                }));
+        }
+    }
+    reels
+}
+
+/// Keep the newest segments that fit in `max` (counting the crossfade overlap), in time order.
+fn fit(mut segs: Vec<Segment>, max: f64, fade: f64) -> Vec<Segment> {
+    segs.sort_by_key(|s| std::cmp::Reverse(s.at));
+    let mut total = 0.0;
+    let mut kept = Vec::new();
+    for s in segs {
+        let add = if kept.is_empty() { s.len } else { s.len - fade };
+        if total + add > max {
+            continue;
+        }
+        total += add;
+        kept.push(s);
+    }
+    kept.sort_by_key(|s| s.at);
+    kept
+}
+
+/// ffmpeg command cutting `segs` and chaining them with xfade/acrossfade.
+fn reel_command(segs: &[Segment], fade: f64, out: &Path) -> Command {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-hide_banner", "-loglevel", "error", "-y"]);
+    for s in segs {
+        cmd.args([
+            "-ss",
+            &format!("{:.3}", s.start),
+            "-t",
+            &format!("{:.3}", s.len),
+        ])
+        .arg("-i")
+        .arg(file_arg(&s.file));
+    }
+    // Inputs are normalized so xfade accepts clips recorded with different settings.
+    let mut graph = String::new();
+    for i in 0..segs.len() {
+        graph.push_str(&format!(
+            "[{i}:v:0]fps=60,format=yuv420p,settb=AVTB,setpts=PTS-STARTPTS[v{i}];\
+             [{i}:a:0]aresample=48000,asetpts=PTS-STARTPTS[a{i}];"
+        ));
+    }
+    let (mut v, mut a) = ("v0".to_string(), "a0".to_string());
+    let mut offset = 0.0;
+    for i in 1..segs.len() {
+        offset += segs[i - 1].len - fade;
+        graph.push_str(&format!(
+            "[{v}][v{i}]xfade=transition=fade:duration={fade}:offset={offset:.3}[vx{i}];\
+             [{a}][a{i}]acrossfade=d={fade}[ax{i}];"
+        ));
+        v = format!("vx{i}");
+        a = format!("ax{i}");
+    }
+    graph.pop(); // trailing ';'
+    cmd.args(["-filter_complex", &graph])
+        .args(["-map", &format!("[{v}]"), "-map", &format!("[{a}]")])
+        .args(["-c:v", "libx264", "-preset", "medium", "-crf", "20"])
+        .args(["-c:a", "aac", "-b:a", "192k", "-movflags", "+faststart"])
+        .arg(file_arg(out))
+        .stdin(Stdio::null());
+    cmd
+}
+
+pub fn run(args: HighlightArgs) -> i32 {
+    let dir = args
+        .dir
+        .clone()
+        .unwrap_or_else(|| env::current_dir().expect("cwd"));
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| env::current_dir().expect("cwd"));
+    if let Err(e) = fs::create_dir_all(&output) {
+        eprintln!("ERROR: cannot create {}: {}", output.display(), e);
+        return 2;
+    }
+    // A fade can't be longer than the shortest segment it joins.
+    let min_len = (args.before + args.after).as_secs_f64();
+    let fade = args.crossfade.clamp(0.0, min_len / 2.0);
+
+    let reels = collect(&args, &dir);
+    if reels.is_empty() {
+        println!(
+            "No timeline markers in clips of the last {}s under {} (convert with --layout sessions).",
+            args.since.as_secs(),
+            dir.display()
+        );
+        return 0;
+    }
+
+    let day = chrono::Local::now().format("%Y%m%d");
+    let mut failed = 0;
+    for (name, segs) in reels {
+        let segs = fit(segs, args.max_length.as_secs_f64(), fade);
+        if segs.is_empty() {
+            continue;
+        }
+        let out = output.join(format!("{}-highlights-{}.mp4", sanitize(&name), day));
+        let total: f64 = segs.iter().map(|s| s.len).sum::<f64>() - fade * (segs.len() - 1) as f64;
+        println!(
+            "== {}: {} moment(s), {:.0}s -> {}",
+            name,
+            segs.len(),
+            total,
+            out.display()
+        );
+        match reel_command(&segs, fade, &out).status() {
+            Ok(st) if st.success() => println!("[ok] wrote {}", out.display()),
+            Ok(st) => {
+                eprintln!("[fail] ffmpeg status: {}", st);
+                failed += 1;
+            }
+            Err(e) => {
+                eprintln!("[fail] launching ffmpeg: {}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 { 1 } else { 0 }
+}
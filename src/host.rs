@@ -0,0 +1,39 @@
+//! Short machine identifier for telling apart clips recorded on different PCs.
+
+use std::{env, fs, process::Command};
+
+const MAX_LEN: usize = 16;
+
+/// This machine's name, reduced to a short filename-safe tag (lowercase ASCII letters,
+/// digits and `-`); "host" if it can't be determined.
+pub fn machine_tag() -> String {
+    let raw = env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| env::var("HOSTNAME").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| {
+            let out = Command::new("hostname").output().ok()?;
+            Some(String::from_utf8_lossy(&out.stdout).into_owned())
+        })
+        .unwrap_or_default();
+    // Use the first label of an FQDN ("steamdeck.local" -> "steamdeck").
+    let first = raw.trim().split('.').next().unwrap_or("");
+    let tag = tag_from(first);
+    if tag.is_empty() { "host".into() } else { tag }
+}
+
+/// Normalize a user-given or detected name into a tag.
+pub fn tag_from(name: &str) -> String {
+    let mut tag = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            tag.push(c.to_ascii_lowercase());
+        } else if !tag.is_empty() && !tag.ends_with('-') {
+            tag.push('-');
+        }
+        if tag.len() >= MAX_LEN {
+            break;
+        }
+    }
+    tag.trim_end_matches('-').to_string()
+}
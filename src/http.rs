@@ -0,0 +1,522 @@
+//! Just enough HTTP/1.1 for the agent and `pull`: a thread-per-connection server (with a
+//! cap on live connections) and a blocking client, one request per connection.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::json::Value;
+
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+/// Connections handled at once; past this new ones get a 503 instead of a thread.
+const MAX_CONNECTIONS: usize = 64;
+
+pub struct Request {
+    pub method: String,
+    /// Decoded path, without the query string.
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub peer: SocketAddr,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Start of an open-ended `Range: bytes=<start>-` request, the only form `pull` sends.
+    pub fn range_start(&self) -> Option<u64> {
+        let spec = self.header("Range")?.trim().strip_prefix("bytes=")?;
+        spec.strip_suffix('-')?.parse().ok()
+    }
+
+    pub fn query(&self, key: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Path split on `/`, without empty segments.
+    pub fn segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|s| !s.is_empty()).collect()
+    }
+}
+
+pub enum Body {
+    Bytes(Vec<u8>),
+    /// `len` bytes from the file's current position.
+    File {
+        file: File,
+        len: u64,
+    },
+    /// Written as it is read, until EOF, without a length (server-sent events).
+    Stream(Box<dyn Read + Send>),
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: Vec<u8>) -> Self {
+        Response {
+            status,
+            headers: vec![("Content-Type".into(), content_type.into())],
+            body: Body::Bytes(body),
+        }
+    }
+
+    pub fn json(status: u16, v: &Value) -> Self {
+        Response::new(status, "application/json", format!("{v}\n").into_bytes())
+    }
+
+    /// `{"error": msg}` with `status`.
+    pub fn error(status: u16, msg: &str) -> Self {
+        Response::json(status, &crate::json::obj([("error", msg.into())]))
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        206 => "Partial Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        416 => "Range Not Satisfiable",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// `%XX` decoding (and `+` as space in query strings).
+pub fn percent_decode(s: &str, plus_is_space: bool) -> String {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'%' if i + 2 < b.len() => {
+                let hex = std::str::from_utf8(&b[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(v) => {
+                        out.push(v);
+                        i += 3;
+                        continue;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b'+' if plus_is_space => out.push(b' '),
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Encode one path segment.
+pub fn percent_encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with padding, for the client's basic-auth header.
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().fold(0u32, |acc, b| acc << 8 | *b as u32) << (8 * (3 - chunk.len()));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Standard base64 (with or without padding).
+pub fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in s.trim_end_matches('=').bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn read_head(reader: &mut impl BufRead) -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    let mut total = 0;
+    loop {
+        let mut line = String::new();
+        // Bounded, so one endless line can't grow the buffer before the check below.
+        let limit = (MAX_HEADER_BYTES - total + 1) as u64;
+        let n = reader.by_ref().take(limit).read_line(&mut line)?;
+        total += n;
+        if n == 0 || total > MAX_HEADER_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad HTTP head"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if line.is_empty() {
+            return Ok(lines);
+        }
+        lines.push(line);
+    }
+}
+
+fn parse_headers(lines: &[String]) -> Vec<(String, String)> {
+    lines
+        .iter()
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+fn read_request(stream: &TcpStream, peer: SocketAddr) -> io::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let head = read_head(&mut reader)?;
+    let Some(request_line) = head.first() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "empty request line",
+        ));
+    };
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "bad request line",
+        ));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+            (percent_decode(k, true), percent_decode(v, true))
+        })
+        .collect();
+    Ok(Request {
+        method: method.to_string(),
+        path: percent_decode(path, false),
+        query,
+        headers: parse_headers(&head[1..]),
+        peer,
+    })
+}
+
+fn write_response(mut stream: &TcpStream, resp: Response, head_only: bool) -> io::Result<()> {
+    let len = match &resp.body {
+        Body::Bytes(b) => Some(b.len() as u64),
+        Body::File { len, .. } => Some(*len),
+        Body::Stream(_) => None,
+    };
+    let mut head = format!("HTTP/1.1 {} {}\r\n", resp.status, reason(resp.status));
+    for (k, v) in &resp.headers {
+        head.push_str(&format!("{k}: {v}\r\n"));
+    }
+    if let Some(len) = len {
+        head.push_str(&format!("Content-Length: {len}\r\n"));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    stream.write_all(head.as_bytes())?;
+    if head_only {
+        return Ok(());
+    }
+    match resp.body {
+        Body::Bytes(b) => stream.write_all(&b)?,
+        Body::File { file, len } => {
+            io::copy(&mut file.take(len), &mut stream)?;
+        }
+        Body::Stream(mut r) => {
+            let mut buf = [0u8; 8192];
+            loop {
+                let n = r.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                stream.write_all(&buf[..n])?;
+                stream.flush()?;
+            }
+        }
+    }
+    stream.flush()
+}
+
+/// Serve `handler` on `listener` until the process ends.
+pub fn serve(
+    listener: TcpListener,
+    handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+) -> io::Result<()> {
+    serve_capped(listener, MAX_CONNECTIONS, handler)
+}
+
+/// A taken connection slot, given back when the handler thread ends (or unwinds).
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn serve_capped(
+    listener: TcpListener,
+    max: usize,
+    handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+) -> io::Result<()> {
+    let handler = Arc::new(handler);
+    let live = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        if live.fetch_add(1, Ordering::SeqCst) >= max {
+            live.fetch_sub(1, Ordering::SeqCst);
+            // Answered from the accept loop, so a slow client can't hold it up for long.
+            let _ = stream.set_write_timeout(Some(Duration::from_secs(2)));
+            let busy = Response::error(503, "too many connections").header("Retry-After", "1");
+            let _ = write_response(&stream, busy, false);
+            continue;
+        }
+        let slot = Slot(Arc::clone(&live));
+        let handler = Arc::clone(&handler);
+        thread::spawn(move || {
+            let _slot = slot;
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(30)));
+            let Ok(peer) = stream.peer_addr() else { return };
+            let resp = match read_request(&stream, peer) {
+                Ok(req) => {
+                    let resp = handler(&req);
+                    let _ = write_response(&stream, resp, req.method == "HEAD");
+                    return;
+                }
+                Err(_) => Response::error(400, "malformed request"),
+            };
+            let _ = write_response(&stream, resp, false);
+        });
+    }
+    Ok(())
+}
+
+/// A response being read by the client; `body` yields exactly the announced length.
+pub struct ClientResponse {
+    pub status: u16,
+    pub body: io::Take<BufReader<TcpStream>>,
+}
+
+impl ClientResponse {
+    pub fn text(mut self) -> io::Result<String> {
+        let mut s = String::new();
+        self.body.read_to_string(&mut s)?;
+        Ok(s)
+    }
+
+    /// The body as JSON, or the `error` the server sent.
+    pub fn json(self) -> io::Result<Value> {
+        let status = self.status;
+        let v = crate::json::parse(&self.text()?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if !(200..300).contains(&status) {
+            return Err(io::Error::other(format!(
+                "HTTP {status}: {}",
+                v.str_field("error")
+            )));
+        }
+        Ok(v)
+    }
+}
+
+/// Send one request to `authority` (`host:port`) and read the response head.
+pub fn request(
+    authority: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, String)],
+) -> io::Result<ClientResponse> {
+    send(authority, method, path, headers, &[])
+}
+
+/// Like [`request`], with a request body.
+pub fn send(
+    authority: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> io::Result<ClientResponse> {
+    let addr = authority
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::other(format!("{authority}: no address")))?;
+    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(10))?;
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+    let mut head =
+        format!("{method} {path} HTTP/1.1\r\nHost: {authority}\r\nConnection: close\r\n");
+    for (k, v) in headers {
+        head.push_str(&format!("{k}: {v}\r\n"));
+    }
+    head.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+
+    let mut reader = BufReader::new(stream);
+    let lines = read_head(&mut reader)?;
+    let status = lines
+        .first()
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad status line"))?;
+    let headers = parse_headers(&lines[1..]);
+    let len = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(u64::MAX);
+    Ok(ClientResponse {
+        status,
+        body: reader.take(len),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, mpsc};
+
+    #[test]
+    fn percent_coding() {
+        assert_eq!(percent_decode("a%20b%2Fc", false), "a b/c");
+        assert_eq!(percent_decode("a+b", false), "a+b");
+        assert_eq!(percent_decode("a+b", true), "a b");
+        assert_eq!(percent_decode("%e6%97%a5", false), "日");
+        // Bad or cut-off escapes are kept as written.
+        assert_eq!(percent_decode("100%", false), "100%");
+        assert_eq!(percent_decode("%4", false), "%4");
+        assert_eq!(percent_decode("%zz%41", false), "%zzA");
+        assert_eq!(percent_decode("%ff", false), "\u{fffd}");
+
+        assert_eq!(percent_encode("Clip_1-2.~"), "Clip_1-2.~");
+        assert_eq!(percent_encode("a b/c?"), "a%20b%2Fc%3F");
+        assert_eq!(percent_encode("日"), "%E6%97%A5");
+        for s in ["fg_1_2_3", "a b+c%d", "日本/録画", ""] {
+            assert_eq!(percent_decode(&percent_encode(s), true), s);
+        }
+    }
+
+    #[test]
+    fn base64() {
+        // RFC 4648 section 10.
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, coded) in vectors {
+            assert_eq!(base64_encode(plain.as_bytes()), coded);
+            assert_eq!(base64_decode(coded).unwrap(), plain.as_bytes());
+            assert_eq!(
+                base64_decode(coded.trim_end_matches('=')).unwrap(),
+                plain.as_bytes()
+            );
+        }
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(base64_decode(&base64_encode(&bytes)).unwrap(), bytes);
+        assert_eq!(base64_decode("Zm9v YmFy"), None);
+        assert_eq!(base64_decode("Zm9v-_"), None);
+    }
+
+    #[test]
+    fn connections_past_the_cap_get_503() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let authority = listener.local_addr().unwrap().to_string();
+        let (entered_tx, entered) = mpsc::channel();
+        let (release, release_rx) = mpsc::channel::<()>();
+        let (entered_tx, release_rx) = (Mutex::new(entered_tx), Mutex::new(release_rx));
+        thread::spawn(move || {
+            serve_capped(listener, 1, move |req| {
+                if req.path == "/hold" {
+                    entered_tx.lock().unwrap().send(()).unwrap();
+                    release_rx.lock().unwrap().recv().unwrap();
+                }
+                Response::new(200, "text/plain", b"ok".to_vec())
+            })
+        });
+
+        let held = {
+            let authority = authority.clone();
+            thread::spawn(move || request(&authority, "GET", "/hold", &[]).unwrap().status)
+        };
+        entered.recv().unwrap();
+        let busy = request(&authority, "GET", "/", &[]).unwrap();
+        assert_eq!(busy.status, 503);
+        assert!(busy.text().unwrap().contains("too many connections"));
+
+        release.send(()).unwrap();
+        assert_eq!(held.join().unwrap(), 200);
+        // The slot comes back once the handler thread is done with it.
+        let mut status = 0;
+        for _ in 0..100 {
+            status = request(&authority, "GET", "/", &[]).unwrap().status;
+            if status == 200 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(status, 200);
+    }
+}
@@ -0,0 +1,360 @@
+//! `index` subcommand: an `index.html` (or `README.md`) for the output folder, listing its
+//! clips by game and month with links and thumbnails, so the folder can be browsed like an
+//! archive: opened from a file manager, a NAS share or any static web server.
+//!
+//! Game, record time and length come from the catalog when it has the file, otherwise from
+//! the default `Game-YYYYMMDD-HHMMSS` file name and the file's time (set to the record
+//! start). Thumbnails are the `<name>.jpg` next to a clip, as the `thumbnail`
+//! post-processing step writes them; `--thumbnails` makes the missing ones.
+
+use crate::{
+    catalog::Catalog, http::percent_encode, preview::escape, quota::format_size,
+    visibility::Visibility,
+};
+use chrono::{DateTime, Local};
+use clap::{ArgAction, Args, ValueEnum};
+use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+use steamclipconverter::{atomic, ffmpeg::file_arg};
+
+/// Extensions listed as clips.
+const VIDEO_EXTS: &[&str] = &["mp4", "mkv", "webm", "mov"];
+
+/// Width of the thumbnails `--thumbnails` makes.
+const THUMB_WIDTH: u32 = 320;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum IndexFormat {
+    /// index.html, with a grid of thumbnails.
+    #[default]
+    Html,
+    /// README.md, which Git hosts and many file browsers render.
+    Markdown,
+}
+
+impl IndexFormat {
+    fn file_name(self) -> &'static str {
+        match self {
+            IndexFormat::Html => "index.html",
+            IndexFormat::Markdown => "README.md",
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct IndexArgs {
+    #[command(flatten)]
+    source: Source,
+
+    #[arg(long, value_enum, default_value_t)]
+    format: IndexFormat,
+
+    /// Write the index here instead of index.html / README.md in the folder.
+    #[arg(long, value_name = "FILE")]
+    to: Option<PathBuf>,
+}
+
+/// Which clips an index or gallery lists; shared with `export-site`.
+#[derive(Args, Debug)]
+pub struct Source {
+    /// Output folder to index (searched recursively).
+    pub dir: PathBuf,
+
+    /// Catalog to take game names, record times and lengths from (defaults to the user
+    /// catalog).
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+
+    /// Make a thumbnail (`<name>.jpg`, a frame one second in) for clips without one.
+    #[arg(long, action = ArgAction::SetTrue)]
+    thumbnails: bool,
+
+    /// Also list clips Steam marked private, which are left out by default.
+    #[arg(long, action = ArgAction::SetTrue)]
+    include_private: bool,
+}
+
+pub struct Item {
+    pub path: PathBuf,
+    pub thumb: Option<PathBuf>,
+    pub game: String,
+    /// Local record start, `YYYY-MM-DD HH:MM`.
+    pub when: String,
+    pub duration: Option<f64>,
+    pub size: u64,
+    /// Tags given in `review`.
+    pub tags: Vec<String>,
+}
+
+/// Items by game, then by month (`YYYY-MM`).
+type Groups = BTreeMap<String, BTreeMap<String, Vec<Item>>>;
+
+pub fn run(args: IndexArgs) -> i32 {
+    let Some((items, private)) = collect(&args.source) else {
+        return 2;
+    };
+    let mut groups = Groups::new();
+    let listed = items.len();
+    for item in items {
+        let month = item.when.get(..7).unwrap_or("unknown").to_string();
+        groups
+            .entry(item.game.clone())
+            .or_default()
+            .entry(month)
+            .or_default()
+            .push(item);
+    }
+
+    let out = args
+        .to
+        .clone()
+        .unwrap_or_else(|| args.source.dir.join(args.format.file_name()));
+    let base = out.parent().unwrap_or(Path::new("."));
+    let title = args.source.title();
+    let text = match args.format {
+        IndexFormat::Html => html(&title, &groups, base),
+        IndexFormat::Markdown => markdown(&title, &groups, base),
+    };
+    if let Err(e) = atomic::write(&out, text) {
+        eprintln!("ERROR: cannot write {}: {}", out.display(), e);
+        return 1;
+    }
+    println!(
+        "Wrote {}: {} clip(s) from {} game(s){}",
+        out.display(),
+        listed,
+        groups.len(),
+        private_note(private)
+    );
+    0
+}
+
+/// `, 2 private clip(s) left out (--include-private)`, or nothing.
+pub fn private_note(private: usize) -> String {
+    if private > 0 {
+        format!(", {private} private clip(s) left out (--include-private)")
+    } else {
+        String::new()
+    }
+}
+
+impl Source {
+    /// The folder's name, as the page title.
+    pub fn title(&self) -> String {
+        self.dir
+            .canonicalize()
+            .ok()
+            .and_then(|d| d.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "Clips".to_string())
+    }
+}
+
+/// The clips to list, newest first, and how many private ones were left out. None (after
+/// the error is printed) when the folder isn't one.
+pub fn collect(args: &Source) -> Option<(Vec<Item>, usize)> {
+    if !args.dir.is_dir() {
+        eprintln!("ERROR: not a directory: {}", args.dir.display());
+        return None;
+    }
+    let catalog = args
+        .catalog
+        .clone()
+        .or_else(Catalog::default_path)
+        .filter(|p| p.is_file())
+        .and_then(|p| match Catalog::open(&p) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                eprintln!("[warn] cannot read catalog {}: {}", p.display(), e);
+                None
+            }
+        });
+    let default_name = Regex::new(r"^(.+?)-(\d{4})(\d{2})(\d{2})-(\d{2})(\d{2})\d{2}").unwrap();
+
+    let mut files = Vec::new();
+    find_videos(&args.dir, &mut files);
+    let mut items = Vec::new();
+    let mut private = 0;
+    for path in files {
+        let entry = catalog.as_ref().and_then(|c| c.find_output(&path));
+        if entry.is_some_and(|e| e.review_deleted()) {
+            continue;
+        }
+        if !args.include_private && entry.is_some_and(|e| e.visibility == Some(Visibility::Private))
+        {
+            private += 1;
+            continue;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let parsed = default_name.captures(&stem);
+        let game = match (entry, &parsed) {
+            (Some(e), _) => e.game.clone(),
+            (None, Some(c)) => c[1].to_string(),
+            (None, None) => "Other".to_string(),
+        };
+        let when = match (entry, &parsed) {
+            (Some(e), _) if e.date.len() == 8 && e.time.len() >= 4 => format!(
+                "{}-{}-{} {}:{}",
+                &e.date[..4],
+                &e.date[4..6],
+                &e.date[6..],
+                &e.time[..2],
+                &e.time[2..4]
+            ),
+            (None, Some(c)) => format!("{}-{}-{} {}:{}", &c[2], &c[3], &c[4], &c[5], &c[6]),
+            _ => fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .map(|t| {
+                    DateTime::<Local>::from(t)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_default(),
+        };
+        let thumb = thumbnail(&path, args.thumbnails);
+        items.push(Item {
+            size: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            duration: entry.and_then(|e| e.duration),
+            tags: entry.map(|e| e.tags.clone()).unwrap_or_default(),
+            path,
+            thumb,
+            game,
+            when,
+        });
+    }
+    items.sort_by(|a, b| b.when.cmp(&a.when));
+    Some((items, private))
+}
+
+fn find_videos(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_videos(&path, out);
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| VIDEO_EXTS.contains(&e.to_ascii_lowercase().as_str()))
+            // Still being written (see `atomic`).
+            && !path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .is_some_and(|s| s.ends_with(".partial"))
+        {
+            out.push(path);
+        }
+    }
+}
+
+/// The clip's `<name>.jpg`, made first when `make` is set and it is missing.
+fn thumbnail(video: &Path, make: bool) -> Option<PathBuf> {
+    let thumb = video.with_extension("jpg");
+    if !thumb.is_file() && make {
+        let made = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-ss", "1"])
+            .arg("-i")
+            .arg(file_arg(video))
+            .args(["-frames:v", "1", "-vf", &format!("scale={THUMB_WIDTH}:-2")])
+            .arg(file_arg(&thumb))
+            .status();
+        if !made.is_ok_and(|s| s.success()) {
+            eprintln!("[warn] cannot make a thumbnail of {}", video.display());
+        }
+    }
+    thumb.is_file().then_some(thumb)
+}
+
+/// `path` as a URL relative to the index's folder `base` (absolute when outside it).
+pub fn link(path: &Path, base: &Path) -> String {
+    let abs = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf());
+    let (path, base) = (abs(path), abs(base));
+    let (relative, rest) = match path.strip_prefix(&base) {
+        Ok(rel) => (true, rel.to_path_buf()),
+        Err(_) => (false, path),
+    };
+    let parts: Vec<String> = rest
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(percent_encode(&s.to_string_lossy())),
+            // A Windows drive, as in file:///C:/...
+            Component::Prefix(p) => Some(p.as_os_str().to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    let joined = parts.join("/");
+    if relative {
+        joined
+    } else {
+        format!("file:///{joined}")
+    }
+}
+
+/// `2025-02-01 09:00 · 0:42 · 18.3 MiB`
+pub fn caption(item: &Item) -> String {
+    let mut caption = item.when.clone();
+    if let Some(secs) = item.duration {
+        let s = secs.round() as u64;
+        caption.push_str(&format!(" · {}:{:02}", s / 60, s % 60));
+    }
+    caption.push_str(&format!(" · {}", format_size(item.size)));
+    caption
+}
+
+fn html(title: &str, groups: &Groups, base: &Path) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape(title));
+    for (game, months) in groups {
+        body.push_str(&format!("<h2>{}</h2>\n", escape(game)));
+        for (month, items) in months.iter().rev() {
+            body.push_str(&format!(
+                "<h3>{}</h3>\n<div class=\"grid\">\n",
+                escape(month)
+            ));
+            for item in items {
+                let href = link(&item.path, base);
+                let picture = match &item.thumb {
+                    Some(t) => format!("<img src=\"{}\" alt=\"\" loading=\"lazy\">", link(t, base)),
+                    None => "<div class=\"none\"></div>".to_string(),
+                };
+                body.push_str(&format!(
+                    "<figure><a href=\"{href}\">{picture}</a><figcaption><a href=\"{href}\">{}</a></figcaption></figure>\n",
+                    escape(&caption(item))
+                ));
+            }
+            body.push_str("</div>\n");
+        }
+    }
+    format!(
+        "<!doctype html>\n<html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width\">\
+         <title>{}</title><style>body{{font-family:sans-serif;margin:2em}}\
+         .grid{{display:flex;flex-wrap:wrap;gap:1em}}figure{{margin:0;width:{THUMB_WIDTH}px}}\
+         img,.none{{width:100%;aspect-ratio:16/9;object-fit:cover;background:#ddd}}\
+         figcaption{{font-size:.9em}}</style></head>\n<body>\n{}</body></html>\n",
+        escape(title),
+        body
+    )
+}
+
+fn markdown(title: &str, groups: &Groups, base: &Path) -> String {
+    let mut text = format!("# {title}\n");
+    for (game, months) in groups {
+        text.push_str(&format!("\n## {game}\n"));
+        for (month, items) in months.iter().rev() {
+            text.push_str(&format!("\n### {month}\n\n"));
+            for item in items {
+                let href = link(&item.path, base);
+                let picture = item
+                    .thumb
+                    .as_ref()
+                    .map(|t| format!("[![]({})]({href}) ", link(t, base)))
+                    .unwrap_or_default();
+                text.push_str(&format!("- {picture}[{}]({href})\n", caption(item)));
+            }
+        }
+    }
+    text
+}
@@ -0,0 +1,78 @@
+//! `latest` subcommand: convert only the most recent clip, for a hotkey or a Stream Deck
+//! button pressed right after saving a highlight, and optionally put the result on the
+//! clipboard.
+//!
+//! The clip usually isn't settled yet when the button is pressed, so instead of skipping it
+//! as still recording, `latest` waits (up to `--max-wait`) until it is.
+
+use crate::{Cli, run_conversion};
+use clap::{ArgAction, Args};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+use steamclipconverter::{
+    ClipDir,
+    settle::{self, Readiness, SettleRules},
+};
+
+#[derive(Args, Debug)]
+pub struct LatestArgs {
+    /// Same as --copy-to-clipboard before `latest`.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub copy_to_clipboard: bool,
+
+    /// Same as --copy-path before `latest`: copy the output's path as text instead of the file.
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub path_only: bool,
+
+    /// How long to wait for the newest clip to finish being written before giving up.
+    #[arg(long, value_name = "DURATION", default_value = "2m", value_parser = crate::duration::parse_duration)]
+    pub max_wait: Duration,
+}
+
+pub fn run(mut cli: Cli, args: LatestArgs) -> i32 {
+    cli.copy_to_clipboard |= args.copy_to_clipboard;
+    cli.copy_path |= args.path_only;
+    let summary = run_conversion(cli, None, Some(&args));
+    let Some(output) = summary.converted.last() else {
+        eprintln!("ERROR: the latest clip was not converted");
+        return 1;
+    };
+    println!("{}", output.display());
+    0
+}
+
+/// Keep only the newest of `clips` (by recording time), once it has settled. Exits when it
+/// is still being written after `--max-wait`.
+pub fn pick(clips: Vec<ClipDir>, rules: &SettleRules, args: &LatestArgs) -> Vec<ClipDir> {
+    let Some(newest) = clips
+        .into_iter()
+        .max_by(|a, b| (&a.date, &a.time).cmp(&(&b.date, &b.time)))
+    else {
+        return Vec::new();
+    };
+    let deadline = Instant::now() + args.max_wait;
+    loop {
+        let wait = match settle::check(&newest.dir, rules) {
+            Ok(Readiness::Complete) | Err(_) => break,
+            Ok(Readiness::Settling { wait }) => wait,
+            Ok(Readiness::Growing) => Duration::from_secs(1),
+        };
+        if Instant::now() + wait > deadline {
+            eprintln!(
+                "ERROR: {} is still being written after {}s (--max-wait)",
+                newest.dir.display(),
+                args.max_wait.as_secs()
+            );
+            std::process::exit(1);
+        }
+        println!(
+            "[wait] {} is still being written; waiting {}s",
+            newest.dir.display(),
+            wait.as_secs().max(1)
+        );
+        thread::sleep(wait);
+    }
+    vec![newest]
+}
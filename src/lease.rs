@@ -0,0 +1,225 @@
+//! `--share-work`: several machines converting the same recordings (a shared input) into
+//! one output folder split the backlog instead of each converting everything.
+//!
+//! Before converting a clip, an instance claims it by creating `<key>.lease` in
+//! `<output>/.leases/`; creating a file that must not exist yet is atomic on local disks,
+//! NFS and SMB alike, so only one instance gets each clip. The lease names its owner and
+//! when it runs out, and is renewed while the clip is worked on; one that ran out (its owner
+//! crashed or lost the share) is taken over. A finished clip leaves `<key>.done` with the
+//! output's path, so the others skip it as already converted.
+//!
+//! Keys are the recording's kind, appid and start time, which are the same whatever path
+//! each machine mounts the recordings at.
+
+use crate::{host, json, report::say};
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use steamclipconverter::{ClipDir, ClipKind, atomic};
+
+/// Folder of the leases, in the output folder.
+pub const DIR_NAME: &str = ".leases";
+
+/// What [`Leases::claim`] found.
+pub enum Claim {
+    /// This instance holds the clip now.
+    Claimed,
+    /// Another instance is converting it.
+    Held { owner: String },
+    /// Another instance converted it to this file.
+    Done(PathBuf),
+}
+
+pub struct Leases {
+    inner: Arc<Inner>,
+    /// Dropped to stop the renewing thread.
+    _stop: mpsc::Sender<()>,
+}
+
+struct Inner {
+    dir: PathBuf,
+    output_dir: PathBuf,
+    owner: String,
+    ttl: Duration,
+    /// Keys of the leases this instance holds.
+    held: Mutex<HashSet<String>>,
+}
+
+impl Leases {
+    /// Leases in `<output_dir>/.leases`, lasting `ttl` unless renewed.
+    pub fn new(output_dir: &Path, ttl: Duration) -> io::Result<Leases> {
+        let dir = output_dir.join(DIR_NAME);
+        fs::create_dir_all(&dir)?;
+        let inner = Arc::new(Inner {
+            dir,
+            output_dir: output_dir.to_path_buf(),
+            owner: format!("{}:{}", host::machine_tag(), std::process::id()),
+            ttl,
+            held: Mutex::new(HashSet::new()),
+        });
+        let (stop, stopped) = mpsc::channel::<()>();
+        let renewing = Arc::clone(&inner);
+        thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(ttl / 3) {
+                renewing.renew();
+            }
+        });
+        Ok(Leases { inner, _stop: stop })
+    }
+
+    /// Try to take `clip` for this instance.
+    pub fn claim(&self, clip: &ClipDir) -> io::Result<Claim> {
+        let inner = &self.inner;
+        let key = key(clip);
+        if let Some(output) = inner.done_output(&key) {
+            return Ok(Claim::Done(output));
+        }
+        let path = inner.lease_path(&key);
+        // A second try after taking over a lease that ran out.
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut f) => {
+                    f.write_all(inner.lease_text(&key).as_bytes())?;
+                    inner.held.lock().unwrap().insert(key);
+                    return Ok(Claim::Claimed);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+            let Some((owner, expires)) = inner.read_lease(&path) else {
+                // Released in the meantime.
+                continue;
+            };
+            if expires > SystemTime::now() {
+                return Ok(Claim::Held { owner });
+            }
+            // Move it aside first: of several instances taking over, only one renames it.
+            let stale = inner
+                .dir
+                .join(format!("{key}.stale-{}", inner.owner.replace(':', "-")));
+            if fs::rename(&path, &stale).is_ok() {
+                say!("[share] taking over {key} from {owner} (its lease ran out)");
+                let _ = fs::remove_file(&stale);
+            }
+        }
+        Ok(Claim::Held {
+            owner: "another instance".into(),
+        })
+    }
+
+    /// `clip` was converted to `output`: tell the other instances, and let the lease go.
+    pub fn done(&self, clip: &ClipDir, output: &Path) {
+        let inner = &self.inner;
+        let key = key(clip);
+        let relative = output.strip_prefix(&inner.output_dir).unwrap_or(output);
+        let text = json::obj([
+            ("owner", inner.owner.as_str().into()),
+            ("output", relative.to_string_lossy().as_ref().into()),
+        ]);
+        if let Err(e) = atomic::write(&inner.dir.join(format!("{key}.done")), format!("{text}\n")) {
+            eprintln!("[warn] cannot mark {key} done for the other instances: {e}");
+        }
+        self.release(clip);
+    }
+
+    /// Let `clip` go (if this instance holds it) for another instance or a later run.
+    pub fn release(&self, clip: &ClipDir) {
+        let inner = &self.inner;
+        let key = key(clip);
+        if inner.held.lock().unwrap().remove(&key) && inner.owns(&key) {
+            let _ = fs::remove_file(inner.lease_path(&key));
+        }
+    }
+}
+
+impl Drop for Leases {
+    fn drop(&mut self) {
+        let held: Vec<String> = self.inner.held.lock().unwrap().drain().collect();
+        for key in held {
+            if self.inner.owns(&key) {
+                let _ = fs::remove_file(self.inner.lease_path(&key));
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn lease_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.lease"))
+    }
+
+    fn lease_text(&self, key: &str) -> String {
+        let expires = (SystemTime::now() + self.ttl)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let text = json::obj([
+            ("key", key.into()),
+            ("owner", self.owner.as_str().into()),
+            ("expires", expires.into()),
+        ]);
+        format!("{text}\n")
+    }
+
+    /// Owner and end of the lease at `path`; None when there is none. A lease still being
+    /// written counts as lasting from its file's time.
+    fn read_lease(&self, path: &Path) -> Option<(String, SystemTime)> {
+        let text = fs::read_to_string(path).ok()?;
+        let value = json::parse(&text).ok();
+        let expires = value
+            .as_ref()
+            .and_then(|v| v.get("expires"))
+            .and_then(json::Value::as_u64)
+            .map(|s| UNIX_EPOCH + Duration::from_secs(s))
+            .or_else(|| Some(fs::metadata(path).ok()?.modified().ok()? + self.ttl))?;
+        let owner = value
+            .as_ref()
+            .map(|v| v.str_field("owner").to_string())
+            .filter(|o| !o.is_empty())
+            .unwrap_or_else(|| "another instance".into());
+        Some((owner, expires))
+    }
+
+    fn owns(&self, key: &str) -> bool {
+        self.read_lease(&self.lease_path(key))
+            .is_some_and(|(owner, _)| owner == self.owner)
+    }
+
+    /// The output another instance recorded for `key`, if it still exists.
+    fn done_output(&self, key: &str) -> Option<PathBuf> {
+        let text = fs::read_to_string(self.dir.join(format!("{key}.done"))).ok()?;
+        let value = json::parse(&text).ok()?;
+        let output = self.output_dir.join(value.str_field("output"));
+        output.is_file().then_some(output)
+    }
+
+    /// Push out the end of every lease still held.
+    fn renew(&self) {
+        let held: Vec<String> = self.held.lock().unwrap().iter().cloned().collect();
+        for key in held {
+            if !self.owns(&key) {
+                eprintln!("[warn] lost the lease on {key} to another instance");
+                self.held.lock().unwrap().remove(&key);
+                continue;
+            }
+            if let Err(e) = atomic::write(&self.lease_path(&key), self.lease_text(&key)) {
+                eprintln!("[warn] cannot renew the lease on {key}: {e}");
+            }
+        }
+    }
+}
+
+/// `clip_570_20250101_100000`: the recording, independent of where it is mounted.
+fn key(clip: &ClipDir) -> String {
+    let kind = match clip.kind() {
+        ClipKind::Clip => "clip",
+        ClipKind::Background => "bg",
+    };
+    format!("{kind}_{}_{}_{}", clip.appid, clip.date, clip.time)
+}
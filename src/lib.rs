@@ -0,0 +1,33 @@
+//! Library side of steamclipconverter: finding Steam clip folders and converting them, so
+//! other tools can embed the same logic the CLI uses.
+
+pub mod appdirs;
+pub mod appinfo;
+pub mod atomic;
+pub mod client;
+pub mod convert;
+pub mod error;
+pub mod ffi;
+pub mod ffmpeg;
+pub mod http;
+pub mod json;
+pub mod mpd;
+pub mod native;
+pub mod probe;
+pub mod scan;
+pub mod settle;
+pub mod sha256;
+pub mod snapshot;
+pub mod ssh;
+pub mod steam;
+pub mod usage;
+
+pub use convert::{
+    Backend, CancellationToken, ClipCallback, ClipEvent, Converter, Phase, Progress,
+    ProgressCallback, Summary,
+};
+pub use error::Error;
+pub use scan::{
+    ClipDir, ClipKind, ClipStream, UNKNOWN_APPID, find_fg_clip_dirs, scan_clip_dirs, scan_stream,
+};
+pub use steam::resolve_app_name;
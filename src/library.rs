@@ -0,0 +1,131 @@
+//! `--export-library`: hand launcher extensions a list of each game's converted clips.
+
+use crate::{
+    catalog::{Catalog, CatalogEntry},
+    json::{self, Value},
+    visibility::Visibility,
+};
+use clap::ValueEnum;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use steamclipconverter::atomic;
+
+/// Playnite's built-in Steam library plugin; games it imports use the appid as GameId.
+const PLAYNITE_STEAM_PLUGIN_ID: &str = "cb91dfc9-b977-43bf-8e70-55f46e410fab";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LibraryFormat {
+    /// Playnite: games keyed by (Steam plugin id, appid).
+    Playnite,
+    /// Lutris: games keyed by service "steam" + service_id, with a slug for matching.
+    Lutris,
+}
+
+impl LibraryFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            LibraryFormat::Playnite => "playnite-clips.json",
+            LibraryFormat::Lutris => "lutris-clips.json",
+        }
+    }
+}
+
+/// Write the clip associations of every catalogued output that still exists into
+/// `out_dir`, returning the written path and the number of games.
+pub fn export(
+    catalog: &Catalog,
+    format: LibraryFormat,
+    out_dir: &Path,
+) -> io::Result<(PathBuf, usize)> {
+    let mut by_game: BTreeMap<u32, Vec<&CatalogEntry>> = BTreeMap::new();
+    for e in catalog.entries() {
+        if e.output.is_file() {
+            by_game.entry(e.appid).or_default().push(e);
+        }
+    }
+
+    let games: Vec<Value> = by_game
+        .into_iter()
+        .map(|(appid, mut clips)| {
+            clips.sort_by(|a, b| (&a.date, &a.time).cmp(&(&b.date, &b.time)));
+            // Prefer a resolved name over the appid fallback used when lookup failed.
+            let name = clips
+                .iter()
+                .rev()
+                .map(|c| c.game.as_str())
+                .find(|g| *g != appid.to_string())
+                .unwrap_or("");
+            let clips = Value::Arr(clips.iter().map(|c| clip_json(c)).collect());
+            match format {
+                LibraryFormat::Playnite => json::obj([
+                    ("pluginId", PLAYNITE_STEAM_PLUGIN_ID.into()),
+                    ("gameId", appid.to_string().into()),
+                    ("name", name.into()),
+                    ("clips", clips),
+                ]),
+                LibraryFormat::Lutris => json::obj([
+                    ("service", "steam".into()),
+                    ("service_id", appid.to_string().into()),
+                    ("name", name.into()),
+                    ("slug", slug(name).into()),
+                    ("clips", clips),
+                ]),
+            }
+        })
+        .collect();
+    let count = games.len();
+
+    let doc = json::obj([
+        ("generator", "steamclipconverter".into()),
+        ("version", 1u32.into()),
+        ("games", Value::Arr(games)),
+    ]);
+    fs::create_dir_all(out_dir)?;
+    let path = out_dir.join(format.file_name());
+    atomic::write(&path, format!("{doc}\n"))?;
+    Ok((path, count))
+}
+
+fn clip_json(c: &CatalogEntry) -> Value {
+    json::obj([
+        ("path", c.output.to_string_lossy().as_ref().into()),
+        ("recorded", recorded_at(&c.date, &c.time).into()),
+        ("size", c.size.into()),
+        ("visibility", c.visibility.map(Visibility::as_str).into()),
+    ])
+}
+
+/// `YYYYMMDD` + `HHMMSS` as a local ISO 8601 timestamp, or None if malformed.
+fn recorded_at(date: &str, time: &str) -> Option<String> {
+    if date.len() != 8
+        || time.len() != 6
+        || !date.chars().chain(time.chars()).all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}",
+        &date[..4],
+        &date[4..6],
+        &date[6..],
+        &time[..2],
+        &time[2..4],
+        &time[4..]
+    ))
+}
+
+/// Lutris-style slug: lowercase ASCII alphanumerics joined by single dashes.
+pub fn slug(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_lowercase());
+        } else if !out.is_empty() && !out.ends_with('-') && c != '\'' {
+            out.push('-');
+        }
+    }
+    out.trim_end_matches('-').to_string()
+}
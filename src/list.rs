@@ -0,0 +1,128 @@
+//! `list` subcommand: what a conversion run would do with each clip, without doing it.
+
+use crate::{
+    AppNames, Kinds, duration,
+    folder_match::{self, FolderPattern},
+    game_matches, json, resolve_input_dir,
+    skip::{self, SkipReason},
+};
+use clap::{ArgAction, Args};
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+use steamclipconverter::{
+    ClipKind, find_fg_clip_dirs, settle::SettleRules, snapshot::ScanSnapshot,
+};
+
+#[derive(Args, Debug)]
+pub struct ListArgs {
+    /// Directory to search (defaults to <SteamRoot>/userdata with a warning).
+    input: Option<PathBuf>,
+
+    /// Restrict to specific appids; repeatable.
+    #[arg(long = "gameId", action = ArgAction::Append)]
+    game_ids: Vec<u32>,
+
+    /// Restrict to games whose name matches, like --game of a run; repeatable.
+    #[arg(long = "game", value_name = "NAME")]
+    games: Vec<String>,
+
+    /// Restrict to clips whose folder name matches, like --match of a run; repeatable.
+    #[arg(long = "match", value_name = "PATTERN", value_parser = FolderPattern::parse)]
+    matches: Vec<FolderPattern>,
+
+    /// Leave out clips recorded without a game (appid 0).
+    #[arg(long, action = ArgAction::SetTrue)]
+    skip_unknown_app: bool,
+
+    /// Clips (fg_*), background recordings (bg_*), or all.
+    #[arg(long, value_enum, default_value_t = Kinds::Clips)]
+    kind: Kinds,
+
+    /// Same as for conversion: folders changed more recently count as in progress.
+    #[arg(long, value_name = "DURATION", default_value = "30s", value_parser = duration::parse_duration)]
+    settle_time: Duration,
+
+    /// One JSON object per clip instead of a table.
+    #[arg(long, action = ArgAction::SetTrue)]
+    json: bool,
+}
+
+pub fn run(args: ListArgs) -> i32 {
+    let input_dir = resolve_input_dir(args.input.clone());
+    let mut clips = match find_fg_clip_dirs(&input_dir, &mut ScanSnapshot::load(&input_dir)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ERROR[find]: {}", e);
+            return 1;
+        }
+    };
+    clips.retain(|c| args.kind.includes(c.kind()));
+    clips.sort_by(|a, b| a.dir.cmp(&b.dir));
+    let names = AppNames::discover();
+    let games: HashSet<u32> = args.game_ids.iter().copied().collect();
+    let rules = SettleRules {
+        settle_time: args.settle_time,
+        ..SettleRules::default()
+    };
+
+    let mut problems = 0;
+    for clip in &clips {
+        let name = names.resolve(clip.appid);
+        let filtered = (!games.is_empty() && !games.contains(&clip.appid))
+            || (!args.games.is_empty() && !game_matches(&args.games, name.as_deref(), clip.appid))
+            || (args.skip_unknown_app && clip.is_unknown_app());
+        let verdict = if filtered {
+            Err(SkipReason::FilteredGame)
+        } else if !folder_match::matches(&args.matches, clip) {
+            Err(SkipReason::NotMatched)
+        } else {
+            skip::assess(clip, &rules).map(|_| ())
+        };
+        if verdict.as_ref().is_err_and(SkipReason::is_problem) {
+            problems += 1;
+        }
+        let game = name.unwrap_or_else(|| clip.appid.to_string());
+
+        if args.json {
+            let (status, reason, detail) = match &verdict {
+                Ok(()) => ("ready", None, None),
+                Err(r) => ("skip", Some(r.code()), Some(r.to_string())),
+            };
+            let line = json::obj([
+                ("dir", clip.dir.to_string_lossy().as_ref().into()),
+                ("kind", kind_name(clip.kind()).into()),
+                ("appid", clip.appid.into()),
+                ("game", game.as_str().into()),
+                ("date", clip.date.as_str().into()),
+                ("time", clip.time.as_str().into()),
+                ("status", status.into()),
+                ("reason", reason.into()),
+                ("detail", detail.into()),
+            ]);
+            println!("{line}");
+        } else {
+            let status = match &verdict {
+                Ok(()) => "ready".to_string(),
+                Err(r) => format!("skip:{} ({})", r.code(), r),
+            };
+            println!(
+                "{} {} {:<24} {}  {}",
+                clip.date,
+                clip.time,
+                game,
+                status,
+                clip.dir.display()
+            );
+        }
+    }
+    if !args.json {
+        println!("{} clip(s), {} with problems", clips.len(), problems);
+    }
+    if problems > 0 { 1 } else { 0 }
+}
+
+fn kind_name(kind: ClipKind) -> &'static str {
+    match kind {
+        ClipKind::Clip => "clip",
+        ClipKind::Background => "background",
+    }
+}
@@ -1,44 +1,97 @@
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
-use clap::{ArgAction, Parser};
-use filetime::{set_file_times, FileTime};
-use regex::Regex;
-use sanitize_filename::sanitize;
+use catalog::{Catalog, CatalogEntry};
+use chrono::{DateTime, Local, Utc};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use device::TargetDevice;
+use filetime::{FileTime, set_file_times};
+use folder_match::FolderPattern;
+use profile::{EncodeProfile, HwAccel};
+use report::say;
+use skip::{RunSummary, SkipReason};
+use staged::StagedInput;
 use std::{
     collections::HashSet,
     env, fs, io,
     path::{Path, PathBuf},
-    process::Command,
+    time::Duration,
 };
+use steamclipconverter::{
+    Backend, ClipDir, ClipKind, Error, atomic,
+    ffmpeg::{
+        AudioSelect, Component, ComponentKind, Container, Region, RemuxOptions, TrackLabel,
+        default_audio_title, missing_components,
+    },
+    find_fg_clip_dirs, http, json,
+    probe::{StreamInfo, probe_streams},
+    settle::SettleRules,
+    sha256,
+    snapshot::ScanSnapshot,
+    ssh::SshRemote,
+    steam::{self, AppNames},
+};
+use timestamp::TimestampSource;
 
-/// OS-specific default Steam root candidates (NOT steamapps; that's added later).
-macro_rules! steam_default_root_candidates {
-    () => {{
-        let mut v: Vec<PathBuf> = Vec::new();
-        #[cfg(target_os = "macos")]
-        {
-            if let Ok(home) = std::env::var("HOME") {
-                v.push(PathBuf::from(format!(
-                    "{home}/Library/Application Support/Steam"
-                )));
-            }
-        }
-        #[cfg(target_os = "linux")]
-        {
-            if let Ok(home) = std::env::var("HOME") {
-                v.push(PathBuf::from(format!("{home}/.local/share/Steam")));
-            }
-        }
-        #[cfg(target_os = "windows")]
-        {
-            if let Ok(pf86) = std::env::var("PROGRAMFILES(X86)") {
-                v.push(PathBuf::from(format!(r"{pf86}\Steam")));
-            } else {
-                v.push(PathBuf::from(r"C:\Program Files (x86)\Steam"));
-            }
-        }
-        v
-    }};
-}
+mod agent;
+mod archive;
+mod auth;
+mod batch;
+mod bench;
+mod catalog;
+mod config;
+mod contact_sheet;
+mod desktop;
+mod device;
+mod duration;
+mod encrypt;
+mod energy;
+mod events;
+mod export_raw;
+mod folder_match;
+mod gc;
+mod highlight;
+mod host;
+mod index;
+mod latest;
+mod lease;
+mod library;
+mod list;
+mod mdns;
+mod merge;
+mod order;
+mod overlap;
+mod overrides;
+mod pipeline;
+mod plan;
+mod postprocess;
+mod power;
+mod preview;
+mod profile;
+mod progress_bar;
+mod progress_file;
+mod project;
+mod queue;
+mod quota;
+mod reconvert;
+mod remote;
+mod repair;
+mod report;
+mod review;
+mod schema;
+mod screenshots;
+mod session;
+mod site;
+mod skip;
+mod staged;
+mod stats;
+mod sync;
+mod syslog;
+mod template;
+mod thermal;
+mod timeline;
+mod timestamp;
+mod toml;
+mod verify;
+mod visibility;
+mod watch;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -46,6 +99,9 @@ macro_rules! steam_default_root_candidates {
     about = "Convert Steam 'fg_*' clip folders (with session.mpd) to MP4"
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+
     /// Positional shorthand for --input. If present alone, treated as --input.
     input_positional: Option<PathBuf>,
 
@@ -61,32 +117,1177 @@ struct Cli {
     #[arg(long = "gameId", action = ArgAction::Append)]
     game_ids: Vec<u32>,
 
+    /// Only clips recorded from this local date or time on: 2025-08-01 or "2025-08-01 18:00".
+    #[arg(long, value_name = "WHEN", value_parser = timestamp::parse_since)]
+    since: Option<DateTime<Utc>>,
+
+    /// Only clips recorded up to this local date (the whole day) or time:
+    /// 2025-08-28 or "2025-08-28 13:00".
+    #[arg(long, value_name = "WHEN", value_parser = timestamp::parse_until)]
+    until: Option<DateTime<Utc>>,
+
+    /// Only clips of games whose name matches (repeatable, case-insensitive): a part of the
+    /// name (--game rimworld) or a glob (--game "Counter-Strike*"). Games without a known
+    /// name are matched by appid.
+    #[arg(long = "game", value_name = "NAME")]
+    games: Vec<String>,
+
+    /// Only clips whose folder name matches (repeatable): a glob of the fg_*/bg_* or clip_*
+    /// folder name (--match 'fg_294100_202508*'), or a regex after `re:`.
+    #[arg(long = "match", value_name = "PATTERN", value_parser = FolderPattern::parse)]
+    matches: Vec<FolderPattern>,
+
+    /// Game names for clips whose games aren't installed here (e.g. userdata copied from
+    /// another PC): lines of `<appid> <name>`, or the JSON of the Steam Web API's app list.
+    /// `appnames.txt` in the config directory is always read too.
+    #[arg(long, value_name = "FILE")]
+    app_names: Option<PathBuf>,
+
+    /// Ask the Steam store (with curl) for names found neither in appmanifests, name lists
+    /// nor Steam's appinfo cache; answers are cached, so later runs need no network.
+    #[arg(long, action = ArgAction::SetTrue)]
+    online_names: bool,
+
+    /// Leave out clips Steam recorded without a game (appid 0, named Unknown-...).
+    #[arg(long, action = ArgAction::SetTrue)]
+    skip_unknown_app: bool,
+
+    /// Which recordings to convert: clips (fg_*), Steam's background game recording
+    /// (bg_*), or all.
+    #[arg(long, value_enum, default_value_t = Kinds::Clips)]
+    kind: Kinds,
+
+    /// Skip clips whose game name can't be found instead of naming them by appid, and
+    /// exit with status 1 if there were any.
+    #[arg(long, action = ArgAction::SetTrue)]
+    strict_names: bool,
+
+    /// Delete after converting even when the clips are in a folder a sync client
+    /// (Syncthing, OneDrive, Dropbox, ...) seems to mirror.
+    #[arg(long, action = ArgAction::SetTrue)]
+    ignore_sync_warning: bool,
+
     /// After successful conversion, delete the fg_... folder; if it was the only subdir
     /// in its parent 'video' dir, also delete its grandparent 'clip_<appid>_<date>_<time>' dir.
     #[arg(long, action = ArgAction::SetTrue)]
     delete_after: bool,
+
+    /// What to do when the output file is already there: overwrite it, skip the clip (so
+    /// re-runs only convert new clips, and --delete-after only removes what this run
+    /// converted), rename the new one with -1, -2, ... or fail the clip.
+    #[arg(long, value_enum, default_value_t = OnExists::Overwrite)]
+    on_exists: OnExists,
+
+    /// Also let --delete-after and --gc-empty remove folders under PATH, which isn't one of
+    /// Steam's gamerecordings folders (repeatable).
+    #[arg(long, value_name = "PATH")]
+    allow_delete_under: Vec<PathBuf>,
+
+    /// After the batch, remove leftovers across the whole input tree: empty video/ and
+    /// timelines/ folders, and clip_* folders whose recording is gone.
+    #[arg(long, action = ArgAction::SetTrue)]
+    gc_empty: bool,
+
+    /// Don't ask before runs that delete files (the plan is still printed and checked).
+    #[arg(long, short = 'y', action = ArgAction::SetTrue)]
+    yes: bool,
+
+    /// Config file with the post-processing chain (defaults to config.toml in the per-user
+    /// config directory, if present).
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Conversion catalog to record outputs in (defaults to the per-user data directory).
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+
+    /// Don't record conversions (and their checksums) in the catalog.
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_catalog: bool,
+
+    /// Keep the catalog in --output itself, shared by every machine converting into it:
+    /// recordings another machine already converted are skipped.
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["catalog", "no_catalog"])]
+    portable_catalog: bool,
+
+    /// Split the clips with other machines converting the same recordings into this
+    /// --output: each clip is claimed with a lease in <output>/.leases first, and clips
+    /// another instance holds or has finished are skipped.
+    #[arg(long, action = ArgAction::SetTrue)]
+    share_work: bool,
+
+    /// How long a --share-work claim lasts unless renewed (it is renewed every third of
+    /// this while the clip converts): how soon another instance takes over a crashed one's
+    /// clip.
+    #[arg(long, value_name = "DURATION", default_value = "10m", value_parser = duration::parse_duration)]
+    lease_time: Duration,
+
+    /// Ignore the saved scan snapshot and list every directory again.
+    #[arg(long, action = ArgAction::SetTrue)]
+    full_rescan: bool,
+
+    /// Don't resume an interrupted run's queue for this input and output; scan and decide
+    /// again from scratch.
+    #[arg(long, action = ArgAction::SetTrue)]
+    fresh_queue: bool,
+
+    /// Instead of converting, write each game's catalogued clips to a file in --output that
+    /// this launcher's extensions can import.
+    #[arg(long, value_enum, value_name = "LAUNCHER")]
+    export_library: Option<library::LibraryFormat>,
+
+    /// How outputs are arranged in --output: `sessions` groups clips and screenshots per
+    /// game and day with a session.json that cross-references them.
+    #[arg(long, value_enum, default_value_t)]
+    layout: session::Layout,
+
+    /// With `--layout sessions`, also keep an editor project in each session folder listing
+    /// its clips in order, with Steam's timeline markers placed on them.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    export_project: Option<project::ProjectFormat>,
+
+    /// A clip folder counts as finished only when none of its files changed for this long
+    /// (e.g. 30s, 2m); younger folders are skipped as still recording.
+    #[arg(long, value_name = "DURATION", default_value = "30s", value_parser = duration::parse_duration)]
+    settle_time: Duration,
+
+    /// Also watch the folder this long and skip it if chunks or bytes are still being added
+    /// (0 = off). Helps on slow disks where mtimes lag behind the writes.
+    #[arg(long, value_name = "DURATION", default_value = "0", value_parser = duration::parse_duration)]
+    settle_growth: Duration,
+
+    /// What to do when running on battery (Steam Deck, laptops).
+    #[arg(long, value_enum, default_value_t)]
+    power_policy: power::PowerPolicy,
+
+    /// Pause between clips while the CPU is hotter than this (°C), resuming 5°C below it.
+    #[arg(long, value_name = "CELSIUS")]
+    max_temp: Option<f32>,
+
+    /// Report the CPU time ffmpeg used per clip and for the run, and the energy the machine
+    /// used where it has a meter (RAPL on Linux, powermetrics on macOS, nvidia-smi), to
+    /// compare encoders and profiles.
+    #[arg(long, action = ArgAction::SetTrue)]
+    energy_report: bool,
+
+    /// Keep running and convert new clips as soon as Steam has finished writing them.
+    #[arg(long, action = ArgAction::SetTrue)]
+    watch: bool,
+
+    /// What to do when a saved clip and a background recording of the same game cover the
+    /// same time (with --kind all).
+    #[arg(long, value_enum, default_value_t = overlap::OnOverlap::KeepBoth)]
+    on_overlap: overlap::OnOverlap,
+
+    /// Plan the run and print what would be converted, skipped, overwritten and deleted,
+    /// without running ffmpeg or changing any file.
+    #[arg(long, action = ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Join a recording Steam split into several fg_* folders of one clip_* folder into a
+    /// single MP4, named after its first part.
+    #[arg(long, action = ArgAction::SetTrue)]
+    merge: bool,
+
+    /// How often --watch looks for new clips.
+    #[arg(long, value_name = "DURATION", default_value = "5s", value_parser = duration::parse_duration)]
+    watch_interval: Duration,
+
+    /// Conversion order: `priority` does manually saved clips first, then the newest.
+    #[arg(long, value_enum, default_value_t)]
+    order: order::ClipOrder,
+
+    /// Write what happened to every clip (converted / failed / skipped with a reason code)
+    /// as JSON to this file.
+    #[arg(long, value_name = "FILE")]
+    summary_json: Option<PathBuf>,
+
+    /// `json`: print one JSON record per clip (source, appid, game, output, duration, size,
+    /// status, error) and a closing summary record on stdout, for scripts; the progress
+    /// lines go to stderr.
+    #[arg(long, value_enum, default_value_t)]
+    format: report::Format,
+
+    /// Where each clip's record start (file times, session day) comes from.
+    #[arg(long, value_enum, default_value_t)]
+    timestamp_source: TimestampSource,
+
+    /// Name outputs from a template instead of Game-YYYYMMDD-HHMMSS.mp4, e.g.
+    /// "{game|slug}/{date|fmt:%Y-%m}/{time}.mp4" (see README for variables and filters).
+    #[arg(long, value_name = "TEMPLATE", value_parser = template::Template::parse)]
+    name_template: Option<template::Template>,
+
+    /// Append this machine's tag to every file name (Game-YYYYMMDD-HHMMSS-<tag>.mp4), so
+    /// clips from several PCs merged into one archive can't collide.
+    #[arg(long, action = ArgAction::SetTrue)]
+    host_suffix: bool,
+
+    /// Machine tag to use instead of the detected host name (implies --host-suffix).
+    #[arg(long, value_name = "TAG")]
+    host: Option<String>,
+
+    /// Free up space before the next session: convert and delete the oldest recordings only
+    /// until this much is left below Steam's storage limit (e.g. 10G). Needs --delete-after.
+    #[arg(long, value_name = "SIZE", value_parser = quota::parse_size, requires = "delete_after")]
+    make_room: Option<u64>,
+
+    /// Recording storage limit for --make-room, instead of the one read from Steam's config.
+    #[arg(long, value_name = "SIZE", value_parser = quota::parse_size)]
+    quota_limit: Option<u64>,
+
+    /// Also report run start/end, failures and deletions to the system log (journald/syslog,
+    /// macOS unified log, Windows Event Log), for scheduled or service runs.
+    #[arg(long, action = ArgAction::SetTrue)]
+    system_log: bool,
+
+    /// Stream the run's events (clips starting, progress, converted, skipped, deleted) as
+    /// server-sent events at http://ADDR/events, e.g. 127.0.0.1:47801.
+    #[arg(long, value_name = "ADDR")]
+    events: Option<String>,
+
+    /// Keep this JSON file updated with the run's progress (clips in progress, counts, ETA),
+    /// for widgets and plugins that poll.
+    #[arg(long, value_name = "FILE")]
+    progress_file: Option<PathBuf>,
+
+    /// Write a grid of one labelled frame per clip this run converted (game, record date,
+    /// length) to this image, e.g. sheet.jpg.
+    #[arg(long, value_name = "FILE")]
+    contact_sheet: Option<PathBuf>,
+
+    /// Write a poster frame next to each output as <name>.jpg (for Jellyfin, Plex and file
+    /// browsers), taken while the clip converts.
+    #[arg(long, action = ArgAction::SetTrue)]
+    thumbnails: bool,
+
+    /// How far into the clip the poster frame is: 3, 0:05 or 1m.
+    #[arg(long, value_name = "TIME", default_value = "3", value_parser = duration::parse_offset, requires = "thumbnails")]
+    thumbnail_at: f64,
+
+    /// Also write <name>.sheet.jpg, a grid of N frames spread over the clip.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(2..=64), requires = "thumbnails")]
+    thumbnail_sheet: Option<u32>,
+
+    /// Also write <name>.preview.gif, a short silent loop from the poster frame on.
+    #[arg(long, action = ArgAction::SetTrue, requires = "thumbnails")]
+    thumbnail_preview: bool,
+
+    /// Print plain log lines only, without the progress bars drawn on a terminal.
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_progress: bool,
+
+    /// Encrypt every output to this age recipient (`age:age1...` or `age:ssh-ed25519 ...`,
+    /// repeatable) with the `age` tool, writing `<name>.mp4.age` and removing the plaintext
+    /// before post-processing runs.
+    #[arg(long, value_name = "age:RECIPIENT", value_parser = encrypt::Recipient::parse)]
+    encrypt: Vec<encrypt::Recipient>,
+
+    /// After the run, put the converted files on the clipboard, ready to paste into a chat
+    /// app or file manager.
+    #[arg(long, action = ArgAction::SetTrue)]
+    copy_to_clipboard: bool,
+
+    /// Copy the converted files' paths to the clipboard as text instead of the files
+    /// (implies --copy-to-clipboard).
+    #[arg(long, action = ArgAction::SetTrue)]
+    copy_path: bool,
+
+    /// After the run, open the folder of the last converted file in the file manager
+    /// (Finder, Explorer, or xdg-open).
+    #[arg(long, action = ArgAction::SetTrue)]
+    reveal: bool,
+
+    /// After the run, open the last converted file in a player to check it.
+    #[arg(long, action = ArgAction::SetTrue)]
+    play: bool,
+
+    /// Player for --play (e.g. mpv) instead of the config's `player` or the system default.
+    #[arg(long, value_name = "CMD")]
+    player: Option<String>,
+
+    #[command(flatten)]
+    stages: pipeline::StageLimits,
+
+    #[command(flatten)]
+    encode: EncodeArgs,
+}
+
+/// How streams are copied or transcoded; shared by conversion and `reconvert`.
+#[derive(Args, Clone, Debug)]
+struct EncodeArgs {
+    /// Who writes the MP4: ffmpeg, or the built-in remuxer (`native`), which needs no ffmpeg
+    /// but only copies streams.
+    #[arg(long, value_name = "BACKEND", default_value = "ffmpeg", value_parser = Backend::parse)]
+    backend: Backend,
+
+    /// Probe each clip and transcode only the streams this kind of device can't play
+    /// (everything else is still stream-copied).
+    #[arg(long, value_enum)]
+    target_device: Option<TargetDevice>,
+
+    /// Transcode the video with this profile instead of copying it (audio is still copied).
+    #[arg(
+        long,
+        value_enum,
+        value_name = "PROFILE",
+        conflicts_with = "target_device"
+    )]
+    encode: Option<EncodeProfile>,
+
+    /// Quality for --encode, lower is better (default from the profile, e.g. 21 for
+    /// h264-medium); passed as the hardware encoder's closest setting with --hwaccel.
+    #[arg(long, value_name = "N", requires = "encode", value_parser = clap::value_parser!(u32).range(0..=63))]
+    crf: Option<u32>,
+
+    /// Encoder preset for --encode, passed as is (e.g. veryfast/slow for x264, p1-p7 for
+    /// NVENC, 0-13 for SVT-AV1).
+    #[arg(long, value_name = "PRESET", requires = "encode")]
+    preset: Option<String>,
+
+    /// Encode on the GPU for --encode.
+    #[arg(long, value_enum, requires = "encode")]
+    hwaccel: Option<HwAccel>,
+
+    /// Run ffmpeg on this machine instead (ssh://[user@]host[:port][/work-dir]): each clip is
+    /// copied there with rsync and the output copied back. Needs a login without a password.
+    #[arg(long, value_name = "URL", value_parser = SshRemote::parse)]
+    remote: Option<SshRemote>,
+
+    /// Which audio to keep: the game track, the microphone track, all tracks, all tracks
+    /// mixed into one (transcoded to AAC), or none.
+    #[arg(long, value_enum, default_value_t)]
+    audio: AudioChoice,
+
+    /// Keep only audio track N (from 1) instead of what --audio picks.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "audio")]
+    audio_track: Option<u32>,
+
+    /// For clips to share: keep the game audio only, never the microphone track (voice
+    /// chat) Steam may have recorded next to it, whatever else asks for audio.
+    #[arg(long, action = ArgAction::SetTrue)]
+    strip_mic: bool,
+
+    /// Transcode surround (5.1/7.1) game audio to stereo AAC; video is still copied.
+    #[arg(long, value_enum)]
+    downmix: Option<Downmix>,
+
+    /// Tag each audio track with a title ("Game", "Microphone") so players show meaningful names.
+    #[arg(long, action = ArgAction::SetTrue)]
+    label_audio: bool,
+
+    /// Language tag for labeled audio tracks (ISO 639-2, e.g. eng, jpn). Implies --label-audio.
+    #[arg(long, value_name = "LANG")]
+    audio_language: Option<String>,
+
+    /// Compare the audio and video track durations and, when they drift apart, retime the
+    /// audio (atempo + aresample, so audio is transcoded) to match the video.
+    #[arg(long, action = ArgAction::SetTrue)]
+    fix_drift: bool,
+
+    /// Silence channel N (from 1) of the game audio, for setups that mix the microphone
+    /// into one channel instead of a track of its own. Transcodes audio.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    mute_voice_channel: Option<u32>,
+
+    /// Video to put before each clip (scaled to the clip's size; needs an audio track).
+    #[arg(long, value_name = "FILE")]
+    prepend: Option<PathBuf>,
+
+    /// Video to put after each clip (scaled to the clip's size; needs an audio track).
+    #[arg(long, value_name = "FILE")]
+    append: Option<PathBuf>,
+
+    /// Image (e.g. a transparent PNG) overlaid on the recording's bottom-right corner.
+    #[arg(long, value_name = "FILE")]
+    watermark: Option<PathBuf>,
+
+    /// Blur this area of the recording, in pixels from the top-left corner (repeatable):
+    /// a webcam overlay, a username in the HUD. Re-encodes video.
+    #[arg(long, value_name = "X,Y,W,H", value_parser = Region::parse)]
+    blur: Vec<Region>,
+
+    /// ffmpeg's own -loglevel (default: error, so only problems are shown).
+    #[arg(long, value_name = "LEVEL", value_parser = [
+        "quiet", "panic", "fatal", "error", "warning", "info", "verbose", "debug", "trace",
+    ])]
+    ffmpeg_loglevel: Option<String>,
+
+    /// Show ffmpeg's native progress line (-stats) while each clip converts.
+    #[arg(long, action = ArgAction::SetTrue)]
+    ffmpeg_stats: bool,
+
+    /// Leave the MP4 index at the end of the file instead of moving it to the front, which
+    /// rewrites the whole file once more (costly on network shares). Players then have to
+    /// read the end of the file before they can start.
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "movflags")]
+    no_faststart: bool,
+
+    /// ffmpeg -movflags for the outputs instead of +faststart, e.g. frag_keyframe+empty_moov
+    /// for a fragmented MP4 that stays playable up to where a long transcode was cut off.
+    #[arg(long, value_name = "FLAGS")]
+    movflags: Option<String>,
+
+    /// Write this format instead of MP4: mkv (copies any codec), webm (VP9 and Opus, for
+    /// browsers and chats) or gif (no sound; 15 fps and 480 pixels wide unless --fps and
+    /// --scale say otherwise). WebM and GIF are always encoded.
+    #[arg(long, value_name = "FORMAT", value_parser = Container::parse)]
+    container: Option<Container>,
+
+    /// Bring the video to this frame rate. Re-encodes video.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=240))]
+    fps: Option<u32>,
+
+    /// Scale the video to this width in pixels, keeping the aspect ratio. Re-encodes video.
+    #[arg(long, value_name = "WIDTH", value_parser = clap::value_parser!(u32).range(16..=7680))]
+    scale: Option<u32>,
+
+    /// Only keep the recording from this far in: 90, 1:30, 00:01:30 or 1m30s. A clip's
+    /// own convert.toml [trim] takes precedence.
+    #[arg(long, value_name = "TIME", value_parser = duration::parse_offset)]
+    start_offset: Option<f64>,
+
+    /// Keep this much from --start-offset (or the start) on, e.g. 45s.
+    #[arg(long, value_name = "TIME", value_parser = duration::parse_offset, conflicts_with = "end_offset")]
+    duration: Option<f64>,
+
+    /// Keep the recording up to this far in (same forms as --start-offset).
+    #[arg(long, value_name = "TIME", value_parser = duration::parse_offset)]
+    end_offset: Option<f64>,
+}
+
+impl EncodeArgs {
+    /// --container, MP4 by default.
+    fn container(&self) -> Container {
+        self.container.unwrap_or_default()
+    }
+
+    /// --start-offset and --duration/--end-offset as seconds from the clip start.
+    fn trim(&self) -> (Option<f64>, Option<f64>) {
+        let end = self
+            .end_offset
+            .or_else(|| Some(self.start_offset.unwrap_or(0.0) + self.duration?));
+        (self.start_offset, end)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Downmix {
+    Stereo,
+}
+
+/// `--audio`: Steam records the game audio first and, when it is enabled, the microphone as
+/// a second track.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum AudioChoice {
+    /// The first track.
+    #[default]
+    Game,
+    /// The second track.
+    Mic,
+    /// Every track.
+    All,
+    /// Every track mixed into one.
+    Mix,
+    /// No audio.
+    None,
+}
+
+/// `--kind`: which of Steam's recorders to take folders from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Kinds {
+    Clips,
+    Background,
+    All,
+}
+
+impl Kinds {
+    fn includes(self, kind: ClipKind) -> bool {
+        match self {
+            Kinds::Clips => kind == ClipKind::Clip,
+            Kinds::Background => kind == ClipKind::Background,
+            Kinds::All => true,
+        }
+    }
+}
+
+/// `--on-exists`: what happens when the output file is already there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OnExists {
+    Overwrite,
+    Skip,
+    Rename,
+    Error,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Convert one clip repeatedly with different settings and report throughput.
+    Bench(bench::BenchArgs),
+    /// Check converted files for truncation/bit-rot (decode test + catalog checksums).
+    Verify(verify::VerifyArgs),
+    /// Convert catalogued outputs again from their originals with the current encode settings.
+    Reconvert(Box<reconvert::ReconvertArgs>),
+    /// Copy Steam screenshots out with the same game-name-and-date naming as clips.
+    Screenshots(screenshots::ScreenshotsArgs),
+    /// Show the clips that would be converted and why the others would be skipped.
+    List(list::ListArgs),
+    /// Compare the space Steam's recordings take with the storage limit set in Steam.
+    Quota(quota::QuotaArgs),
+    /// Cut a highlight reel from the timeline markers of recent session-layout clips.
+    Highlight(highlight::HighlightArgs),
+    /// Watch converted clips one by one and keep, delete, trim or tag each.
+    Review(review::ReviewArgs),
+    /// Pack unconverted clip folders into one archive with an index, to convert elsewhere.
+    ExportRaw(export_raw::ExportRawArgs),
+    /// Offer this machine's clips to `pull` on another machine over the local network,
+    /// with browser previews of the raw recordings (alias: serve).
+    #[command(visible_alias = "serve")]
+    Agent(agent::AgentArgs),
+    /// Convert the clips an `agent` offers, with the conversion options given before `pull`.
+    Pull(remote::PullArgs),
+    /// Count, size and duration of the converted clips, per game and per day.
+    Stats(stats::StatsArgs),
+    /// Convert only the most recent clip (for a hotkey), optionally onto the clipboard.
+    Latest(latest::LatestArgs),
+    /// Maintain the conversion catalog (`catalog repair` after a crash or power loss).
+    Catalog(repair::CatalogArgs),
+    /// Write an index.html or README.md of the output folder's clips by game and month.
+    Index(index::IndexArgs),
+    /// Write a gallery page of the output folder with search and filters by game, date and
+    /// tag, for a web server or a phone.
+    ExportSite(site::SiteArgs),
 }
 
 fn main() {
     // Allow "single positional only" to behave like --input.
     let argv: Vec<String> = env::args().collect();
     let mut argv_for_clap = argv.clone();
-    if argv.len() == 2 && !argv[1].starts_with('-') {
+    if argv.len() == 2 && !argv[1].starts_with('-') && !is_subcommand(&argv[1]) {
         argv_for_clap = vec![argv[0].clone(), "--input".into(), argv[1].clone()];
     }
-    let cli = Cli::parse_from(argv_for_clap);
+    let mut cli = Cli::parse_from(argv_for_clap);
+    report::set_json(cli.format == report::Format::Json);
+
+    match cli.command.take() {
+        Some(Cmd::Bench(args)) => std::process::exit(bench::run(args)),
+        Some(Cmd::Verify(args)) => std::process::exit(verify::run(args)),
+        Some(Cmd::Reconvert(args)) => std::process::exit(reconvert::run(*args)),
+        Some(Cmd::Screenshots(args)) => std::process::exit(screenshots::run(args)),
+        Some(Cmd::List(args)) => std::process::exit(list::run(args)),
+        Some(Cmd::Quota(args)) => std::process::exit(quota::run(args)),
+        Some(Cmd::Highlight(args)) => std::process::exit(highlight::run(args)),
+        Some(Cmd::Review(args)) => std::process::exit(review::run(args)),
+        Some(Cmd::ExportRaw(args)) => std::process::exit(export_raw::run(args)),
+        Some(Cmd::Agent(args)) => std::process::exit(agent::run(args)),
+        Some(Cmd::Pull(args)) => convert(cli, Some(args)),
+        Some(Cmd::Stats(args)) => std::process::exit(stats::run(args)),
+        Some(Cmd::Latest(args)) => std::process::exit(latest::run(cli, args)),
+        Some(Cmd::Catalog(args)) => std::process::exit(repair::run(args)),
+        Some(Cmd::Index(args)) => std::process::exit(index::run(args)),
+        Some(Cmd::ExportSite(args)) => std::process::exit(site::run(args)),
+        None => match cli.export_library {
+            Some(format) => std::process::exit(export_library(&cli, format)),
+            None => convert(cli, None),
+        },
+    }
+}
+
+fn is_subcommand(arg: &str) -> bool {
+    use clap::CommandFactory;
+    Cli::command()
+        .get_subcommands()
+        .any(|c| c.get_name() == arg || c.get_all_aliases().any(|a| a == arg))
+}
+
+/// Default mode: find clips under the input directory (or in an archive, or on the agent
+/// `pull` names) and convert them.
+fn convert(cli: Cli, pull: Option<remote::PullArgs>) {
+    let summary = run_conversion(cli, pull, None);
+    if summary.skipped_for(&SkipReason::UnresolvedName) > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Scan (or resume), confirm and convert; `latest` narrows the run to the newest clip and
+/// never touches the interrupted-run queue.
+fn run_conversion(
+    mut cli: Cli,
+    pull: Option<remote::PullArgs>,
+    latest: Option<&latest::LatestArgs>,
+) -> RunSummary {
+    let explicit = cli.input.clone().or(cli.input_positional.clone());
+    let archive = explicit.as_deref().filter(|p| archive::is_archive(p));
+    let staged_input = archive.is_some() || pull.is_some();
+    if staged_input && (cli.delete_after || cli.gc_empty) {
+        eprintln!(
+            "ERROR: --delete-after and --gc-empty don't apply to an archive or agent input \
+             (use pull --delete-remote)"
+        );
+        std::process::exit(2);
+    }
+    if cli.watch && (staged_input || latest.is_some() || cli.make_room.is_some() || cli.gc_empty) {
+        eprintln!(
+            "ERROR: --watch needs a plain input folder and doesn't combine with latest, \
+             --make-room or --gc-empty"
+        );
+        std::process::exit(2);
+    }
+    if cli.dry_run && (staged_input || latest.is_some() || cli.watch || cli.make_room.is_some()) {
+        eprintln!(
+            "ERROR: --dry-run needs a plain input folder and doesn't combine with latest, \
+             --watch or --make-room"
+        );
+        std::process::exit(2);
+    }
+    if cli.merge && (staged_input || latest.is_some() || cli.watch) {
+        eprintln!(
+            "ERROR: --merge needs a plain input folder and doesn't combine with latest or --watch"
+        );
+        std::process::exit(2);
+    }
+    if cli.thumbnails
+        && let Some(flag) = [
+            ("--merge", cli.merge),
+            ("--remote", cli.encode.remote.is_some()),
+            ("--backend native", cli.encode.backend == Backend::Native),
+        ]
+        .into_iter()
+        .find_map(|(flag, set)| set.then_some(flag))
+    {
+        eprintln!(
+            "ERROR: --thumbnails can't be used with {flag}; the thumbnail post-processing step \
+             makes them from the outputs instead"
+        );
+        std::process::exit(2);
+    }
+    if cli.thumbnails && !cli.encrypt.is_empty() {
+        eprintln!("ERROR: --thumbnails would show what the --encrypt'ed clips contain");
+        std::process::exit(2);
+    }
+    if cli.merge && cli.encode.container() == Container::Gif {
+        eprintln!("ERROR: --merge can't join GIFs; use another --container");
+        std::process::exit(2);
+    }
+    let config = match config::Config::load(cli.config.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            std::process::exit(2);
+        }
+    };
+    let staged: Option<Box<dyn StagedInput>> = match (&pull, archive) {
+        (Some(args), _) => match remote::Remote::connect(args) {
+            Ok(r) => Some(Box::new(r)),
+            Err(e) => {
+                eprintln!("ERROR: cannot reach the agent: {}", e);
+                std::process::exit(2);
+            }
+        },
+        (None, Some(p)) => match archive::Archive::open(p) {
+            Ok(a) => Some(Box::new(a)),
+            Err(e) => {
+                eprintln!("ERROR: cannot read archive {}: {}", p.display(), e);
+                std::process::exit(2);
+            }
+        },
+        (None, None) => None,
+    };
+    let staged = staged.as_deref();
+    let delete_remote = staged.is_some_and(|s| s.deletes_source());
+    let input_dir = match staged {
+        Some(s) => s.origin(),
+        None => resolve_input_dir(explicit),
+    };
+
+    let output_dir = cli
+        .output
+        .clone()
+        .unwrap_or_else(|| env::current_dir().expect("cwd"));
+    if !cli.dry_run
+        && let Err(e) = fs::create_dir_all(&output_dir)
+    {
+        eprintln!(
+            "ERROR: cannot create output dir {}: {}",
+            output_dir.display(),
+            e
+        );
+        std::process::exit(2);
+    }
 
-    // Determine input directory.
-    let input_dir = if let Some(p) = cli.input.or(cli.input_positional) {
+    if let Some(e) = missing_branding(&cli.encode)
+        .or_else(|| bad_trim(&cli.encode))
+        .or_else(|| strip_mic_conflict(&cli.encode))
+        .or_else(|| {
+            (!cli.dry_run)
+                .then(|| ffmpeg_preflight(&cli.encode))
+                .flatten()
+        })
+    {
+        eprintln!("ERROR: {}", e);
+        std::process::exit(2);
+    }
+    if cli.export_project.is_some() && cli.layout != session::Layout::Sessions {
+        eprintln!("ERROR: --export-project needs --layout sessions (one project per session)");
+        std::process::exit(2);
+    }
+
+    let catalog = open_catalog(&cli, &output_dir);
+
+    // --make-room: usage is tracked as clips are deleted, and the run stops at the target.
+    let mut room = None;
+    if let Some(headroom) = cli.make_room {
+        let Some(limit) = quota::storage_limit(cli.quota_limit, &input_dir) else {
+            eprintln!(
+                "ERROR: no recording storage limit found in Steam's config; pass --quota-limit"
+            );
+            std::process::exit(2);
+        };
+        let used = quota::recording_usage(&input_dir);
+        let target = limit.bytes.saturating_sub(headroom);
+        say!(
+            "[quota] {} used of {}; converting until at most {} remain",
+            quota::format_size(used),
+            quota::format_size(limit.bytes),
+            quota::format_size(target)
+        );
+        room = Some((used, target));
+    }
+
+    if cli.watch {
+        // Runs until interrupted (or the scanner thread stops).
+        return watch::run(
+            &mut cli,
+            &input_dir,
+            output_dir,
+            catalog,
+            config.postprocess,
+        );
+    }
+
+    let mut summary = RunSummary::default();
+    if cli.dry_run {
+        let clips = scan_clips(
+            &cli,
+            &input_dir,
+            None,
+            &output_dir,
+            &mut summary,
+            false,
+            None,
+        );
+        // Decides, like the run, whether anything is deleted at all.
+        guard_synced_deletes(&mut cli, &clips);
+        // Nothing of the run's own reporting either: no log, events or progress file.
+        cli.system_log = false;
+        cli.events = None;
+        cli.progress_file = None;
+        let batch = batch::Batch::new(&cli, output_dir, None, catalog, summary, None, None);
+        batch.dry_run(&clips);
+        if cli.gc_empty {
+            say!(
+                "[plan] then sweep empty video/, timelines/ and clip_* folders under {}",
+                input_dir.display()
+            );
+        }
+        return RunSummary::default();
+    }
+
+    let resumed = if cli.fresh_queue || latest.is_some() {
+        None
+    } else {
+        queue::load(&input_dir, &output_dir)
+    };
+    let (work, run_queue) = match resumed {
+        Some(work) => {
+            let left: Vec<ClipDir> = work
+                .iter()
+                .filter(|(_, p)| !matches!(p, queue::Progress::Done))
+                .map(|(c, _)| c.clone())
+                .collect();
+            say!(
+                "[resume] continuing an interrupted run: {} of {} clip(s) left (--fresh-queue to scan again)",
+                left.len(),
+                work.len()
+            );
+            guard_synced_deletes(&mut cli, &left);
+            if !plan::confirm(&cli, &input_dir, &output_dir, &left, delete_remote) {
+                std::process::exit(2);
+            }
+            let run_queue = queue::RunQueue::reopen(&input_dir, &output_dir).map(Some);
+            (work, run_queue)
+        }
+        None => {
+            let clips = scan_clips(
+                &cli,
+                &input_dir,
+                staged,
+                &output_dir,
+                &mut summary,
+                room.is_some(),
+                latest,
+            );
+            guard_synced_deletes(&mut cli, &clips);
+            if !plan::confirm(&cli, &input_dir, &output_dir, &clips, delete_remote) {
+                std::process::exit(2);
+            }
+            let run_queue = match latest {
+                Some(_) => Ok(None),
+                None => queue::RunQueue::create(&input_dir, &output_dir, &clips).map(Some),
+            };
+            let work = clips
+                .into_iter()
+                .map(|c| (c, queue::Progress::Pending))
+                .collect();
+            (work, run_queue)
+        }
+    };
+    let run_queue = run_queue
+        .map_err(|e| {
+            eprintln!(
+                "[warn] cannot save the run queue, an interrupted run won't resume: {}",
+                e
+            )
+        })
+        .ok()
+        .flatten();
+
+    let batch = batch::Batch::new(&cli, output_dir, staged, catalog, summary, room, run_queue)
+        .postprocess(config.postprocess);
+    let player = cli.player.clone().or(config.player);
+    let (summary, room) = batch.run(work, &cli.stages);
+
+    if cli.gc_empty {
+        gc_tree(&input_dir, &cli.allow_delete_under);
+    }
+    if let Some((used, target)) = room
+        && used > target
+    {
+        eprintln!(
+            "[warn] still {} over the --make-room target (nothing left that could be converted)",
+            quota::format_size(used - target)
+        );
+    }
+
+    say!("\nDone. {}", summary.line());
+    // Parallel conversions report failures as they happen; list them again in one place.
+    if summary.any_failed() {
+        eprintln!("Failed:");
+        for (dir, msg) in summary.failures() {
+            eprintln!("  {}: {}", dir.display(), msg.lines().next().unwrap_or(msg));
+        }
+    }
+    hand_to_desktop(&cli, &summary.converted, player.as_deref());
+    report::summary(&summary);
+    if let Some(path) = &cli.summary_json
+        && let Err(e) = atomic::write(path, format!("{}\n", summary.to_json()))
+    {
+        eprintln!("[warn] cannot write {}: {}", path.display(), e);
+    }
+    summary
+}
+
+/// --copy-to-clipboard / --copy-path, --reveal and --play, once the run is done.
+fn hand_to_desktop(cli: &Cli, outputs: &[PathBuf], player: Option<&str>) {
+    if outputs.is_empty() {
+        if cli.copy_to_clipboard || cli.copy_path || cli.reveal || cli.play {
+            say!("[desktop] nothing converted; clipboard and file manager left alone");
+        }
+        return;
+    }
+    if cli.copy_to_clipboard || cli.copy_path {
+        match desktop::copy_to_clipboard(outputs, !cli.copy_path) {
+            Ok(what) => say!("[clipboard] copied {} {}", outputs.len(), what),
+            Err(e) => eprintln!("[warn] cannot copy to the clipboard: {}", e),
+        }
+    }
+    if cli.reveal
+        && let Some(last) = outputs.last()
+        && let Err(e) = desktop::reveal(last)
+    {
+        eprintln!("[warn] cannot open the output folder: {}", e);
+    }
+    if cli.play
+        && let Some(last) = outputs.last()
+    {
+        say!("[play] {}", last.display());
+        if let Err(e) = desktop::open_in_player(last, player) {
+            eprintln!("[warn] cannot open a player: {}", e);
+        }
+    }
+}
+
+/// Turn --delete-after (and --gc-empty) off when the clips are in a synced folder, unless
+/// --ignore-sync-warning: deleting mid-sync can propagate a half-deleted folder.
+fn guard_synced_deletes(cli: &mut Cli, clips: &[ClipDir]) {
+    if !(cli.delete_after || cli.gc_empty) || cli.ignore_sync_warning {
+        return;
+    }
+    if let Some((service, dir)) = sync::detect(clips) {
+        eprintln!(
+            "[warn] {} looks synced by {}; converting without deleting anything, since \
+             deleting mid-sync can leave partial folders on other devices \
+             (--ignore-sync-warning to delete anyway)",
+            dir.display(),
+            service
+        );
+        cli.delete_after = false;
+        cli.gc_empty = false;
+    }
+}
+
+/// Find the clip folders to convert: scan (or list the `staged` input), apply the game
+/// filters and sort. Exits when nothing is left.
+fn scan_clips(
+    cli: &Cli,
+    input_dir: &Path,
+    staged: Option<&dyn StagedInput>,
+    output_dir: &Path,
+    summary: &mut RunSummary,
+    make_room: bool,
+    latest: Option<&latest::LatestArgs>,
+) -> Vec<ClipDir> {
+    // Step 1: recursively find fg_*/bg_* clip folders (reusing the last scan's snapshot if allowed)
+    let mut clips = match staged {
+        Some(s) => s.clips(),
+        None => {
+            let mut snapshot = if cli.full_rescan {
+                ScanSnapshot::default()
+            } else {
+                ScanSnapshot::load(input_dir)
+            };
+            let clips = match find_fg_clip_dirs(input_dir, &mut snapshot) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("ERROR[find]: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if !cli.dry_run
+                && let Err(e) = snapshot.save(input_dir)
+            {
+                eprintln!("[warn] could not save scan snapshot: {}", e);
+            }
+            clips
+        }
+    };
+    clips.retain(|c| cli.kind.includes(c.kind()));
+    if clips.is_empty() {
+        let what = match cli.kind {
+            Kinds::Clips => "fg_*",
+            Kinds::Background => "bg_*",
+            Kinds::All => "fg_*/bg_*",
+        };
+        eprintln!("No {what} clip folders found under {}", input_dir.display());
+        // Leftovers are still worth sweeping when no recordings remain at all.
+        if cli.gc_empty && !cli.dry_run && plan::confirm(cli, input_dir, output_dir, &[], false) {
+            gc_tree(input_dir, &cli.allow_delete_under);
+        }
+        report::summary(summary);
+        std::process::exit(0);
+    }
+
+    // Optional filter by --gameId / --game / --skip-unknown-app
+    if !cli.game_ids.is_empty() || !cli.games.is_empty() || cli.skip_unknown_app {
+        let wanted = game_filter(cli);
+        let (keep, dropped): (Vec<_>, Vec<_>) = clips.into_iter().partition(|c| wanted(c));
+        for c in &dropped {
+            summary.skip_quietly(c, SkipReason::FilteredGame);
+        }
+        clips = keep;
+    }
+    if !cli.matches.is_empty() {
+        let (keep, dropped): (Vec<_>, Vec<_>) = clips
+            .into_iter()
+            .partition(|c| folder_match::matches(&cli.matches, c));
+        for c in &dropped {
+            summary.skip_quietly(c, SkipReason::NotMatched);
+        }
+        clips = keep;
+    }
+    if cli.since.is_some() || cli.until.is_some() {
+        let wanted = range_filter(cli);
+        let (keep, dropped): (Vec<_>, Vec<_>) = clips.into_iter().partition(|c| wanted(c));
+        for c in &dropped {
+            summary.skip_quietly(c, SkipReason::OutsideRange);
+        }
+        clips = keep;
+    }
+
+    if clips.is_empty() {
+        say!("Nothing to convert after filtering.");
+        report::summary(summary);
+        std::process::exit(0);
+    }
+
+    // A saved clip inside a background recording of the same game is the same footage.
+    if clips.iter().any(order::is_manual) && !clips.iter().all(order::is_manual) {
+        let mut covered: Vec<(usize, PathBuf)> = Vec::new();
+        for o in overlap::find(&clips) {
+            let (longer, shorter) = (&clips[o.longer], &clips[o.shorter]);
+            say!(
+                "[overlap] {} and {} share {:.0}s{}",
+                shorter.dir.display(),
+                longer.dir.display(),
+                o.shared,
+                if o.contained { " (fully covered)" } else { "" }
+            );
+            if cli.on_overlap == overlap::OnOverlap::KeepLonger && o.contained {
+                covered.push((o.shorter, longer.dir.clone()));
+            }
+        }
+        covered.sort_by_key(|(i, _)| std::cmp::Reverse(*i));
+        covered.dedup_by_key(|(i, _)| *i);
+        for (i, by) in covered {
+            let clip = clips.remove(i);
+            summary.skip(&clip, SkipReason::Overlapped(by));
+        }
+    }
+
+    // --merge: later parts go along with the first one.
+    if cli.merge {
+        let before = clips.len();
+        clips.retain(merge::is_first_part);
+        if clips.len() < before {
+            say!(
+                "[merge] {} later part(s) will be joined to their first part",
+                before - clips.len()
+            );
+        }
+    }
+
+    let clip_order = if make_room {
+        order::ClipOrder::Oldest
+    } else {
+        cli.order
+    };
+    order::sort_clips(&mut clips, clip_order);
+
+    if let Some(args) = latest {
+        let rules = SettleRules {
+            settle_time: cli.settle_time,
+            growth_window: cli.settle_growth,
+        };
+        say!(
+            "Found {} clip folder(s); converting the latest.",
+            clips.len()
+        );
+        return latest::pick(clips, &rules, args);
+    }
+
+    say!("Found {} clip folder(s).", clips.len());
+
+    clips
+}
+
+/// Whether a clip passes --gameId, --game and --skip-unknown-app.
+fn game_filter(cli: &Cli) -> impl Fn(&ClipDir) -> bool + Send + 'static {
+    let set: HashSet<u32> = cli.game_ids.iter().copied().collect();
+    let skip_unknown = cli.skip_unknown_app;
+    let patterns = cli.games.clone();
+    // Names are only looked up when --game needs them.
+    let names = (!patterns.is_empty()).then(|| app_names(cli));
+    move |c| {
+        (set.is_empty() || set.contains(&c.appid))
+            && !(skip_unknown && c.is_unknown_app())
+            && names
+                .as_ref()
+                .is_none_or(|n| game_matches(&patterns, n.resolve(c.appid).as_deref(), c.appid))
+    }
+}
+
+/// Whether one of the --game `patterns` matches the game's `name`, or its appid when the
+/// name isn't known: a case-insensitive substring, or with `*`/`?` a glob of the whole name.
+fn game_matches(patterns: &[String], name: Option<&str>, appid: u32) -> bool {
+    let id = appid.to_string();
+    let text: Vec<char> = name.unwrap_or(&id).to_lowercase().chars().collect();
+    patterns.iter().any(|p| {
+        let p = p.to_lowercase();
+        if p.contains(['*', '?']) {
+            glob_match(&p.chars().collect::<Vec<_>>(), &text)
+        } else {
+            text.iter().collect::<String>().contains(&p)
+        }
+    })
+}
+
+/// `*` is any run of characters, `?` any one.
+pub(crate) fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        Some((&c, rest)) => text
+            .split_first()
+            .is_some_and(|(&t, tail)| (c == '?' || c == t) && glob_match(rest, tail)),
+    }
+}
+
+/// Where game names are looked up: this machine's libraries, the Steam folder a copied
+/// `userdata` came with, the name lists (--app-names, appnames.txt), Steam's appinfo cache,
+/// and with --online-names the store.
+fn app_names(cli: &Cli) -> AppNames {
+    let mut names = AppNames::discover();
+    if let Some(input) = cli.input.as_ref().or(cli.input_positional.as_ref()) {
+        names.add_roots_near(input);
+    }
+    if let Some(path) = &cli.app_names
+        && let Err(e) = names.load(path)
+    {
+        eprintln!("ERROR: cannot read --app-names {}: {}", path.display(), e);
+        std::process::exit(2);
+    }
+    names.set_online(cli.online_names);
+    names
+}
+
+/// Whether a clip's folder-name time is within --since and --until (no bound passes all).
+fn range_filter(cli: &Cli) -> impl Fn(&ClipDir) -> bool + Send + 'static {
+    let (since, until) = (cli.since, cli.until);
+    move |c| {
+        since.is_none() && until.is_none()
+            || timestamp::folder_time(c)
+                .is_some_and(|t| since.is_none_or(|s| t >= s) && until.is_none_or(|u| t <= u))
+    }
+}
+
+/// Sweep only inside Steam's recording folders (and --allow-delete-under paths), so an
+/// --input pointing at some other video folder is left alone.
+fn gc_tree(input_dir: &Path, allow: &[PathBuf]) {
+    let roots = if plan::may_delete(input_dir, allow) {
+        vec![input_dir.to_path_buf()]
+    } else {
+        let mut roots = quota::recording_dirs(input_dir);
+        roots.extend(
+            allow
+                .iter()
+                .filter(|a| a.starts_with(input_dir) && !plan::may_delete(a, &[]))
+                .cloned(),
+        );
+        roots
+    };
+    if roots.is_empty() {
+        eprintln!(
+            "[warn] no gamerecordings folder under {}; nothing swept (--allow-delete-under to sweep it)",
+            input_dir.display()
+        );
+        return;
+    }
+    let removed: usize = roots.iter().map(|r| gc::sweep(r)).sum();
+    say!("[gc] {} leftover folder(s) removed", removed);
+}
+
+/// The directory to scan: `explicit` if given, else `<SteamRoot>/userdata` with a warning.
+/// Exits when there is no usable directory.
+fn resolve_input_dir(explicit: Option<PathBuf>) -> PathBuf {
+    let input_dir = if let Some(p) = explicit {
         p
     } else {
         // No input provided: default to <SteamRoot>/userdata and WARN.
-        let candidates = steam_default_root_candidates!();
+        let candidates = steam::default_roots();
         let chosen_root = candidates
             .iter()
             .find(|p| p.is_dir())
             .cloned()
-            .or_else(|| candidates.get(0).cloned());
+            .or_else(|| candidates.first().cloned());
         match chosen_root {
             Some(root) => {
                 let userdata = root.join("userdata");
@@ -115,306 +1316,677 @@ fn main() {
         eprintln!("ERROR: input is not a directory: {}", input_dir.display());
         std::process::exit(2);
     }
+    input_dir
+}
 
-    let output_dir = cli
+/// `--export-library` mode: dump clip associations from the catalog, no scanning.
+fn export_library(cli: &Cli, format: library::LibraryFormat) -> i32 {
+    let out_dir = cli
         .output
+        .clone()
         .unwrap_or_else(|| env::current_dir().expect("cwd"));
-    if let Err(e) = fs::create_dir_all(&output_dir) {
-        eprintln!(
-            "ERROR: cannot create output dir {}: {}",
-            output_dir.display(),
-            e
-        );
-        std::process::exit(2);
-    }
-
-    // Discover steamapps roots (for app-name lookup), across platforms.
-    let steamapps_roots = discover_steamapps_roots();
-
-    // Step 1: recursively find fg_* clip folders
-    let mut clips = match find_fg_clip_dirs(&input_dir) {
-        Ok(v) => v,
+    let Some(path) = catalog_path(cli, &out_dir) else {
+        eprintln!("ERROR: no catalog location; pass --catalog");
+        return 2;
+    };
+    let catalog = match Catalog::open(&path) {
+        Ok(c) => c,
         Err(e) => {
-            eprintln!("ERROR[find]: {}", e);
-            std::process::exit(1);
+            eprintln!("ERROR: cannot read catalog {}: {}", path.display(), e);
+            return 2;
         }
     };
-    if clips.is_empty() {
-        eprintln!("No fg_* clip folders found under {}", input_dir.display());
-        std::process::exit(0);
+    match library::export(&catalog, format, &out_dir) {
+        Ok((written, games)) => {
+            say!("[ok] wrote {} ({} game(s))", written.display(), games);
+            0
+        }
+        Err(e) => {
+            eprintln!("ERROR: cannot write library export: {}", e);
+            1
+        }
     }
+}
 
-    // Optional filter by --gameId
-    if !cli.game_ids.is_empty() {
-        let set: HashSet<u32> = cli.game_ids.into_iter().collect();
-        clips.retain(|c| set.contains(&c.appid));
+/// Tag the output of `clip` with what it is, so it survives copies and uploads that lose
+/// the file times: a title of the game and record time (unless the clip has its own), the
+/// record date and `creation_time`, the game and its `steam_appid`.
+fn tag_output(opts: &mut RemuxOptions, clip: &ClipDir, game: &str, start: Option<DateTime<Utc>>) {
+    if opts.title.is_none() {
+        opts.title = Some(match start {
+            Some(t) => format!(
+                "{game} {}",
+                t.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => game.to_string(),
+        });
     }
-
-    if clips.is_empty() {
-        println!("Nothing to convert after --gameId filtering.");
-        std::process::exit(0);
+    if let Some(t) = start {
+        opts.tags.extend([
+            (
+                "date".into(),
+                t.with_timezone(&Local).format("%Y-%m-%d").to_string(),
+            ),
+            (
+                "creation_time".into(),
+                t.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            ),
+        ]);
     }
+    opts.tags.extend([
+        ("game".into(), game.to_string()),
+        ("steam_appid".into(), clip.appid.to_string()),
+    ]);
+}
 
-    // Deterministic order
-    clips.sort_by(|a, b| a.dir.cmp(&b.dir));
+/// Set the output's file times to the clip's record start.
+fn stamp_output(
+    out_path: &Path,
+    clip: &ClipDir,
+    start: Option<DateTime<Utc>>,
+) -> Result<(), Error> {
+    let st = start.ok_or_else(|| Error::InvalidClip {
+        path: clip.dir.clone(),
+        reason: "could not determine start time for mtime".into(),
+    })?;
+    let ft = FileTime::from_system_time(st.into());
+    set_file_times(out_path, ft, ft).map_err(|source| Error::Io {
+        path: out_path.to_path_buf(),
+        source,
+    })
+}
 
-    println!("Found {} clip folder(s).", clips.len());
+/// Append the output's record; false when the catalog couldn't be updated.
+fn record_output(
+    cat: &mut Catalog,
+    clip: &ClipDir,
+    out_path: &Path,
+    game_name: &str,
+    source_digest: Option<sha256::TreeDigest>,
+    seq: Option<u32>,
+    encrypted: Option<(&encrypt::Encryption, Option<f64>)>,
+) -> bool {
+    let entry = CatalogEntry::for_output(
+        &clip.dir, out_path, clip.appid, game_name, &clip.date, &clip.time,
+    )
+    .map(|e| CatalogEntry {
+        visibility: visibility::detect(clip),
+        source_digest,
+        seq,
+        // An encrypted file can't be probed; keep the duration of the plaintext.
+        duration: encrypted.map_or(e.duration, |(_, d)| d),
+        recipients: encrypted.map_or_else(Vec::new, |(enc, _)| enc.recipients.clone()),
+        ..e
+    });
+    match entry.and_then(|e| cat.append(e)) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("[warn] catalog update failed: {}", e);
+            false
+        }
+    }
+}
 
-    for clip in clips {
-        println!(
-            "== {} (appid={}, start={} {}) ==",
-            clip.dir.display(),
-            clip.appid,
-            clip.date,
-            clip.time
-        );
+/// `fname`, or `<stem>-2.mp4`, `-3`, ... if the catalog says another recording already
+/// owns that name (e.g. the same game and second recorded on two machines).
+fn unclaimed_name(cat: &Catalog, dir: &Path, fname: &str, clip: &ClipDir) -> String {
+    let (stem, ext) = fname.rsplit_once('.').unwrap_or((fname, "mp4"));
+    let mut candidate = fname.to_string();
+    for n in 2.. {
+        let taken = cat
+            .find_output(&dir.join(&candidate))
+            .is_some_and(|e| (e.appid, &e.date, &e.time) != (clip.appid, &clip.date, &clip.time));
+        if !taken {
+            break;
+        }
+        candidate = format!("{stem}-{n}.{ext}");
+    }
+    candidate
+}
 
-        let mpd = clip.dir.join("session.mpd");
-        if !mpd.is_file() {
-            eprintln!("[skip] missing session.mpd");
-            continue;
-        }
-
-        // Resolve game name (best-effort)
-        let game_name = resolve_app_name(clip.appid, &steamapps_roots)
-            .unwrap_or_else(|| clip.appid.to_string());
-
-        // Filename: GameName-YYYYMMDD-HHMMSS.mp4  (sanitize for safety)
-        let fname = format!("{}-{}-{}.mp4", sanitize(&game_name), clip.date, clip.time);
-        let out_path = output_dir.join(&fname);
-
-        println!("converting to {}", out_path.display());
-
-        // Remux via ffmpeg using the local MPD.
-        let status = Command::new("ffmpeg")
-            .current_dir(&clip.dir) // MPD uses relative paths
-            .args([
-                "-hide_banner",
-                "-loglevel",
-                "error",
-                "-y",
-                "-i",
-                "session.mpd",
-                "-map",
-                "0:v:0",
-                "-map",
-                "0:a:0?",
-                "-c",
-                "copy",
-                "-movflags",
-                "+faststart",
-                out_path.to_str().unwrap(),
-            ])
-            .status();
-
-        match status {
-            Ok(s) if s.success() => {
-                println!("[ok] wrote {}", out_path.display());
-
-                // Set file times to the record start time (compact Chrono parse).
-                if let Some(st) = to_systemtime(&clip.date, &clip.time) {
-                    let ft = FileTime::from_system_time(st);
-                    if let Err(e) = set_file_times(&out_path, ft, ft) {
-                        eprintln!("[warn] failed to set file times: {}", e);
-                        std::process::exit(2);
-                    }
-                } else {
-                    eprintln!("[warn] could not parse start time for mtime");
-                    std::process::exit(2);
-                }
+/// Where the catalog lives: --catalog, the output dir with --portable-catalog, or the
+/// per-user default.
+fn catalog_path(cli: &Cli, output_dir: &Path) -> Option<PathBuf> {
+    if cli.portable_catalog {
+        return Some(output_dir.join(catalog::PORTABLE_FILE));
+    }
+    cli.catalog.clone().or_else(Catalog::default_path)
+}
 
-                // Delete-after semantics
-                if cli.delete_after {
-                    if let Err(e) = fs::remove_dir_all(&clip.dir) {
-                        eprintln!("[warn] delete failed for {}: {}", clip.dir.display(), e);
-                    } else {
-                        println!("[del] removed {}", clip.dir.display());
-                        maybe_remove_clip_grandparent(&clip);
-                    }
-                }
-            }
-            Ok(s) => {
-                eprintln!("[fail] ffmpeg status: {}", s);
-            }
-            Err(e) => {
-                eprintln!("[fail] launching ffmpeg: {}", e);
-            }
+/// The catalog this run records into, unless disabled or unreadable (conversion goes on either way).
+fn open_catalog(cli: &Cli, output_dir: &Path) -> Option<Catalog> {
+    if cli.no_catalog {
+        return None;
+    }
+    let path = catalog_path(cli, output_dir)?;
+    match Catalog::open(&path) {
+        Ok(c) => Some(c),
+        // A catalog from a newer release must not be appended to behind its back.
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            eprintln!("ERROR: {}: {}", path.display(), e);
+            std::process::exit(2);
+        }
+        Err(e) => {
+            eprintln!("[warn] cannot open catalog {}: {}", path.display(), e);
+            None
         }
     }
-
-    println!("\nDone.");
 }
 
-/// Represents one clip folder like fg_294100_20250828_124021
-struct ClipDir {
-    dir: PathBuf,
-    appid: u32,
-    date: String, // YYYYMMDD
-    time: String, // HHMMSS
+/// Decide how this clip is handed to ffmpeg: copy by default, transcoding only the streams
+/// that `--target-device` / `--downmix` ask for, plus any branding and blurring.
+fn remux_options_for(enc: &EncodeArgs, clip: &ClipDir) -> RemuxOptions {
+    // ffmpeg runs inside the clip folder, so branding paths have to be absolute.
+    let absolute = |p: &Option<PathBuf>| {
+        p.as_deref()
+            .map(|p| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf()))
+    };
+    RemuxOptions {
+        intro: absolute(&enc.prepend),
+        outro: absolute(&enc.append),
+        watermark: absolute(&enc.watermark),
+        blur: enc.blur.clone(),
+        loglevel: enc.ffmpeg_loglevel.clone(),
+        stats: enc.ffmpeg_stats,
+        trim_start: enc.trim().0,
+        trim_end: enc.trim().1,
+        movflags: match &enc.movflags {
+            Some(flags) => Some(flags.clone()),
+            None => enc.no_faststart.then(String::new),
+        },
+        container: enc.container(),
+        fps: enc.fps,
+        width: enc.scale,
+        ..stream_options_for(enc, clip)
+    }
 }
 
-/// Recursively enumerate subfolders that match the fg_* pattern anywhere under `parent`.
-fn find_fg_clip_dirs(parent: &Path) -> io::Result<Vec<ClipDir>> {
-    let re = Regex::new(r"^fg_(\d+)_(\d{8})_(\d{6})$").unwrap();
-    let mut out: Vec<ClipDir> = Vec::new();
+/// --strip-mic together with flags that ask for the microphone, as an error message.
+fn strip_mic_conflict(enc: &EncodeArgs) -> Option<String> {
+    if !enc.strip_mic {
+        return None;
+    }
+    let flag = match (enc.audio, enc.audio_track) {
+        (_, Some(n)) if n != 1 => format!("--audio-track {n}"),
+        (AudioChoice::Mic | AudioChoice::All | AudioChoice::Mix, _) => format!(
+            "--audio {}",
+            enc.audio
+                .to_possible_value()
+                .expect("no skipped variants")
+                .get_name()
+        ),
+        _ => return None,
+    };
+    Some(format!(
+        "--strip-mic keeps the game audio only; it can't be combined with {flag}"
+    ))
+}
 
-    let mut stack: Vec<PathBuf> = vec![parent.to_path_buf()];
-    while let Some(dir) = stack.pop() {
-        let entries = match fs::read_dir(&dir) {
-            Ok(it) => it,
-            Err(_) => continue, // skip unreadable dirs
-        };
+/// A trim range that ends before it starts, as an error message.
+fn bad_trim(enc: &EncodeArgs) -> Option<String> {
+    if let (start, Some(end)) = enc.trim()
+        && end <= start.unwrap_or(0.0)
+    {
+        return Some(format!(
+            "--end-offset/--duration: the kept part must end after --start-offset ({end}s <= {}s)",
+            start.unwrap_or(0.0)
+        ));
+    }
+    None
+}
 
-        for ent in entries.flatten() {
-            let p = ent.path();
-            if !p.is_dir() {
-                continue;
-            }
+/// Branding files that don't exist, as an error message.
+fn missing_branding(enc: &EncodeArgs) -> Option<String> {
+    [
+        ("--prepend", &enc.prepend),
+        ("--append", &enc.append),
+        ("--watermark", &enc.watermark),
+    ]
+    .into_iter()
+    .find_map(|(flag, p)| {
+        p.as_ref()
+            .filter(|p| !p.is_file())
+            .map(|p| format!("{flag} {}: no such file", p.display()))
+    })
+}
 
-            if let Some(name) = p.file_name().and_then(|s| s.to_str()) {
-                if let Some(caps) = re.captures(name) {
-                    let appid: u32 = caps.get(1).unwrap().as_str().parse().unwrap_or(0);
-                    if appid != 0 {
-                        let date = caps.get(2).unwrap().as_str().to_string();
-                        let time = caps.get(3).unwrap().as_str().to_string();
-                        out.push(ClipDir {
-                            dir: p.clone(),
-                            appid,
-                            date,
-                            time,
-                        });
-                    }
-                    // clip folder is terminal; don't descend into it
-                    continue;
-                }
+/// Check that ffmpeg is installed with everything `enc` can need, as an error message
+/// naming what's missing. With `--backend native`, check instead that nothing asks for a
+/// transcode.
+fn ffmpeg_preflight(enc: &EncodeArgs) -> Option<String> {
+    // Before the backends: native writes the game track only, just what --strip-mic asks
+    // for, but must refuse the same contradictions.
+    if let Some(e) = strip_mic_conflict(enc) {
+        return Some(e);
+    }
+    if enc.backend == Backend::Native && enc.movflags.is_some() {
+        return Some(
+            "--movflags needs --backend ffmpeg (native always writes the index up front)".into(),
+        );
+    }
+    if enc.backend == Backend::Native && enc.remote.is_some() {
+        return Some("--remote runs ffmpeg on that machine; it needs --backend ffmpeg".into());
+    }
+    if enc.backend == Backend::Native {
+        return [
+            ("--target-device", enc.target_device.is_some()),
+            ("--encode", enc.encode.is_some()),
+            ("--audio", enc.audio != AudioChoice::Game),
+            ("--audio-track", enc.audio_track.is_some()),
+            ("--downmix", enc.downmix.is_some()),
+            ("--fix-drift", enc.fix_drift),
+            ("--mute-voice-channel", enc.mute_voice_channel.is_some()),
+            ("--prepend", enc.prepend.is_some()),
+            ("--append", enc.append.is_some()),
+            ("--watermark", enc.watermark.is_some()),
+            ("--blur", !enc.blur.is_empty()),
+            ("--start-offset", enc.start_offset.is_some()),
+            ("--duration", enc.duration.is_some()),
+            ("--end-offset", enc.end_offset.is_some()),
+            ("--container", enc.container() != Container::Mp4),
+            ("--fps", enc.fps.is_some()),
+            ("--scale", enc.scale.is_some()),
+        ]
+        .into_iter()
+        .find(|(_, set)| *set)
+        .map(|(flag, _)| format!("{flag} needs --backend ffmpeg (native only copies streams)"));
+    }
+    let branded = enc.prepend.is_some() || enc.append.is_some() || enc.watermark.is_some();
+    let container = enc.container();
+    if container != Container::Mp4 && (enc.movflags.is_some() || enc.no_faststart) {
+        return Some("--movflags/--no-faststart only apply to --container mp4".into());
+    }
+    if matches!(container, Container::Webm | Container::Gif) {
+        if enc.target_device.is_some() {
+            return Some("--target-device makes MP4s; use --container mp4 or mkv".into());
+        }
+        if container == Container::Gif && enc.encode.is_some() {
+            return Some("--encode can't be used with --container gif".into());
+        }
+        if enc.encode.is_some_and(|p| p != EncodeProfile::Av1Small) {
+            return Some(
+                "--container webm takes VP9 (its default) or AV1: use --encode av1-small or \
+                 leave --encode out"
+                    .into(),
+            );
+        }
+    }
+    if container == Container::Gif && branded {
+        return Some(
+            "--prepend/--append/--watermark can't be used with --container gif (it has no sound \
+             to join)"
+                .into(),
+        );
+    }
+    if branded && !matches!(enc.audio, AudioChoice::Game | AudioChoice::Mic) {
+        return Some(
+            "--prepend/--append/--watermark join one audio track; use --audio game, mic or \
+             --audio-track"
+                .into(),
+        );
+    }
+    if enc.audio == AudioChoice::Mix && (enc.fix_drift || enc.mute_voice_channel.is_some()) {
+        return Some(
+            "--audio mix can't be combined with --fix-drift or --mute-voice-channel".into(),
+        );
+    }
+    // The pan is built for one track's channel layout, and would be applied to every track.
+    if enc.audio == AudioChoice::All && enc.mute_voice_channel.is_some() {
+        return Some(
+            "--mute-voice-channel mutes a channel of one track; it can't be combined with \
+             --audio all"
+                .into(),
+        );
+    }
+    // Branding joins the audio in a filter graph; these add a plain -af next to it, which
+    // ffmpeg refuses.
+    if branded && (enc.fix_drift || enc.mute_voice_channel.is_some()) {
+        return Some(
+            "--prepend/--append/--watermark can't be combined with --fix-drift or \
+             --mute-voice-channel"
+                .into(),
+        );
+    }
+    if enc.remote.is_some() && enc.hwaccel == Some(HwAccel::Auto) {
+        return Some(
+            "--hwaccel auto tries the encoders of this machine; name the remote's with --hwaccel"
+                .into(),
+        );
+    }
+    let hwaccel = enc.encode.and_then(|p| profile::resolve(p, enc.hwaccel));
+    if hwaccel == Some(HwAccel::Vaapi) && (branded || !enc.blur.is_empty()) {
+        return Some(format!(
+            "--hwaccel vaapi{} can't be combined with --prepend/--append/--watermark/--blur",
+            if enc.hwaccel == Some(HwAccel::Auto) {
+                " (what auto chose here)"
+            } else {
+                ""
             }
-
-            stack.push(p);
+        ));
+    }
+    if let Some(remote) = &enc.remote {
+        if branded {
+            return Some(
+                "--prepend/--append/--watermark can't be used with --remote (the files are on \
+                 this machine)"
+                    .into(),
+            );
         }
+        // What its ffmpeg can encode is only known there: a clip fails with its message.
+        return remote.check().err();
+    }
+    if let Some(problem) = enc.encode.and_then(|p| profile::check(p, enc.hwaccel)) {
+        return Some(problem);
+    }
+    let mut required = Component::remux(container);
+    if enc.encode.is_none()
+        && (enc.target_device.is_some()
+            || branded
+            || !enc.blur.is_empty()
+            || enc.fps.is_some()
+            || enc.scale.is_some()
+            || !container.copies_streams())
+    {
+        required.push(Component::new(
+            ComponentKind::Encoder,
+            container.video_encoder(),
+        ));
+    }
+    let audio_encoded = enc.target_device.is_some()
+        || enc.downmix.is_some()
+        || enc.audio == AudioChoice::Mix
+        || enc.fix_drift
+        || enc.mute_voice_channel.is_some()
+        || branded;
+    match container {
+        Container::Webm => required.push(Component::new(ComponentKind::Encoder, "libopus")),
+        Container::Gif => {}
+        _ if audio_encoded => required.push(Component::new(ComponentKind::Encoder, "aac")),
+        _ => {}
+    }
+    let hint = if cfg!(target_os = "macos") {
+        "`brew install ffmpeg`, or a build from https://ffmpeg.org/download.html"
+    } else if cfg!(target_os = "windows") {
+        "`winget install Gyan.FFmpeg`, or a full build from https://ffmpeg.org/download.html"
+    } else {
+        "your distribution's full ffmpeg package (on Fedora, `ffmpeg` from RPM Fusion rather \
+         than `ffmpeg-free`), or a static build from https://ffmpeg.org/download.html"
+    };
+    match missing_components(&required) {
+        Ok(missing) if missing.is_empty() => None,
+        Ok(missing) => Some(format!(
+            "this ffmpeg build has no {}; install {}",
+            missing
+                .iter()
+                .map(Component::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            hint
+        )),
+        Err(e @ Error::Spawn { .. }) => Some(format!("{}; install {}", e, hint)),
+        Err(e) => Some(format!("cannot list ffmpeg's components: {}", e)),
     }
-
-    Ok(out)
 }
 
-/// If fg dir was the ONLY directory in its parent 'video', also remove the 'clip_*' grandparent.
-fn maybe_remove_clip_grandparent(clip: &ClipDir) {
-    // parent should be .../video/
-    let Some(video_dir) = clip.dir.parent() else {
-        return;
-    };
-    if video_dir.file_name().and_then(|s| s.to_str()) != Some("video") {
-        return;
+/// Copy-or-transcode decisions per stream for `enc`.
+fn stream_options_for(enc: &EncodeArgs, clip: &ClipDir) -> RemuxOptions {
+    let label_audio = enc.label_audio || enc.audio_language.is_some();
+    if enc.target_device.is_none()
+        && enc.encode.is_none()
+        && enc.audio == AudioChoice::Game
+        && enc.audio_track.is_none()
+        && enc.downmix.is_none()
+        && !label_audio
+        && !enc.fix_drift
+        && enc.mute_voice_channel.is_none()
+    {
+        return RemuxOptions::default();
     }
+    let streams = match probe_streams(&clip.dir) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("[warn] probe failed: {}", e);
+            Vec::new()
+        }
+    };
+    let audio = audio_select(enc, &streams);
+    // The streams as they go into the output, which the audio options below work on.
+    let mapped = mapped_streams(&streams, audio);
 
-    // Are there any subdirectories left in video/ ?
-    let mut any_left = false;
-    if let Ok(rd) = fs::read_dir(video_dir) {
-        for ent in rd.flatten() {
-            if ent.path().is_dir() {
-                any_left = true;
-                break;
-            }
+    let mut opts = match enc.target_device {
+        Some(device) if !streams.is_empty() => device.plan(&streams),
+        Some(_) => {
+            eprintln!("[warn] no stream info; stream-copying video");
+            RemuxOptions::default()
         }
+        None => RemuxOptions::default(),
+    };
+    if let Some(profile) = enc.encode {
+        let (encoder, args, input) =
+            profile::video(profile, enc.crf, enc.preset.as_deref(), enc.hwaccel);
+        opts.video_encoder = Some(encoder);
+        opts.video_args = args;
+        opts.input_args = input;
     }
-    if any_left {
-        return; // not the only one
+    opts.audio = audio;
+    if let AudioSelect::Mix(n) = audio {
+        say!("[audio] mixing {n} audio tracks into one");
+        if opts.audio_encoder.is_none() {
+            opts.audio_encoder = Some("aac".into());
+            opts.audio_args = ["-b:a", "192k"].map(String::from).to_vec();
+        }
     }
 
-    // grandparent expected to be clip_<appid>_<date>_<time>
-    let Some(clip_parent) = video_dir.parent() else {
-        return;
-    };
-    if let Some(name) = clip_parent.file_name().and_then(|s| s.to_str()) {
-        let re = Regex::new(r"^clip_\d+_\d{8}_\d{6}$").unwrap();
-        if re.is_match(name) {
-            match fs::remove_dir_all(clip_parent) {
-                Ok(_) => println!("[del] removed {}", clip_parent.display()),
-                Err(e) => eprintln!("[warn] failed to remove {}: {}", clip_parent.display(), e),
+    if let Some(Downmix::Stereo) = enc.downmix {
+        // Skip clips we know are already mono/stereo; unknown layouts are downmixed anyway.
+        let channels = mapped
+            .iter()
+            .find(|s| s.codec_type == "audio")
+            .map(|s| s.channels)
+            .unwrap_or(0);
+        if channels == 0 || channels > 2 {
+            if opts.audio_encoder.is_none() {
+                opts.audio_encoder = Some("aac".into());
+                opts.audio_args = ["-b:a", "192k"].map(String::from).to_vec();
             }
+            opts.audio_args.extend(["-ac", "2"].map(String::from));
         }
     }
-}
 
-/// Discover steamapps roots across OSes:
-/// - default Steam roots from macro
-/// - plus any additional libraries from libraryfolders.vdf (under <root>/config/ or <root>/steamapps/)
-fn discover_steamapps_roots() -> Vec<PathBuf> {
-    let mut roots = Vec::new();
+    if let Some(n) = enc.mute_voice_channel {
+        mute_channel(&mapped, n, &mut opts);
+    }
+    if enc.fix_drift {
+        fix_drift(&mapped, &mut opts);
+    }
 
-    let steam_roots = steam_default_root_candidates!();
-    for root in steam_roots {
-        let sa = root.join("steamapps");
-        if sa.is_dir() {
-            roots.push(sa.clone());
-        }
+    if label_audio {
+        // Label only mapped streams: ffmpeg rejects metadata for streams that don't exist.
+        let present = mapped.iter().any(|s| s.codec_type == "audio");
+        let titles: Vec<String> = match audio {
+            AudioSelect::Track(i) if present => vec![default_audio_title(i as usize)],
+            AudioSelect::All => (0..mapped.iter().filter(|s| s.codec_type == "audio").count())
+                .map(default_audio_title)
+                .collect(),
+            AudioSelect::Mix(_) => vec!["Game + Microphone".to_string()],
+            _ => Vec::new(),
+        };
+        opts.audio_labels = titles
+            .into_iter()
+            .map(|title| TrackLabel {
+                title,
+                language: enc.audio_language.clone().unwrap_or_else(|| "und".into()),
+            })
+            .collect();
+    }
 
-        let vdf1 = root.join("config").join("libraryfolders.vdf");
-        let vdf2 = root.join("steamapps").join("libraryfolders.vdf");
+    if !opts.is_copy() {
+        say!(
+            "[transcode] {}: video={} audio={}",
+            describe_streams(&streams),
+            opts.video_encoder.as_deref().unwrap_or("copy"),
+            opts.audio_encoder.as_deref().unwrap_or("copy")
+        );
+    }
+    opts
+}
 
-        for vdf in [vdf1, vdf2] {
-            if vdf.is_file() {
-                if let Ok(txt) = fs::read_to_string(&vdf) {
-                    for path in parse_libraryfolders_paths(&txt) {
-                        let sp = Path::new(&path).join("steamapps");
-                        if sp.is_dir() {
-                            roots.push(sp);
-                        }
-                    }
-                }
-            }
+/// `--audio`/`--audio-track` as the streams to map, given the clip's probed streams (empty
+/// when the probe failed).
+fn audio_select(enc: &EncodeArgs, streams: &[StreamInfo]) -> AudioSelect {
+    let count = streams.iter().filter(|s| s.codec_type == "audio").count() as u32;
+    let missing = |n: u32, what: &str| {
+        if !streams.is_empty() && n >= count {
+            eprintln!("[warn] the clip has no {what}; writing it without audio");
         }
+    };
+    if let Some(n) = enc.audio_track {
+        missing(n - 1, &format!("audio track {n}"));
+        return AudioSelect::Track(n - 1);
+    }
+    match enc.audio {
+        _ if enc.strip_mic && enc.audio != AudioChoice::None => AudioSelect::Track(0),
+        AudioChoice::Game => AudioSelect::Track(0),
+        AudioChoice::Mic => {
+            missing(1, "microphone track");
+            AudioSelect::Track(1)
+        }
+        AudioChoice::All => AudioSelect::All,
+        AudioChoice::Mix if count >= 2 => AudioSelect::Mix(count),
+        // Nothing to mix.
+        AudioChoice::Mix => AudioSelect::Track(0),
+        AudioChoice::None => AudioSelect::Silent,
     }
-
-    roots.sort();
-    roots.dedup();
-    roots
 }
 
-/// Extract library "path" values from libraryfolders.vdf
-fn parse_libraryfolders_paths(vdf_text: &str) -> Vec<String> {
-    // Accept lines like: "path" "/Volumes/External/SteamLibrary" or "path" "D:\\SteamLibrary"
-    let path_re = Regex::new(r#""path"\s*"([^"]+)""#).unwrap();
-    path_re
-        .captures_iter(vdf_text)
-        .map(|c| c[1].to_string())
+/// The video streams and the audio streams `audio` keeps.
+fn mapped_streams(streams: &[StreamInfo], audio: AudioSelect) -> Vec<StreamInfo> {
+    let mut index = 0;
+    streams
+        .iter()
+        .filter(|s| {
+            if s.codec_type != "audio" {
+                return true;
+            }
+            index += 1;
+            match audio {
+                AudioSelect::Track(i) => index - 1 == i,
+                AudioSelect::All | AudioSelect::Mix(_) => true,
+                AudioSelect::Silent => false,
+            }
+        })
+        .cloned()
         .collect()
 }
 
-/// Read appmanifest_<appid>.acf from any steamapps root and extract "name"
-fn resolve_app_name(appid: u32, steamapps_roots: &[PathBuf]) -> Option<String> {
-    let manifest = format!("appmanifest_{}.acf", appid);
-    for root in steamapps_roots {
-        let p = root.join(&manifest);
-        if p.is_file() {
-            if let Ok(txt) = fs::read_to_string(&p) {
-                if let Some(name) = parse_acf_name(&txt) {
-                    return Some(name);
-                }
-            }
-        }
+/// Below this the tracks are considered in sync (about a frame at 30 fps).
+const DRIFT_TOLERANCE_SECS: f64 = 0.04;
+
+/// `--fix-drift`: stretch the audio to the video's length when timescale rounding in the
+/// segments has made them differ. Large mismatches are real content differences (a track
+/// that stopped early), not drift, and are left alone.
+fn fix_drift(streams: &[StreamInfo], opts: &mut RemuxOptions) {
+    let track = |kind: &str| {
+        streams
+            .iter()
+            .find(|s| s.codec_type == kind)
+            .and_then(|s| s.duration)
+    };
+    let (Some(video), Some(audio)) = (track("video"), track("audio")) else {
+        say!("[drift] track durations not reported; leaving audio as is");
+        return;
+    };
+    let drift = audio - video;
+    if drift.abs() <= DRIFT_TOLERANCE_SECS {
+        return;
     }
-    None
+    let tempo = audio / video;
+    if !(0.98..=1.02).contains(&tempo) {
+        eprintln!(
+            "[warn] audio {:.2}s vs video {:.2}s is too far apart to be drift; not correcting",
+            audio, video
+        );
+        return;
+    }
+    say!(
+        "[drift] audio {:+.3}s over {:.1}s; retiming audio by {:.6}",
+        drift,
+        video,
+        tempo
+    );
+    add_audio_filter(opts, format!("atempo={tempo:.6},aresample=async=1"));
 }
 
-/// Minimal ACF parser: `"name"   "Some Game"`
-fn parse_acf_name(acf_text: &str) -> Option<String> {
-    let re = Regex::new(r#""name"\s*"([^"]+)""#).unwrap();
-    re.captures(acf_text).map(|c| c[1].to_string())
+/// `--mute-voice-channel`: rebuild the game audio with channel `n` (from 1) silenced and
+/// the others passed through. Best-effort: needs the channel count from the probe.
+fn mute_channel(streams: &[StreamInfo], n: u32, opts: &mut RemuxOptions) {
+    let channels = streams
+        .iter()
+        .find(|s| s.codec_type == "audio")
+        .map(|s| s.channels)
+        .unwrap_or(0);
+    if channels == 0 {
+        eprintln!("[warn] audio channel count unknown; not muting channel {n}");
+        return;
+    }
+    if n > channels {
+        eprintln!("[warn] the audio has only {channels} channel(s); not muting channel {n}");
+        return;
+    }
+    let layout = match channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        6 => "5.1".to_string(),
+        8 => "7.1".to_string(),
+        c => format!("{c}c"),
+    };
+    let mut pan = format!("pan={layout}");
+    for c in 0..channels {
+        if c + 1 == n {
+            pan.push_str(&format!("|c{c}=0*c{c}"));
+        } else {
+            pan.push_str(&format!("|c{c}=c{c}"));
+        }
+    }
+    say!("[voice] muting audio channel {n} of {channels}");
+    add_audio_filter(opts, pan);
 }
 
-/// Convert to SystemTime assuming the clip's filename time is in **UTC**.
-/// Inputs are "YYYYMMDD" and "HHMMSS" (already sliced from folder name).
-fn to_systemtime(date8: &str, time6: &str) -> Option<std::time::SystemTime> {
-    use std::time::{Duration, UNIX_EPOCH};
-
-    let d = NaiveDate::parse_from_str(date8, "%Y%m%d").ok()?;
-    let t = NaiveTime::parse_from_str(time6, "%H%M%S").ok()?;
-    let ndt = NaiveDateTime::new(d, t);
+/// Append `filter` to the audio filter chain (one `-af` per output stream), switching the
+/// audio to AAC if it was going to be copied.
+fn add_audio_filter(opts: &mut RemuxOptions, filter: String) {
+    if opts.audio_encoder.is_none() {
+        opts.audio_encoder = Some("aac".into());
+        opts.audio_args = ["-b:a", "192k"].map(String::from).to_vec();
+    }
+    match opts.audio_args.iter().position(|a| a == "-af") {
+        Some(i) if i + 1 < opts.audio_args.len() => {
+            let chain = &mut opts.audio_args[i + 1];
+            chain.push(',');
+            chain.push_str(&filter);
+        }
+        _ => opts.audio_args.extend(["-af".to_string(), filter]),
+    }
+}
 
-    // Filenames are UTC; interpret naivedatetime as UTC then build SystemTime.
-    let dt_utc = Utc.from_utc_datetime(&ndt);
-    let secs = dt_utc.timestamp();
-    let nanos = dt_utc.timestamp_subsec_nanos();
+/// "h264/aac"-style summary of the probed codecs.
+fn describe_streams(streams: &[StreamInfo]) -> String {
+    streams
+        .iter()
+        .map(|s| s.codec_name.as_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
 
-    Some(UNIX_EPOCH + Duration::from_secs(secs as u64) + Duration::from_nanos(nanos as u64))
+/// If fg dir was the ONLY directory in its parent 'video', also remove the 'clip_*' grandparent.
+fn maybe_remove_clip_grandparent(clip: &ClipDir) {
+    match steam::remove_clip_parent(clip) {
+        Ok(Some(parent)) => say!("[del] removed {}", parent.display()),
+        Ok(None) => {}
+        Err(e) => eprintln!(
+            "[warn] failed to remove the clip_* folder of {}: {}",
+            clip.dir.display(),
+            e
+        ),
+    }
 }
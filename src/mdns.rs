@@ -0,0 +1,330 @@
+//! mDNS / DNS-SD for the agent: announcing `_steamclipconv._tcp.local` so `pull` can find
+//! a recording machine on the local network without knowing its address.
+//!
+//! Announcing goes through the system's responder when there is one (`avahi-publish-service`
+//! on Linux, `dns-sd` on macOS), since it already owns UDP port 5353; otherwise a minimal
+//! responder answers queries itself. Browsing sends a one-shot ("legacy unicast") query from
+//! an ephemeral port, which every responder answers directly.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// DNS-SD service type (service names may have at most 15 characters).
+pub const SERVICE: &str = "_steamclipconv._tcp.local";
+const GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const PORT: u16 = 5353;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const TTL: u32 = 120;
+
+/// Keeps the announcement alive; dropping it withdraws a system-responder announcement.
+pub struct Advertisement {
+    child: Option<Child>,
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        if let Some(c) = &mut self.child {
+            let _ = c.kill();
+            let _ = c.wait();
+        }
+    }
+}
+
+/// Announce an agent called `instance` listening on `port`.
+pub fn advertise(instance: &str, port: u16) -> io::Result<Advertisement> {
+    let kind = "_steamclipconv._tcp";
+    let txt = "path=/api/clips";
+    let system = if cfg!(target_os = "macos") {
+        Command::new("dns-sd")
+            .args(["-R", instance, kind, "local", &port.to_string(), txt])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    } else {
+        Command::new("avahi-publish-service")
+            .args([instance, kind, &port.to_string(), txt])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    };
+    if let Ok(mut child) = system {
+        // It exits right away when no daemon is running.
+        thread::sleep(Duration::from_millis(300));
+        if child.try_wait()?.is_none() {
+            return Ok(Advertisement { child: Some(child) });
+        }
+    }
+    let sock = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, PORT))?;
+    sock.join_multicast_v4(&GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    let responder = Responder {
+        instance: format!("{instance}.{SERVICE}"),
+        host: format!("{}.local", instance.replace([' ', '.'], "-")),
+        port,
+    };
+    thread::spawn(move || responder.run(sock));
+    Ok(Advertisement { child: None })
+}
+
+/// An agent that answered a browse.
+#[derive(Clone, Debug)]
+pub struct Found {
+    pub instance: String,
+    pub addr: SocketAddr,
+}
+
+/// Ask the local network for agents, collecting answers for `wait`.
+pub fn browse(wait: Duration) -> io::Result<Vec<Found>> {
+    let sock = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))?;
+    let mut query = header(0x5c17, 0, 1, 0, 0);
+    put_name(&mut query, SERVICE);
+    query.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    sock.send_to(&query, SocketAddrV4::new(GROUP, PORT))?;
+
+    let mut instances = Vec::new();
+    let mut srv: HashMap<String, (String, u16)> = HashMap::new();
+    let mut hosts: HashMap<String, Ipv4Addr> = HashMap::new();
+    let deadline = Instant::now() + wait;
+    let mut buf = [0u8; 9000];
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        sock.set_read_timeout(Some(left.max(Duration::from_millis(1))))?;
+        let Ok((n, _)) = sock.recv_from(&mut buf) else {
+            break;
+        };
+        let Some(msg) = Message::parse(&buf[..n]) else {
+            continue;
+        };
+        for r in msg.records {
+            match r.rtype {
+                TYPE_PTR if r.name.eq_ignore_ascii_case(SERVICE) => {
+                    if let Some((target, _)) = read_name(msg.raw, r.rdata_at)
+                        && !instances.contains(&target)
+                    {
+                        instances.push(target);
+                    }
+                }
+                TYPE_SRV if r.rdata.len() >= 6 => {
+                    let port = u16::from_be_bytes([r.rdata[4], r.rdata[5]]);
+                    if let Some((target, _)) = read_name(msg.raw, r.rdata_at + 6) {
+                        srv.insert(r.name.to_lowercase(), (target.to_lowercase(), port));
+                    }
+                }
+                TYPE_A if r.rdata.len() == 4 => {
+                    let ip = Ipv4Addr::new(r.rdata[0], r.rdata[1], r.rdata[2], r.rdata[3]);
+                    hosts.insert(r.name.to_lowercase(), ip);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(instances
+        .into_iter()
+        .filter_map(|inst| {
+            let (host, port) = srv.get(&inst.to_lowercase())?;
+            let ip = hosts.get(host)?;
+            let label = inst
+                .strip_suffix(&format!(".{SERVICE}"))
+                .unwrap_or(&inst)
+                .to_string();
+            Some(Found {
+                instance: label,
+                addr: SocketAddr::V4(SocketAddrV4::new(*ip, *port)),
+            })
+        })
+        .collect())
+}
+
+struct Responder {
+    /// `<instance>._steamclipconv._tcp.local`
+    instance: String,
+    host: String,
+    port: u16,
+}
+
+impl Responder {
+    fn run(self, sock: UdpSocket) {
+        let mut buf = [0u8; 9000];
+        loop {
+            let Ok((n, from)) = sock.recv_from(&mut buf) else {
+                continue;
+            };
+            let Some(msg) = Message::parse(&buf[..n]) else {
+                continue;
+            };
+            if msg.is_response {
+                continue;
+            }
+            let wanted = msg.questions.iter().any(|(name, qtype)| {
+                (name.eq_ignore_ascii_case(SERVICE) && matches!(*qtype, TYPE_PTR | TYPE_ANY))
+                    || (name.eq_ignore_ascii_case(&self.instance)
+                        && matches!(*qtype, TYPE_SRV | TYPE_TXT | TYPE_ANY))
+                    || (name.eq_ignore_ascii_case(&self.host)
+                        && matches!(*qtype, TYPE_A | TYPE_ANY))
+            });
+            if !wanted {
+                continue;
+            }
+            let Some(ip) = local_ipv4() else { continue };
+            // One-shot queries from another port get a direct reply echoing the question.
+            let legacy = from.port() != PORT;
+            let reply = self.answer(if legacy { msg.id } else { 0 }, ip, legacy.then_some(&msg));
+            let to = if legacy {
+                from
+            } else {
+                SocketAddr::V4(SocketAddrV4::new(GROUP, PORT))
+            };
+            let _ = sock.send_to(&reply, to);
+        }
+    }
+
+    fn answer(&self, id: u16, ip: Ipv4Addr, echo: Option<&Message>) -> Vec<u8> {
+        let questions = echo.map_or(&[][..], |m| &m.questions[..]);
+        let mut out = header(id, 0x8400, questions.len() as u16, 1, 3);
+        for (name, qtype) in questions {
+            put_name(&mut out, name);
+            out.extend_from_slice(&qtype.to_be_bytes());
+            out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        }
+        let mut rdata = Vec::new();
+        put_name(&mut rdata, &self.instance);
+        put_record(&mut out, SERVICE, TYPE_PTR, &rdata);
+
+        let mut rdata = vec![0, 0, 0, 0];
+        rdata.extend_from_slice(&self.port.to_be_bytes());
+        put_name(&mut rdata, &self.host);
+        put_record(&mut out, &self.instance, TYPE_SRV, &rdata);
+
+        let txt = b"path=/api/clips";
+        let mut rdata = vec![txt.len() as u8];
+        rdata.extend_from_slice(txt);
+        put_record(&mut out, &self.instance, TYPE_TXT, &rdata);
+
+        put_record(&mut out, &self.host, TYPE_A, &ip.octets());
+        out
+    }
+}
+
+/// The address other machines reach this one on (the route to the mDNS group).
+fn local_ipv4() -> Option<Ipv4Addr> {
+    let s = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    s.connect(SocketAddrV4::new(GROUP, PORT)).ok()?;
+    match s.local_addr().ok()? {
+        SocketAddr::V4(a) if !a.ip().is_unspecified() => Some(*a.ip()),
+        _ => None,
+    }
+}
+
+fn header(id: u16, flags: u16, qd: u16, an: u16, ar: u16) -> Vec<u8> {
+    let mut h = Vec::with_capacity(512);
+    for v in [id, flags, qd, an, 0, ar] {
+        h.extend_from_slice(&v.to_be_bytes());
+    }
+    h
+}
+
+fn put_name(out: &mut Vec<u8>, name: &str) {
+    for label in name.trim_end_matches('.').split('.') {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        out.push(label.len() as u8);
+        out.extend_from_slice(label);
+    }
+    out.push(0);
+}
+
+fn put_record(out: &mut Vec<u8>, name: &str, rtype: u16, rdata: &[u8]) {
+    put_name(out, name);
+    out.extend_from_slice(&rtype.to_be_bytes());
+    out.extend_from_slice(&CLASS_IN.to_be_bytes());
+    out.extend_from_slice(&TTL.to_be_bytes());
+    out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    out.extend_from_slice(rdata);
+}
+
+/// Read a (possibly compressed) name at `pos`; returns it and the offset after it.
+fn read_name(msg: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..128 {
+        let len = *msg.get(pos)? as usize;
+        if len == 0 {
+            return Some((labels.join("."), end.unwrap_or(pos + 1)));
+        }
+        if len & 0xc0 == 0xc0 {
+            let ptr = ((len & 0x3f) << 8) | *msg.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = ptr;
+            continue;
+        }
+        let label = msg.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len;
+    }
+    None
+}
+
+struct Record<'a> {
+    name: String,
+    rtype: u16,
+    rdata: &'a [u8],
+    /// Offset of `rdata` in the message, for names inside it.
+    rdata_at: usize,
+}
+
+struct Message<'a> {
+    raw: &'a [u8],
+    id: u16,
+    is_response: bool,
+    questions: Vec<(String, u16)>,
+    records: Vec<Record<'a>>,
+}
+
+impl<'a> Message<'a> {
+    fn parse(raw: &'a [u8]) -> Option<Message<'a>> {
+        let word = |i: usize| Some(u16::from_be_bytes([*raw.get(i)?, *raw.get(i + 1)?]));
+        let id = word(0)?;
+        let is_response = word(2)? & 0x8000 != 0;
+        let qd = word(4)?;
+        let rr = word(6)? as usize + word(8)? as usize + word(10)? as usize;
+        let mut pos = 12;
+        let mut questions = Vec::new();
+        for _ in 0..qd {
+            let (name, next) = read_name(raw, pos)?;
+            questions.push((name, word(next)?));
+            pos = next + 4;
+        }
+        let mut records = Vec::new();
+        for _ in 0..rr {
+            let (name, next) = read_name(raw, pos)?;
+            let rtype = word(next)?;
+            let len = word(next + 8)? as usize;
+            let rdata_at = next + 10;
+            let rdata = raw.get(rdata_at..rdata_at + len)?;
+            records.push(Record {
+                name,
+                rtype,
+                rdata,
+                rdata_at,
+            });
+            pos = rdata_at + len;
+        }
+        Some(Message {
+            raw,
+            id,
+            is_response,
+            questions,
+            records,
+        })
+    }
+}
@@ -0,0 +1,125 @@
+//! `--merge`: one MP4 per recording when Steam split it across several `fg_*` folders in
+//! the same `clip_<appid>_<date>_<time>/video/`.
+//!
+//! The first part (by recording time) stands for the whole recording in the run: it names
+//! the output, carries the catalog record and the `convert.toml`, and the later parts are
+//! converted and deleted with it. Parts are converted one by one to temporary files and
+//! joined with ffmpeg's concat demuxer, which copies the streams.
+
+use crate::order;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use steamclipconverter::{
+    ClipDir, Error, atomic,
+    ffmpeg::{RemuxOptions, file_arg},
+};
+
+/// Every part of `clip`'s recording in recording order, `clip` included; just `clip` when
+/// it isn't in a `clip_*` folder.
+fn parts(clip: &ClipDir) -> Vec<ClipDir> {
+    let Some(video) = clip.dir.parent().filter(|_| order::is_manual(clip)) else {
+        return vec![clip.clone()];
+    };
+    let mut parts: Vec<ClipDir> = fs::read_dir(video)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .filter_map(|p| ClipDir::from_path(&p))
+        .filter(|c| c.kind() == clip.kind())
+        .collect();
+    if !parts.iter().any(|c| c.dir == clip.dir) {
+        return vec![clip.clone()];
+    }
+    parts.sort_by(|a, b| (&a.date, &a.time, &a.dir).cmp(&(&b.date, &b.time, &b.dir)));
+    parts
+}
+
+/// Whether `clip` starts its recording (later parts are converted along with it).
+pub fn is_first_part(clip: &ClipDir) -> bool {
+    parts(clip).first().is_none_or(|c| c.dir == clip.dir)
+}
+
+/// The parts recorded after `clip` in its `clip_*` folder.
+pub fn later_parts(clip: &ClipDir) -> Vec<ClipDir> {
+    let mut parts = parts(clip);
+    match parts.iter().position(|c| c.dir == clip.dir) {
+        Some(i) => parts.split_off(i + 1),
+        None => Vec::new(),
+    }
+}
+
+/// Temporary output of part `i` (from 0): `Game-...part1.mp4` next to `out`, keeping the
+/// extension so ffmpeg picks the same container.
+pub fn part_path(out: &Path, i: usize) -> PathBuf {
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match out.extension() {
+        Some(ext) => format!("{stem}.part{}.{}", i + 1, ext.to_string_lossy()),
+        None => format!("{stem}.part{}", i + 1),
+    };
+    out.with_file_name(name)
+}
+
+/// Join `files` into `out` without re-encoding (through a `.partial` file, like a
+/// conversion), with the movflags and tags of `opts`. ffmpeg's messages are returned in the
+/// error.
+pub fn concat(files: &[PathBuf], out: &Path, opts: &RemuxOptions) -> Result<(), Error> {
+    let list_path = out.with_file_name(format!(
+        "{}.concat.txt",
+        out.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let mut list = String::new();
+    for f in files {
+        let abs = std::path::absolute(f).map_err(|source| Error::Io {
+            path: f.clone(),
+            source,
+        })?;
+        // The demuxer's quoting: `'` ends the string, so it is written as '\''.
+        let quoted = abs.to_string_lossy().replace('\'', r"'\''");
+        list.push_str(&format!("file '{quoted}'\n"));
+    }
+    fs::write(&list_path, list).map_err(|source| Error::Io {
+        path: list_path.clone(),
+        source,
+    })?;
+    let partial = atomic::partial_path(out);
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-hide_banner", "-y", "-nostats"])
+        .args(["-loglevel", opts.loglevel.as_deref().unwrap_or("error")])
+        .args(["-f", "concat", "-safe", "0", "-i"])
+        .arg(file_arg(&list_path))
+        .args(["-map", "0", "-c", "copy"]);
+    if let Some(title) = &opts.title {
+        cmd.args(["-metadata", &format!("title={title}")]);
+    }
+    for (key, value) in &opts.tags {
+        cmd.args(["-metadata", &format!("{key}={value}")]);
+    }
+    if let Some(flags) = opts.movflags() {
+        cmd.args(["-movflags", &flags]);
+    }
+    let result = cmd.arg(file_arg(&partial)).stdin(Stdio::null()).output();
+    let _ = fs::remove_file(&list_path);
+    let output = result.map_err(|source| Error::Spawn {
+        program: "ffmpeg".into(),
+        source,
+    })?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&partial);
+        return Err(Error::Ffmpeg {
+            exit: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    atomic::commit(&partial, out).map_err(|e| {
+        let _ = fs::remove_file(&partial);
+        Error::Io {
+            path: out.to_path_buf(),
+            source: e,
+        }
+    })
+}
@@ -0,0 +1,353 @@
+//! Reading `session.mpd`: which representations a clip has, and the init and media segment
+//! files of each, in order.
+//!
+//! Only what Steam writes is covered: one period, `SegmentTemplate` naming (by `$Number$`,
+//! or by `$Time$` with a `SegmentTimeline`), files relative to the clip folder.
+
+use crate::Error;
+use regex::Regex;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Video,
+    Audio,
+    Other,
+}
+
+/// One encoded stream and its files.
+#[derive(Clone, Debug)]
+pub struct Representation {
+    pub id: String,
+    pub kind: Kind,
+    pub init: PathBuf,
+    pub media: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Mpd {
+    /// `mediaPresentationDuration`, in seconds.
+    pub duration: Option<f64>,
+    pub representations: Vec<Representation>,
+}
+
+impl Mpd {
+    /// The first representation of `kind`.
+    pub fn first(&self, kind: Kind) -> Option<&Representation> {
+        self.representations.iter().find(|r| r.kind == kind)
+    }
+}
+
+/// Parse `<clip_dir>/session.mpd` and list the segment files it names. Media segments are
+/// only checked to exist when the MPD counts them (`SegmentTimeline`); otherwise they are
+/// numbered from `startNumber` until the next file is missing.
+pub fn read(clip_dir: &Path) -> Result<Mpd, Error> {
+    let path = clip_dir.join("session.mpd");
+    let text = fs::read_to_string(&path).map_err(|e| Error::io(&path, e))?;
+    let invalid = |reason: String| Error::InvalidClip {
+        path: path.clone(),
+        reason,
+    };
+    let root = parse_xml(&text).map_err(|e| invalid(format!("session.mpd: {e}")))?;
+    if root.name != "MPD" {
+        return Err(invalid(format!("session.mpd: root is <{}>", root.name)));
+    }
+    let mut mpd = Mpd {
+        duration: root
+            .attr("mediaPresentationDuration")
+            .and_then(iso_duration),
+        ..Mpd::default()
+    };
+    let Some(period) = root.child("Period") else {
+        return Ok(mpd);
+    };
+    for set in period.children_named("AdaptationSet") {
+        for rep in set.children_named("Representation") {
+            let id = rep.attr("id").unwrap_or_default().to_string();
+            let kind = [rep, set]
+                .iter()
+                .find_map(|e| e.attr("contentType").or(e.attr("mimeType")))
+                .map(|t| {
+                    if t.starts_with("video") {
+                        Kind::Video
+                    } else if t.starts_with("audio") {
+                        Kind::Audio
+                    } else {
+                        Kind::Other
+                    }
+                })
+                .unwrap_or(Kind::Other);
+            let Some(tpl) = rep
+                .child("SegmentTemplate")
+                .or_else(|| set.child("SegmentTemplate"))
+            else {
+                return Err(invalid(format!(
+                    "representation {id} has no SegmentTemplate"
+                )));
+            };
+            let bandwidth = rep.attr("bandwidth").unwrap_or("0");
+            let name = |n: u64, t: u64| {
+                expand_template(tpl.attr("media").unwrap_or(""), &id, n, t, bandwidth)
+            };
+            let init = clip_dir.join(expand_template(
+                tpl.attr("initialization").unwrap_or(""),
+                &id,
+                0,
+                0,
+                bandwidth,
+            ));
+            let start: u64 = tpl
+                .attr("startNumber")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(1);
+
+            let mut media = Vec::new();
+            if let Some(timeline) = tpl.child("SegmentTimeline") {
+                let mut t = 0u64;
+                let mut n = start;
+                for s in timeline.children_named("S") {
+                    let num = |k: &str| s.attr(k).and_then(|v| v.parse::<i64>().ok());
+                    if let Some(at) = num("t") {
+                        t = at.max(0) as u64;
+                    }
+                    let d = num("d").unwrap_or(0).max(0) as u64;
+                    for _ in 0..=num("r").unwrap_or(0).max(0) {
+                        let p = clip_dir.join(name(n, t));
+                        if !p.is_file() {
+                            return Err(invalid(format!("missing segment {}", p.display())));
+                        }
+                        media.push(p);
+                        n += 1;
+                        t += d;
+                    }
+                }
+            } else {
+                let mut n = start;
+                loop {
+                    let p = clip_dir.join(name(n, 0));
+                    if !p.is_file() {
+                        break;
+                    }
+                    media.push(p);
+                    n += 1;
+                }
+            }
+            mpd.representations.push(Representation {
+                id,
+                kind,
+                init,
+                media,
+            });
+        }
+    }
+    Ok(mpd)
+}
+
+/// `$RepresentationID$`, `$Number$` (or `$Number%05d$`), `$Time$`, `$Bandwidth$` and `$$`.
+fn expand_template(tpl: &str, id: &str, number: u64, time: u64, bandwidth: &str) -> String {
+    let mut out = String::new();
+    // Between `$` signs every other piece is an identifier.
+    for (i, piece) in tpl.split('$').enumerate() {
+        if i % 2 == 0 {
+            out.push_str(piece);
+            continue;
+        }
+        let (ident, width) = match piece.split_once('%') {
+            Some((ident, fmt)) => (
+                ident,
+                fmt.trim_start_matches('0')
+                    .trim_end_matches('d')
+                    .parse()
+                    .unwrap_or(0),
+            ),
+            None => (piece, 0),
+        };
+        match ident {
+            "" => out.push('$'),
+            "RepresentationID" => out.push_str(id),
+            "Number" => out.push_str(&format!("{number:0width$}")),
+            "Time" => out.push_str(&format!("{time:0width$}")),
+            "Bandwidth" => out.push_str(bandwidth),
+            other => {
+                out.push('$');
+                out.push_str(other);
+                out.push('$');
+            }
+        }
+    }
+    out
+}
+
+/// An ISO 8601 duration like `PT1M2.5S` or `P1DT2H`, in seconds.
+fn iso_duration(s: &str) -> Option<f64> {
+    let re = Regex::new(
+        r"^P(?:(\d+(?:\.\d+)?)D)?(?:T(?:(\d+(?:\.\d+)?)H)?(?:(\d+(?:\.\d+)?)M)?(?:(\d+(?:\.\d+)?)S)?)?$",
+    )
+    .unwrap();
+    let caps = re.captures(s.trim())?;
+    let part = |i: usize| {
+        caps.get(i)
+            .map_or(0.0, |m| m.as_str().parse().unwrap_or(0.0))
+    };
+    Some(part(1) * 86400.0 + part(2) * 3600.0 + part(3) * 60.0 + part(4))
+}
+
+/// An XML element with its attributes and child elements; text content is dropped, since
+/// an MPD keeps everything in attributes. Namespace prefixes are removed from names.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn parse_xml(text: &str) -> Result<Element, String> {
+    let attr_re = Regex::new(r#"([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    // Open elements, innermost last; the bottom one collects the document's root.
+    let mut stack = vec![Element::default()];
+    let mut rest = text;
+    while let Some(i) = rest.find('<') {
+        rest = &rest[i..];
+        let skip_to = |rest: &str, end: &str| {
+            rest.find(end).map(|j| j + end.len()).ok_or_else(|| {
+                // Quote the first 12 characters, not bytes: a cut may not split one.
+                let cut = rest.char_indices().nth(12).map_or(rest.len(), |(k, _)| k);
+                format!("unterminated '{}'", &rest[..cut])
+            })
+        };
+        if rest.starts_with("<!--") {
+            rest = &rest[skip_to(rest, "-->")?..];
+            continue;
+        }
+        if rest.starts_with("<?") {
+            rest = &rest[skip_to(rest, "?>")?..];
+            continue;
+        }
+        if rest.starts_with("<![CDATA[") {
+            rest = &rest[skip_to(rest, "]]>")?..];
+            continue;
+        }
+        if rest.starts_with("<!") {
+            rest = &rest[skip_to(rest, ">")?..];
+            continue;
+        }
+        let end = skip_to(rest, ">")?;
+        let tag = &rest[1..end - 1];
+        rest = &rest[end..];
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = local_name(name.trim());
+            let done = stack.pop().filter(|e| e.name == name && !stack.is_empty());
+            let Some(done) = done else {
+                return Err(format!("unexpected '</{name}>'"));
+            };
+            stack.last_mut().unwrap().children.push(done);
+            continue;
+        }
+        let (tag, closed) = match tag.strip_suffix('/') {
+            Some(t) => (t, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let element = Element {
+            name: local_name(&tag[..name_end]).to_string(),
+            attrs: attr_re
+                .captures_iter(&tag[name_end..])
+                .map(|c| {
+                    let v = c.get(2).or(c.get(3)).map_or("", |m| m.as_str());
+                    (local_name(&c[1]).to_string(), unescape(v))
+                })
+                .collect(),
+            children: Vec::new(),
+        };
+        if closed {
+            stack.last_mut().unwrap().children.push(element);
+        } else {
+            stack.push(element);
+        }
+    }
+    if stack.len() > 1 {
+        return Err(format!("<{}> is never closed", stack.last().unwrap().name));
+    }
+    stack
+        .pop()
+        .and_then(|doc| doc.children.into_iter().next())
+        .ok_or_else(|| "no root element".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unterminated_markup_quotes_whole_characters() {
+        // 12 bytes in would be the middle of a character.
+        let err = parse_xml("<MPD><!-- 録画の設定").unwrap_err();
+        assert_eq!(err, "unterminated '<!-- 録画の設定'");
+        let err = parse_xml("<MPD><Représentation id='0'").unwrap_err();
+        assert_eq!(err, "unterminated '<Représentat'");
+    }
+
+    #[test]
+    fn malformed_documents_are_errors() {
+        for (text, err) in [
+            ("", "no root element"),
+            ("<MPD><Period>", "<Period> is never closed"),
+            ("<MPD></Period></MPD>", "unexpected '</Period>'"),
+            ("</MPD>", "unexpected '</MPD>'"),
+            ("<MPD><?xml", "unterminated '<?xml'"),
+        ] {
+            assert_eq!(parse_xml(text).unwrap_err(), err, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn representation_without_template_is_invalid() {
+        let dir = std::env::temp_dir().join(format!("scc-mpd-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("session.mpd"),
+            "<MPD><Period><AdaptationSet contentType='video'>\
+             <Representation id='0'/></AdaptationSet></Period></MPD>",
+        )
+        .unwrap();
+        let err = read(&dir).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("representation 0 has no SegmentTemplate"),
+            "{err}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
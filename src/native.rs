@@ -0,0 +1,1158 @@
+//! The `native` backend: converting without ffmpeg. The fMP4 init and chunk segments that
+//! `session.mpd` names are parsed directly, and their samples are written into a plain
+//! (non-fragmented) MP4 with the index up front, like ffmpeg's `-movflags +faststart`.
+//!
+//! Stream copy only: the first video and the first audio representation, as ffmpeg maps
+//! them. Anything that needs a decoder (transcodes, trims, branding) stays with ffmpeg.
+
+use crate::{
+    Error,
+    ffmpeg::{AudioSelect, Container, RemuxOptions},
+    mpd::{self, Kind, Representation},
+    probe::StreamInfo,
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// Why `opts` can't be done natively, if it can't: anything the muxer would otherwise
+/// silently leave out of the output.
+pub fn unsupported(opts: &RemuxOptions) -> Option<&'static str> {
+    if opts.video_encoder.is_some() || opts.audio_encoder.is_some() {
+        Some("transcoding")
+    } else if !opts.video_args.is_empty()
+        || !opts.audio_args.is_empty()
+        || !opts.input_args.is_empty()
+    {
+        Some("encoder or input arguments")
+    } else if opts.is_branded() {
+        Some("an intro, outro or watermark")
+    } else if !opts.blur.is_empty() {
+        Some("blurring")
+    } else if opts.trim_start.is_some() || opts.trim_end.is_some() {
+        Some("trimming")
+    } else if opts.container != Container::Mp4 {
+        Some("a container other than MP4")
+    } else if opts.fps.is_some() || opts.width.is_some() {
+        Some("changing the frame rate or size")
+    } else if opts.previews.is_some() {
+        Some("thumbnails")
+    } else if opts.audio != AudioSelect::Track(0) {
+        Some("audio other than the game track")
+    } else if opts.movflags.is_some() {
+        Some("movflags (the index is always written up front)")
+    } else {
+        None
+    }
+}
+
+/// Remux the clip in `clip_dir` into `out_path`. `on_copy(done, total)` is called with the
+/// sample bytes written so far; returning false stops the conversion with
+/// `Error::Cancelled` and removes the partial output.
+pub fn remux(
+    clip_dir: &Path,
+    out_path: &Path,
+    opts: &RemuxOptions,
+    on_copy: &mut dyn FnMut(u64, u64) -> bool,
+) -> Result<(), Error> {
+    if let Some(what) = unsupported(opts) {
+        return Err(Error::Unsupported {
+            reason: format!("{what} needs the ffmpeg backend"),
+        });
+    }
+    let mut tracks = load_tracks(clip_dir)?;
+    if let (Some(audio), Some(label)) = (
+        tracks.iter_mut().find(|t| t.kind == Kind::Audio),
+        opts.audio_labels.first(),
+    ) {
+        audio.label = Some((label.title.clone(), label.language.clone()));
+    }
+    let mut tags: Vec<(&str, &str)> = opts.title.iter().map(|t| ("title", t.as_str())).collect();
+    tags.extend(opts.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+    let result = write_mp4(&tracks, out_path, &tags, on_copy);
+    if result.is_err() {
+        let _ = fs::remove_file(out_path);
+    }
+    result
+}
+
+/// Stream info read from the segments, for when ffprobe isn't installed.
+pub fn probe_streams(clip_dir: &Path) -> io::Result<Vec<StreamInfo>> {
+    let tracks = load_tracks(clip_dir).map_err(io::Error::other)?;
+    Ok(tracks
+        .iter()
+        .map(|t| StreamInfo {
+            codec_type: match t.kind {
+                Kind::Video => "video",
+                Kind::Audio => "audio",
+                Kind::Other => "data",
+            }
+            .into(),
+            codec_name: t.codec_name().into(),
+            pix_fmt: String::new(),
+            channels: t.channels(),
+            duration: Some(t.media_duration() as f64 / t.timescale as f64).filter(|d| *d > 0.0),
+        })
+        .collect())
+}
+
+/// Duration in seconds of a `session.mpd` (as it declares) or an MP4 file (from its
+/// `mvhd`), for when ffprobe isn't installed.
+pub fn duration(path: &Path) -> io::Result<f64> {
+    if path.extension().is_some_and(|e| e == "mpd") {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        return mpd::read(dir)
+            .map_err(io::Error::other)?
+            .duration
+            .ok_or_else(|| io::Error::other("session.mpd declares no duration"));
+    }
+    let mut f = File::open(path)?;
+    let len = f.metadata()?.len();
+    let moov = read_top_level(&mut f, len)?
+        .into_iter()
+        .find(|(kind, _, _)| kind == b"moov")
+        .ok_or_else(|| io::Error::other("no moov box"))?;
+    let header = header_len(&mut f, moov.1)?;
+    let body = read_at(&mut f, moov.1 + header, moov.2 - header)?;
+    let mvhd = find(&body, b"mvhd").ok_or_else(|| io::Error::other("no mvhd box"))?;
+    let (timescale, duration) = match mvhd.first() {
+        Some(1) => (be32(mvhd, 20), be64(mvhd, 24)),
+        _ => (be32(mvhd, 12), be32(mvhd, 16) as u64),
+    };
+    if timescale == 0 || duration == 0 {
+        return Err(io::Error::other("no duration in mvhd"));
+    }
+    Ok(duration as f64 / timescale as f64)
+}
+
+/// One sample of a track, where it sits in its chunk file.
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    offset: u64,
+    size: u32,
+    duration: u32,
+    cto: i64,
+    sync: bool,
+}
+
+/// The samples of one `moof`, which become one chunk of the output.
+#[derive(Debug)]
+struct Fragment {
+    file: usize,
+    /// Decode time of the first sample, in the track's timescale.
+    start: u64,
+    samples: Vec<Sample>,
+}
+
+/// `trex` defaults for samples whose `tfhd`/`trun` give no value.
+#[derive(Clone, Copy, Debug, Default)]
+struct Defaults {
+    duration: u32,
+    size: u32,
+    flags: u32,
+}
+
+#[derive(Debug)]
+struct Track {
+    kind: Kind,
+    timescale: u32,
+    movie_timescale: u32,
+    /// The init segment's `trak` box body.
+    trak: Vec<u8>,
+    files: Vec<PathBuf>,
+    fragments: Vec<Fragment>,
+    /// Title and language from `--label-audio`.
+    label: Option<(String, String)>,
+}
+
+impl Track {
+    fn samples(&self) -> impl Iterator<Item = &Sample> {
+        self.fragments.iter().flat_map(|f| f.samples.iter())
+    }
+
+    fn media_duration(&self) -> u64 {
+        self.samples().map(|s| s.duration as u64).sum()
+    }
+
+    /// The first sample entry of `stsd`: its four-character code and body.
+    fn sample_entry(&self) -> Option<Atom<'_>> {
+        let stsd = find_path(&self.trak, &[b"mdia", b"minf", b"stbl", b"stsd"])?;
+        children(stsd.get(8..)?).ok()?.into_iter().next()
+    }
+
+    fn codec_name(&self) -> &'static str {
+        match self.sample_entry().map(|(k, _)| k).as_ref() {
+            Some(b"avc1" | b"avc3") => "h264",
+            Some(b"hvc1" | b"hev1") => "hevc",
+            Some(b"av01") => "av1",
+            Some(b"vp09") => "vp9",
+            Some(b"mp4a") => "aac",
+            Some(b"Opus") => "opus",
+            Some(b"fLaC") => "flac",
+            Some(b"ac-3") => "ac3",
+            Some(b"ec-3") => "eac3",
+            _ => "",
+        }
+    }
+
+    fn channels(&self) -> u32 {
+        match (self.kind, self.sample_entry()) {
+            // AudioSampleEntry: 6 reserved, data_reference_index, 8 reserved, channelcount.
+            (Kind::Audio, Some((_, body))) if body.len() >= 18 => be16(body, 16) as u32,
+            _ => 0,
+        }
+    }
+}
+
+/// Read the MPD, the init segments and every `moof` of the first video and audio
+/// representations.
+fn load_tracks(clip_dir: &Path) -> Result<Vec<Track>, Error> {
+    let mpd = mpd::read(clip_dir)?;
+    let mut tracks = Vec::new();
+    for kind in [Kind::Video, Kind::Audio] {
+        if let Some(rep) = mpd.first(kind) {
+            tracks.push(load_track(rep)?);
+        }
+    }
+    if !tracks.iter().any(|t| t.kind == Kind::Video) {
+        return Err(Error::InvalidClip {
+            path: clip_dir.to_path_buf(),
+            reason: "session.mpd has no video representation".into(),
+        });
+    }
+    Ok(tracks)
+}
+
+fn load_track(rep: &Representation) -> Result<Track, Error> {
+    let invalid = |path: &Path, reason: &str| Error::InvalidClip {
+        path: path.to_path_buf(),
+        reason: reason.into(),
+    };
+    let init = fs::read(&rep.init).map_err(|e| Error::io(&rep.init, e))?;
+    let top = children(&init).map_err(|e| invalid(&rep.init, &e))?;
+    let moov = top
+        .iter()
+        .find(|(k, _)| k == b"moov")
+        .ok_or_else(|| invalid(&rep.init, "init segment has no moov box"))?
+        .1;
+    let mvhd = find(moov, b"mvhd").ok_or_else(|| invalid(&rep.init, "no mvhd box"))?;
+    let movie_timescale = match mvhd.first() {
+        Some(1) => be32(mvhd, 20),
+        _ => be32(mvhd, 12),
+    };
+    let trak = find(moov, b"trak").ok_or_else(|| invalid(&rep.init, "no trak box"))?;
+    let mdhd =
+        find_path(trak, &[b"mdia", b"mdhd"]).ok_or_else(|| invalid(&rep.init, "no mdhd box"))?;
+    let timescale = match mdhd.first() {
+        Some(1) => be32(mdhd, 20),
+        _ => be32(mdhd, 12),
+    };
+    if timescale == 0 {
+        return Err(invalid(&rep.init, "track timescale is 0"));
+    }
+    let trex = find_path(moov, &[b"mvex", b"trex"]);
+    let defaults = trex
+        .filter(|t| t.len() >= 24)
+        .map(|t| Defaults {
+            duration: be32(t, 12),
+            size: be32(t, 16),
+            flags: be32(t, 20),
+        })
+        .unwrap_or_default();
+
+    let mut track = Track {
+        kind: rep.kind,
+        timescale,
+        movie_timescale: if movie_timescale == 0 {
+            1000
+        } else {
+            movie_timescale
+        },
+        trak: trak.to_vec(),
+        files: rep.media.clone(),
+        fragments: Vec::new(),
+        label: None,
+    };
+    let mut next_start = 0;
+    for (file, path) in rep.media.iter().enumerate() {
+        let mut f = File::open(path).map_err(|e| Error::io(path, e))?;
+        let len = f.metadata().map_err(|e| Error::io(path, e))?.len();
+        let boxes = read_top_level(&mut f, len).map_err(|e| invalid(path, &e.to_string()))?;
+        for (i, (kind, offset, size)) in boxes.iter().enumerate() {
+            if kind != b"moof" {
+                continue;
+            }
+            let header = header_len(&mut f, *offset).map_err(|e| Error::io(path, e))?;
+            let body =
+                read_at(&mut f, offset + header, *size - header).map_err(|e| Error::io(path, e))?;
+            // Samples without a trun data_offset start at the following mdat's payload.
+            let mdat_payload = match boxes.get(i + 1) {
+                Some(&([b'm', b'd', b'a', b't'], o, _)) => {
+                    Some(o + header_len(&mut f, o).map_err(|e| Error::io(path, e))?)
+                }
+                _ => None,
+            };
+            let frag = parse_moof(&body, *offset, mdat_payload, defaults, file, next_start)
+                .map_err(|e| invalid(path, &e))?;
+            if let Some(frag) = frag {
+                next_start =
+                    frag.start + frag.samples.iter().map(|s| s.duration as u64).sum::<u64>();
+                track.fragments.push(frag);
+            }
+        }
+    }
+    Ok(track)
+}
+
+/// The samples of a `moof` at file offset `moof_offset`, or None when it has none.
+fn parse_moof(
+    moof: &[u8],
+    moof_offset: u64,
+    mdat_payload: Option<u64>,
+    trex: Defaults,
+    file: usize,
+    next_start: u64,
+) -> Result<Option<Fragment>, String> {
+    let Some(traf) = find(moof, b"traf") else {
+        return Ok(None);
+    };
+    let tfhd = find(traf, b"tfhd").ok_or("traf without tfhd")?;
+    let flags = be24(tfhd, 1);
+    let mut at = 8;
+    let mut field = |flag: u32, len: usize| {
+        (flags & flag != 0).then(|| {
+            let v = if len == 8 {
+                be64(tfhd, at)
+            } else {
+                be32(tfhd, at) as u64
+            };
+            at += len;
+            v
+        })
+    };
+    let base = field(0x1, 8).unwrap_or(moof_offset);
+    field(0x2, 4); // sample_description_index
+    let d = Defaults {
+        duration: field(0x8, 4).map_or(trex.duration, |v| v as u32),
+        size: field(0x10, 4).map_or(trex.size, |v| v as u32),
+        flags: field(0x20, 4).map_or(trex.flags, |v| v as u32),
+    };
+    if tfhd.len() < at {
+        return Err("tfhd is truncated".into());
+    }
+    let start = match find(traf, b"tfdt") {
+        Some(t) if t.first() == Some(&1) => be64(t, 4),
+        Some(t) => be32(t, 4) as u64,
+        None => next_start,
+    };
+
+    let mut samples = Vec::new();
+    let mut data_end = mdat_payload;
+    for (kind, trun) in children(traf)? {
+        if &kind != b"trun" {
+            continue;
+        }
+        let version = trun.first().copied().unwrap_or(0);
+        let flags = be24(trun, 1);
+        let count = be32(trun, 4) as usize;
+        let mut at = 8;
+        let mut offset = if flags & 0x1 != 0 {
+            at += 4;
+            base.checked_add_signed(be32(trun, 8) as i32 as i64)
+                .ok_or("trun data_offset out of range")?
+        } else {
+            data_end.ok_or("trun without data_offset and no mdat")?
+        };
+        let first_flags = (flags & 0x4 != 0).then(|| {
+            at += 4;
+            be32(trun, at - 4)
+        });
+        let per_sample = [0x100, 0x200, 0x400, 0x800]
+            .iter()
+            .filter(|f| flags & **f != 0)
+            .count();
+        if trun.len() < at + count * per_sample * 4 {
+            return Err("trun is truncated".into());
+        }
+        for i in 0..count {
+            let mut next = |flag: u32| {
+                (flags & flag != 0).then(|| {
+                    at += 4;
+                    be32(trun, at - 4)
+                })
+            };
+            let duration = next(0x100).unwrap_or(d.duration);
+            let size = next(0x200).unwrap_or(d.size);
+            let sample_flags = next(0x400)
+                .or(if i == 0 { first_flags } else { None })
+                .unwrap_or(d.flags);
+            let cto = next(0x800).map_or(0, |v| {
+                if version == 0 {
+                    v as i64
+                } else {
+                    v as i32 as i64
+                }
+            });
+            samples.push(Sample {
+                offset,
+                size,
+                duration,
+                cto,
+                // sample_is_non_sync_sample
+                sync: sample_flags & 0x10000 == 0,
+            });
+            offset += size as u64;
+        }
+        data_end = Some(offset);
+    }
+    Ok((!samples.is_empty()).then_some(Fragment {
+        file,
+        start,
+        samples,
+    }))
+}
+
+/// Lay the fragments of all tracks out in decode order and write the MP4.
+fn write_mp4(
+    tracks: &[Track],
+    out_path: &Path,
+    tags: &[(&str, &str)],
+    on_copy: &mut dyn FnMut(u64, u64) -> bool,
+) -> Result<(), Error> {
+    // Output chunks: (track, fragment), ordered by start time across tracks.
+    let mut chunks: Vec<(usize, usize)> = tracks
+        .iter()
+        .enumerate()
+        .flat_map(|(t, track)| (0..track.fragments.len()).map(move |f| (t, f)))
+        .collect();
+    let secs =
+        |&(t, f): &(usize, usize)| tracks[t].fragments[f].start as f64 / tracks[t].timescale as f64;
+    chunks.sort_by(|a, b| secs(a).total_cmp(&secs(b)).then(a.0.cmp(&b.0)));
+    let payload: u64 = tracks
+        .iter()
+        .flat_map(Track::samples)
+        .map(|s| s.size as u64)
+        .sum();
+
+    let ftyp = ftyp(tracks);
+    let mdat_header: u64 = if payload + 8 > u32::MAX as u64 { 16 } else { 8 };
+    // The moov size doesn't depend on the offsets, only on whether they need 64 bits.
+    let probe = moov(tracks, &chunks, 0, false, tags);
+    let wide = ftyp.len() as u64 + probe.len() as u64 + mdat_header + payload > u32::MAX as u64;
+    let probe_len = if wide {
+        moov(tracks, &chunks, 0, true, tags).len()
+    } else {
+        probe.len()
+    };
+    let data_start = ftyp.len() as u64 + probe_len as u64 + mdat_header;
+    let moov = moov(tracks, &chunks, data_start, wide, tags);
+
+    let io_err = |e: io::Error| Error::io(out_path, e);
+    let mut out = BufWriter::new(File::create(out_path).map_err(io_err)?);
+    out.write_all(&ftyp).map_err(io_err)?;
+    out.write_all(&moov).map_err(io_err)?;
+    if mdat_header == 16 {
+        out.write_all(&1u32.to_be_bytes()).map_err(io_err)?;
+        out.write_all(b"mdat").map_err(io_err)?;
+        out.write_all(&(payload + 16).to_be_bytes())
+            .map_err(io_err)?;
+    } else {
+        out.write_all(&((payload + 8) as u32).to_be_bytes())
+            .map_err(io_err)?;
+        out.write_all(b"mdat").map_err(io_err)?;
+    }
+
+    let mut done = 0;
+    let mut buf = Vec::new();
+    for (t, f) in chunks {
+        let track = &tracks[t];
+        let frag = &track.fragments[f];
+        let path = &track.files[frag.file];
+        let mut src = File::open(path).map_err(|e| Error::io(path, e))?;
+        // Samples of a fragment are normally contiguous; copy each unbroken run at once.
+        let mut i = 0;
+        while i < frag.samples.len() {
+            let start = frag.samples[i].offset;
+            let mut end = start;
+            while i < frag.samples.len() && frag.samples[i].offset == end {
+                end += frag.samples[i].size as u64;
+                i += 1;
+            }
+            buf.resize((end - start) as usize, 0);
+            src.seek(SeekFrom::Start(start))
+                .and_then(|_| src.read_exact(&mut buf))
+                .map_err(|e| Error::io(path, e))?;
+            out.write_all(&buf).map_err(io_err)?;
+            done += end - start;
+        }
+        if !on_copy(done, payload) {
+            return Err(Error::Cancelled);
+        }
+    }
+    out.flush().map_err(io_err)?;
+    Ok(())
+}
+
+fn ftyp(tracks: &[Track]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(b"isom");
+    body.extend_from_slice(&0x200u32.to_be_bytes());
+    body.extend_from_slice(b"isomiso2");
+    if tracks.iter().any(|t| t.codec_name() == "h264") {
+        body.extend_from_slice(b"avc1");
+    }
+    body.extend_from_slice(b"mp41");
+    atom(b"ftyp", &body)
+}
+
+/// The `moov` for `chunks` laid out from file offset `data_start`.
+fn moov(
+    tracks: &[Track],
+    chunks: &[(usize, usize)],
+    data_start: u64,
+    wide: bool,
+    tags: &[(&str, &str)],
+) -> Vec<u8> {
+    // Chunk offsets per track, in output order.
+    let mut offsets: Vec<Vec<u64>> = vec![Vec::new(); tracks.len()];
+    let mut at = data_start;
+    for &(t, f) in chunks {
+        offsets[t].push(at);
+        at += tracks[t].fragments[f]
+            .samples
+            .iter()
+            .map(|s| s.size as u64)
+            .sum::<u64>();
+    }
+
+    let movie_timescale = tracks[0].movie_timescale;
+    let to_movie = |v: u64, track: &Track| {
+        (v as u128 * movie_timescale as u128 / track.timescale as u128) as u64
+    };
+    // Tracks that start later than the earliest one are delayed by an empty edit.
+    let start_secs = |t: &Track| {
+        t.fragments
+            .first()
+            .map_or(0.0, |f| f.start as f64 / t.timescale as f64)
+    };
+    let earliest = tracks.iter().map(start_secs).fold(f64::INFINITY, f64::min);
+
+    let mut body = Vec::new();
+    let mut movie_duration = 0;
+    let mut traks = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        let delay = ((start_secs(track) - earliest) * movie_timescale as f64).round() as u64;
+        let duration = to_movie(track.media_duration(), track);
+        movie_duration = movie_duration.max(delay + duration);
+        traks.extend(trak(
+            track,
+            i as u32 + 1,
+            &offsets[i],
+            wide,
+            delay,
+            duration,
+        ));
+    }
+    let created = tags
+        .iter()
+        .find(|(k, _)| *k == "creation_time")
+        .and_then(|(_, v)| mac_time(v));
+    body.extend(mvhd(
+        tracks,
+        movie_duration,
+        tracks.len() as u32 + 1,
+        created,
+    ));
+    body.extend(traks);
+    if !tags.is_empty() {
+        body.extend(udta_tags(tags));
+    }
+    atom(b"moov", &body)
+}
+
+fn mvhd(tracks: &[Track], duration: u64, next_track_id: u32, created: Option<u32>) -> Vec<u8> {
+    let mut b = Vec::new();
+    // Creation and modification time.
+    let created = created.unwrap_or(0).to_be_bytes();
+    b.extend_from_slice(&created);
+    b.extend_from_slice(&created);
+    b.extend_from_slice(&tracks[0].movie_timescale.to_be_bytes());
+    b.extend_from_slice(&clamp32(duration).to_be_bytes());
+    b.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+    b.extend_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+    b.extend_from_slice(&[0; 10]);
+    b.extend_from_slice(&UNITY_MATRIX);
+    b.extend_from_slice(&[0; 24]); // pre_defined
+    b.extend_from_slice(&next_track_id.to_be_bytes());
+    full(b"mvhd", 0, 0, &b)
+}
+
+const UNITY_MATRIX: [u8; 36] = [
+    0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0,
+];
+
+/// The init segment's `trak` with a new id, durations, edit list and sample tables.
+fn trak(track: &Track, id: u32, offsets: &[u64], wide: bool, delay: u64, duration: u64) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (kind, b) in children(&track.trak).unwrap_or_default() {
+        match &kind {
+            b"tkhd" => {
+                let mut b = b.to_vec();
+                b[1..4].copy_from_slice(&[0, 0, 3]); // enabled, in movie
+                let version = b[0];
+                let (id_at, dur_at) = if version == 1 { (20, 28) } else { (12, 20) };
+                put32(&mut b, id_at, id);
+                put_duration(&mut b, version, dur_at, duration);
+                body.extend(atom(b"tkhd", &b));
+                body.extend(edts(track, delay, duration));
+            }
+            b"mdia" => body.extend(mdia(track, b, offsets, wide)),
+            b"edts" => {} // rebuilt after tkhd
+            _ => body.extend(atom(&kind, b)),
+        }
+    }
+    atom(b"trak", &body)
+}
+
+/// An edit list when the track starts late or its first sample has a composition offset;
+/// None is needed for a track that plays from its first sample at time 0.
+fn edts(track: &Track, delay: u64, duration: u64) -> Vec<u8> {
+    let media_time = track.samples().next().map_or(0, |s| s.cto.max(0) as u64);
+    if delay == 0 && media_time == 0 {
+        return Vec::new();
+    }
+    let mut entries: Vec<(u64, i64)> = Vec::new();
+    if delay > 0 {
+        entries.push((delay, -1));
+    }
+    entries.push((duration, media_time as i64));
+    let v1 = entries.iter().any(|(d, _)| *d > u32::MAX as u64);
+    let mut b = Vec::new();
+    b.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for (d, t) in entries {
+        if v1 {
+            b.extend_from_slice(&d.to_be_bytes());
+            b.extend_from_slice(&t.to_be_bytes());
+        } else {
+            b.extend_from_slice(&(d as u32).to_be_bytes());
+            b.extend_from_slice(&(t as i32).to_be_bytes());
+        }
+        b.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+    }
+    atom(b"edts", &full(b"elst", v1 as u8, 0, &b))
+}
+
+fn mdia(track: &Track, mdia: &[u8], offsets: &[u64], wide: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (kind, b) in children(mdia).unwrap_or_default() {
+        match &kind {
+            b"mdhd" => {
+                let mut b = b.to_vec();
+                let version = b[0];
+                let (dur_at, lang_at) = if version == 1 { (24, 32) } else { (16, 20) };
+                put_duration(&mut b, version, dur_at, track.media_duration());
+                if let Some(code) = track.label.as_ref().and_then(|(_, l)| language_code(l))
+                    && b.len() >= lang_at + 2
+                {
+                    b[lang_at..lang_at + 2].copy_from_slice(&code.to_be_bytes());
+                }
+                body.extend(atom(b"mdhd", &b));
+            }
+            b"hdlr" => match &track.label {
+                // pre_defined, handler_type, 3 reserved words, then the name.
+                Some((title, _)) if b.len() >= 24 => {
+                    let mut b = b[..24].to_vec();
+                    b.extend_from_slice(title.as_bytes());
+                    b.push(0);
+                    body.extend(atom(b"hdlr", &b));
+                }
+                _ => body.extend(atom(b"hdlr", b)),
+            },
+            b"minf" => {
+                let mut minf = Vec::new();
+                for (kind, b) in children(b).unwrap_or_default() {
+                    if &kind == b"stbl" {
+                        minf.extend(stbl(track, b, offsets, wide));
+                    } else {
+                        minf.extend(atom(&kind, b));
+                    }
+                }
+                body.extend(atom(b"minf", &minf));
+            }
+            _ => body.extend(atom(&kind, b)),
+        }
+    }
+    atom(b"mdia", &body)
+}
+
+/// Sample tables for every sample of the track, keeping the init segment's `stsd`.
+fn stbl(track: &Track, stbl: &[u8], offsets: &[u64], wide: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    if let Some(stsd) = find(stbl, b"stsd") {
+        body.extend(atom(b"stsd", stsd));
+    }
+    let samples: Vec<&Sample> = track.samples().collect();
+
+    // stts: runs of equal durations.
+    let durations = runs(samples.iter().map(|s| s.duration as i64));
+    body.extend(table(b"stts", 0, &durations, |b, &(n, d)| {
+        b.extend_from_slice(&n.to_be_bytes());
+        b.extend_from_slice(&(d as u32).to_be_bytes());
+    }));
+    // ctts: runs of composition offsets, when there are any.
+    if samples.iter().any(|s| s.cto != 0) {
+        let ctos = runs(samples.iter().map(|s| s.cto));
+        let signed = ctos.iter().any(|(_, c)| *c < 0);
+        body.extend(table(b"ctts", signed as u8, &ctos, |b, &(n, c)| {
+            b.extend_from_slice(&n.to_be_bytes());
+            b.extend_from_slice(&(c as i32).to_be_bytes());
+        }));
+    }
+    // stss: only when not every sample is a sync sample.
+    if samples.iter().any(|s| !s.sync) {
+        let sync: Vec<u32> = (1..)
+            .zip(&samples)
+            .filter(|(_, s)| s.sync)
+            .map(|(i, _)| i)
+            .collect();
+        body.extend(table(b"stss", 0, &sync, |b, i| {
+            b.extend_from_slice(&i.to_be_bytes())
+        }));
+    }
+    // stsc: one chunk per fragment; an entry wherever the samples per chunk change.
+    let mut stsc: Vec<(u32, u32)> = Vec::new();
+    for (i, f) in track.fragments.iter().enumerate() {
+        let n = f.samples.len() as u32;
+        if stsc.last().is_none_or(|&(_, last)| last != n) {
+            stsc.push((i as u32 + 1, n));
+        }
+    }
+    body.extend(table(b"stsc", 0, &stsc, |b, &(first, n)| {
+        b.extend_from_slice(&first.to_be_bytes());
+        b.extend_from_slice(&n.to_be_bytes());
+        b.extend_from_slice(&1u32.to_be_bytes()); // sample_description_index
+    }));
+    // stsz: sample_size 0 (sizes vary), then every size.
+    let mut stsz = Vec::new();
+    stsz.extend_from_slice(&0u32.to_be_bytes());
+    stsz.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+    for s in &samples {
+        stsz.extend_from_slice(&s.size.to_be_bytes());
+    }
+    body.extend(full(b"stsz", 0, 0, &stsz));
+    if wide {
+        body.extend(table(b"co64", 0, offsets, |b, o| {
+            b.extend_from_slice(&o.to_be_bytes())
+        }));
+    } else {
+        body.extend(table(b"stco", 0, offsets, |b, o| {
+            b.extend_from_slice(&(*o as u32).to_be_bytes())
+        }));
+    }
+    atom(b"stbl", &body)
+}
+
+/// `udta/meta/ilst`: the container tags as iTunes-style metadata. `title` and `date` are
+/// `©nam` and `©day`, `creation_time` is in `mvhd`, and any other key a freeform `----`
+/// item (which ffmpeg reads back under that key).
+fn udta_tags(tags: &[(&str, &str)]) -> Vec<u8> {
+    let data = |value: &str| {
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u32.to_be_bytes()); // UTF-8
+        data.extend_from_slice(&0u32.to_be_bytes()); // locale
+        data.extend_from_slice(value.as_bytes());
+        atom(b"data", &data)
+    };
+    let mut items = Vec::new();
+    for &(key, value) in tags {
+        match key {
+            "title" => items.extend(atom(b"\xa9nam", &data(value))),
+            "date" => items.extend(atom(b"\xa9day", &data(value))),
+            "creation_time" => {}
+            _ => {
+                let mut item = full(b"mean", 0, 0, b"com.apple.iTunes");
+                item.extend(full(b"name", 0, 0, key.as_bytes()));
+                item.extend(data(value));
+                items.extend(atom(b"----", &item));
+            }
+        }
+    }
+    let ilst = atom(b"ilst", &items);
+    let mut hdlr = vec![0; 4];
+    hdlr.extend_from_slice(b"mdirappl");
+    hdlr.extend_from_slice(&[0; 9]);
+    let mut meta = full(b"hdlr", 0, 0, &hdlr);
+    meta.extend(ilst);
+    atom(b"udta", &full(b"meta", 0, 0, &meta))
+}
+
+/// `YYYY-MM-DDTHH:MM:SS[Z]` (UTC) as seconds since 1904, the MP4 epoch.
+fn mac_time(iso: &str) -> Option<u32> {
+    let num = |r: std::ops::Range<usize>| iso.get(r)?.parse::<i64>().ok();
+    let (y, m, d) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hh, mm, ss) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    // Days from 1970-01-01 of the civil date (Howard Hinnant's algorithm).
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    // 1904-01-01 is 24107 days before 1970-01-01.
+    let secs = (days + 24_107) * 86_400 + hh * 3600 + mm * 60 + ss;
+    u32::try_from(secs).ok()
+}
+
+/// ISO 639-2 code packed as in `mdhd`: three 5-bit letters.
+fn language_code(lang: &str) -> Option<u16> {
+    let b = lang.as_bytes();
+    if b.len() != 3 || !b.iter().all(u8::is_ascii_lowercase) {
+        return None;
+    }
+    Some(b.iter().fold(0u16, |acc, c| (acc << 5) | (c - 0x60) as u16))
+}
+
+/// Run-length encoding as (count, value).
+fn runs(values: impl Iterator<Item = i64>) -> Vec<(u32, i64)> {
+    let mut out: Vec<(u32, i64)> = Vec::new();
+    for v in values {
+        match out.last_mut() {
+            Some((n, last)) if *last == v => *n += 1,
+            _ => out.push((1, v)),
+        }
+    }
+    out
+}
+
+/// A full box holding an entry count followed by `entries`.
+fn table<T>(kind: &[u8; 4], version: u8, entries: &[T], put: impl Fn(&mut Vec<u8>, &T)) -> Vec<u8> {
+    let mut b = Vec::new();
+    b.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for e in entries {
+        put(&mut b, e);
+    }
+    full(kind, version, 0, &b)
+}
+
+fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + 8);
+    out.extend_from_slice(&((body.len() + 8) as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+    out
+}
+
+fn full(kind: &[u8; 4], version: u8, flags: u32, body: &[u8]) -> Vec<u8> {
+    let mut b = Vec::with_capacity(body.len() + 4);
+    b.push(version);
+    b.extend_from_slice(&flags.to_be_bytes()[1..]);
+    b.extend_from_slice(body);
+    atom(kind, &b)
+}
+
+/// A box as (type, body).
+type Atom<'a> = ([u8; 4], &'a [u8]);
+
+/// The boxes in `data`.
+fn children(data: &[u8]) -> Result<Vec<Atom<'_>>, String> {
+    let mut out = Vec::new();
+    let mut at = 0;
+    while at + 8 <= data.len() {
+        let size = be32(data, at) as usize;
+        let kind: [u8; 4] = data[at + 4..at + 8].try_into().unwrap();
+        let (header, size) = match size {
+            0 => (8, data.len() - at),
+            1 if at + 16 <= data.len() => (
+                16,
+                usize::try_from(be64(data, at + 8)).unwrap_or(usize::MAX),
+            ),
+            _ => (8, size),
+        };
+        // Sizes come from the file: a huge one mustn't wrap around past the bounds check.
+        if size < header || at.checked_add(size).is_none_or(|end| end > data.len()) {
+            return Err(format!(
+                "'{}' box runs past its parent",
+                String::from_utf8_lossy(&kind)
+            ));
+        }
+        out.push((kind, &data[at + header..at + size]));
+        at += size;
+    }
+    Ok(out)
+}
+
+fn find<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    children(data)
+        .ok()?
+        .into_iter()
+        .find(|(k, _)| k == kind)
+        .map(|(_, b)| b)
+}
+
+fn find_path<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    path.iter().try_fold(data, |d, kind| find(d, kind))
+}
+
+/// Top-level boxes of a file as (type, offset, size), without reading their bodies.
+fn read_top_level(f: &mut File, len: u64) -> io::Result<Vec<([u8; 4], u64, u64)>> {
+    let mut out = Vec::new();
+    let mut at = 0;
+    while at + 8 <= len {
+        let mut h = [0u8; 16];
+        let n = if at + 16 <= len { 16 } else { 8 };
+        f.seek(SeekFrom::Start(at))?;
+        f.read_exact(&mut h[..n])?;
+        let kind: [u8; 4] = h[4..8].try_into().unwrap();
+        let (header, size) = match be32(&h, 0) {
+            0 => (8, len - at),
+            1 if n == 16 => (16, be64(&h, 8)),
+            s => (8, s as u64),
+        };
+        if size < header || at.checked_add(size).is_none_or(|end| end > len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "'{}' box at {} runs past the end of the file",
+                    String::from_utf8_lossy(&kind),
+                    at
+                ),
+            ));
+        }
+        out.push((kind, at, size));
+        at += size;
+    }
+    Ok(out)
+}
+
+fn header_len(f: &mut File, offset: u64) -> io::Result<u64> {
+    let mut h = [0u8; 4];
+    f.seek(SeekFrom::Start(offset))?;
+    f.read_exact(&mut h)?;
+    Ok(if u32::from_be_bytes(h) == 1 { 16 } else { 8 })
+}
+
+fn read_at(f: &mut File, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; len as usize];
+    f.seek(SeekFrom::Start(offset))?;
+    f.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Big-endian reads that yield 0 past the end, so truncated boxes don't panic.
+fn be16(b: &[u8], at: usize) -> u16 {
+    b.get(at..at + 2)
+        .map_or(0, |s| u16::from_be_bytes(s.try_into().unwrap()))
+}
+
+fn be24(b: &[u8], at: usize) -> u32 {
+    b.get(at..at + 3)
+        .map_or(0, |s| u32::from_be_bytes([0, s[0], s[1], s[2]]))
+}
+
+fn be32(b: &[u8], at: usize) -> u32 {
+    b.get(at..at + 4)
+        .map_or(0, |s| u32::from_be_bytes(s.try_into().unwrap()))
+}
+
+fn be64(b: &[u8], at: usize) -> u64 {
+    b.get(at..at + 8)
+        .map_or(0, |s| u64::from_be_bytes(s.try_into().unwrap()))
+}
+
+fn put32(b: &mut [u8], at: usize, v: u32) {
+    if let Some(s) = b.get_mut(at..at + 4) {
+        s.copy_from_slice(&v.to_be_bytes());
+    }
+}
+
+/// Write a duration field of a version 0 (32-bit) or 1 (64-bit) box.
+fn put_duration(b: &mut [u8], version: u8, at: usize, v: u64) {
+    if version == 1 {
+        if let Some(s) = b.get_mut(at..at + 8) {
+            s.copy_from_slice(&v.to_be_bytes());
+        }
+    } else {
+        put32(b, at, clamp32(v));
+    }
+}
+
+fn clamp32(v: u64) -> u32 {
+    v.min(u32::MAX as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh scratch directory under the system temp dir.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scc-native-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// An fMP4 init segment of one h264 track at 1000 ticks a second, samples 100 ticks long.
+    fn init_segment() -> Vec<u8> {
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend_from_slice(&[0; 13]);
+        let stsd = [words(&[1]), atom(b"avc1", &[0; 78])].concat();
+        let stbl = atom(b"stbl", &full(b"stsd", 0, 0, &stsd));
+        let mdia = [
+            full(
+                b"mdhd",
+                0,
+                0,
+                &[words(&[0, 0, 1000, 0]), vec![0; 4]].concat(),
+            ),
+            full(b"hdlr", 0, 0, &hdlr),
+            atom(b"minf", &stbl),
+        ]
+        .concat();
+        let trak = [full(b"tkhd", 0, 0, &[0; 80]), atom(b"mdia", &mdia)].concat();
+        let mvex = atom(b"mvex", &full(b"trex", 0, 0, &words(&[1, 1, 100, 0, 0])));
+        let moov = [
+            full(
+                b"mvhd",
+                0,
+                0,
+                &[words(&[0, 0, 1000, 0]), vec![0; 80]].concat(),
+            ),
+            atom(b"trak", &trak),
+            mvex,
+        ]
+        .concat();
+        [atom(b"ftyp", b"iso6\0\0\0\0iso6"), atom(b"moov", &moov)].concat()
+    }
+
+    /// A media segment starting at `start` with one sample per entry of `payloads`.
+    fn media_segment(start: u64, payloads: &[&[u8]]) -> Vec<u8> {
+        let sizes: Vec<u32> = payloads.iter().map(|p| p.len() as u32).collect();
+        // trun: sample sizes only, data at the mdat payload.
+        let trun = full(
+            b"trun",
+            0,
+            0x200,
+            &[words(&[sizes.len() as u32]), words(&sizes)].concat(),
+        );
+        let traf = [
+            full(b"tfhd", 0, 0, &words(&[1])),
+            full(b"tfdt", 1, 0, &start.to_be_bytes()),
+            trun,
+        ]
+        .concat();
+        let moof = [full(b"mfhd", 0, 0, &words(&[1])), atom(b"traf", &traf)].concat();
+        [atom(b"moof", &moof), atom(b"mdat", &payloads.concat())].concat()
+    }
+
+    fn write_clip(dir: &Path, segments: &[Vec<u8>]) {
+        fs::write(
+            dir.join("session.mpd"),
+            r#"<?xml version="1.0"?>
+<MPD mediaPresentationDuration="PT0.5S"><Period><AdaptationSet contentType="video">
+<Representation id="0" bandwidth="1"><SegmentTemplate initialization="init-stream$RepresentationID$.m4s"
+ media="chunk-stream$RepresentationID$-$Number%05d$.m4s" startNumber="1"/></Representation>
+</AdaptationSet></Period></MPD>"#,
+        )
+        .unwrap();
+        fs::write(dir.join("init-stream0.m4s"), init_segment()).unwrap();
+        for (i, s) in segments.iter().enumerate() {
+            fs::write(dir.join(format!("chunk-stream0-{:05}.m4s", i + 1)), s).unwrap();
+        }
+    }
+
+    #[test]
+    fn remuxes_init_and_segments_into_one_mp4() {
+        let dir = scratch("remux");
+        write_clip(
+            &dir,
+            &[
+                media_segment(0, &[b"aaaa", b"bbbbb", b"cc"]),
+                media_segment(300, &[b"ddd", b"e"]),
+            ],
+        );
+        let out = dir.join("out.mp4");
+        remux(&dir, &out, &RemuxOptions::default(), &mut |_, _| true).unwrap();
+
+        let mut f = File::open(&out).unwrap();
+        let len = f.metadata().unwrap().len();
+        let boxes = read_top_level(&mut f, len).unwrap();
+        let kinds: Vec<&[u8; 4]> = boxes.iter().map(|(k, _, _)| k).collect();
+        assert_eq!(kinds, [b"ftyp", b"moov", b"mdat"]);
+        let (_, mdat, size) = boxes[2];
+        assert_eq!(
+            read_at(&mut f, mdat + 8, size - 8).unwrap(),
+            b"aaaabbbbbccddde"
+        );
+        assert!((duration(&out).unwrap() - 0.5).abs() < 1e-9);
+
+        let (_, offset, size) = boxes[1];
+        let moov = read_at(&mut f, offset + 8, size - 8).unwrap();
+        let stsz = find_path(&moov, &[b"trak", b"mdia", b"minf", b"stbl", b"stsz"]).unwrap();
+        assert_eq!(&stsz[4..], &words(&[0, 5, 4, 5, 2, 3, 1])[..]);
+        let stco = find_path(&moov, &[b"trak", b"mdia", b"minf", b"stbl", b"stco"]).unwrap();
+        let payload = mdat as u32 + 8;
+        assert_eq!(&stco[4..], &words(&[2, payload, payload + 11])[..]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_boxes_are_errors() {
+        // A 64-bit size of u64::MAX, and a 32-bit one past the end.
+        let huge = [
+            words(&[1]),
+            b"moof".to_vec(),
+            u64::MAX.to_be_bytes().to_vec(),
+        ]
+        .concat();
+        assert!(children(&huge).is_err());
+        let long = [words(&[u32::MAX]), b"moof".to_vec(), vec![0; 8]].concat();
+        assert!(children(&long).is_err());
+        // A 64-bit size too small for its own header.
+        let short = [words(&[1]), b"free".to_vec(), 8u64.to_be_bytes().to_vec()].concat();
+        assert!(children(&short).is_err());
+
+        let dir = scratch("oversized");
+        let path = dir.join("chunk.m4s");
+        for data in [&huge, &long, &short] {
+            fs::write(&path, [atom(b"styp", b"msdh"), data.clone()].concat()).unwrap();
+            let mut f = File::open(&path).unwrap();
+            let len = f.metadata().unwrap().len();
+            let err = read_top_level(&mut f, len).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn malformed_segments_fail_the_remux() {
+        let dir = scratch("malformed");
+        let out = dir.join("out.mp4");
+        let mut huge_moof = media_segment(0, &[b"aaaa"]);
+        huge_moof[..4].copy_from_slice(&1u32.to_be_bytes());
+        huge_moof.splice(8..8, u64::MAX.to_be_bytes());
+        // A trun counting far more samples than it holds.
+        let mut truncated = media_segment(0, &[b"aaaa"]);
+        let at = truncated.windows(4).position(|w| w == b"trun").unwrap() + 8;
+        truncated[at..at + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        for segment in [huge_moof, truncated] {
+            write_clip(&dir, &[segment]);
+            let result = remux(&dir, &out, &RemuxOptions::default(), &mut |_, _| true);
+            assert!(
+                matches!(result, Err(Error::InvalidClip { .. })),
+                "{result:?}"
+            );
+            assert!(!out.exists());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
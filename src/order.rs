@@ -0,0 +1,47 @@
+//! `--order`: which clips get converted first.
+
+use clap::ValueEnum;
+use std::cmp::Reverse;
+use steamclipconverter::ClipDir;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ClipOrder {
+    /// By folder path (stable, the historical order).
+    #[default]
+    Path,
+    /// Most recently recorded first.
+    Newest,
+    /// Oldest recording first.
+    Oldest,
+    /// Manually saved clips before background recordings, newest first within each group,
+    /// so a clip saved to share right now doesn't wait behind a stale backlog.
+    Priority,
+}
+
+/// A clip the user saved on purpose lives in `clips/clip_<appid>_<date>_<time>/video/`;
+/// everything else is a background recording.
+pub fn is_manual(clip: &ClipDir) -> bool {
+    clip.dir
+        .parent()
+        .and_then(|video| video.parent())
+        .and_then(|p| p.file_name())
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with("clip_"))
+}
+
+pub fn sort_clips(clips: &mut [ClipDir], order: ClipOrder) {
+    // Start with path order so ties stay deterministic.
+    clips.sort_by(|a, b| a.dir.cmp(&b.dir));
+    match order {
+        ClipOrder::Path => {}
+        ClipOrder::Oldest => clips.sort_by(|a, b| (&a.date, &a.time).cmp(&(&b.date, &b.time))),
+        ClipOrder::Newest => clips.sort_by(|a, b| (&b.date, &b.time).cmp(&(&a.date, &a.time))),
+        ClipOrder::Priority => clips.sort_by_key(|c| {
+            (
+                !is_manual(c),
+                Reverse(c.date.clone()),
+                Reverse(c.time.clone()),
+            )
+        }),
+    }
+}
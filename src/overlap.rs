@@ -0,0 +1,81 @@
+//! `--on-overlap`: a clip saved by hand and Steam's background recording of the same game
+//! often cover the same minutes, and converting both archives that footage twice.
+//!
+//! Windows are the record start from the folder name plus the recording's duration (from
+//! ffprobe, or read from the segments), so only pairs of a manual clip and a background
+//! recording of the same appid are probed.
+
+use crate::{order, timestamp};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use std::collections::HashMap;
+use steamclipconverter::{ClipDir, probe::probe_duration};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OnOverlap {
+    /// Convert both, only reporting the overlap.
+    #[default]
+    KeepBoth,
+    /// Skip a recording whose whole window is inside a longer one of the same game.
+    KeepLonger,
+}
+
+/// A manual clip and a background recording of the same game that share time.
+pub struct Overlap {
+    /// Indices into the scanned clips: the longer recording, then the shorter.
+    pub longer: usize,
+    pub shorter: usize,
+    /// Seconds both cover.
+    pub shared: f64,
+    /// The shorter one lies entirely within the longer.
+    pub contained: bool,
+}
+
+/// When a recording starts and ends.
+type Window = (DateTime<Utc>, DateTime<Utc>);
+
+/// Recorded window of `clip`.
+fn window(clip: &ClipDir) -> Option<Window> {
+    let start = timestamp::folder_time(clip)?;
+    let secs = probe_duration(&clip.dir.join("session.mpd")).ok()?;
+    let end = start + chrono::Duration::milliseconds((secs * 1000.0) as i64);
+    Some((start, end))
+}
+
+/// Every overlapping pair of a manual clip and a background recording in `clips`.
+pub fn find(clips: &[ClipDir]) -> Vec<Overlap> {
+    let mut windows: HashMap<usize, Option<Window>> = HashMap::new();
+    let mut out = Vec::new();
+    for (m, manual) in clips
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| order::is_manual(c))
+    {
+        for (b, background) in clips.iter().enumerate() {
+            if order::is_manual(background) || background.appid != manual.appid {
+                continue;
+            }
+            let Some((ms, me)) = *windows.entry(m).or_insert_with(|| window(manual)) else {
+                break;
+            };
+            let Some((bs, be)) = *windows.entry(b).or_insert_with(|| window(background)) else {
+                continue;
+            };
+            let shared = (me.min(be) - ms.max(bs)).num_milliseconds() as f64 / 1000.0;
+            if shared <= 0.0 {
+                continue;
+            }
+            // On a tie the saved clip stays: it has the title and thumbnails.
+            let (longer, shorter) = if be - bs > me - ms { (b, m) } else { (m, b) };
+            let (ls, le) = if longer == b { (bs, be) } else { (ms, me) };
+            let (ss, se) = if longer == b { (ms, me) } else { (bs, be) };
+            out.push(Overlap {
+                longer,
+                shorter,
+                shared,
+                contained: ls <= ss && se <= le,
+            });
+        }
+    }
+    out
+}
@@ -0,0 +1,87 @@
+//! Per-clip `convert.toml` inside an fg_* folder, for tweaking one clip without flags:
+//!
+//! ```toml
+//! skip = false
+//! title = "Ace on Mirage"   # added to the file name and written as the title tag
+//! preset = "web"            # same values as --target-device
+//! visibility = "private"    # public, unlisted or private; kept in the catalog/sidecars
+//! [trim]
+//! start = "0:05"            # seconds, M:SS or H:MM:SS(.fff)
+//! end = 42.5
+//! ```
+
+use crate::{device::TargetDevice, json::Value, toml, visibility::Visibility};
+use clap::ValueEnum;
+use std::{fs, io, path::Path};
+
+pub const FILE_NAME: &str = "convert.toml";
+
+#[derive(Clone, Debug, Default)]
+pub struct ClipOverrides {
+    pub skip: bool,
+    pub title: Option<String>,
+    pub preset: Option<TargetDevice>,
+    pub trim_start: Option<f64>,
+    pub trim_end: Option<f64>,
+}
+
+impl ClipOverrides {
+    /// Read `<clip_dir>/convert.toml`; `Ok(None)` when there is none.
+    pub fn load(clip_dir: &Path) -> Result<Option<Self>, String> {
+        let text = match fs::read_to_string(clip_dir.join(FILE_NAME)) {
+            Ok(t) => t,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let doc = toml::parse(&text)?;
+        let Value::Obj(fields) = &doc else {
+            return Ok(Some(Self::default()));
+        };
+
+        let mut o = ClipOverrides::default();
+        for (key, v) in fields {
+            match (key.as_str(), v) {
+                ("skip", Value::Bool(b)) => o.skip = *b,
+                ("title", Value::Str(s)) if !s.trim().is_empty() => {
+                    o.title = Some(s.trim().to_string())
+                }
+                ("preset", Value::Str(s)) => {
+                    o.preset = Some(
+                        TargetDevice::from_str(s, true)
+                            .map_err(|_| format!("unknown preset '{s}' (web, ios, tv)"))?,
+                    )
+                }
+                ("trim", Value::Obj(trim)) => {
+                    // A top-level key written below [trim] lands here; don't drop it.
+                    if let Some((k, _)) = trim.iter().find(|(k, _)| k != "start" && k != "end") {
+                        return Err(format!("unsupported key 'trim.{k}'"));
+                    }
+                    o.trim_start = v.get("start").map(parse_timestamp).transpose()?;
+                    o.trim_end = v.get("end").map(parse_timestamp).transpose()?;
+                }
+                // Read by visibility::detect; only validated here.
+                ("visibility", Value::Str(s)) if Visibility::parse(s).is_some() => {}
+                (k, _) => return Err(format!("unsupported or mistyped key '{k}'")),
+            }
+        }
+        if let (Some(s), Some(e)) = (o.trim_start, o.trim_end)
+            && e <= s
+        {
+            return Err(format!("trim end ({e}s) must be after start ({s}s)"));
+        }
+        Ok(Some(o))
+    }
+}
+
+/// Seconds from a number or a `[[H:]M:]S[.fff]` string.
+fn parse_timestamp(v: &Value) -> Result<f64, String> {
+    let secs = match v {
+        Value::Num(n) => Some(*n),
+        Value::Str(s) => s.split(':').try_fold(0.0, |acc: f64, part| {
+            part.trim().parse::<f64>().ok().map(|n| acc * 60.0 + n)
+        }),
+        _ => None,
+    };
+    secs.filter(|s| s.is_finite() && *s >= 0.0)
+        .ok_or_else(|| format!("bad trim time {v}"))
+}
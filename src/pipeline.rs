@@ -0,0 +1,121 @@
+//! Staged execution: each stage has its own worker count and hands its results to the next
+//! over a bounded channel, so cheap IO-bound work can run ahead of (or behind) CPU-bound
+//! work instead of everything waiting on one global job count.
+
+use clap::Args;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, SyncSender},
+    },
+    thread::Scope,
+};
+
+/// Worker counts per stage of a conversion run.
+#[derive(Args, Clone, Debug)]
+pub struct StageLimits {
+    /// Clips checked and probed (settle check, overrides, ffprobe) at the same time.
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub probe_jobs: u16,
+
+    /// ffmpeg conversions running at the same time (default: one per CPU core, at most 4).
+    #[arg(long, short = 'j', visible_alias = "jobs", value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub convert_jobs: Option<u16>,
+
+    /// Finished outputs post-processed at the same time (file times, catalog checksums,
+    /// session files, post-processing, --delete-after).
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub post_jobs: u16,
+}
+
+/// Default conversion workers: remuxing is mostly disk-bound, so more than this rarely helps.
+const MAX_DEFAULT_CONVERT_JOBS: u16 = 4;
+
+impl StageLimits {
+    /// `--convert-jobs`, or the CPU count capped at [`MAX_DEFAULT_CONVERT_JOBS`].
+    pub fn convert_workers(&self) -> u16 {
+        self.convert_jobs.unwrap_or_else(|| {
+            let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+            cores.clamp(1, usize::from(MAX_DEFAULT_CONVERT_JOBS)) as u16
+        })
+    }
+}
+
+/// Run `f` on `workers` threads over everything arriving on `input`; results go to `output`.
+/// The stage ends (closing `output`) once `input` is closed and drained.
+pub fn stage<'scope, 'env, I, O, F>(
+    scope: &'scope Scope<'scope, 'env>,
+    workers: u16,
+    input: Receiver<I>,
+    output: SyncSender<O>,
+    f: &'scope F,
+) where
+    I: Send + 'scope,
+    O: Send + 'scope,
+    F: Fn(I) -> Option<O> + Sync,
+{
+    let input = Arc::new(Mutex::new(input));
+    for _ in 0..workers.max(1) {
+        let (input, output) = (Arc::clone(&input), output.clone());
+        scope.spawn(move || {
+            loop {
+                // Hold the lock only while taking the next item.
+                let next = input.lock().unwrap().recv();
+                let Ok(item) = next else { break };
+                if let Some(out) = f(item)
+                    && output.send(out).is_err()
+                {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Final stage: like [`stage`] without an output.
+pub fn sink<'scope, 'env, I, F>(
+    scope: &'scope Scope<'scope, 'env>,
+    workers: u16,
+    input: Receiver<I>,
+    f: &'scope F,
+) where
+    I: Send + 'scope,
+    F: Fn(I) + Sync,
+{
+    let input = Arc::new(Mutex::new(input));
+    for _ in 0..workers.max(1) {
+        let input = Arc::clone(&input);
+        scope.spawn(move || {
+            loop {
+                let next = input.lock().unwrap().recv();
+                let Ok(item) = next else { break };
+                f(item);
+            }
+        });
+    }
+}
+
+/// Put the `(index, item)` pairs of a parallel stage back into index order, dropping the
+/// `None`s, so the next stage sees items in the order they were queued.
+pub fn in_order<'scope, 'env, T: Send + 'scope>(
+    scope: &'scope Scope<'scope, 'env>,
+    input: Receiver<(usize, Option<T>)>,
+    output: SyncSender<T>,
+) {
+    scope.spawn(move || {
+        let mut waiting = BTreeMap::new();
+        let mut next = 0;
+        for (i, item) in input {
+            waiting.insert(i, item);
+            while let Some(item) = waiting.remove(&next) {
+                next += 1;
+                if let Some(item) = item
+                    && output.send(item).is_err()
+                {
+                    return;
+                }
+            }
+        }
+    });
+}
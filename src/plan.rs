@@ -0,0 +1,231 @@
+//! Upfront plan for runs that delete things: say what will happen, refuse combinations
+//! that would destroy data, and get an explicit yes before starting.
+
+use crate::{Cli, report::say};
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+use steamclipconverter::ClipDir;
+
+enum Issue {
+    /// Would lose data; the run is refused even with --yes.
+    Danger(String),
+    /// Probably not what was meant, but allowed once confirmed.
+    Warning(String),
+}
+
+fn canonical(p: &Path) -> PathBuf {
+    fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf())
+}
+
+/// Everything `--delete-after` may remove for this clip: the fg_* folder, and its
+/// clip_* grandparent when that becomes empty.
+fn deletable_roots(clip: &ClipDir) -> Vec<PathBuf> {
+    let dir = canonical(&clip.dir);
+    let mut roots = vec![dir.clone()];
+    if let Some(grand) = dir.parent().and_then(Path::parent)
+        && grand
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("clip_"))
+    {
+        roots.push(grand.to_path_buf());
+    }
+    roots
+}
+
+/// `--allow-delete-under` paths are the `allow` list.
+pub use steamclipconverter::steam::may_delete;
+
+fn check(cli: &Cli, output_dir: &Path, clips: &[ClipDir]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if !cli.delete_after {
+        return issues;
+    }
+    let output = canonical(output_dir);
+    if let Some(root) = clips
+        .iter()
+        .flat_map(deletable_roots)
+        .find(|r| output.starts_with(r))
+    {
+        issues.push(Issue::Danger(format!(
+            "--output {} is inside {}, which --delete-after removes after converting",
+            output.display(),
+            root.display()
+        )));
+    }
+    let outside = clips
+        .iter()
+        .filter(|c| !may_delete(&c.dir, &cli.allow_delete_under))
+        .count();
+    if outside > 0 {
+        issues.push(Issue::Warning(format!(
+            "{outside} clip(s) aren't in a Steam gamerecordings folder; they are converted but \
+             not deleted (--allow-delete-under <path> to delete them)"
+        )));
+    }
+    if cli.no_catalog {
+        issues.push(Issue::Danger(
+            "--delete-after with --no-catalog: originals would be deleted without a checksum \
+             record of what they became, so `verify`/`reconvert` couldn't help later"
+                .into(),
+        ));
+    }
+    if let Some((a, b)) = overlapping(clips) {
+        issues.push(Issue::Danger(format!(
+            "{} is {} {}: deleting one would remove the other's recording",
+            a.display(),
+            if canonical(a) == canonical(b) {
+                "the same folder as"
+            } else {
+                "inside"
+            },
+            b.display()
+        )));
+    }
+    if let Some(errors) = clips
+        .iter()
+        .find_map(|c| same_failing_disk(&c.dir, &output))
+    {
+        issues.push(Issue::Danger(format!(
+            "--output {} is on the same disk as the recordings, which has reported {errors} I/O \
+             error(s); convert onto another disk before deleting the originals",
+            output.display()
+        )));
+    }
+    if cli.settle_time.is_zero() {
+        issues.push(Issue::Warning(
+            "--delete-after with --settle-time 0: a clip still being recorded could be \
+             converted truncated and then deleted"
+                .into(),
+        ));
+    }
+    issues
+}
+
+/// Two clips whose folders are the same (through a symlink) or one inside the other, as
+/// (inner, outer) the way they were found.
+fn overlapping(clips: &[ClipDir]) -> Option<(&Path, &Path)> {
+    let mut dirs: Vec<(PathBuf, &Path)> = clips
+        .iter()
+        .map(|c| (canonical(&c.dir), c.dir.as_path()))
+        .collect();
+    // A folder sorts right before what is inside it.
+    dirs.sort();
+    dirs.windows(2)
+        .find(|w| w[1].0.starts_with(&w[0].0))
+        .map(|w| (w[1].1, w[0].1))
+}
+
+/// The I/O errors of the disk holding both `clip_dir` and `output`, when they share one
+/// that has reported any.
+#[cfg(target_os = "linux")]
+fn same_failing_disk(clip_dir: &Path, output: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    // The output folder may not exist yet: where it would be created.
+    let output = output.ancestors().find(|a| a.exists())?;
+    let dev = fs::metadata(clip_dir).ok()?.dev();
+    if fs::metadata(output).ok()?.dev() != dev {
+        return None;
+    }
+    // glibc's major()/minor().
+    let major = ((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0xfff);
+    let minor = ((dev >> 12) & 0xffff_ff00) | (dev & 0xff);
+    // .../block/sda/sda1 for a partition, .../block/sda for the whole disk; SCSI and SATA
+    // disks count failed commands in device/ioerr_cnt.
+    let block = fs::canonicalize(format!("/sys/dev/block/{major}:{minor}")).ok()?;
+    let count = [block.join("device"), block.parent()?.join("device")]
+        .iter()
+        .find_map(|d| fs::read_to_string(d.join("ioerr_cnt")).ok())?;
+    let count = u64::from_str_radix(count.trim().trim_start_matches("0x"), 16).ok()?;
+    (count > 0).then_some(count)
+}
+
+/// Disk error counts are only read on Linux.
+#[cfg(not(target_os = "linux"))]
+fn same_failing_disk(_clip_dir: &Path, _output: &Path) -> Option<u64> {
+    None
+}
+
+/// Print the plan for a destructive run and decide whether to go ahead. Runs without
+/// destructive flags pass straight through. `delete_remote` is `pull --delete-remote`.
+pub fn confirm(
+    cli: &Cli,
+    input_dir: &Path,
+    output_dir: &Path,
+    clips: &[ClipDir],
+    delete_remote: bool,
+) -> bool {
+    if !cli.delete_after && !cli.gc_empty && !delete_remote {
+        return true;
+    }
+    say!("Plan:");
+    say!(
+        "  convert {} clip(s) from {} into {}",
+        clips.len(),
+        input_dir.display(),
+        output_dir.display()
+    );
+    if cli.delete_after {
+        say!(
+            "  DELETE each fg_* folder after it converts successfully (and its clip_* folder once empty)"
+        );
+    }
+    if delete_remote {
+        say!("  DELETE each clip on the agent once it is converted and its segments verified");
+    }
+    if let Some(headroom) = cli.make_room {
+        say!(
+            "  oldest first, stopping once {} is free below the recording storage limit",
+            crate::quota::format_size(headroom)
+        );
+    }
+    if cli.gc_empty {
+        say!(
+            "  REMOVE empty video/ and timelines/ folders and clip_* folders without a recording under {}",
+            input_dir.display()
+        );
+    }
+    match (&cli.catalog, cli.no_catalog) {
+        (_, true) => say!("  catalog: off"),
+        (Some(p), _) => say!("  catalog: {}", p.display()),
+        (None, _) => say!("  catalog: default location"),
+    }
+
+    let issues = check(cli, output_dir, clips);
+    let mut refuse = false;
+    for issue in &issues {
+        match issue {
+            Issue::Danger(m) => {
+                eprintln!("[danger] {}", m);
+                refuse = true;
+            }
+            Issue::Warning(m) => eprintln!("[warn] {}", m),
+        }
+    }
+    if refuse {
+        eprintln!("ERROR: refusing to run this combination; fix the flags above.");
+        return false;
+    }
+    if cli.yes {
+        return true;
+    }
+    if !io::stdin().is_terminal() {
+        eprintln!("ERROR: this run deletes files; pass --yes to confirm non-interactively.");
+        return false;
+    }
+    // The question goes where the plan went.
+    if crate::report::json() {
+        eprint!("Proceed? [y/N] ");
+    } else {
+        print!("Proceed? [y/N] ");
+        let _ = io::stdout().flush();
+    }
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes" | "YES" | "Yes")
+}
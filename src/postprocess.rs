@@ -0,0 +1,336 @@
+//! Post-processing chain from `config.toml`, run in order on each new output after it is
+//! catalogued:
+//!
+//! ```toml
+//! [[postprocess]]
+//! type = "thumbnail"      # <name>.jpg next to the output; at = seconds, width = pixels
+//! at = 3
+//!
+//! [[postprocess]]
+//! type = "upload-s3"      # through the AWS CLI: bucket, prefix, endpoint_url, profile
+//! bucket = "clips"
+//!
+//! [[postprocess]]
+//! type = "webhook"        # POST a JSON description of the output to an http:// URL
+//! url = "http://homeassistant.local:8123/api/webhook/clips"
+//!
+//! [[postprocess]]
+//! type = "command"        # any program; {output} {clip} {appid} {game} in args
+//! program = "notify-send"
+//! args = ["Clip ready", "{output}"]
+//! ```
+//!
+//! Each type is a [`Step`] registered in [`STEPS`]; a new one only needs an entry there.
+
+use crate::{
+    http,
+    json::{self, Value},
+};
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+use steamclipconverter::{ClipDir, ffmpeg::file_arg};
+
+/// The output a chain runs on.
+pub struct Output<'a> {
+    pub path: &'a Path,
+    pub clip: &'a ClipDir,
+    pub game: &'a str,
+}
+
+impl Output<'_> {
+    fn clip_id(&self) -> String {
+        self.clip
+            .dir
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// `{output}`, `{clip}`, `{appid}` and `{game}` replaced in `arg`.
+    fn expand(&self, arg: &str) -> String {
+        arg.replace("{output}", &self.path.to_string_lossy())
+            .replace("{clip}", &self.clip_id())
+            .replace("{appid}", &self.clip.appid.to_string())
+            .replace("{game}", self.game)
+    }
+}
+
+/// One post-processing step, built from its `[[postprocess]]` table.
+pub trait Step: Send + Sync {
+    fn run(&self, out: &Output) -> Result<(), String>;
+}
+
+type Factory = fn(&Params) -> Result<Box<dyn Step>, String>;
+
+/// Every `type` a `[[postprocess]]` table can name.
+const STEPS: &[(&str, Factory)] = &[
+    ("thumbnail", Thumbnail::build),
+    ("upload-s3", UploadS3::build),
+    ("webhook", Webhook::build),
+    ("command", Hook::build),
+];
+
+/// The steps of `config.toml`, in order.
+#[derive(Default)]
+pub struct Chain {
+    steps: Vec<(&'static str, Box<dyn Step>)>,
+}
+
+impl Chain {
+    /// Build from the `postprocess` array of the config.
+    pub fn from_config(tables: &[Value]) -> Result<Chain, String> {
+        let mut steps = Vec::new();
+        for (n, table) in tables.iter().enumerate() {
+            let at = |e: String| format!("postprocess #{}: {e}", n + 1);
+            let params = Params(table);
+            let kind = params.required_str("type").map_err(at)?;
+            let (name, build) = STEPS
+                .iter()
+                .find(|(name, _)| *name == kind)
+                .ok_or_else(|| {
+                    let known: Vec<&str> = STEPS.iter().map(|(n, _)| *n).collect();
+                    at(format!("unknown type '{kind}' ({})", known.join(", ")))
+                })?;
+            steps.push((*name, build(&params).map_err(at)?));
+        }
+        Ok(Chain { steps })
+    }
+
+    /// Run every step in order; the first failure ends the chain for this output, since
+    /// later steps may rely on it (e.g. a webhook announcing an upload).
+    pub fn run(&self, out: &Output) -> Result<(), String> {
+        for (name, step) in &self.steps {
+            step.run(out).map_err(|e| format!("{name}: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The keys of one `[[postprocess]]` table.
+pub struct Params<'a>(&'a Value);
+
+impl<'a> Params<'a> {
+    /// Fail on keys the step doesn't know, so a typo doesn't silently do nothing.
+    fn allow(&self, keys: &[&str]) -> Result<(), String> {
+        if let Value::Obj(fields) = self.0 {
+            for (k, _) in fields {
+                if k != "type" && !keys.contains(&k.as_str()) {
+                    return Err(format!("unsupported key '{k}'"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn str(&self, key: &str) -> Result<Option<&'a str>, String> {
+        match self.0.get(key) {
+            None => Ok(None),
+            Some(Value::Str(s)) => Ok(Some(s)),
+            Some(_) => Err(format!("'{key}' must be a string")),
+        }
+    }
+
+    fn required_str(&self, key: &str) -> Result<&'a str, String> {
+        self.str(key)?
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| format!("'{key}' is required"))
+    }
+
+    fn num(&self, key: &str) -> Result<Option<f64>, String> {
+        match self.0.get(key) {
+            None => Ok(None),
+            Some(Value::Num(n)) if *n >= 0.0 => Ok(Some(*n)),
+            Some(_) => Err(format!("'{key}' must be a non-negative number")),
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Vec<String>, String> {
+        match self.0.get(key) {
+            None => Ok(Vec::new()),
+            Some(Value::Arr(items)) => items
+                .iter()
+                .map(|v| {
+                    v.as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| format!("'{key}' must be an array of strings"))
+                })
+                .collect(),
+            Some(_) => Err(format!("'{key}' must be an array of strings")),
+        }
+    }
+}
+
+/// Run `cmd`, turning a failed start or a non-zero exit into an error naming `what`.
+fn run_command(mut cmd: Command, what: &str) -> Result<(), String> {
+    let status = cmd
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|e| format!("cannot run {what}: {e}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{what} exited with {status}"))
+    }
+}
+
+/// A still frame saved next to the output, for galleries and media servers.
+struct Thumbnail {
+    at: f64,
+    width: Option<u32>,
+}
+
+impl Thumbnail {
+    fn build(p: &Params) -> Result<Box<dyn Step>, String> {
+        p.allow(&["at", "width"])?;
+        Ok(Box::new(Thumbnail {
+            at: p.num("at")?.unwrap_or(1.0),
+            width: p.num("width")?.map(|w| w as u32).filter(|w| *w > 0),
+        }))
+    }
+}
+
+impl Step for Thumbnail {
+    fn run(&self, out: &Output) -> Result<(), String> {
+        let mut cmd = Command::new("ffmpeg");
+        cmd.args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-ss", &format!("{:.3}", self.at)])
+            .arg("-i")
+            .arg(file_arg(out.path))
+            .args(["-frames:v", "1"]);
+        if let Some(w) = self.width {
+            cmd.args(["-vf", &format!("scale={w}:-2")]);
+        }
+        cmd.arg(file_arg(&out.path.with_extension("jpg")));
+        run_command(cmd, "ffmpeg")
+    }
+}
+
+/// `aws s3 cp` of the output to `s3://<bucket>/<prefix><file name>`; credentials, region
+/// and TLS are the AWS CLI's business, and `endpoint_url` covers MinIO and other S3 clones.
+struct UploadS3 {
+    bucket: String,
+    prefix: String,
+    endpoint_url: Option<String>,
+    profile: Option<String>,
+}
+
+impl UploadS3 {
+    fn build(p: &Params) -> Result<Box<dyn Step>, String> {
+        p.allow(&["bucket", "prefix", "endpoint_url", "profile"])?;
+        Ok(Box::new(UploadS3 {
+            bucket: p.required_str("bucket")?.to_string(),
+            prefix: p.str("prefix")?.unwrap_or("").to_string(),
+            endpoint_url: p.str("endpoint_url")?.map(str::to_string),
+            profile: p.str("profile")?.map(str::to_string),
+        }))
+    }
+}
+
+impl Step for UploadS3 {
+    fn run(&self, out: &Output) -> Result<(), String> {
+        let name = out.path.file_name().unwrap_or_default().to_string_lossy();
+        let prefix = out.expand(&self.prefix);
+        let mut cmd = Command::new("aws");
+        cmd.args(["s3", "cp", "--only-show-errors"])
+            .arg(out.path)
+            .arg(format!("s3://{}/{}{}", self.bucket, prefix, name));
+        if let Some(url) = &self.endpoint_url {
+            cmd.args(["--endpoint-url", url]);
+        }
+        if let Some(profile) = &self.profile {
+            cmd.args(["--profile", profile]);
+        }
+        run_command(cmd, "aws s3 cp")
+    }
+}
+
+/// A POST of `{"event": "converted", ...}` to an `http://` URL. There is no TLS client
+/// built in; for https, use a `command` step with curl.
+struct Webhook {
+    authority: String,
+    path: String,
+}
+
+impl Webhook {
+    fn build(p: &Params) -> Result<Box<dyn Step>, String> {
+        p.allow(&["url"])?;
+        let url = p.required_str("url")?;
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            format!("'{url}' isn't an http:// URL (for https, use a command step with curl)")
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let authority = if authority.contains(':') {
+            authority.to_string()
+        } else {
+            format!("{authority}:80")
+        };
+        Ok(Box::new(Webhook {
+            authority,
+            path: path.to_string(),
+        }))
+    }
+}
+
+impl Step for Webhook {
+    fn run(&self, out: &Output) -> Result<(), String> {
+        let body = json::obj([
+            ("event", "converted".into()),
+            ("output", out.path.to_string_lossy().as_ref().into()),
+            ("clip", out.clip_id().into()),
+            ("appid", out.clip.appid.into()),
+            ("game", out.game.into()),
+            (
+                "size",
+                std::fs::metadata(out.path).map(|m| m.len()).ok().into(),
+            ),
+        ]);
+        let headers = [("Content-Type", "application/json".to_string())];
+        let resp = http::send(
+            &self.authority,
+            "POST",
+            &self.path,
+            &headers,
+            format!("{body}\n").as_bytes(),
+        )
+        .map_err(|e| e.to_string())?;
+        if (200..300).contains(&resp.status) {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", resp.status))
+        }
+    }
+}
+
+/// A user's own program, with the output described in its arguments and environment
+/// (`SCC_OUTPUT`, `SCC_CLIP`, `SCC_APPID`, `SCC_GAME`).
+struct Hook {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Hook {
+    fn build(p: &Params) -> Result<Box<dyn Step>, String> {
+        p.allow(&["program", "args"])?;
+        Ok(Box::new(Hook {
+            program: p.required_str("program")?.to_string(),
+            args: p.strings("args")?,
+        }))
+    }
+}
+
+impl Step for Hook {
+    fn run(&self, out: &Output) -> Result<(), String> {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(self.args.iter().map(|a| out.expand(a)))
+            .env("SCC_OUTPUT", out.path)
+            .env("SCC_CLIP", out.clip_id())
+            .env("SCC_APPID", out.clip.appid.to_string())
+            .env("SCC_GAME", out.game);
+        run_command(cmd, &self.program)
+    }
+}
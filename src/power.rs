@@ -0,0 +1,91 @@
+//! `--power-policy`: don't drain a Steam Deck or laptop battery converting clips.
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PowerPolicy {
+    /// On battery, stop before the next clip and leave the rest for when AC is back.
+    #[default]
+    Defer,
+    /// On battery, keep going but stream-copy only (no transcoding) with a single thread.
+    LowPower,
+    /// Convert the same way regardless of power source.
+    Ignore,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// No battery found or the platform isn't supported; treated like AC.
+    Unknown,
+}
+
+/// What the machine is running on right now.
+pub fn power_source() -> PowerSource {
+    #[cfg(target_os = "linux")]
+    {
+        linux_power_source()
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos_power_source()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn linux_power_source() -> PowerSource {
+    use std::fs;
+
+    let Ok(entries) = fs::read_dir("/sys/class/power_supply") else {
+        return PowerSource::Unknown;
+    };
+    let read = |p: &std::path::Path, f: &str| {
+        fs::read_to_string(p.join(f))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut has_battery = false;
+    for ent in entries.flatten() {
+        let p = ent.path();
+        match read(&p, "type").as_str() {
+            // Any online mains/USB-PD supply means we're plugged in.
+            "Mains" | "USB" if read(&p, "online") == "1" => return PowerSource::Ac,
+            // Peripheral batteries (controllers, mice) report scope=Device.
+            "Battery" if read(&p, "scope") != "Device" => {
+                has_battery = true;
+                if read(&p, "status") == "Discharging" {
+                    return PowerSource::Battery;
+                }
+            }
+            _ => {}
+        }
+    }
+    if has_battery {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn macos_power_source() -> PowerSource {
+    let Ok(out) = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+    else {
+        return PowerSource::Unknown;
+    };
+    let txt = String::from_utf8_lossy(&out.stdout);
+    if txt.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else if txt.contains("'AC Power'") {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
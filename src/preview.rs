@@ -0,0 +1,87 @@
+//! Browser preview of raw recordings served by the agent: an index page, and a player page
+//! that streams a clip's DASH manifest and segments as they are on disk.
+//!
+//! Browsers don't play DASH by themselves, so the player page loads dash.js from its CDN;
+//! the viewing browser needs internet access for that, the agent doesn't.
+
+use crate::http::{Response, percent_encode};
+
+const DASHJS: &str = "https://cdn.dashjs.org/latest/dash.all.min.js";
+
+/// MIME type of a file in a clip folder, so players accept the manifest and segments.
+pub fn content_type(path: &str) -> &'static str {
+    let ext = path.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("mpd") => "application/dash+xml",
+        Some("m4s") => "video/iso.segment",
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("m4a") => "audio/mp4",
+        Some("json") => "application/json",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn page(title: &str, body: &str) -> Response {
+    let html = format!(
+        "<!doctype html>\n<html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width\">\
+         <title>{}</title><style>body{{font-family:sans-serif;margin:2em}}\
+         td{{padding:.2em 1em .2em 0}}video{{max-width:100%}}</style></head>\
+         <body>{}</body></html>\n",
+        escape(title),
+        body
+    );
+    Response::new(200, "text/html; charset=utf-8", html.into_bytes())
+}
+
+/// One row of the index.
+pub struct Row {
+    pub id: String,
+    pub game: String,
+    /// `YYYY-MM-DD HH:MM:SS`
+    pub when: String,
+    pub ready: bool,
+}
+
+pub fn index(host: &str, rows: &[Row]) -> Response {
+    let mut body = format!("<h1>Recordings on {}</h1>", escape(host));
+    if rows.is_empty() {
+        body.push_str("<p>No clips.</p>");
+    } else {
+        body.push_str("<table>");
+        for r in rows {
+            body.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td><a href=\"/preview/{}\">preview</a></td><td>{}</td></tr>",
+                escape(&r.when),
+                escape(&r.game),
+                percent_encode(&r.id),
+                if r.ready { "" } else { "still recording" }
+            ));
+        }
+        body.push_str("</table>");
+    }
+    page(&format!("Recordings on {host}"), &body)
+}
+
+pub fn player(id: &str, title: &str) -> Response {
+    let manifest = format!("/api/clips/{}/files/session.mpd", percent_encode(id));
+    let body = format!(
+        "<p><a href=\"/\">&larr; all clips</a></p><h1>{}</h1>\
+         <video id=\"v\" controls autoplay muted></video>\
+         <p>Raw recording, nothing converted. Manifest: <a href=\"{m}\">{m}</a></p>\
+         <script src=\"{DASHJS}\"></script>\
+         <script>dashjs.MediaPlayer().create().initialize(document.getElementById('v'), '{m}', true);</script>",
+        escape(title),
+        m = manifest
+    );
+    page(title, &body)
+}
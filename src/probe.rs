@@ -0,0 +1,92 @@
+//! Asking ffprobe what a clip actually contains. Without ffprobe installed, the answers are
+//! read from the files themselves (see `native`), which covers everything but `pix_fmt`.
+
+use crate::{ffmpeg::file_arg, native};
+use std::{io, path::Path, process::Command};
+
+/// One stream as reported by ffprobe.
+#[derive(Clone, Debug, Default)]
+pub struct StreamInfo {
+    pub codec_type: String, // "video" / "audio"
+    pub codec_name: String, // "h264", "hevc", "aac", ...
+    pub pix_fmt: String,
+    pub channels: u32,
+    /// Seconds, when the container reports a per-stream duration.
+    pub duration: Option<f64>,
+}
+
+/// Probe the streams of `<clip_dir>/session.mpd`.
+pub fn probe_streams(clip_dir: &Path) -> io::Result<Vec<StreamInfo>> {
+    let out = Command::new("ffprobe")
+        .current_dir(clip_dir)
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "stream=codec_type,codec_name,pix_fmt,channels,duration",
+            "-of",
+            "compact=p=0",
+            "session.mpd",
+        ])
+        .output();
+    let out = match out {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return native::probe_streams(clip_dir),
+        out => out?,
+    };
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "ffprobe status: {}: {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(parse_compact_streams(&String::from_utf8_lossy(&out.stdout)))
+}
+
+/// Parse ffprobe `-of compact=p=0` lines like `codec_name=h264|codec_type=video|pix_fmt=yuv420p`.
+fn parse_compact_streams(txt: &str) -> Vec<StreamInfo> {
+    txt.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|line| {
+            let mut s = StreamInfo::default();
+            for kv in line.trim().split('|') {
+                let Some((k, v)) = kv.split_once('=') else {
+                    continue;
+                };
+                match k {
+                    "codec_type" => s.codec_type = v.to_string(),
+                    "codec_name" => s.codec_name = v.to_string(),
+                    "pix_fmt" => s.pix_fmt = v.to_string(),
+                    "channels" => s.channels = v.parse().unwrap_or(0),
+                    "duration" => s.duration = v.parse().ok().filter(|d: &f64| *d > 0.0),
+                    _ => {}
+                }
+            }
+            s
+        })
+        .collect()
+}
+
+/// Container duration in seconds of a media file.
+pub fn probe_duration(path: &Path) -> io::Result<f64> {
+    let out = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(file_arg(path))
+        .output();
+    let out = match out {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return native::duration(path),
+        out => out?,
+    };
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "ffprobe status: {}: {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    let txt = String::from_utf8_lossy(&out.stdout);
+    txt.trim()
+        .parse::<f64>()
+        .map_err(|_| io::Error::other(format!("no duration reported ({})", txt.trim())))
+}
@@ -0,0 +1,400 @@
+//! `--encode` profiles: transcode the video instead of copying Steam's stream, whose bitrate
+//! is often far higher than the picture needs. Audio is still copied.
+//!
+//! A profile is a codec and a speed/size trade-off; `--crf` and `--preset` override its
+//! quality and speed, and `--hwaccel` moves the encode to the GPU. Each encoder has its own
+//! knobs, so `--crf` is passed as the closest one (`-cq` for NVENC, `-global_quality` for
+//! QSV, `-qp` for VAAPI, `-q:v` for VideoToolbox) and `--preset` as given.
+//!
+//! `--hwaccel auto` picks the first GPU that can encode the profile's codec here, found by
+//! a test encode of a few black frames with each one ffmpeg lists, so one command line
+//! works on a desktop with NVIDIA, an Intel laptop and a Deck. The results are kept in the
+//! cache folder until ffmpeg is updated.
+
+use crate::report::say;
+use clap::ValueEnum;
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+use steamclipconverter::{
+    appdirs, atomic,
+    ffmpeg::{Component, ComponentKind, missing_components},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum EncodeProfile {
+    /// H.264, quick to encode, plays everywhere.
+    H264Fast,
+    /// H.264, the usual balance.
+    H264Medium,
+    /// H.264, slower and close to the original.
+    H264Quality,
+    /// HEVC close to the original at about half the size of H.264.
+    HevcQuality,
+    /// HEVC for archiving many hours at a fraction of the size.
+    HevcSmall,
+    /// AV1, smallest files; slow without a GPU encoder.
+    Av1Small,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HwAccel {
+    /// NVIDIA.
+    Nvenc,
+    /// Intel Quick Sync.
+    Qsv,
+    /// macOS.
+    Videotoolbox,
+    /// Linux VA-API (AMD, Intel), on /dev/dri/renderD128.
+    Vaapi,
+    /// Whichever of these works on this machine, or the CPU when none does.
+    Auto,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Codec {
+    H264,
+    Hevc,
+    Av1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Speed {
+    Fast,
+    Medium,
+    Slow,
+}
+
+/// VA-API device used with `--hwaccel vaapi`.
+const VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+/// `--hwaccel auto` tries these in order and takes the first that works.
+const AUTO_ORDER: [HwAccel; 4] = [
+    HwAccel::Nvenc,
+    HwAccel::Qsv,
+    HwAccel::Vaapi,
+    HwAccel::Videotoolbox,
+];
+
+/// Test encode results in the cache folder: the ffmpeg version on the first line, then
+/// `<encoder>\t<yes|no>`.
+const PROBE_CACHE_FILE: &str = "hwaccel-probe.tsv";
+
+impl EncodeProfile {
+    fn codec(self) -> Codec {
+        match self {
+            EncodeProfile::H264Fast | EncodeProfile::H264Medium | EncodeProfile::H264Quality => {
+                Codec::H264
+            }
+            EncodeProfile::HevcQuality | EncodeProfile::HevcSmall => Codec::Hevc,
+            EncodeProfile::Av1Small => Codec::Av1,
+        }
+    }
+
+    fn speed(self) -> Speed {
+        match self {
+            EncodeProfile::H264Fast => Speed::Fast,
+            EncodeProfile::H264Medium | EncodeProfile::HevcSmall | EncodeProfile::Av1Small => {
+                Speed::Medium
+            }
+            EncodeProfile::H264Quality | EncodeProfile::HevcQuality => Speed::Slow,
+        }
+    }
+
+    /// Default `--crf`, on the scale of the codec's software encoder.
+    fn crf(self) -> u32 {
+        match self {
+            EncodeProfile::H264Fast => 23,
+            EncodeProfile::H264Medium => 21,
+            EncodeProfile::H264Quality => 18,
+            EncodeProfile::HevcQuality => 20,
+            EncodeProfile::HevcSmall => 28,
+            EncodeProfile::Av1Small => 35,
+        }
+    }
+}
+
+/// The ffmpeg encoders for `codec`: software first, then one per `--hwaccel`.
+fn candidates(codec: Codec) -> [(&'static str, Option<HwAccel>); 5] {
+    match codec {
+        Codec::H264 => [
+            ("libx264", None),
+            ("h264_nvenc", Some(HwAccel::Nvenc)),
+            ("h264_qsv", Some(HwAccel::Qsv)),
+            ("h264_videotoolbox", Some(HwAccel::Videotoolbox)),
+            ("h264_vaapi", Some(HwAccel::Vaapi)),
+        ],
+        Codec::Hevc => [
+            ("libx265", None),
+            ("hevc_nvenc", Some(HwAccel::Nvenc)),
+            ("hevc_qsv", Some(HwAccel::Qsv)),
+            ("hevc_videotoolbox", Some(HwAccel::Videotoolbox)),
+            ("hevc_vaapi", Some(HwAccel::Vaapi)),
+        ],
+        Codec::Av1 => [
+            ("libsvtav1", None),
+            ("av1_nvenc", Some(HwAccel::Nvenc)),
+            ("av1_qsv", Some(HwAccel::Qsv)),
+            ("av1_vaapi", Some(HwAccel::Vaapi)),
+            // The reference encoder, for builds without SVT-AV1.
+            ("libaom-av1", None),
+        ],
+    }
+}
+
+/// Whether ffmpeg lists the encoder `name`; asked once per encoder and run.
+fn has_encoder(name: &str) -> bool {
+    static LISTED: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+    let mut listed = LISTED.lock().unwrap();
+    if let Some((_, has)) = listed.iter().find(|(n, _)| n == name) {
+        return *has;
+    }
+    let has = missing_components(&[Component::new(ComponentKind::Encoder, name)])
+        .is_ok_and(|missing| missing.is_empty());
+    listed.push((name.to_string(), has));
+    has
+}
+
+/// `hwaccel` with `auto` replaced by the first GPU encoding `profile`'s codec on this
+/// machine, or None (the CPU) when there is none; decided once per codec and run.
+pub fn resolve(profile: EncodeProfile, hwaccel: Option<HwAccel>) -> Option<HwAccel> {
+    if hwaccel != Some(HwAccel::Auto) {
+        return hwaccel;
+    }
+    static CHOSEN: Mutex<Vec<(Codec, Option<HwAccel>)>> = Mutex::new(Vec::new());
+    let codec = profile.codec();
+    let mut chosen = CHOSEN.lock().unwrap();
+    if let Some((_, hw)) = chosen.iter().find(|(c, _)| *c == codec) {
+        return *hw;
+    }
+    let found = AUTO_ORDER.into_iter().find_map(|hw| {
+        encoder(profile, Some(hw))
+            .filter(|name| has_encoder(name) && encodes(profile, hw, name))
+            .map(|name| (hw, name))
+    });
+    match found {
+        Some((_, name)) => say!("[hwaccel] auto: encoding with {name}"),
+        None => say!("[hwaccel] auto: no GPU encoder works here for this profile; using the CPU"),
+    }
+    let hw = found.map(|(hw, _)| hw);
+    chosen.push((codec, hw));
+    hw
+}
+
+/// Whether the encoder `name` for `hwaccel` encodes on this machine: ffmpeg lists every
+/// encoder it was built with, whether or not there is a GPU or driver for it. Asked from
+/// the cache, or by a test encode that is then cached.
+fn encodes(profile: EncodeProfile, hwaccel: HwAccel, name: &str) -> bool {
+    static CACHE: Mutex<Option<ProbeCache>> = Mutex::new(None);
+    let mut cache = CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(ProbeCache::load);
+    if let Some((_, works)) = cache.results.iter().find(|(n, _)| n == name) {
+        return *works;
+    }
+    say!("[hwaccel] auto: trying {name}");
+    let (_, args, input) = video(profile, None, None, Some(hwaccel));
+    let works = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(&input)
+        .args(["-f", "lavfi", "-i", "color=c=black:s=256x256:r=30:d=0.2"])
+        .args(["-c:v", name])
+        .args(&args)
+        .args(["-f", "null", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+    cache.results.push((name.to_string(), works));
+    cache.save();
+    works
+}
+
+/// The cached test encodes, for the ffmpeg on PATH.
+struct ProbeCache {
+    /// `ffmpeg -version`'s first line; results of another build are dropped.
+    ffmpeg: String,
+    results: Vec<(String, bool)>,
+}
+
+impl ProbeCache {
+    fn path() -> Option<PathBuf> {
+        appdirs::user_cache_dir().map(|d| d.join(PROBE_CACHE_FILE))
+    }
+
+    fn load() -> ProbeCache {
+        let ffmpeg = Command::new("ffmpeg")
+            .args(["-hide_banner", "-version"])
+            .output()
+            .ok()
+            .and_then(|o| {
+                String::from_utf8_lossy(&o.stdout)
+                    .lines()
+                    .next()
+                    .map(str::to_string)
+            })
+            .unwrap_or_default();
+        let text = Self::path()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .unwrap_or_default();
+        let mut lines = text.lines();
+        let results = if !ffmpeg.is_empty() && lines.next() == Some(ffmpeg.as_str()) {
+            lines
+                .filter_map(|l| l.split_once('\t'))
+                .map(|(name, works)| (name.to_string(), works == "yes"))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        ProbeCache { ffmpeg, results }
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        let mut text = format!("{}\n", self.ffmpeg);
+        for (name, works) in &self.results {
+            text.push_str(&format!("{name}\t{}\n", if *works { "yes" } else { "no" }));
+        }
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = atomic::write(&path, text);
+    }
+}
+
+/// The encoder `profile` uses with `hwaccel`; None for AV1 on VideoToolbox, which has none.
+pub fn encoder(profile: EncodeProfile, hwaccel: Option<HwAccel>) -> Option<&'static str> {
+    let hwaccel = resolve(profile, hwaccel);
+    let mut matching = candidates(profile.codec())
+        .into_iter()
+        .filter(|(_, hw)| *hw == hwaccel)
+        .map(|(name, _)| name);
+    let first = matching.next()?;
+    Some(match matching.next() {
+        Some(fallback) if !has_encoder(first) && has_encoder(fallback) => fallback,
+        _ => first,
+    })
+}
+
+/// Why `profile` with `hwaccel` can't be encoded by this ffmpeg, naming the encoders for
+/// that codec it does have.
+pub fn check(profile: EncodeProfile, hwaccel: Option<HwAccel>) -> Option<String> {
+    let flag = profile.to_possible_value()?.get_name().to_string();
+    let Some(name) = encoder(profile, hwaccel) else {
+        return Some(format!(
+            "--encode {flag} has no hardware encoder on that --hwaccel"
+        ));
+    };
+    if has_encoder(name) {
+        return None;
+    }
+    let others: Vec<String> = candidates(profile.codec())
+        .into_iter()
+        .filter(|(n, _)| *n != name && has_encoder(n))
+        .map(|(n, hw)| match hw.and_then(|h| h.to_possible_value()) {
+            Some(v) => format!("{n} (--hwaccel {})", v.get_name()),
+            None => n.to_string(),
+        })
+        .collect();
+    Some(format!(
+        "--encode {flag} needs the {name} encoder, which this ffmpeg doesn't have{}",
+        if others.is_empty() {
+            String::new()
+        } else {
+            format!("; it has {}", others.join(", "))
+        }
+    ))
+}
+
+/// Encoder, its arguments and any arguments before `-i` for `profile`.
+pub fn video(
+    profile: EncodeProfile,
+    crf: Option<u32>,
+    preset: Option<&str>,
+    hwaccel: Option<HwAccel>,
+) -> (String, Vec<String>, Vec<String>) {
+    let hwaccel = resolve(profile, hwaccel);
+    let name = encoder(profile, hwaccel).unwrap_or("libx264");
+    let crf = crf.unwrap_or(profile.crf());
+    let speed = profile.speed();
+    let pick = |fast: &str, medium: &str, slow: &str| {
+        preset.map(str::to_string).unwrap_or_else(|| {
+            match speed {
+                Speed::Fast => fast,
+                Speed::Medium => medium,
+                Speed::Slow => slow,
+            }
+            .to_string()
+        })
+    };
+    let mut input = Vec::new();
+    let mut args: Vec<String> = match (name, hwaccel) {
+        ("libaom-av1", _) => vec![
+            "-cpu-used".into(),
+            pick("8", "6", "4"),
+            "-crf".into(),
+            crf.to_string(),
+            "-b:v".into(),
+            "0".into(),
+            "-row-mt".into(),
+            "1".into(),
+        ],
+        ("libsvtav1", _) => vec![
+            "-preset".into(),
+            pick("10", "8", "5"),
+            "-crf".into(),
+            crf.to_string(),
+        ],
+        // Auto is resolved above.
+        (_, None | Some(HwAccel::Auto)) => vec![
+            "-preset".into(),
+            pick("veryfast", "medium", "slow"),
+            "-crf".into(),
+            crf.to_string(),
+        ],
+        (_, Some(HwAccel::Nvenc)) => vec![
+            "-preset".into(),
+            pick("p3", "p5", "p7"),
+            "-rc".into(),
+            "vbr".into(),
+            "-cq".into(),
+            crf.to_string(),
+            "-b:v".into(),
+            "0".into(),
+        ],
+        (_, Some(HwAccel::Qsv)) => vec![
+            "-preset".into(),
+            pick("veryfast", "medium", "slow"),
+            "-global_quality".into(),
+            crf.to_string(),
+        ],
+        // Quality from 1 to 100, higher is better.
+        (_, Some(HwAccel::Videotoolbox)) => vec![
+            "-q:v".into(),
+            100u32.saturating_sub(crf * 2).clamp(1, 100).to_string(),
+        ],
+        (_, Some(HwAccel::Vaapi)) => {
+            input.extend(["-vaapi_device".to_string(), VAAPI_DEVICE.to_string()]);
+            vec![
+                "-vf".into(),
+                "format=nv12,hwupload".into(),
+                "-qp".into(),
+                crf.to_string(),
+            ]
+        }
+    };
+    // Hardware encoders pick their own surface format.
+    if hwaccel.is_none() {
+        args.extend(["-pix_fmt".to_string(), "yuv420p".to_string()]);
+    }
+    // Apple players only take HEVC in MP4 tagged hvc1.
+    if profile.codec() == Codec::Hevc {
+        args.extend(["-tag:v".to_string(), "hvc1".to_string()]);
+    }
+    (name.to_string(), args, input)
+}
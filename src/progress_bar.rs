@@ -0,0 +1,199 @@
+//! Progress bars on a terminal: one line per clip converting (percent done and time left,
+//! from ffmpeg's `-progress` output or the native muxer) under the run's log, and an
+//! overall line of clips done out of the total with the run's ETA.
+//!
+//! The bars are drawn from the same events as `--progress-file` and stay at the bottom:
+//! [`say!`](crate::report::say) lines and a clip's buffered output are printed above them
+//! through [`above`]. Off when stdout isn't a terminal, with `--no-progress`,
+//! `--format json` and `--ffmpeg-stats`, which leaves the plain log lines.
+
+use crate::{
+    json::Value,
+    progress_file::{Current, RunState},
+};
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// `progress` events redraw at most this often; everything else redraws at once.
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+const BAR_WIDTH: usize = 24;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// How many lines the bars take on screen now, and what they show.
+static SCREEN: Mutex<Screen> = Mutex::new(Screen {
+    lines: 0,
+    frame: String::new(),
+});
+
+struct Screen {
+    lines: usize,
+    frame: String,
+}
+
+impl Screen {
+    /// Erase the bars, leaving the cursor where they began.
+    fn clear(&mut self, out: &mut impl Write) {
+        if self.lines > 0 {
+            let _ = write!(out, "\x1b[{}F\x1b[J", self.lines);
+            self.lines = 0;
+        }
+    }
+
+    fn draw(&mut self, out: &mut impl Write) {
+        let _ = out.write_all(self.frame.as_bytes());
+        self.lines = self.frame.lines().count();
+    }
+}
+
+pub struct ProgressBars {
+    state: Mutex<State>,
+}
+
+struct State {
+    run: RunState,
+    drawn: Option<Instant>,
+}
+
+/// Whether a run should draw bars.
+pub fn enabled(no_progress: bool) -> bool {
+    !no_progress
+        && !crate::report::json()
+        && io::stdout().is_terminal()
+        && std::env::var("TERM").is_ok_and(|t| t != "dumb")
+}
+
+/// True while bars are on screen, so output has to go through [`above`].
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Print `text` (whole lines) above the bars.
+pub fn above(text: &str) {
+    let mut screen = SCREEN.lock().unwrap();
+    let mut out = io::stdout().lock();
+    screen.clear(&mut out);
+    let _ = out.write_all(text.as_bytes());
+    if !text.is_empty() && !text.ends_with('\n') {
+        let _ = out.write_all(b"\n");
+    }
+    screen.draw(&mut out);
+    let _ = out.flush();
+}
+
+impl ProgressBars {
+    pub fn new() -> ProgressBars {
+        ACTIVE.store(true, Ordering::Relaxed);
+        ProgressBars {
+            state: Mutex::new(State {
+                run: RunState::new(),
+                drawn: None,
+            }),
+        }
+    }
+
+    /// Update from one run event (see `events.rs` for the kinds) and redraw.
+    pub fn observe(&self, kind: &str, data: &Value) {
+        let mut s = self.state.lock().unwrap();
+        if !s.run.observe(kind, data)
+            || (kind == "progress" && s.drawn.is_some_and(|t| t.elapsed() < MIN_INTERVAL))
+        {
+            return;
+        }
+        s.drawn = Some(Instant::now());
+        let frame = if s.run.finished {
+            String::new()
+        } else {
+            render(&s.run)
+        };
+        let mut screen = SCREEN.lock().unwrap();
+        let mut out = io::stdout().lock();
+        screen.clear(&mut out);
+        screen.frame = frame;
+        screen.draw(&mut out);
+        let _ = out.flush();
+    }
+}
+
+impl Drop for ProgressBars {
+    fn drop(&mut self) {
+        let mut screen = SCREEN.lock().unwrap();
+        let mut out = io::stdout().lock();
+        screen.clear(&mut out);
+        screen.frame.clear();
+        let _ = out.flush();
+        ACTIVE.store(false, Ordering::Relaxed);
+    }
+}
+
+fn render(run: &RunState) -> String {
+    let mut frame = String::new();
+    for c in &run.current {
+        frame.push_str(&clip_line(c));
+        frame.push('\n');
+    }
+    let done = run.converted + run.failed + run.skipped;
+    let mut line = if run.total > 0 {
+        format!(
+            "{} {done}/{} clips",
+            bar(Some(done as f64 / run.total as f64)),
+            run.total
+        )
+    } else {
+        format!("{done} clip(s) done")
+    };
+    if run.failed > 0 {
+        line.push_str(&format!(", {} failed", run.failed));
+    }
+    if let Some(eta) = run.eta() {
+        line.push_str(&format!("  ETA {}", clock(eta)));
+    }
+    frame.push_str(&line);
+    frame.push('\n');
+    frame
+}
+
+fn clip_line(c: &Current) -> String {
+    let game: String = c.game.chars().take(20).collect();
+    let mut line = format!(
+        "{game:<20} {}",
+        bar(c.percent.map(|p| p.min(100) as f64 / 100.0))
+    );
+    match c.percent {
+        Some(p) => line.push_str(&format!(" {p:>3}%")),
+        None => line.push_str("    "),
+    }
+    if let Some(eta) = c.eta {
+        line.push_str(&format!("  ETA {}", clock(eta)));
+    }
+    line
+}
+
+/// `[#########---------------]`, or a blank bar when how far isn't known.
+fn bar(fraction: Option<f64>) -> String {
+    let filled = fraction.map_or(0, |f| {
+        (f.clamp(0.0, 1.0) * BAR_WIDTH as f64).round() as usize
+    });
+    let rest = if fraction.is_some() { '-' } else { ' ' };
+    format!(
+        "[{}{}]",
+        "#".repeat(filled),
+        rest.to_string().repeat(BAR_WIDTH - filled)
+    )
+}
+
+/// `M:SS`, or `H:MM:SS` from an hour up.
+fn clock(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
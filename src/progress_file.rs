@@ -0,0 +1,210 @@
+//! `--progress-file`: the run's live state as one small JSON document, rewritten as clips
+//! start, progress and finish, for widgets and plugins that would rather poll a file than
+//! read stdout or hold an `--events` stream open.
+//!
+//! It is fed the same events as `--events` and replaced atomically (write, then rename),
+//! so a reader never sees half a document.
+
+use crate::json::{self, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// `progress` events rewrite the file at most this often; everything else rewrites it at once.
+const MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct ProgressFile {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+struct State {
+    run: RunState,
+    written: Option<Instant>,
+}
+
+/// The run as its events describe it; the terminal progress bars (`progress_bar.rs`) are
+/// drawn from it too.
+#[derive(Default)]
+pub struct RunState {
+    started_at: String,
+    pub finished: bool,
+    pub total: u64,
+    pub converted: u64,
+    pub failed: u64,
+    pub skipped: u64,
+    /// Clips converting right now, in the order they started.
+    pub current: Vec<Current>,
+    started: u64,
+    /// Time spent on each clip that finished converting, for the run's ETA.
+    spent: Vec<Duration>,
+}
+
+pub struct Current {
+    clip: String,
+    pub game: String,
+    output: String,
+    /// 1-based position among the clips this run started.
+    index: u64,
+    pub percent: Option<u64>,
+    pub eta: Option<u64>,
+    since: Instant,
+}
+
+impl ProgressFile {
+    pub fn new(path: &Path) -> ProgressFile {
+        ProgressFile {
+            path: path.to_path_buf(),
+            state: Mutex::new(State {
+                run: RunState::new(),
+                written: None,
+            }),
+        }
+    }
+
+    /// Update from one run event (see `events.rs` for the kinds) and rewrite the file.
+    pub fn observe(&self, kind: &str, data: &Value) {
+        let mut s = self.state.lock().unwrap();
+        if !s.run.observe(kind, data)
+            || (kind == "progress" && s.written.is_some_and(|t| t.elapsed() < MIN_INTERVAL))
+        {
+            return;
+        }
+        s.written = Some(Instant::now());
+        let doc = s.run.to_json();
+        drop(s);
+        if let Err(e) = self.write(&doc) {
+            eprintln!("[warn] cannot write {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn write(&self, doc: &Value) -> std::io::Result<()> {
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = self.path.with_file_name(format!(".{name}.tmp"));
+        fs::write(&tmp, format!("{doc}\n"))?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+impl RunState {
+    pub fn new() -> RunState {
+        RunState {
+            started_at: now(),
+            ..RunState::default()
+        }
+    }
+
+    /// Update from one run event; false for kinds that don't change it.
+    pub fn observe(&mut self, kind: &str, data: &Value) -> bool {
+        let clip = data.str_field("clip").to_string();
+        match kind {
+            "run-started" => self.total = data.get("clips").and_then(Value::as_u64).unwrap_or(0),
+            "convert-started" => {
+                self.started += 1;
+                let index = self.started;
+                self.current.push(Current {
+                    clip,
+                    game: data.str_field("game").to_string(),
+                    output: data.str_field("output").to_string(),
+                    index,
+                    percent: None,
+                    eta: None,
+                    since: Instant::now(),
+                });
+            }
+            "progress" => {
+                if let Some(c) = self.current.iter_mut().find(|c| c.clip == clip) {
+                    c.percent = data.get("percent").and_then(Value::as_u64);
+                    c.eta = data.get("eta_secs").and_then(Value::as_u64);
+                }
+            }
+            "converted" | "failed" => {
+                if let Some(i) = self.current.iter().position(|c| c.clip == clip) {
+                    let c = self.current.remove(i);
+                    self.spent.push(c.since.elapsed());
+                }
+                if kind == "converted" {
+                    self.converted += 1;
+                } else {
+                    self.failed += 1;
+                }
+            }
+            "skipped" => self.skipped += 1,
+            "run-finished" => {
+                self.finished = true;
+                self.current.clear();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Seconds until the run is done: what the clips in progress say they need, plus the
+    /// clips not started yet at the average time a clip has taken so far.
+    pub fn eta(&self) -> Option<u64> {
+        if self.finished {
+            return Some(0);
+        }
+        let waiting = self
+            .total
+            .saturating_sub(self.converted + self.failed + self.skipped)
+            .saturating_sub(self.current.len() as u64);
+        let average = (!self.spent.is_empty())
+            .then(|| self.spent.iter().sum::<Duration>().as_secs_f64() / self.spent.len() as f64);
+        // A clip without an ETA of its own is assumed to take the average.
+        let current: Option<u64> = self
+            .current
+            .iter()
+            .map(|c| {
+                c.eta.or_else(|| {
+                    average.map(|avg| (avg - c.since.elapsed().as_secs_f64()).max(0.0) as u64)
+                })
+            })
+            .sum();
+        match (average, current) {
+            (Some(avg), Some(cur)) => Some(cur + (avg * waiting as f64).round() as u64),
+            (None, Some(cur)) if waiting == 0 => Some(cur),
+            _ => None,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let current = self
+            .current
+            .iter()
+            .map(|c| {
+                json::obj([
+                    ("clip", c.clip.as_str().into()),
+                    ("game", c.game.as_str().into()),
+                    ("output", c.output.as_str().into()),
+                    ("index", c.index.into()),
+                    ("percent", c.percent.into()),
+                    ("eta_secs", c.eta.into()),
+                ])
+            })
+            .collect();
+        json::obj([
+            ("generator", "steamclipconverter".into()),
+            ("version", 1u32.into()),
+            (
+                "state",
+                if self.finished { "finished" } else { "running" }.into(),
+            ),
+            ("started_at", self.started_at.as_str().into()),
+            ("updated_at", now().into()),
+            ("total", self.total.into()),
+            ("converted", self.converted.into()),
+            ("failed", self.failed.into()),
+            ("skipped", self.skipped.into()),
+            ("current", Value::Arr(current)),
+            ("eta_secs", self.eta().into()),
+        ])
+    }
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
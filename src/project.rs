@@ -0,0 +1,232 @@
+//! `--export-project`: an editor project per session folder, so an edit can start with the
+//! session's clips laid out back to back and their timeline markers already placed.
+//!
+//! Written from session.json after every clip, so the project always matches the folder.
+
+use crate::{
+    json::{self, Value},
+    session::{self, Item},
+};
+use clap::ValueEnum;
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use steamclipconverter::atomic;
+
+/// Timeline rate. Steam records at 30 or 60 fps; 60 places cuts and markers exactly for both.
+const RATE: f64 = 60.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ProjectFormat {
+    /// OpenTimelineIO (`session.otio`), imported by DaVinci Resolve, Premiere (via plugin),
+    /// Kdenlive and others; clips link to the files and carry their markers.
+    Otio,
+    /// CMX 3600 edit decision list (`session.edl`); markers become `* LOC:` locator comments.
+    Edl,
+}
+
+impl ProjectFormat {
+    fn file_name(self) -> &'static str {
+        match self {
+            ProjectFormat::Otio => "session.otio",
+            ProjectFormat::Edl => "session.edl",
+        }
+    }
+}
+
+/// A clip going into the project.
+struct Entry<'a> {
+    file: &'a str,
+    frames: i64,
+    markers: Vec<(i64, &'a str)>,
+}
+
+fn frames(secs: f64) -> i64 {
+    (secs * RATE).round() as i64
+}
+
+/// Clips of the session with a known length; anything else can't be placed.
+fn entries(items: &[Item]) -> Vec<Entry<'_>> {
+    items
+        .iter()
+        .filter_map(|i| match i {
+            Item::Clip {
+                file,
+                duration: Some(d),
+                markers,
+                ..
+            } => Some(Entry {
+                file,
+                frames: frames(*d),
+                markers: markers
+                    .iter()
+                    .map(|m| (frames(m.offset), m.title.as_str()))
+                    .collect(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rewrite the project file of session folder `dir`.
+pub fn write(dir: &Path, title: &str, format: ProjectFormat) -> io::Result<PathBuf> {
+    let items = session::read_items(dir)?;
+    let clips = entries(&items);
+    let text = match format {
+        ProjectFormat::Otio => format!("{}\n", otio(dir, title, &clips)),
+        ProjectFormat::Edl => edl(title, &clips),
+    };
+    let path = dir.join(format.file_name());
+    atomic::write(&path, text)?;
+    Ok(path)
+}
+
+fn rational(value: i64) -> Value {
+    json::obj([
+        ("OTIO_SCHEMA", "RationalTime.1".into()),
+        ("rate", RATE.into()),
+        ("value", (value as f64).into()),
+    ])
+}
+
+fn range(start: i64, duration: i64) -> Value {
+    json::obj([
+        ("OTIO_SCHEMA", "TimeRange.1".into()),
+        ("start_time", rational(start)),
+        ("duration", rational(duration)),
+    ])
+}
+
+fn file_url(path: &Path) -> String {
+    let abs = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut s = abs.to_string_lossy().replace('\\', "/");
+    if !s.starts_with('/') {
+        s.insert(0, '/'); // C:/... -> /C:/...
+    }
+    let mut url = String::from("file://");
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                url.push(b as char)
+            }
+            _ => {
+                let _ = write!(url, "%{b:02X}");
+            }
+        }
+    }
+    url
+}
+
+fn otio(dir: &Path, title: &str, clips: &[Entry]) -> Value {
+    let children = clips
+        .iter()
+        .map(|c| {
+            let markers = c
+                .markers
+                .iter()
+                .map(|(at, name)| {
+                    json::obj([
+                        ("OTIO_SCHEMA", "Marker.2".into()),
+                        ("name", (*name).into()),
+                        ("color", "RED".into()),
+                        ("marked_range", range(*at, 0)),
+                        ("comment", "".into()),
+                        ("metadata", Value::Obj(Vec::new())),
+                    ])
+                })
+                .collect();
+            json::obj([
+                ("OTIO_SCHEMA", "Clip.2".into()),
+                ("name", c.file.into()),
+                ("source_range", range(0, c.frames)),
+                ("effects", Value::Arr(Vec::new())),
+                ("markers", Value::Arr(markers)),
+                ("enabled", true.into()),
+                ("metadata", Value::Obj(Vec::new())),
+                (
+                    "media_references",
+                    json::obj([(
+                        "DEFAULT_MEDIA",
+                        json::obj([
+                            ("OTIO_SCHEMA", "ExternalReference.1".into()),
+                            ("name", c.file.into()),
+                            ("target_url", file_url(&dir.join(c.file)).into()),
+                            ("available_range", range(0, c.frames)),
+                            ("metadata", Value::Obj(Vec::new())),
+                        ]),
+                    )]),
+                ),
+                ("active_media_reference_key", "DEFAULT_MEDIA".into()),
+            ])
+        })
+        .collect();
+    let track = json::obj([
+        ("OTIO_SCHEMA", "Track.1".into()),
+        ("name", "V1".into()),
+        ("kind", "Video".into()),
+        ("children", Value::Arr(children)),
+        ("effects", Value::Arr(Vec::new())),
+        ("markers", Value::Arr(Vec::new())),
+        ("enabled", true.into()),
+        ("source_range", Value::Null),
+        ("metadata", Value::Obj(Vec::new())),
+    ]);
+    json::obj([
+        ("OTIO_SCHEMA", "Timeline.1".into()),
+        ("name", title.into()),
+        ("global_start_time", Value::Null),
+        ("metadata", Value::Obj(Vec::new())),
+        (
+            "tracks",
+            json::obj([
+                ("OTIO_SCHEMA", "Stack.1".into()),
+                ("name", "tracks".into()),
+                ("children", Value::Arr(vec![track])),
+                ("effects", Value::Arr(Vec::new())),
+                ("markers", Value::Arr(Vec::new())),
+                ("enabled", true.into()),
+                ("source_range", Value::Null),
+                ("metadata", Value::Obj(Vec::new())),
+            ]),
+        ),
+    ])
+}
+
+/// Non-drop-frame timecode at [`RATE`].
+fn timecode(frames: i64) -> String {
+    let fps = RATE as i64;
+    let (secs, ff) = (frames / fps, frames % fps);
+    format!(
+        "{:02}:{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        ff
+    )
+}
+
+fn edl(title: &str, clips: &[Entry]) -> String {
+    // Record side starts at the conventional 01:00:00:00.
+    let mut rec = frames(3600.0);
+    let mut out = format!("TITLE: {title}\nFCM: NON-DROP FRAME\n\n");
+    for (n, c) in clips.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{:03}  AX       V     C        {} {} {} {}",
+            n + 1,
+            timecode(0),
+            timecode(c.frames),
+            timecode(rec),
+            timecode(rec + c.frames)
+        );
+        let _ = writeln!(out, "* FROM CLIP NAME: {}", c.file);
+        for (at, name) in &c.markers {
+            let _ = writeln!(out, "* LOC: {} RED     {}", timecode(rec + at), name);
+        }
+        out.push('\n');
+        rec += c.frames;
+    }
+    out
+}
@@ -0,0 +1,199 @@
+//! Persistent run queue: the clips a run decided to work on, and how far each one got, kept
+//! as an append-only journal so a crash or reboot mid-backlog resumes where it stopped
+//! instead of rescanning and deciding again.
+//!
+//! First line: the queue (input, output, clips in order). Then one line per step:
+//! `{"i":3,"state":"converted",...}` once the output is written, `{"i":3,"state":"done"}`
+//! once nothing is left to do for that clip. The file is removed when a run completes.
+
+use crate::json::{self, Value};
+use chrono::{DateTime, Utc};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use steamclipconverter::{ClipDir, appdirs, atomic, snapshot::fnv1a64};
+
+const KIND: &str = "steamclipconverter.queue";
+
+/// A clip whose output was written but not yet post-processed.
+#[derive(Clone, Debug)]
+pub struct Converted {
+    pub out_path: PathBuf,
+    pub game_name: String,
+    pub fname: String,
+    pub target_dir: PathBuf,
+    pub start: Option<DateTime<Utc>>,
+    pub seq: Option<u32>,
+}
+
+/// Where a queued clip stands.
+#[derive(Clone, Debug)]
+pub enum Progress {
+    Pending,
+    Converted(Converted),
+    Done,
+}
+
+pub struct RunQueue {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+fn queue_path(input: &Path, output: &Path) -> Option<PathBuf> {
+    let canon = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let key = format!(
+        "{}\0{}",
+        canon(input).to_string_lossy(),
+        canon(output).to_string_lossy()
+    );
+    Some(appdirs::user_data_dir()?.join(format!("queue-{:016x}.jsonl", fnv1a64(key.as_bytes()))))
+}
+
+fn clip_json(c: &ClipDir) -> Value {
+    json::obj([
+        ("dir", c.dir.to_string_lossy().as_ref().into()),
+        ("appid", c.appid.into()),
+        ("date", c.date.as_str().into()),
+        ("time", c.time.as_str().into()),
+    ])
+}
+
+fn clip_from(v: &Value) -> Option<ClipDir> {
+    Some(ClipDir {
+        dir: PathBuf::from(v.get("dir")?.as_str()?),
+        appid: v.get("appid")?.as_u64()? as u32,
+        date: v.str_field("date").to_string(),
+        time: v.str_field("time").to_string(),
+    })
+}
+
+/// An interrupted run's queue for this input and output: every clip with its progress.
+/// `None` when there is none, nothing in it is left to do, or it can't be read (the next
+/// run's queue then replaces it).
+pub fn load(input: &Path, output: &Path) -> Option<Vec<(ClipDir, Progress)>> {
+    let path = queue_path(input, output)?;
+    let txt = fs::read_to_string(&path).ok()?;
+    let mut lines = txt.lines();
+    let header = json::parse(lines.next()?).ok()?;
+    if header.str_field("kind") != KIND {
+        return None;
+    }
+    let Some(Value::Arr(clips)) = header.get("clips") else {
+        return None;
+    };
+    let mut queue: Vec<(ClipDir, Progress)> = clips
+        .iter()
+        .filter_map(clip_from)
+        .map(|c| (c, Progress::Pending))
+        .collect();
+    // A torn last line (crash mid-write) is simply ignored.
+    for ev in lines.filter_map(|l| json::parse(l).ok()) {
+        let Some(slot) = ev
+            .get("i")
+            .and_then(Value::as_u64)
+            .and_then(|i| queue.get_mut(i as usize))
+        else {
+            continue;
+        };
+        slot.1 = match ev.str_field("state") {
+            "converted" => Progress::Converted(Converted {
+                out_path: PathBuf::from(ev.str_field("output")),
+                game_name: ev.str_field("game").to_string(),
+                fname: ev.str_field("file").to_string(),
+                target_dir: PathBuf::from(ev.str_field("target_dir")),
+                start: DateTime::parse_from_rfc3339(ev.str_field("start"))
+                    .ok()
+                    .map(|t| t.with_timezone(&Utc)),
+                seq: ev.get("seq").and_then(Value::as_u64).map(|n| n as u32),
+            }),
+            "done" => Progress::Done,
+            _ => continue,
+        };
+    }
+    if queue.iter().all(|(_, p)| matches!(p, Progress::Done)) {
+        let _ = fs::remove_file(&path);
+        return None;
+    }
+    Some(queue)
+}
+
+impl RunQueue {
+    /// Start a fresh journal for `clips`, replacing any previous one.
+    pub fn create(input: &Path, output: &Path, clips: &[ClipDir]) -> io::Result<Self> {
+        let path = queue_path(input, output)
+            .ok_or_else(|| io::Error::other("no per-user data directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let header = json::obj([
+            ("kind", KIND.into()),
+            ("input", input.to_string_lossy().as_ref().into()),
+            ("output", output.to_string_lossy().as_ref().into()),
+            ("clips", Value::Arr(clips.iter().map(clip_json).collect())),
+        ]);
+        atomic::write(&path, format!("{header}\n"))?;
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(RunQueue {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Keep appending to the journal of the interrupted run that [`load`] found.
+    pub fn reopen(input: &Path, output: &Path) -> io::Result<Self> {
+        let path = queue_path(input, output)
+            .ok_or_else(|| io::Error::other("no per-user data directory"))?;
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(RunQueue {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    fn append(&self, ev: Value) {
+        let mut f = self.file.lock().unwrap();
+        // Flushed per line; losing the journal only costs redoing a step, so errors are
+        // reported but don't stop the run.
+        if let Err(e) = writeln!(f, "{ev}").and_then(|()| f.sync_data()) {
+            eprintln!(
+                "[warn] cannot update run queue {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    pub fn mark_converted(&self, i: usize, c: &Converted) {
+        self.append(json::obj([
+            ("i", (i as u64).into()),
+            ("state", "converted".into()),
+            ("output", c.out_path.to_string_lossy().as_ref().into()),
+            ("game", c.game_name.as_str().into()),
+            ("file", c.fname.as_str().into()),
+            ("target_dir", c.target_dir.to_string_lossy().as_ref().into()),
+            (
+                "start",
+                c.start
+                    .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+                    .into(),
+            ),
+            ("seq", c.seq.map(u64::from).into()),
+        ]));
+    }
+
+    pub fn mark_done(&self, i: usize) {
+        self.append(json::obj([
+            ("i", (i as u64).into()),
+            ("state", "done".into()),
+        ]));
+    }
+
+    /// The run completed: nothing to resume.
+    pub fn finish(self) {
+        drop(self.file);
+        let _ = fs::remove_file(&self.path);
+    }
+}
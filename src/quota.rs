@@ -0,0 +1,262 @@
+//! `quota` subcommand and `--make-room`: how full Steam's recording folder is compared with
+//! the storage limit set in Steam, and converting just enough to get back under it.
+//!
+//! Steam keeps the limit in its VDF config files rather than anywhere documented, so the
+//! lookup is best effort: any recording setting whose key looks like a size limit is taken.
+//! `--quota-limit` overrides it when nothing is found or the guess is wrong.
+
+use crate::{json, resolve_input_dir};
+use clap::{ArgAction, Args};
+use regex::Regex;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Args, Debug)]
+pub struct QuotaArgs {
+    /// Directory to search (defaults to <SteamRoot>/userdata with a warning).
+    input: Option<PathBuf>,
+
+    /// Storage limit to compare against instead of the one read from Steam's config
+    /// (e.g. 20G, 500M).
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    limit: Option<u64>,
+
+    /// Print one JSON object instead of a table.
+    #[arg(long, action = ArgAction::SetTrue)]
+    json: bool,
+}
+
+/// The recording storage limit and the file it came from.
+#[derive(Clone, Debug)]
+pub struct StorageLimit {
+    pub bytes: u64,
+    pub source: Option<PathBuf>,
+}
+
+/// Parse a size like `20G`, `1.5GB`, `500MiB` or `1048576` (bytes). Units are binary.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let split = t
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(t.len());
+    let (num, unit) = t.split_at(split);
+    let n: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid size {s:?} (expected e.g. 20G, 500M)"))?;
+    let mult: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        other => return Err(format!("unknown size unit {other:?} in {s:?}")),
+    };
+    Ok((n * mult as f64) as u64)
+}
+
+/// Human-readable size, e.g. `12.3 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut v = bytes as f64;
+    let mut unit = 0;
+    while v >= 1024.0 && unit < UNITS.len() - 1 {
+        v /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{v:.1} {}", UNITS[unit])
+    }
+}
+
+/// Total size of all files below `dir`.
+pub fn tree_size(dir: &Path) -> u64 {
+    let mut total = 0;
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        for ent in fs::read_dir(&d).into_iter().flatten().flatten() {
+            match ent.file_type() {
+                Ok(t) if t.is_dir() => stack.push(ent.path()),
+                Ok(t) if t.is_file() => total += ent.metadata().map(|m| m.len()).unwrap_or(0),
+                _ => {}
+            }
+        }
+    }
+    total
+}
+
+/// Every `gamerecordings` folder below `input_dir` (or `input_dir` itself).
+pub fn recording_dirs(input_dir: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    let mut stack = vec![input_dir.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        if d.file_name().and_then(|n| n.to_str()) == Some("gamerecordings") {
+            out.push(d);
+            continue;
+        }
+        for ent in fs::read_dir(&d).into_iter().flatten().flatten() {
+            let p = ent.path();
+            let name = ent.file_name();
+            // Clip folders are leaves; don't walk into thousands of chunks looking for more.
+            let name = name.to_string_lossy();
+            if p.is_dir() && !name.starts_with("fg_") && !name.starts_with("bg_") {
+                stack.push(p);
+            }
+        }
+    }
+    out.sort();
+    out
+}
+
+/// Bytes Steam's recordings take up below `input_dir`.
+pub fn recording_usage(input_dir: &Path) -> u64 {
+    recording_dirs(input_dir).iter().map(|d| tree_size(d)).sum()
+}
+
+/// A size-limit value from VDF text: a `"key" "value"` pair whose key is about recording and
+/// a limit. Values are in GB unless the key says MB or the number is clearly bytes.
+fn limit_from_vdf(text: &str) -> Option<u64> {
+    let re = Regex::new(r#""([^"]+)"\s+"(\d+(?:\.\d+)?)""#).unwrap();
+    re.captures_iter(text).find_map(|c| {
+        let key = c[1].to_ascii_lowercase();
+        let about_limit = ["limit", "max", "quota", "storage"]
+            .iter()
+            .any(|w| key.contains(w));
+        if !key.contains("record") || !about_limit {
+            return None;
+        }
+        let n: f64 = c[2].parse().ok().filter(|n| *n > 0.0)?;
+        let bytes = if key.contains("mb") {
+            n * (1u64 << 20) as f64
+        } else if key.contains("bytes") || n >= (1u64 << 20) as f64 {
+            n
+        } else {
+            n * (1u64 << 30) as f64
+        };
+        Some(bytes as u64)
+    })
+}
+
+/// Config files that may carry the recording settings: each account's localconfig.vdf
+/// below `input_dir`, then the Steam roots' global config.
+fn config_candidates(input_dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![input_dir.join("config").join("localconfig.vdf")];
+    let mut accounts: Vec<PathBuf> = fs::read_dir(input_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path().join("config").join("localconfig.vdf"))
+        .collect();
+    accounts.sort();
+    files.extend(accounts);
+    // userdata/<id>/... -> <SteamRoot>/config/config.vdf
+    for anc in input_dir.ancestors() {
+        if anc.file_name().and_then(|n| n.to_str()) == Some("userdata")
+            && let Some(root) = anc.parent()
+        {
+            files.push(root.join("config").join("config.vdf"));
+        }
+    }
+    for root in crate::steam::default_roots() {
+        files.push(root.join("config").join("config.vdf"));
+    }
+    files
+}
+
+/// The limit from `explicit`, or else the first one found in Steam's config.
+pub fn storage_limit(explicit: Option<u64>, input_dir: &Path) -> Option<StorageLimit> {
+    if let Some(bytes) = explicit {
+        return Some(StorageLimit {
+            bytes,
+            source: None,
+        });
+    }
+    config_candidates(input_dir).into_iter().find_map(|f| {
+        let text = fs::read_to_string(&f).ok()?;
+        limit_from_vdf(&text).map(|bytes| StorageLimit {
+            bytes,
+            source: Some(f),
+        })
+    })
+}
+
+pub fn run(args: QuotaArgs) -> i32 {
+    let input_dir = resolve_input_dir(args.input.clone());
+    let dirs: Vec<(PathBuf, u64)> = recording_dirs(&input_dir)
+        .into_iter()
+        .map(|d| {
+            let size = tree_size(&d);
+            (d, size)
+        })
+        .collect();
+    let used: u64 = dirs.iter().map(|(_, s)| s).sum();
+    let limit = storage_limit(args.limit, &input_dir);
+
+    if args.json {
+        let line = json::obj([
+            ("used", used.into()),
+            ("limit", limit.as_ref().map(|l| l.bytes).into()),
+            (
+                "limit_source",
+                limit
+                    .as_ref()
+                    .and_then(|l| l.source.as_ref())
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .into(),
+            ),
+            (
+                "headroom",
+                limit.as_ref().map(|l| l.bytes.saturating_sub(used)).into(),
+            ),
+            (
+                "folders",
+                json::Value::Arr(
+                    dirs.iter()
+                        .map(|(d, s)| {
+                            json::obj([
+                                ("dir", d.to_string_lossy().as_ref().into()),
+                                ("used", (*s).into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ]);
+        println!("{line}");
+        return 0;
+    }
+
+    for (d, s) in &dirs {
+        println!("{:>10}  {}", format_size(*s), d.display());
+    }
+    match &limit {
+        Some(l) => {
+            let pct = if l.bytes > 0 {
+                used as f64 * 100.0 / l.bytes as f64
+            } else {
+                0.0
+            };
+            println!(
+                "\n{} of {} used ({:.0}%), {} headroom",
+                format_size(used),
+                format_size(l.bytes),
+                pct,
+                format_size(l.bytes.saturating_sub(used))
+            );
+            match &l.source {
+                Some(src) => println!("limit read from {}", src.display()),
+                None => println!("limit from --limit"),
+            }
+        }
+        None => {
+            println!(
+                "\n{} used; no recording storage limit found in Steam's config (pass --limit)",
+                format_size(used)
+            );
+        }
+    }
+    0
+}
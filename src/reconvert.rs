@@ -0,0 +1,223 @@
+//! `reconvert` subcommand: redo catalogued outputs from their original clip folders.
+
+use crate::{
+    EncodeArgs, bad_trim,
+    catalog::{Catalog, CatalogEntry},
+    encrypt::Encryption,
+    ffmpeg_preflight, missing_branding, record_output, remux_options_for, stamp_output, tag_output,
+    timestamp::{self, TimestampSource},
+};
+use clap::{ArgAction, Args};
+use std::{fs, path::PathBuf};
+use steamclipconverter::{
+    ClipDir, Converter, Error, Progress,
+    ffmpeg::{Container, RemuxOptions},
+    probe::probe_duration,
+};
+
+#[derive(Args, Debug)]
+pub struct ReconvertArgs {
+    /// Only outputs matching KEY=VALUE (repeatable, all must match). Keys: appid, game
+    /// (case-insensitive substring), date (YYYYMMDD prefix, e.g. 202508), output (path substring).
+    #[arg(long, value_name = "KEY=VALUE", action = ArgAction::Append)]
+    filter: Vec<String>,
+
+    /// Catalog to read (defaults to the user catalog).
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+
+    /// Where each clip's record start (file times) comes from.
+    #[arg(long, value_enum, default_value_t)]
+    timestamp_source: TimestampSource,
+
+    #[command(flatten)]
+    encode: EncodeArgs,
+}
+
+/// `--filter KEY=VALUE` over catalog entries; also used by `review`.
+pub struct Filter {
+    key: String,
+    value: String,
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (k, v) = s
+            .split_once('=')
+            .ok_or_else(|| format!("filter '{s}' is not KEY=VALUE"))?;
+        if !matches!(k, "appid" | "game" | "date" | "output") {
+            return Err(format!("unknown filter key '{k}'"));
+        }
+        Ok(Filter {
+            key: k.to_string(),
+            value: v.to_string(),
+        })
+    }
+
+    pub fn matches(&self, e: &CatalogEntry) -> bool {
+        match self.key.as_str() {
+            "appid" => e.appid.to_string() == self.value,
+            "game" => e.game.to_lowercase().contains(&self.value.to_lowercase()),
+            "date" => e.date.starts_with(&self.value),
+            "output" => e.output.to_string_lossy().contains(&self.value),
+            _ => false,
+        }
+    }
+}
+
+pub fn run(args: ReconvertArgs) -> i32 {
+    if args.encode.container.is_some() {
+        eprintln!("ERROR: reconvert keeps each output's format; --container can't be used");
+        return 2;
+    }
+    if let Some(e) = missing_branding(&args.encode)
+        .or_else(|| bad_trim(&args.encode))
+        .or_else(|| ffmpeg_preflight(&args.encode))
+    {
+        eprintln!("ERROR: {}", e);
+        return 2;
+    }
+    let filters = match args
+        .filter
+        .iter()
+        .map(|f| Filter::parse(f))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return 2;
+        }
+    };
+
+    let Some(path) = args.catalog.clone().or_else(Catalog::default_path) else {
+        eprintln!("ERROR: no catalog location; pass --catalog");
+        return 2;
+    };
+    let mut catalog = match Catalog::open(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ERROR: cannot read catalog {}: {}", path.display(), e);
+            return 2;
+        }
+    };
+
+    let selected: Vec<CatalogEntry> = catalog
+        .entries()
+        .into_iter()
+        .filter(|e| filters.iter().all(|f| f.matches(e)))
+        .cloned()
+        .collect();
+    if selected.is_empty() {
+        println!("No catalogued outputs match.");
+        return 0;
+    }
+    println!("Reconverting {} output(s).", selected.len());
+
+    let (mut done, mut skipped, mut failed) = (0, 0, 0);
+    for entry in selected {
+        println!("== {} ==", entry.output.display());
+        if entry.review_deleted() {
+            println!("[skip] deleted during review");
+            skipped += 1;
+            continue;
+        }
+        if !entry.source.join("session.mpd").is_file() {
+            eprintln!(
+                "[skip] original clip no longer available: {}",
+                entry.source.display()
+            );
+            skipped += 1;
+            continue;
+        }
+        let clip = ClipDir {
+            dir: entry.source.clone(),
+            appid: entry.appid,
+            date: entry.date.clone(),
+            time: entry.time.clone(),
+        };
+
+        // Encrypted outputs are converted as `<name>.mp4` and encrypted again to the same
+        // recipients.
+        let encryption = (!entry.recipients.is_empty()).then(|| Encryption {
+            recipients: entry.recipients.clone(),
+        });
+        let plain = match &encryption {
+            Some(_) => entry.output.with_extension(""),
+            None => entry.output.clone(),
+        };
+
+        // Write next to the old file and swap it in only once ffmpeg succeeded.
+        let ext = plain
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "mp4".into());
+        let tmp = plain.with_extension(format!("reconvert-tmp.{ext}"));
+
+        let mut opts = RemuxOptions {
+            container: Container::parse(&ext.to_ascii_lowercase()).unwrap_or_default(),
+            ..remux_options_for(&args.encode, &clip)
+        };
+        let start = timestamp::record_start(&clip, args.timestamp_source);
+        tag_output(&mut opts, &clip, &entry.game, start);
+        let mut converter = Converter::new().options(opts).backend(args.encode.backend);
+        if let Some(remote) = &args.encode.remote {
+            converter = converter.remote(remote.clone());
+        }
+        match converter.convert(&clip, &tmp, &mut |_: &Progress| {}) {
+            Ok(()) => {}
+            Err(e @ Error::Spawn { .. }) => {
+                eprintln!("[fail] {}", e);
+                return 1;
+            }
+            Err(e) => {
+                eprintln!("[fail] {}", e);
+                let _ = fs::remove_file(&tmp);
+                failed += 1;
+                continue;
+            }
+        }
+        let mut duration = None;
+        let tmp = match &encryption {
+            Some(enc) => {
+                duration = probe_duration(&tmp).ok();
+                match enc.encrypt(&tmp) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        eprintln!("[fail] encrypting {}: {}", tmp.display(), e);
+                        let _ = fs::remove_file(&tmp);
+                        failed += 1;
+                        continue;
+                    }
+                }
+            }
+            None => tmp,
+        };
+        if let Err(e) = fs::rename(&tmp, &entry.output) {
+            eprintln!("[fail] replacing {}: {}", entry.output.display(), e);
+            let _ = fs::remove_file(&tmp);
+            failed += 1;
+            continue;
+        }
+        if let Err(e) = stamp_output(&entry.output, &clip, start) {
+            eprintln!("[warn] {}", e);
+        }
+        record_output(
+            &mut catalog,
+            &clip,
+            &entry.output,
+            &entry.game,
+            None,
+            entry.seq,
+            encryption.as_ref().map(|enc| (enc, duration)),
+        );
+        println!("[ok] rewrote {}", entry.output.display());
+        done += 1;
+    }
+
+    println!(
+        "\n{} reconverted, {} skipped, {} failed.",
+        done, skipped, failed
+    );
+    if failed > 0 { 1 } else { 0 }
+}
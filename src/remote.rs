@@ -0,0 +1,357 @@
+//! `pull` subcommand: convert the clips an `agent` offers (see `agent.rs`), fetching each one
+//! into a staging folder just before it converts, and optionally telling the agent to delete
+//! its copy once the converted file is recorded.
+//!
+//! Every segment is checked against the SHA-256 the agent reports, so segments already
+//! staged from an earlier attempt aren't downloaded again. Interrupted downloads resume
+//! from where they stopped (`Range`), within a run and, since a clip that failed to download
+//! keeps its staging folder, in the next run too.
+
+use crate::{
+    agent::DEFAULT_PORT,
+    auth, mdns,
+    report::say,
+    sha256::{self, TreeDigest},
+    staged::StagedInput,
+};
+use clap::{ArgAction, Args};
+use filetime::{FileTime, set_file_mtime};
+use std::{
+    collections::HashSet,
+    env,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io,
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+use steamclipconverter::{
+    ClipDir,
+    client::{self, Client, ClipFile},
+    snapshot::fnv1a64,
+};
+
+#[derive(Args, Debug)]
+pub struct PullArgs {
+    /// Agent to pull from, as HOST or HOST:PORT (e.g. deck.local); found with mDNS when omitted.
+    #[arg(long, value_name = "HOST[:PORT]")]
+    pub from: Option<String>,
+
+    /// Ask the agent to delete each clip once it is converted and its segments verified
+    /// (the agent must run with --allow-delete).
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub delete_remote: bool,
+
+    /// Token the agent was started with.
+    #[arg(long, value_name = "TOKEN", conflicts_with = "token_file")]
+    pub token: Option<String>,
+
+    /// Read the token from the first line of FILE.
+    #[arg(long, value_name = "FILE")]
+    pub token_file: Option<PathBuf>,
+
+    /// User and password for an agent started with --basic-auth.
+    #[arg(long, value_name = "USER:PASSWORD", conflicts_with_all = ["token", "token_file"])]
+    pub basic_auth: Option<String>,
+}
+
+struct RemoteClip {
+    /// Where it is staged.
+    clip: ClipDir,
+    info: client::Clip,
+}
+
+pub struct Remote {
+    client: Client,
+    staging: PathBuf,
+    clips: Vec<RemoteClip>,
+    delete: bool,
+    /// Clips whose every file arrived; the others keep their staged files for a retry.
+    complete: Mutex<HashSet<PathBuf>>,
+}
+
+/// Attempts per file before the clip counts as failed.
+const ATTEMPTS: u32 = 5;
+
+/// Turn `--from` into `host:port`, browsing the network when it is missing or doesn't resolve.
+fn resolve_agent(from: Option<&str>) -> io::Result<String> {
+    if let Some(from) = from {
+        let authority = if from
+            .rsplit_once(':')
+            .is_some_and(|(_, p)| p.parse::<u16>().is_ok())
+        {
+            from.to_string()
+        } else {
+            format!("{from}:{DEFAULT_PORT}")
+        };
+        if authority
+            .to_socket_addrs()
+            .is_ok_and(|mut a| a.next().is_some())
+        {
+            return Ok(authority);
+        }
+        // Not every OS resolves .local names; ask the agents directly.
+        let name = from.trim_end_matches(".local").to_lowercase();
+        let found = mdns::browse(Duration::from_secs(2))?;
+        return found
+            .iter()
+            .find(|f| f.instance.to_lowercase().ends_with(&name))
+            .map(|f| f.addr.to_string())
+            .ok_or_else(|| io::Error::other(format!("cannot resolve agent {from}")));
+    }
+    let found = mdns::browse(Duration::from_secs(2))?;
+    match found.as_slice() {
+        [] => Err(io::Error::other(
+            "no agent answered on the local network; pass --from HOST",
+        )),
+        [one] => {
+            say!("[pull] found {} at {}", one.instance, one.addr);
+            Ok(one.addr.to_string())
+        }
+        many => {
+            let names: Vec<String> = many
+                .iter()
+                .map(|f| format!("{} ({})", f.instance, f.addr))
+                .collect();
+            Err(io::Error::other(format!(
+                "several agents answered, pick one with --from: {}",
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+impl Remote {
+    /// Find the agent and list its clips.
+    pub fn connect(args: &PullArgs) -> io::Result<Remote> {
+        let mut client = Client::new(resolve_agent(args.from.as_deref())?);
+        if let Some(path) = &args.token_file {
+            client = client.with_token(&auth::read_token_file(path)?);
+        } else if let Some(t) = &args.token {
+            client = client.with_token(t);
+        } else if let Some((user, pass)) =
+            args.basic_auth.as_deref().and_then(|b| b.split_once(':'))
+        {
+            client = client.with_basic_auth(user, pass);
+        }
+        let listing = client.list_clips().map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => {
+                io::Error::other(format!("{e}; pass --token or --basic-auth"))
+            }
+            _ => e,
+        })?;
+        let staging = env::temp_dir().join(format!(
+            "steamclipconverter-pull-{:016x}",
+            fnv1a64(client.authority().as_bytes())
+        ));
+        let mut clips = Vec::new();
+        let mut settling = 0;
+        for info in listing.clips {
+            // The id becomes a local folder name; only fg_* names are accepted.
+            let Some(clip) = ClipDir::from_path(&staging.join(&info.id))
+                .filter(|_| !info.id.contains(['/', '\\']))
+            else {
+                continue;
+            };
+            if !info.ready {
+                settling += 1;
+                continue;
+            }
+            clips.push(RemoteClip { clip, info });
+        }
+        say!(
+            "[pull] {} offers {} clip(s){}",
+            client.authority(),
+            clips.len(),
+            if settling > 0 {
+                format!(", {settling} still recording or settling")
+            } else {
+                String::new()
+            }
+        );
+        Ok(Remote {
+            client,
+            staging,
+            clips,
+            delete: args.delete_remote,
+            complete: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn find(&self, clip: &ClipDir) -> Option<&RemoteClip> {
+        self.clips.iter().find(|c| c.clip.dir == clip.dir)
+    }
+
+    /// Download `file` to `dest`, resuming from a `.part` file left by an earlier attempt.
+    fn download(&self, id: &str, file: &ClipFile, sha: &str, dest: &Path) -> io::Result<()> {
+        let mut part = OsString::from(dest.as_os_str());
+        part.push(".part");
+        let part = PathBuf::from(part);
+        let mut attempt = 1;
+        loop {
+            let res = self.download_once(id, file, &part).and_then(|()| {
+                if sha256::file_sha256(&part)? == sha {
+                    return Ok(());
+                }
+                // Resuming can't fix a corrupt prefix; start over.
+                let _ = fs::remove_file(&part);
+                Err(io::Error::other("checksum mismatch"))
+            });
+            match res {
+                Ok(()) => break,
+                Err(e) if attempt < ATTEMPTS => {
+                    let kept = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+                    eprintln!(
+                        "[pull] {}/{}: {} (retrying with {} of {} bytes kept)",
+                        id, file.path, e, kept, file.size
+                    );
+                    thread::sleep(Duration::from_secs(1 << attempt));
+                    attempt += 1;
+                }
+                Err(e) => return Err(io::Error::other(format!("{}: {}", file.path, e))),
+            }
+        }
+        fs::rename(&part, dest)?;
+        set_file_mtime(dest, FileTime::from_unix_time(file.mtime, 0))
+    }
+
+    fn download_once(&self, id: &str, file: &ClipFile, part: &Path) -> io::Result<()> {
+        let have = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+        if have == file.size && have > 0 {
+            return Ok(());
+        }
+        let from = if have < file.size { have } else { 0 };
+        let mut download = self.client.download_file(id, &file.path, from)?;
+        let mut out = if download.partial {
+            OpenOptions::new().append(true).open(part)?
+        } else {
+            // The agent sent the whole file after all.
+            File::create(part)?
+        };
+        io::copy(&mut download.body, &mut out)?;
+        let got = out.metadata()?.len();
+        if got != file.size {
+            return Err(io::Error::other(format!(
+                "connection dropped at {} of {} bytes",
+                got, file.size
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl StagedInput for Remote {
+    fn origin(&self) -> PathBuf {
+        PathBuf::from(format!("http://{}/", self.client.authority()))
+    }
+
+    fn clips(&self) -> Vec<ClipDir> {
+        self.clips.iter().map(|c| c.clip.clone()).collect()
+    }
+
+    /// Download every file of the clip that isn't already staged with the right checksum.
+    fn fetch(&self, clip: &ClipDir) -> io::Result<()> {
+        let remote = self
+            .find(clip)
+            .ok_or_else(|| io::Error::other("not a clip of this agent"))?;
+        let id = &remote.info.id;
+        let files = &remote.info.files;
+        let sums = self.client.clip_checksums(id)?;
+        let mut missing = Vec::new();
+        for file in files {
+            if file
+                .path
+                .split('/')
+                .any(|p| p.is_empty() || p == "." || p == "..")
+            {
+                return Err(io::Error::other(format!(
+                    "refusing file path {}",
+                    file.path
+                )));
+            }
+            let sha = sums
+                .iter()
+                .find(|s| s.path == file.path)
+                .map(|s| s.sha256.as_str())
+                .ok_or_else(|| io::Error::other(format!("no checksum for {}", file.path)))?;
+            let dest = clip.dir.join(&file.path);
+            let present = fs::metadata(&dest).is_ok_and(|m| m.len() == file.size)
+                && sha256::file_sha256(&dest).is_ok_and(|s| s == sha);
+            if !present {
+                missing.push((file, sha, dest));
+            }
+        }
+        if missing.len() < files.len() {
+            say!(
+                "[pull] {}: {} of {} file(s) already here, downloading the rest",
+                id,
+                files.len() - missing.len(),
+                files.len()
+            );
+        }
+        for (file, sha, dest) in missing {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            self.download(id, file, sha, &dest)?;
+        }
+        // Creating the folder counts as a write for the settle check; date it like its files.
+        if let Some(newest) = files.iter().map(|f| f.mtime).max() {
+            set_file_mtime(&clip.dir, FileTime::from_unix_time(newest, 0))?;
+        }
+        self.complete.lock().unwrap().insert(clip.dir.clone());
+        Ok(())
+    }
+
+    fn source_of(&self, clip: &ClipDir) -> ClipDir {
+        let id = self
+            .find(clip)
+            .map(|c| c.info.id.as_str())
+            .unwrap_or_default();
+        ClipDir {
+            dir: PathBuf::from(format!(
+                "http://{}/api/clips/{}",
+                self.client.authority(),
+                id
+            )),
+            ..clip.clone()
+        }
+    }
+
+    fn game_name(&self, clip: &ClipDir) -> Option<String> {
+        self.find(clip)?.info.game.clone()
+    }
+
+    fn deletes_source(&self) -> bool {
+        self.delete
+    }
+
+    /// Ask the agent to delete its copy; it checks the digest against its own files first.
+    fn finished(&self, clip: &ClipDir, digest: &TreeDigest) {
+        let Some(remote) = self.find(clip) else {
+            return;
+        };
+        let id = &remote.info.id;
+        match self.client.delete_clip(id, &digest.sha256) {
+            Ok(()) => say!("[del] {} deleted on the agent", id),
+            Err(e) => eprintln!("[warn] agent kept {}: {}", id, e),
+        }
+    }
+
+    /// Partly downloaded clips stay staged so the next pull resumes them.
+    fn release(&self, clip: &ClipDir) {
+        if clip.dir.starts_with(&self.staging) && self.complete.lock().unwrap().remove(&clip.dir) {
+            let _ = fs::remove_dir_all(&clip.dir);
+        }
+    }
+}
+
+impl Drop for Remote {
+    fn drop(&mut self) {
+        // Only goes when nothing was left for a later run.
+        let _ = fs::remove_dir(&self.staging);
+    }
+}
@@ -0,0 +1,112 @@
+//! `catalog repair`: clean up after a run that was cut off by a crash or power loss.
+//!
+//! Catalog lines torn mid-write are moved to `<catalog>.bad`, and outputs that were still
+//! being written (`*.partial.*`, see `steamclipconverter::atomic`) are removed from the
+//! output folder, so the next run converts those clips again.
+
+use crate::catalog::{self, Catalog};
+use clap::{Args, Subcommand};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Args, Debug)]
+pub struct CatalogArgs {
+    #[command(subcommand)]
+    cmd: CatalogCmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum CatalogCmd {
+    /// Set aside damaged catalog lines and remove half-written outputs.
+    Repair(RepairArgs),
+}
+
+#[derive(Args, Debug)]
+struct RepairArgs {
+    /// Catalog to repair (defaults to the user catalog; a portable catalog is
+    /// steamclipconverter-catalog.jsonl in the output folder).
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+
+    /// Output folder to remove unfinished `*.partial.*` files from (searched recursively).
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(args: CatalogArgs) -> i32 {
+    let CatalogCmd::Repair(args) = args.cmd;
+    let Some(path) = args.catalog.or_else(Catalog::default_path) else {
+        eprintln!("ERROR: no catalog location; pass --catalog");
+        return 2;
+    };
+    let mut code = 0;
+    if path.is_file() {
+        match catalog::repair(&path) {
+            Ok(r) if r.damaged == 0 && !r.truncated => {
+                println!("{}: {} record(s), intact", path.display(), r.records);
+            }
+            Ok(r) => {
+                println!(
+                    "{}: {} record(s) kept, {} damaged line(s) moved to {}.bad",
+                    path.display(),
+                    r.records,
+                    r.damaged,
+                    path.display()
+                );
+            }
+            Err(e) => {
+                eprintln!("ERROR: cannot repair {}: {}", path.display(), e);
+                code = 1;
+            }
+        }
+        // Left by a rewrite that never got to its rename.
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        if fs::remove_file(&tmp).is_ok() {
+            println!("removed {}", Path::new(&tmp).display());
+        }
+    } else {
+        println!("{}: no catalog", path.display());
+    }
+
+    if let Some(dir) = &args.output {
+        if !dir.is_dir() {
+            eprintln!("ERROR: not a directory: {}", dir.display());
+            return 2;
+        }
+        let mut partials = Vec::new();
+        find_partials(dir, &mut partials);
+        partials.sort();
+        for p in &partials {
+            match fs::remove_file(p) {
+                Ok(()) => println!("removed unfinished {}", p.display()),
+                Err(e) => {
+                    eprintln!("[warn] cannot remove {}: {}", p.display(), e);
+                    code = 1;
+                }
+            }
+        }
+        if partials.is_empty() {
+            println!("{}: no unfinished outputs", dir.display());
+        }
+    }
+    code
+}
+
+/// Files under `dir` named `<name>.partial.<ext>`.
+fn find_partials(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_partials(&path, out);
+        } else if path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .is_some_and(|s| s.ends_with(".partial"))
+        {
+            out.push(path);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! `--format json`: machine-readable output for scripts driving a run.
+//!
+//! stdout then carries only JSON, one object per line: a `"type": "clip"` record for every
+//! clip once it is done with (converted, skipped or failed), and a `"type": "summary"`
+//! record at the end. Every clip record has the same keys, null when they don't apply. The
+//! human progress lines a run prints go to stderr instead, through [`say!`].
+
+use crate::skip::{RunSummary, SkipReason};
+use clap::ValueEnum;
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use steamclipconverter::{
+    ClipDir, Error,
+    json::{self, Value},
+};
+
+/// `--format`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Progress lines for people.
+    #[default]
+    Text,
+    /// JSON records on stdout, progress lines on stderr.
+    Json,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(on: bool) {
+    JSON.store(on, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// A human progress line: `println!` normally, on stderr with `--format json` so stdout
+/// stays parseable, above the progress bars while they are drawn.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::report::json() {
+            eprintln!($($arg)*)
+        } else if $crate::progress_bar::active() {
+            $crate::progress_bar::above(&format!($($arg)*))
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+pub(crate) use say;
+
+/// Print `record` as one line on stdout, with `--format json` only.
+fn emit(record: Value) {
+    if json() {
+        let mut out = io::stdout().lock();
+        let _ = writeln!(out, "{record}");
+        let _ = out.flush();
+    }
+}
+
+fn path(p: &Path) -> Value {
+    p.to_string_lossy().as_ref().into()
+}
+
+/// What is known about a clip that wasn't converted.
+fn unconverted(clip: &ClipDir, status: &str, reason: &str, error: String) -> Value {
+    json::obj([
+        ("type", "clip".into()),
+        ("source", path(&clip.dir)),
+        ("appid", clip.appid.into()),
+        ("game", Value::Null),
+        ("output", Value::Null),
+        ("duration", Value::Null),
+        ("size", Value::Null),
+        ("status", status.into()),
+        ("reason", reason.into()),
+        ("error", error.into()),
+        ("deleted", false.into()),
+    ])
+}
+
+pub fn converted(clip: &ClipDir, game: &str, output: &Path, duration: Option<f64>, deleted: bool) {
+    let size = output.metadata().ok().map(|m| m.len());
+    emit(json::obj([
+        ("type", "clip".into()),
+        ("source", path(&clip.dir)),
+        ("appid", clip.appid.into()),
+        ("game", game.into()),
+        ("output", path(output)),
+        ("duration", duration.into()),
+        ("size", size.into()),
+        ("status", "converted".into()),
+        ("reason", Value::Null),
+        ("error", Value::Null),
+        ("deleted", deleted.into()),
+    ]));
+}
+
+pub fn skipped(clip: &ClipDir, reason: &SkipReason) {
+    emit(unconverted(
+        clip,
+        "skipped",
+        reason.code(),
+        reason.to_string(),
+    ));
+}
+
+pub fn failed(clip: &ClipDir, err: &Error) {
+    emit(unconverted(clip, "failed", err.kind(), err.to_string()));
+}
+
+/// The closing record: the counts, then the `--summary-json` lists.
+pub fn summary(summary: &RunSummary) {
+    let mut fields = vec![
+        ("type".to_string(), Value::from("summary")),
+        ("line".to_string(), summary.line().as_str().into()),
+    ];
+    if let Value::Obj(lists) = summary.to_json() {
+        fields.extend(lists);
+    }
+    emit(Value::Obj(fields));
+}
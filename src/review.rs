@@ -0,0 +1,253 @@
+//! `review` subcommand: triage converted clips one by one. Each opens in the system player,
+//! then a prompt decides its fate; every decision is appended to the catalog right away,
+//! so quitting halfway loses nothing.
+
+use crate::{
+    catalog::{Catalog, CatalogEntry},
+    desktop,
+    reconvert::Filter,
+    sha256::file_sha256,
+};
+use clap::{ArgAction, Args};
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use steamclipconverter::ffmpeg::file_arg;
+
+#[derive(Args, Debug)]
+pub struct ReviewArgs {
+    /// Only outputs matching KEY=VALUE (repeatable; same keys as `reconvert`).
+    #[arg(long, value_name = "KEY=VALUE", action = ArgAction::Append)]
+    filter: Vec<String>,
+
+    /// Catalog to read and update (defaults to the user catalog).
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+
+    /// Also show clips that were already kept in an earlier review.
+    #[arg(long, action = ArgAction::SetTrue)]
+    all: bool,
+
+    /// Command to open clips with instead of the system default (e.g. mpv).
+    #[arg(long, value_name = "CMD")]
+    player: Option<String>,
+}
+
+enum Action {
+    Keep,
+    Delete,
+    Trim(f64, f64),
+    Tag(Vec<String>),
+    Replay,
+    Skip,
+    Quit,
+}
+
+/// Seconds from `90`, `1:30` or `1:02:03.5`.
+fn parse_time(s: &str) -> Option<f64> {
+    s.split(':')
+        .try_fold(0.0, |acc, part| {
+            Some(acc * 60.0 + part.parse::<f64>().ok()?)
+        })
+        .filter(|t| *t >= 0.0)
+}
+
+fn parse_action(line: &str) -> Result<Action, String> {
+    let mut words = line.split_whitespace();
+    let cmd = words.next().unwrap_or("");
+    let rest: Vec<&str> = words.collect();
+    match cmd {
+        "k" | "keep" => Ok(Action::Keep),
+        "d" | "delete" => Ok(Action::Delete),
+        "s" | "skip" | "" => Ok(Action::Skip),
+        "q" | "quit" => Ok(Action::Quit),
+        "p" | "play" => Ok(Action::Replay),
+        "t" | "trim" => match rest.as_slice() {
+            [a, b] => match (parse_time(a), parse_time(b)) {
+                (Some(a), Some(b)) if a < b => Ok(Action::Trim(a, b)),
+                _ => Err("trim needs START END with START < END (e.g. t 0:05 1:20)".into()),
+            },
+            _ => Err("trim needs START END (e.g. t 0:05 1:20)".into()),
+        },
+        "g" | "tag" if !rest.is_empty() => Ok(Action::Tag(
+            rest.join(" ")
+                .split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+        )),
+        _ => Err(format!("unknown command '{cmd}'")),
+    }
+}
+
+fn open_in_player(file: &Path, player: Option<&str>) {
+    if let Err(e) = desktop::open_in_player(file, player) {
+        eprintln!(
+            "[warn] cannot open a player: {} (open the file yourself)",
+            e
+        );
+    }
+}
+
+/// Cut `file` down to `start..end` in place (stream copy, so cuts land on keyframes).
+fn trim_in_place(file: &Path, start: f64, end: f64) -> io::Result<()> {
+    let ext = file
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "mp4".into());
+    let tmp = file.with_extension(format!("review-tmp.{ext}"));
+    let status = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(["-ss", &start.to_string(), "-to", &end.to_string()])
+        .arg("-i")
+        .arg(file_arg(file))
+        .args(["-map", "0", "-c", "copy", "-movflags", "+faststart"])
+        .arg(file_arg(&tmp))
+        .stdin(Stdio::null())
+        .status()?;
+    if !status.success() {
+        let _ = fs::remove_file(&tmp);
+        return Err(io::Error::other(format!("ffmpeg status: {status}")));
+    }
+    fs::rename(&tmp, file)
+}
+
+fn save(catalog: &mut Catalog, entry: &CatalogEntry) -> bool {
+    match catalog.append(entry.clone()) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("[warn] catalog update failed: {}", e);
+            false
+        }
+    }
+}
+
+pub fn run(args: ReviewArgs) -> i32 {
+    let filters = match args
+        .filter
+        .iter()
+        .map(|f| Filter::parse(f))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return 2;
+        }
+    };
+    if !io::stdin().is_terminal() {
+        eprintln!("ERROR: review is interactive; run it in a terminal.");
+        return 2;
+    }
+    let Some(path) = args.catalog.clone().or_else(Catalog::default_path) else {
+        eprintln!("ERROR: no catalog location; pass --catalog");
+        return 2;
+    };
+    let mut catalog = match Catalog::open(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ERROR: cannot read catalog {}: {}", path.display(), e);
+            return 2;
+        }
+    };
+
+    let queue: Vec<CatalogEntry> = catalog
+        .entries()
+        .into_iter()
+        .filter(|e| e.output.is_file() && !e.review_deleted())
+        .filter(|e| args.all || e.review.is_none())
+        .filter(|e| filters.iter().all(|f| f.matches(e)))
+        .cloned()
+        .collect();
+    if queue.is_empty() {
+        println!("Nothing to review.");
+        return 0;
+    }
+
+    let (mut kept, mut deleted) = (0, 0);
+    let total = queue.len();
+    let stdin = io::stdin();
+    'clips: for (n, mut entry) in queue.into_iter().enumerate() {
+        println!(
+            "\n[{}/{}] {} ({} {} {}){}",
+            n + 1,
+            total,
+            entry.output.display(),
+            entry.game,
+            entry.date,
+            entry.time,
+            if entry.tags.is_empty() {
+                String::new()
+            } else {
+                format!(" tags: {}", entry.tags.join(", "))
+            }
+        );
+        open_in_player(&entry.output, args.player.as_deref());
+        loop {
+            print!("[k]eep [d]elete [t]rim START END [g] tag a,b [p]lay again [s]kip [q]uit > ");
+            let _ = io::stdout().flush();
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                break 'clips;
+            }
+            match parse_action(line.trim()) {
+                Err(e) => eprintln!("{}", e),
+                Ok(Action::Skip) => break,
+                Ok(Action::Quit) => break 'clips,
+                Ok(Action::Replay) => open_in_player(&entry.output, args.player.as_deref()),
+                Ok(Action::Keep) => {
+                    entry.review = Some("kept".into());
+                    if save(&mut catalog, &entry) {
+                        kept += 1;
+                    }
+                    break;
+                }
+                Ok(Action::Delete) => {
+                    if let Err(e) = fs::remove_file(&entry.output) {
+                        eprintln!("[warn] cannot delete {}: {}", entry.output.display(), e);
+                        continue;
+                    }
+                    println!("[del] removed {}", entry.output.display());
+                    entry.review = Some("deleted".into());
+                    save(&mut catalog, &entry);
+                    deleted += 1;
+                    break;
+                }
+                Ok(Action::Tag(tags)) => {
+                    for t in tags {
+                        if !entry.tags.contains(&t) {
+                            entry.tags.push(t);
+                        }
+                    }
+                    if save(&mut catalog, &entry) {
+                        println!("tags: {}", entry.tags.join(", "));
+                    }
+                }
+                Ok(Action::Trim(start, end)) => {
+                    if let Err(e) = trim_in_place(&entry.output, start, end) {
+                        eprintln!("[warn] trim failed: {}", e);
+                        continue;
+                    }
+                    // The file changed, so its checksum record has to follow.
+                    match (fs::metadata(&entry.output), file_sha256(&entry.output)) {
+                        (Ok(m), Ok(sha)) => {
+                            entry.size = m.len();
+                            entry.sha256 = sha;
+                            save(&mut catalog, &entry);
+                        }
+                        (Err(e), _) | (_, Err(e)) => {
+                            eprintln!("[warn] cannot re-hash trimmed file: {}", e)
+                        }
+                    }
+                    println!("trimmed to {start}s..{end}s; check it:");
+                    open_in_player(&entry.output, args.player.as_deref());
+                }
+            }
+        }
+    }
+    println!("\nReviewed: {} kept, {} deleted.", kept, deleted);
+    0
+}
@@ -0,0 +1,309 @@
+//! Finding clip folders on disk.
+
+use crate::{
+    Error,
+    snapshot::{ScanSnapshot, mtime_nanos},
+};
+use regex::Regex;
+use std::{
+    collections::VecDeque,
+    fs,
+    future::poll_fn,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+/// Folder name of a recording: fg_<appid>_<YYYYMMDD>_<HHMMSS> for clips, bg_... for Steam's
+/// always-on background recording.
+const CLIP_DIR_PATTERN: &str = r"^([fb]g)_(\d+)_(\d{8})_(\d{6})$";
+
+/// [`CLIP_DIR_PATTERN`], compiled once: it is matched against every folder of every scan.
+fn clip_dir_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(CLIP_DIR_PATTERN).unwrap())
+}
+
+/// Appid of recordings Steam didn't attribute to a game (folder `fg_0_...`, or an id that
+/// doesn't fit a `u32`). They are still listed and converted, named "Unknown".
+pub const UNKNOWN_APPID: u32 = 0;
+
+/// Which of Steam's recorders wrote a folder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClipKind {
+    /// `fg_*`: a clip, saved or recorded on demand.
+    Clip,
+    /// `bg_*`: background game recording (`gamerecordings/video/bg_*`).
+    Background,
+}
+
+/// Represents one clip folder like fg_294100_20250828_124021 (or a bg_* background recording)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClipDir {
+    pub dir: PathBuf,
+    pub appid: u32,
+    pub date: String, // YYYYMMDD
+    pub time: String, // HHMMSS
+}
+
+impl ClipDir {
+    /// Whether Steam recorded this clip without a game attached.
+    pub fn is_unknown_app(&self) -> bool {
+        self.appid == UNKNOWN_APPID
+    }
+
+    /// Clip or background recording, by the folder's prefix.
+    pub fn kind(&self) -> ClipKind {
+        let background = self
+            .dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("bg_"));
+        if background {
+            ClipKind::Background
+        } else {
+            ClipKind::Clip
+        }
+    }
+
+    /// Parse a clip folder path by its name, e.g. `.../fg_294100_20250828_124021`.
+    pub fn from_path(dir: &Path) -> Option<ClipDir> {
+        let name = dir.file_name()?.to_str()?;
+        let caps = clip_dir_re().captures(name)?;
+        let appid: u32 = caps[2].parse().unwrap_or(UNKNOWN_APPID);
+        Some(ClipDir {
+            dir: dir.to_path_buf(),
+            appid,
+            date: caps[3].to_string(),
+            time: caps[4].to_string(),
+        })
+    }
+}
+
+/// Recursively enumerate subfolders that match the fg_* (or bg_*) pattern anywhere under
+/// `parent`; see [`ClipDir::kind`] to tell them apart.
+///
+/// Directories whose mtime matches `snapshot` reuse their cached subdirectory list instead of
+/// being listed again; everything visited is recorded back into `snapshot`.
+pub fn find_fg_clip_dirs(
+    parent: &Path,
+    snapshot: &mut ScanSnapshot,
+) -> Result<Vec<ClipDir>, Error> {
+    // Unreadable subdirectories are skipped, but an unreadable root is an error.
+    fs::read_dir(parent).map_err(|source| Error::Scan {
+        path: parent.to_path_buf(),
+        source,
+    })?;
+    let mut out: Vec<ClipDir> = Vec::new();
+    scan_clip_dirs(parent, snapshot, |clip| out.push(clip));
+    Ok(out)
+}
+
+/// Walk `parent` and hand each fg_*/bg_* folder to `on_clip` as soon as it is found.
+pub fn scan_clip_dirs(
+    parent: &Path,
+    snapshot: &mut ScanSnapshot,
+    mut on_clip: impl FnMut(ClipDir),
+) {
+    let re = clip_dir_re();
+
+    let mut stack: Vec<PathBuf> = vec![parent.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let mtime = fs::metadata(&dir).ok().and_then(|m| mtime_nanos(&m));
+
+        let subdirs: Vec<PathBuf> = match mtime.and_then(|t| snapshot.cached_subdirs(&dir, t)) {
+            Some(names) => names.iter().map(|n| dir.join(n)).collect(),
+            None => {
+                let entries = match fs::read_dir(&dir) {
+                    Ok(it) => it,
+                    Err(_) => continue, // skip unreadable dirs
+                };
+                entries
+                    .flatten()
+                    .map(|ent| ent.path())
+                    .filter(|p| p.is_dir())
+                    .collect()
+            }
+        };
+
+        // Names that aren't valid UTF-8 can't be cached; such directories are simply listed
+        // again on every scan instead of losing the entries.
+        let names: Option<Vec<String>> = subdirs
+            .iter()
+            .map(|p| p.file_name().and_then(|s| s.to_str()).map(str::to_string))
+            .collect();
+        if let (Some(t), Some(names)) = (mtime, names) {
+            snapshot.record(&dir, t, names);
+        }
+
+        for p in subdirs {
+            if let Some(name) = p.file_name().and_then(|s| s.to_str())
+                && let Some(caps) = re.captures(name)
+            {
+                let appid: u32 = caps[2].parse().unwrap_or(UNKNOWN_APPID);
+                on_clip(ClipDir {
+                    dir: p.clone(),
+                    appid,
+                    date: caps[3].to_string(),
+                    time: caps[4].to_string(),
+                });
+                // clip folder is terminal; don't descend into it
+                continue;
+            }
+
+            stack.push(p);
+        }
+    }
+}
+
+/// Start scanning `root` on a background thread and return a stream of the clips it finds.
+///
+/// Clips arrive in discovery order while the walk is still running, so a frontend can fill
+/// its list progressively. The stream only needs `std` futures, so it works under tokio or
+/// any other executor: `while let Some(clip) = stream.next().await { ... }`. If the walk
+/// dies (a panic in the scanner thread), the stream ends with an [`Error::Scan`].
+pub fn scan_stream(root: impl Into<PathBuf>) -> ClipStream {
+    let root = root.into();
+    stream_from(root.clone(), move |push| {
+        scan_clip_dirs(&root, &mut ScanSnapshot::default(), push)
+    })
+}
+
+/// Run `walk` on a thread, feeding what it pushes to the returned stream.
+fn stream_from(
+    root: PathBuf,
+    walk: impl FnOnce(&mut dyn FnMut(ClipDir)) + Send + 'static,
+) -> ClipStream {
+    let shared = Arc::new(Mutex::new(StreamState::default()));
+    let finish = Finish {
+        shared: Arc::clone(&shared),
+        root,
+    };
+    thread::spawn(move || {
+        let mut push = |clip: ClipDir| {
+            let mut st = lock(&finish.shared);
+            st.queue.push_back(clip);
+            if let Some(w) = st.waker.take() {
+                w.wake();
+            }
+        };
+        walk(&mut push);
+        // `finish` drops here, or while unwinding.
+    });
+    ClipStream { shared }
+}
+
+/// Ends the stream when the scanner thread is done, whether it returned or panicked.
+struct Finish {
+    shared: Arc<Mutex<StreamState>>,
+    root: PathBuf,
+}
+
+impl Drop for Finish {
+    fn drop(&mut self) {
+        let mut st = lock(&self.shared);
+        if thread::panicking() {
+            st.failed = Some(Error::Scan {
+                path: std::mem::take(&mut self.root),
+                source: io::Error::other("the scanner thread panicked"),
+            });
+        }
+        st.done = true;
+        if let Some(w) = st.waker.take() {
+            w.wake();
+        }
+    }
+}
+
+/// Lock the stream state, even if a panic poisoned it: every update under the lock is a
+/// single push or assignment, so it is never left half-done.
+fn lock(shared: &Mutex<StreamState>) -> std::sync::MutexGuard<'_, StreamState> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Default)]
+struct StreamState {
+    queue: VecDeque<ClipDir>,
+    failed: Option<Error>,
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// Clips produced by [`scan_stream`]; ends when the walk has finished, after an `Err` if it
+/// failed.
+pub struct ClipStream {
+    shared: Arc<Mutex<StreamState>>,
+}
+
+impl ClipStream {
+    /// Next clip, or `None` once the scan is complete.
+    pub async fn next(&mut self) -> Option<Result<ClipDir, Error>> {
+        poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Poll-style access, matching the shape of `futures::Stream::poll_next`.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<ClipDir, Error>>> {
+        let mut st = lock(&self.shared);
+        if let Some(clip) = st.queue.pop_front() {
+            return Poll::Ready(Some(Ok(clip)));
+        }
+        if let Some(e) = st.failed.take() {
+            return Poll::Ready(Some(Err(e)));
+        }
+        if st.done {
+            return Poll::Ready(None);
+        }
+        st.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn collect(mut stream: ClipStream) -> Vec<Result<ClipDir, Error>> {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut out = Vec::new();
+        loop {
+            match stream.poll_next(&mut cx) {
+                Poll::Ready(Some(item)) => out.push(item),
+                Poll::Ready(None) => return out,
+                Poll::Pending => thread::sleep(Duration::from_millis(5)),
+            }
+        }
+    }
+
+    fn clip(name: &str) -> ClipDir {
+        ClipDir::from_path(&Path::new("/clips").join(name)).unwrap()
+    }
+
+    #[test]
+    fn a_panicking_walk_ends_the_stream_with_an_error() {
+        let stream = stream_from(PathBuf::from("/clips"), |push| {
+            push(clip("fg_730_20240101_120000"));
+            panic!("walk failed");
+        });
+        let items = collect(stream);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].as_ref().unwrap().appid, 730);
+        match &items[1] {
+            Err(Error::Scan { path, .. }) => assert_eq!(path, Path::new("/clips")),
+            other => panic!("expected a scan error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn a_finished_walk_ends_the_stream() {
+        let stream = stream_from(PathBuf::from("/clips"), |push| {
+            push(clip("fg_730_20240101_120000"));
+            push(clip("bg_0_20240101_130000"));
+        });
+        let items = collect(stream);
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(Result::is_ok));
+    }
+}
@@ -0,0 +1,53 @@
+//! Versioning for the files this tool persists (catalog, and anything that follows it).
+//!
+//! Each versioned file starts with a header object `{"schema": "<kind>", "version": N}`.
+//! Older files are migrated forward one step at a time; files from a newer release are
+//! refused outright so an older binary can never rewrite (and silently drop) data it
+//! doesn't understand.
+
+use crate::json::{self, Value};
+
+/// One forward step: turns the records of version `from` into version `from + 1`.
+pub struct Migration {
+    pub from: u32,
+    pub apply: fn(Vec<Value>) -> Vec<Value>,
+}
+
+/// Header line for a file of `kind` at `version`.
+pub fn header(kind: &str, version: u32) -> Value {
+    json::obj([("schema", kind.into()), ("version", version.into())])
+}
+
+/// Version declared by a header line, if `line` is a header for `kind`.
+pub fn header_version(kind: &str, line: &Value) -> Option<u32> {
+    if line.get("schema")?.as_str()? != kind {
+        return None;
+    }
+    line.get("version")?.as_u64().map(|v| v as u32)
+}
+
+/// Bring `records` from `found` up to `current`, or explain why that isn't possible.
+pub fn migrate(
+    kind: &str,
+    found: u32,
+    current: u32,
+    migrations: &[Migration],
+    mut records: Vec<Value>,
+) -> Result<Vec<Value>, String> {
+    if found > current {
+        return Err(format!(
+            "{kind} uses schema version {found}, but this steamclipconverter only understands \
+             up to version {current}; upgrade steamclipconverter (the file was left untouched)"
+        ));
+    }
+    let mut version = found;
+    while version < current {
+        let step = migrations
+            .iter()
+            .find(|m| m.from == version)
+            .ok_or_else(|| format!("no migration for {kind} from schema version {version}"))?;
+        records = (step.apply)(records);
+        version += 1;
+    }
+    Ok(records)
+}
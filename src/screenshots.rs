@@ -0,0 +1,216 @@
+//! `screenshots` subcommand: copy Steam screenshots out with the same naming as clips.
+//!
+//! Steam keeps them under `userdata/<id>/760/remote/<appid>/screenshots/` as
+//! `YYYYMMDDHHMMSS_<n>.jpg` (with a `thumbnails/` folder next to them, which is ignored).
+
+use crate::{
+    AppNames, resolve_input_dir,
+    session::{self, Layout},
+};
+use clap::{ArgAction, Args};
+use filetime::{FileTime, set_file_times};
+use regex::Regex;
+use sanitize_filename::sanitize;
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Args, Debug)]
+pub struct ScreenshotsArgs {
+    /// Directory to search (defaults to <SteamRoot>/userdata with a warning).
+    input: Option<PathBuf>,
+
+    /// Output directory (defaults to current working directory).
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Restrict to specific appids; repeatable.
+    #[arg(long = "gameId", action = ArgAction::Append)]
+    game_ids: Vec<u32>,
+
+    /// `sessions` puts screenshots next to the same game's clips of that day (see the
+    /// top-level --layout).
+    #[arg(long, value_enum, default_value_t)]
+    layout: Layout,
+}
+
+/// One screenshot as Steam stored it.
+#[derive(Clone, Debug)]
+pub struct Screenshot {
+    pub path: PathBuf,
+    pub appid: u32,
+    pub date: String, // YYYYMMDD
+    pub time: String, // HHMMSS
+    /// Steam's per-second counter (`_1`, `_2`, ...).
+    pub index: u32,
+}
+
+impl Screenshot {
+    /// Output name matching clips: `GameName-YYYYMMDD-HHMMSS.jpg`, with `_<n>` kept when
+    /// Steam took several shots in the same second.
+    pub fn file_name(&self, game_name: &str) -> String {
+        let ext = self
+            .path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("jpg")
+            .to_ascii_lowercase();
+        let suffix = if self.index > 1 {
+            format!("_{}", self.index)
+        } else {
+            String::new()
+        };
+        format!(
+            "{}-{}-{}{}.{}",
+            sanitize(game_name),
+            self.date,
+            self.time,
+            suffix,
+            ext
+        )
+    }
+}
+
+/// Find screenshots under `root` (a userdata dir, one account, or a `760/remote` dir).
+pub fn find_screenshots(root: &Path) -> Vec<Screenshot> {
+    let name_re = Regex::new(r"^(\d{8})(\d{6})_(\d+)\.(?i:jpe?g|png)$").unwrap();
+    let mut out = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for ent in entries.flatten() {
+            let p = ent.path();
+            if !p.is_dir() {
+                continue;
+            }
+            if p.file_name().is_some_and(|n| n == "screenshots") {
+                let appid = dir
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.parse::<u32>().ok());
+                if let Some(appid) = appid.filter(|a| *a != 0) {
+                    collect_dir(&p, appid, &name_re, &mut out);
+                }
+                continue;
+            }
+            // Clip folders never contain screenshots; don't walk their chunks.
+            if p.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+                n.starts_with("fg_") || n.starts_with("bg_") || n == "gamerecordings"
+            }) {
+                continue;
+            }
+            stack.push(p);
+        }
+    }
+    out
+}
+
+fn collect_dir(dir: &Path, appid: u32, name_re: &Regex, out: &mut Vec<Screenshot>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for ent in entries.flatten() {
+        let p = ent.path();
+        let Some(caps) = p
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| name_re.captures(n))
+        else {
+            continue;
+        };
+        if !p.is_file() {
+            continue;
+        }
+        out.push(Screenshot {
+            appid,
+            date: caps[1].to_string(),
+            time: caps[2].to_string(),
+            index: caps[3].parse().unwrap_or(1),
+            path: p,
+        });
+    }
+}
+
+pub fn run(args: ScreenshotsArgs) -> i32 {
+    let input_dir = resolve_input_dir(args.input.clone());
+    let output_dir = args
+        .output
+        .clone()
+        .unwrap_or_else(|| env::current_dir().expect("cwd"));
+    if let Err(e) = fs::create_dir_all(&output_dir) {
+        eprintln!(
+            "ERROR: cannot create output dir {}: {}",
+            output_dir.display(),
+            e
+        );
+        return 2;
+    }
+    let names = AppNames::discover();
+
+    let mut shots = find_screenshots(&input_dir);
+    if !args.game_ids.is_empty() {
+        let set: HashSet<u32> = args.game_ids.iter().copied().collect();
+        shots.retain(|s| set.contains(&s.appid));
+    }
+    if shots.is_empty() {
+        eprintln!("No screenshots found under {}", input_dir.display());
+        return 0;
+    }
+    shots.sort_by(|a, b| a.path.cmp(&b.path));
+    println!("Found {} screenshot(s).", shots.len());
+
+    let mut failed = 0;
+    for shot in &shots {
+        let game_name = names
+            .resolve(shot.appid)
+            .unwrap_or_else(|| shot.appid.to_string());
+        let fname = shot.file_name(&game_name);
+        let taken = session::screenshot_taken(&shot.date, &shot.time);
+        let target_dir = match (args.layout, taken) {
+            (Layout::Sessions, Some(t)) => session::session_dir(&output_dir, &game_name, t),
+            _ => output_dir.clone(),
+        };
+        let out_path = target_dir.join(&fname);
+        if out_path.exists() {
+            println!("[skip] {} exists", out_path.display());
+            continue;
+        }
+        let copied =
+            fs::create_dir_all(&target_dir).and_then(|_| copy_with_times(&shot.path, &out_path));
+        match copied {
+            Ok(()) => {
+                println!("[ok] {} -> {}", shot.path.display(), out_path.display());
+                if args.layout == Layout::Sessions
+                    && let Some(taken) = taken
+                {
+                    let item = session::Item::Screenshot { file: fname, taken };
+                    if let Err(e) = session::record(&target_dir, shot.appid, &game_name, item) {
+                        eprintln!("[warn] cannot update {}: {}", session::SIDECAR, e);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("[fail] {}: {}", shot.path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    println!("\nDone.");
+    if failed > 0 { 1 } else { 0 }
+}
+
+/// Copy a file and carry its times over. Steam names screenshots in local time, so the
+/// original mtime is kept rather than re-derived from the name like clip timestamps are.
+fn copy_with_times(src: &Path, dst: &Path) -> std::io::Result<()> {
+    fs::copy(src, dst)?;
+    let meta = fs::metadata(src)?;
+    set_file_times(
+        dst,
+        FileTime::from_last_access_time(&meta),
+        FileTime::from_last_modification_time(&meta),
+    )
+}
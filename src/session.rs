@@ -0,0 +1,222 @@
+//! `--layout sessions`: clips and screenshots of one game and day share a folder, with a
+//! `session.json` sidecar saying which screenshots were taken during which clip.
+
+use crate::{
+    json::{self, Value},
+    timeline::Marker,
+    visibility::Visibility,
+};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use clap::ValueEnum;
+use sanitize_filename::sanitize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use steamclipconverter::atomic;
+
+pub const SIDECAR: &str = "session.json";
+
+/// Screenshots taken up to this long after a clip ends still count as part of it.
+const SLACK_SECS: i64 = 60;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// Everything directly in the output directory.
+    #[default]
+    Flat,
+    /// `<output>/<Game>/<YYYY-MM-DD>/` per game and (local) day, with a session.json sidecar.
+    Sessions,
+}
+
+/// Screenshot file names carry local time.
+pub fn screenshot_taken(date: &str, time: &str) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(&format!("{date}{time}"), "%Y%m%d%H%M%S").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Folder for one game's session on the local calendar day of `at`.
+pub fn session_dir(output: &Path, game: &str, at: DateTime<Utc>) -> PathBuf {
+    let day = at.with_timezone(&Local).format("%Y-%m-%d").to_string();
+    output.join(sanitize(game)).join(day)
+}
+
+pub enum Item {
+    Clip {
+        file: String,
+        start: DateTime<Utc>,
+        duration: Option<f64>,
+        visibility: Option<Visibility>,
+        /// Timeline events inside the clip.
+        markers: Vec<Marker>,
+    },
+    Screenshot {
+        file: String,
+        taken: DateTime<Utc>,
+    },
+}
+
+impl Item {
+    fn file(&self) -> &str {
+        match self {
+            Item::Clip { file, .. } | Item::Screenshot { file, .. } => file,
+        }
+    }
+
+    fn at(&self) -> DateTime<Utc> {
+        match self {
+            Item::Clip { start, .. } => *start,
+            Item::Screenshot { taken, .. } => *taken,
+        }
+    }
+}
+
+/// The items in the sidecar of `dir`, in time order (empty when there is none yet).
+pub fn read_items(dir: &Path) -> io::Result<Vec<Item>> {
+    match fs::read_to_string(dir.join(SIDECAR)) {
+        Ok(txt) => json::parse(&txt)
+            .map(|v| load_items(&v))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Add or replace `item` in the sidecar of `dir` and rewrite the cross references.
+pub fn record(dir: &Path, appid: u32, game: &str, item: Item) -> io::Result<()> {
+    let path = dir.join(SIDECAR);
+    let mut items = read_items(dir)?;
+    items.retain(|i| i.file() != item.file());
+    items.push(item);
+    items.sort_by_key(Item::at);
+
+    atomic::write(&path, format!("{}\n", to_json(appid, game, &items)))
+}
+
+fn rfc3339(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+fn parse_time(v: &Value, key: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(v.str_field(key))
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn load_items(doc: &Value) -> Vec<Item> {
+    let list = |key: &str| match doc.get(key) {
+        Some(Value::Arr(items)) => items.clone(),
+        _ => Vec::new(),
+    };
+    let mut out = Vec::new();
+    for c in list("clips") {
+        if let Some(start) = parse_time(&c, "start") {
+            out.push(Item::Clip {
+                file: c.str_field("file").to_string(),
+                start,
+                duration: c.get("duration").and_then(Value::as_f64),
+                visibility: Visibility::parse(c.str_field("visibility")),
+                markers: match c.get("markers") {
+                    Some(Value::Arr(ms)) => ms
+                        .iter()
+                        .filter_map(|m| {
+                            Some(Marker {
+                                offset: m.get("offset")?.as_f64()?,
+                                title: m.str_field("title").to_string(),
+                            })
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                },
+            });
+        }
+    }
+    for s in list("screenshots") {
+        if let Some(taken) = parse_time(&s, "taken") {
+            out.push(Item::Screenshot {
+                file: s.str_field("file").to_string(),
+                taken,
+            });
+        }
+    }
+    out
+}
+
+/// The clip (by file name) that was recording when a screenshot was taken at `taken`.
+fn clip_covering(items: &[Item], taken: DateTime<Utc>) -> Option<&str> {
+    items.iter().rev().find_map(|i| match i {
+        Item::Clip {
+            file,
+            start,
+            duration: Some(d),
+            ..
+        } => {
+            let end = *start + chrono::Duration::seconds(d.ceil() as i64 + SLACK_SECS);
+            (*start <= taken && taken <= end).then_some(file.as_str())
+        }
+        _ => None,
+    })
+}
+
+fn to_json(appid: u32, game: &str, items: &[Item]) -> Value {
+    let mut clips = Vec::new();
+    let mut shots = Vec::new();
+    for item in items {
+        match item {
+            Item::Clip {
+                file,
+                start,
+                duration,
+                visibility,
+                markers,
+            } => {
+                let during: Vec<Value> = items
+                    .iter()
+                    .filter_map(|i| match i {
+                        Item::Screenshot { file: s, taken }
+                            if clip_covering(items, *taken) == Some(file.as_str()) =>
+                        {
+                            Some(s.as_str().into())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                clips.push(json::obj([
+                    ("file", file.as_str().into()),
+                    ("start", rfc3339(*start).into()),
+                    ("duration", (*duration).into()),
+                    ("visibility", visibility.map(Visibility::as_str).into()),
+                    ("screenshots", Value::Arr(during)),
+                    (
+                        "markers",
+                        Value::Arr(
+                            markers
+                                .iter()
+                                .map(|m| {
+                                    json::obj([
+                                        ("offset", m.offset.into()),
+                                        ("title", m.title.as_str().into()),
+                                    ])
+                                })
+                                .collect(),
+                        ),
+                    ),
+                ]));
+            }
+            Item::Screenshot { file, taken } => shots.push(json::obj([
+                ("file", file.as_str().into()),
+                ("taken", rfc3339(*taken).into()),
+                ("clip", clip_covering(items, *taken).into()),
+            ])),
+        }
+    }
+    json::obj([
+        ("appid", appid.into()),
+        ("game", game.into()),
+        ("clips", Value::Arr(clips)),
+        ("screenshots", Value::Arr(shots)),
+    ])
+}
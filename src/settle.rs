@@ -0,0 +1,89 @@
+//! Deciding whether a clip folder is finished, so a recording still being flushed is not
+//! converted into a truncated file.
+//!
+//! Two heuristics, both on the folder's own files: nothing in it may have been modified for
+//! the settle time, and (optionally) its chunk count and total size must not grow across a
+//! short observation window. Slow disks or games that flush lazily need longer values.
+
+use std::{
+    fs, io,
+    path::Path,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// When a clip folder counts as complete.
+#[derive(Clone, Copy, Debug)]
+pub struct SettleRules {
+    /// Minimum time since the newest file in the folder was written.
+    pub settle_time: Duration,
+    /// If non-zero, sample the folder twice this far apart and require no growth.
+    pub growth_window: Duration,
+}
+
+impl Default for SettleRules {
+    fn default() -> Self {
+        SettleRules {
+            settle_time: Duration::from_secs(30),
+            growth_window: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Readiness {
+    Complete,
+    /// Written to recently; ready in about `wait` if nothing else changes.
+    Settling {
+        wait: Duration,
+    },
+    /// Chunks or bytes were added during the growth window.
+    Growing,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct FolderState {
+    chunks: usize,
+    bytes: u64,
+    newest: Option<SystemTime>,
+}
+
+fn folder_state(dir: &Path) -> io::Result<FolderState> {
+    let mut st = FolderState::default();
+    for ent in fs::read_dir(dir)? {
+        let meta = ent?.metadata()?;
+        if !meta.is_file() {
+            continue;
+        }
+        st.bytes += meta.len();
+        st.chunks += 1;
+        if let Ok(m) = meta.modified() {
+            st.newest = st.newest.max(Some(m));
+        }
+    }
+    Ok(st)
+}
+
+/// Check `dir` against `rules`. Blocks for `growth_window` when that check is enabled.
+pub fn check(dir: &Path, rules: &SettleRules) -> io::Result<Readiness> {
+    let before = folder_state(dir)?;
+    if let Some(newest) = before.newest {
+        // A clock in the past (mtime in the future) counts as "just written".
+        let age = SystemTime::now()
+            .duration_since(newest)
+            .unwrap_or(Duration::ZERO);
+        if age < rules.settle_time {
+            return Ok(Readiness::Settling {
+                wait: rules.settle_time - age,
+            });
+        }
+    }
+    if !rules.growth_window.is_zero() {
+        thread::sleep(rules.growth_window);
+        let after = folder_state(dir)?;
+        if after.chunks > before.chunks || after.bytes > before.bytes {
+            return Ok(Readiness::Growing);
+        }
+    }
+    Ok(Readiness::Complete)
+}
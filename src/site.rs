@@ -0,0 +1,155 @@
+//! `export-site` subcommand: a gallery page of the output folder, one self-contained HTML
+//! file with the clips embedded as players and a search box, game and tag filters and a
+//! date range that work in the browser, so the folder can be put on any static web server
+//! or synced to a phone and browsed without this tool.
+//!
+//! Clips are found and described as for `index` (catalog, file name, file time); the list
+//! is written into the page as JSON, so it also works opened straight from disk.
+
+use crate::{
+    index::{self, Source, caption, link, private_note},
+    json::{Value, obj},
+    preview::escape,
+};
+use clap::Args;
+use std::{collections::BTreeSet, path::Path, path::PathBuf};
+use steamclipconverter::atomic;
+
+#[derive(Args, Debug)]
+pub struct SiteArgs {
+    #[command(flatten)]
+    source: Source,
+
+    /// Write the gallery here instead of gallery.html in the folder. Links to the clips are
+    /// relative to it, so keep it inside the folder when publishing.
+    #[arg(long, value_name = "FILE")]
+    to: Option<PathBuf>,
+}
+
+pub fn run(args: SiteArgs) -> i32 {
+    let Some((items, private)) = index::collect(&args.source) else {
+        return 2;
+    };
+    let out = args
+        .to
+        .clone()
+        .unwrap_or_else(|| args.source.dir.join("gallery.html"));
+    let base = out.parent().unwrap_or(Path::new("."));
+    let games: BTreeSet<&str> = items.iter().map(|i| i.game.as_str()).collect();
+    let tags: BTreeSet<&str> = items
+        .iter()
+        .flat_map(|i| i.tags.iter().map(String::as_str))
+        .collect();
+    let clips = Value::Arr(
+        items
+            .iter()
+            .map(|item| {
+                obj([
+                    ("src", link(&item.path, base).into()),
+                    ("thumb", item.thumb.as_ref().map(|t| link(t, base)).into()),
+                    ("game", item.game.as_str().into()),
+                    ("when", item.when.as_str().into()),
+                    ("caption", caption(item).into()),
+                    (
+                        "tags",
+                        Value::Arr(item.tags.iter().map(|t| t.as_str().into()).collect()),
+                    ),
+                ])
+            })
+            .collect(),
+    );
+    let list = |values: &BTreeSet<&str>| {
+        Value::Arr(values.iter().map(|&v| v.into()).collect()).to_string()
+    };
+    let title = args.source.title();
+    // `</` would end the script element early.
+    let data = format!(
+        "const CLIPS={clips};const GAMES={};const TAGS={};",
+        list(&games),
+        list(&tags)
+    )
+    .replace("</", "<\\/");
+    let page = PAGE
+        .replace("{title}", &escape(&title))
+        .replace("{data}", &data);
+    if let Err(e) = atomic::write(&out, page) {
+        eprintln!("ERROR: cannot write {}: {}", out.display(), e);
+        return 1;
+    }
+    println!(
+        "Wrote {}: {} clip(s) from {} game(s){}",
+        out.display(),
+        items.len(),
+        games.len(),
+        private_note(private)
+    );
+    0
+}
+
+/// The gallery; `{title}` and `{data}` are filled in.
+const PAGE: &str = r##"<!doctype html>
+<html><head><meta charset="utf-8"><meta name="viewport" content="width=device-width">
+<title>{title}</title>
+<style>
+body{font-family:sans-serif;margin:1em 2em;background:#fafafa}
+header{position:sticky;top:0;background:#fafafa;padding:.5em 0;display:flex;flex-wrap:wrap;gap:.5em;align-items:center}
+input,select{font:inherit;padding:.3em}
+#q{flex:1;min-width:12em}
+#tags button{font:inherit;border:1px solid #888;border-radius:1em;background:#fff;padding:.1em .7em;margin:.1em;cursor:pointer}
+#tags button.on{background:#333;color:#fff}
+.grid{display:grid;grid-template-columns:repeat(auto-fill,minmax(min(320px,100%),1fr));gap:1em}
+figure{margin:0;background:#fff;box-shadow:0 1px 3px #0003}
+video{width:100%;aspect-ratio:16/9;background:#000;display:block}
+figcaption{padding:.4em;font-size:.9em}
+figcaption b{display:block}
+.tag{font-size:.8em;background:#eee;border-radius:1em;padding:0 .5em;margin-right:.3em}
+#count{color:#666}
+</style></head>
+<body>
+<h1>{title}</h1>
+<header>
+<input id="q" type="search" placeholder="Search game, tag or date">
+<select id="game"><option value="">All games</option></select>
+<label>From <input id="from" type="date"></label>
+<label>to <input id="until" type="date"></label>
+<span id="count"></span>
+</header>
+<p id="tags"></p>
+<div class="grid" id="grid"></div>
+<script>
+{data}
+const $=id=>document.getElementById(id);
+const chosen=new Set();
+for(const g of GAMES){const o=document.createElement("option");o.value=o.textContent=g;$("game").append(o);}
+for(const t of TAGS){const b=document.createElement("button");b.textContent=t;
+b.onclick=()=>{chosen.has(t)?chosen.delete(t):chosen.add(t);b.classList.toggle("on");show();};$("tags").append(b);}
+function card(c){
+const f=document.createElement("figure");
+const v=document.createElement("video");
+v.controls=true;v.preload=c.thumb?"none":"metadata";
+v.src=c.src+(c.thumb?"":"#t=1");if(c.thumb)v.poster=c.thumb;
+const cap=document.createElement("figcaption");
+const g=document.createElement("b");g.textContent=c.game;cap.append(g);
+const a=document.createElement("a");a.href=c.src;a.textContent=c.caption;cap.append(a);
+if(c.tags.length){const p=document.createElement("div");
+for(const t of c.tags){const s=document.createElement("span");s.className="tag";s.textContent=t;p.append(s);}cap.append(p);}
+f.append(v,cap);return f;}
+const cards=CLIPS.map(card);
+function show(){
+const q=$("q").value.trim().toLowerCase(),game=$("game").value,from=$("from").value,until=$("until").value;
+let n=0;
+CLIPS.forEach((c,i)=>{
+const day=c.when.slice(0,10);
+const ok=(!game||c.game===game)&&(!from||day>=from)&&(!until||day<=until)
+&&[...chosen].every(t=>c.tags.includes(t))
+&&(!q||[c.game,c.when,...c.tags].some(s=>s.toLowerCase().includes(q)));
+if(ok)n++;
+cards[i].style.display=ok?"":"none";
+if(!ok&&!cards[i].firstChild.paused)cards[i].firstChild.pause();});
+$("count").textContent=n+" of "+CLIPS.length+" clips";}
+$("grid").append(...cards);
+for(const id of["q","game","from","until"])$(id).addEventListener("input",show);
+show();
+</script>
+</body></html>
+"##;
@@ -0,0 +1,244 @@
+//! Why a clip wasn't converted, in a form automation can act on, and the run summary.
+
+use crate::{
+    json::{self, Value},
+    overrides::{self, ClipOverrides},
+    report::{self, say},
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+use steamclipconverter::{
+    ClipDir, Error,
+    settle::{self, Readiness, SettleRules},
+};
+
+#[derive(Clone, Debug)]
+pub enum SkipReason {
+    /// No session.mpd in the folder (broken or partially deleted clip).
+    MissingMpd,
+    /// Excluded by --gameId or --skip-unknown-app.
+    FilteredGame,
+    /// Recorded before --since or after --until.
+    OutsideRange,
+    /// Folder name not matched by --match.
+    NotMatched,
+    /// Still being recorded or flushed (see --settle-time).
+    InProgress(String),
+    /// `skip = true` in the clip's convert.toml.
+    OverrideSkip,
+    /// The clip's convert.toml couldn't be used.
+    BadOverride(String),
+    /// Left for later by --power-policy defer.
+    DeferredOnBattery,
+    /// The shared catalog already has an output for this recording.
+    AlreadyConverted(PathBuf),
+    /// --make-room already freed enough space.
+    EnoughRoom,
+    /// --strict-names: the game name isn't known, only the appid.
+    UnresolvedName,
+    /// --on-overlap keep-longer: this longer recording of the same game covers it.
+    Overlapped(PathBuf),
+    /// --share-work: another instance (named) is converting it.
+    ClaimedElsewhere(String),
+}
+
+impl SkipReason {
+    /// Stable identifier for scripts.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SkipReason::MissingMpd => "missing_mpd",
+            SkipReason::FilteredGame => "filtered_game",
+            SkipReason::OutsideRange => "outside_range",
+            SkipReason::NotMatched => "not_matched",
+            SkipReason::InProgress(_) => "in_progress",
+            SkipReason::OverrideSkip => "override_skip",
+            SkipReason::BadOverride(_) => "bad_override",
+            SkipReason::DeferredOnBattery => "deferred_battery",
+            SkipReason::AlreadyConverted(_) => "already_converted",
+            SkipReason::EnoughRoom => "enough_room",
+            SkipReason::UnresolvedName => "unresolved_name",
+            SkipReason::Overlapped(_) => "overlapped",
+            SkipReason::ClaimedElsewhere(_) => "claimed_elsewhere",
+        }
+    }
+
+    /// Problems that need a look, as opposed to "nothing to do (yet)".
+    pub fn is_problem(&self) -> bool {
+        matches!(
+            self,
+            SkipReason::MissingMpd | SkipReason::BadOverride(_) | SkipReason::UnresolvedName
+        )
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::MissingMpd => f.write_str("missing session.mpd"),
+            SkipReason::FilteredGame => {
+                f.write_str("excluded by --gameId/--game/--skip-unknown-app")
+            }
+            SkipReason::OutsideRange => f.write_str("recorded outside --since/--until"),
+            SkipReason::NotMatched => f.write_str("folder name not matched by --match"),
+            SkipReason::InProgress(d) => write!(f, "still being written ({d})"),
+            SkipReason::OverrideSkip => write!(f, "{} says skip", overrides::FILE_NAME),
+            SkipReason::BadOverride(e) => write!(f, "{}: {}", overrides::FILE_NAME, e),
+            SkipReason::DeferredOnBattery => f.write_str("deferred: running on battery"),
+            SkipReason::AlreadyConverted(p) => write!(f, "already converted to {}", p.display()),
+            SkipReason::EnoughRoom => f.write_str("--make-room target reached"),
+            SkipReason::UnresolvedName => {
+                f.write_str("game name not found (no appmanifest), and --strict-names is on")
+            }
+            SkipReason::Overlapped(p) => write!(f, "covered by {}", p.display()),
+            SkipReason::ClaimedElsewhere(owner) => write!(f, "being converted by {owner}"),
+        }
+    }
+}
+
+/// Checks every clip goes through before it is converted (or listed as ready).
+pub fn assess(clip: &ClipDir, rules: &SettleRules) -> Result<ClipOverrides, SkipReason> {
+    if !clip.dir.join("session.mpd").is_file() {
+        return Err(SkipReason::MissingMpd);
+    }
+    match settle::check(&clip.dir, rules) {
+        Ok(Readiness::Complete) => {}
+        Ok(Readiness::Settling { wait }) => {
+            return Err(SkipReason::InProgress(format!(
+                "settles in {}s",
+                wait.as_secs().max(1)
+            )));
+        }
+        Ok(Readiness::Growing) => {
+            return Err(SkipReason::InProgress("chunks are growing".into()));
+        }
+        Err(e) => eprintln!("[warn] cannot check whether clip is complete: {}", e),
+    }
+    let overrides = ClipOverrides::load(&clip.dir)
+        .map_err(SkipReason::BadOverride)?
+        .unwrap_or_default();
+    if overrides.skip {
+        return Err(SkipReason::OverrideSkip);
+    }
+    Ok(overrides)
+}
+
+/// What happened to every clip of a run.
+#[derive(Default)]
+pub struct RunSummary {
+    pub converted: Vec<PathBuf>,
+    failed: Vec<(PathBuf, &'static str, String)>,
+    skipped: Vec<(PathBuf, SkipReason)>,
+}
+
+impl RunSummary {
+    /// Record (and report) a skipped clip.
+    pub fn skip(&mut self, clip: &ClipDir, reason: SkipReason) {
+        if reason.is_problem() {
+            eprintln!("[skip] {}: {}", clip.dir.display(), reason);
+        } else {
+            say!("[skip] {}: {}", clip.dir.display(), reason);
+        }
+        report::skipped(clip, &reason);
+        self.skipped.push((clip.dir.clone(), reason));
+    }
+
+    /// Record a skip without printing it (e.g. clips filtered out before the run).
+    pub fn skip_quietly(&mut self, clip: &ClipDir, reason: SkipReason) {
+        report::skipped(clip, &reason);
+        self.skipped.push((clip.dir.clone(), reason));
+    }
+
+    pub fn fail(&mut self, clip: &ClipDir, err: &Error) {
+        eprintln!("[fail] {}", err);
+        report::failed(clip, err);
+        self.failed
+            .push((clip.dir.clone(), err.kind(), err.to_string()));
+    }
+
+    /// How many clips were skipped for the same kind of reason as `reason`.
+    pub fn skipped_for(&self, reason: &SkipReason) -> usize {
+        self.skipped
+            .iter()
+            .filter(|(_, r)| r.code() == reason.code())
+            .count()
+    }
+
+    pub fn any_failed(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    /// Clip folder and error message of every failed clip, in the order they failed.
+    pub fn failures(&self) -> impl Iterator<Item = (&Path, &str)> {
+        self.failed
+            .iter()
+            .map(|(dir, _, msg)| (dir.as_path(), msg.as_str()))
+    }
+
+    fn skip_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, r) in &self.skipped {
+            *counts.entry(r.code()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// One-line human summary, e.g. `2 converted, 0 failed, 3 skipped (in_progress: 3)`.
+    pub fn line(&self) -> String {
+        let mut s = format!(
+            "{} converted, {} failed, {} skipped",
+            self.converted.len(),
+            self.failed.len(),
+            self.skipped.len()
+        );
+        let counts = self.skip_counts();
+        if !counts.is_empty() {
+            let parts: Vec<String> = counts.iter().map(|(k, n)| format!("{k}: {n}")).collect();
+            s.push_str(&format!(" ({})", parts.join(", ")));
+        }
+        s
+    }
+
+    pub fn to_json(&self) -> Value {
+        let path = |p: &PathBuf| Value::from(p.to_string_lossy().as_ref());
+        json::obj([
+            (
+                "converted",
+                Value::Arr(self.converted.iter().map(path).collect()),
+            ),
+            (
+                "failed",
+                Value::Arr(
+                    self.failed
+                        .iter()
+                        .map(|(dir, kind, msg)| {
+                            json::obj([
+                                ("dir", path(dir)),
+                                ("error", (*kind).into()),
+                                ("message", msg.as_str().into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "skipped",
+                Value::Arr(
+                    self.skipped
+                        .iter()
+                        .map(|(dir, r)| {
+                            json::obj([
+                                ("dir", path(dir)),
+                                ("reason", r.code().into()),
+                                ("detail", r.to_string().into()),
+                                ("problem", r.is_problem().into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
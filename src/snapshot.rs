@@ -0,0 +1,148 @@
+//! Persisted directory snapshot used to speed up repeated scans.
+//!
+//! For every directory visited during a scan we remember its mtime and the names of its
+//! subdirectories. On the next run, a directory whose mtime is unchanged can reuse the cached
+//! child list instead of being listed again, which on large `userdata` trees (and spinning
+//! disks) removes most of the scan cost. The subdirectories themselves are still stat'ed, so a
+//! change deep inside the tree is always noticed at the level where it happened.
+
+use crate::{appdirs, atomic};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const HEADER: &str = "# steamclipconverter scan snapshot v1";
+
+/// Modification times this close to the moment the snapshot was taken are not trusted:
+/// coarse filesystem timestamps (FAT, some network shares) could hide a later change.
+const RACY_WINDOW_NANOS: u128 = 2_000_000_000;
+
+#[derive(Clone, Debug)]
+struct DirRecord {
+    mtime: u128,
+    subdirs: Vec<String>,
+}
+
+/// Snapshot of one scan root, loaded from and saved to the user cache directory.
+#[derive(Default)]
+pub struct ScanSnapshot {
+    taken_at: u128,
+    previous: HashMap<PathBuf, DirRecord>,
+    current: HashMap<PathBuf, DirRecord>,
+}
+
+impl ScanSnapshot {
+    /// Load the snapshot for `root`, or start empty if there is none (or it is unreadable).
+    pub fn load(root: &Path) -> Self {
+        let mut snap = ScanSnapshot::default();
+        let Some(path) = snapshot_path(root) else {
+            return snap;
+        };
+        let Ok(txt) = fs::read_to_string(&path) else {
+            return snap;
+        };
+
+        let mut lines = txt.lines();
+        let Some(header) = lines.next() else {
+            return snap;
+        };
+        let mut hparts = header.split('\t');
+        if hparts.next() != Some(HEADER) {
+            return snap; // unknown format: rebuild from scratch
+        }
+        snap.taken_at = hparts.nth(1).and_then(|s| s.parse().ok()).unwrap_or(0);
+
+        for line in lines {
+            let mut parts = line.split('\t');
+            let (Some(mtime), Some(dir)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let Ok(mtime) = mtime.parse::<u128>() else {
+                continue;
+            };
+            let subdirs = parts.map(str::to_string).collect();
+            snap.previous
+                .insert(PathBuf::from(dir), DirRecord { mtime, subdirs });
+        }
+        snap
+    }
+
+    /// Cached subdirectory names of `dir`, if the directory is unchanged since the last scan.
+    pub fn cached_subdirs(&self, dir: &Path, mtime: u128) -> Option<&[String]> {
+        let rec = self.previous.get(dir)?;
+        if rec.mtime != mtime || mtime + RACY_WINDOW_NANOS >= self.taken_at {
+            return None;
+        }
+        Some(&rec.subdirs)
+    }
+
+    /// Record the subdirectories seen in `dir` during the current scan.
+    pub fn record(&mut self, dir: &Path, mtime: u128, subdirs: Vec<String>) {
+        self.current
+            .insert(dir.to_path_buf(), DirRecord { mtime, subdirs });
+    }
+
+    /// Persist what the current scan saw; directories not visited this time are dropped.
+    pub fn save(&self, root: &Path) -> io::Result<()> {
+        let Some(path) = snapshot_path(root) else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut out = format!("{}\t{}\t{}\n", HEADER, root.display(), now_nanos());
+        let mut dirs: Vec<_> = self.current.iter().collect();
+        dirs.sort_by(|a, b| a.0.cmp(b.0));
+        for (dir, rec) in dirs {
+            // Paths we can't represent losslessly in this format are simply rescanned next time.
+            let Some(d) = dir.to_str() else { continue };
+            if d.contains(['\t', '\n']) || rec.subdirs.iter().any(|s| s.contains(['\t', '\n'])) {
+                continue;
+            }
+            out.push_str(&rec.mtime.to_string());
+            out.push('\t');
+            out.push_str(d);
+            for s in &rec.subdirs {
+                out.push('\t');
+                out.push_str(s);
+            }
+            out.push('\n');
+        }
+
+        atomic::write(&path, out)
+    }
+}
+
+/// Modification time of a filesystem entry as nanoseconds since the epoch.
+pub fn mtime_nanos(meta: &fs::Metadata) -> Option<u128> {
+    let t = meta.modified().ok()?;
+    Some(t.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+fn now_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+/// `<cache>/steamclipconverter/scan-<hash of root>.tsv`
+fn snapshot_path(root: &Path) -> Option<PathBuf> {
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let key = fnv1a64(root.to_string_lossy().as_bytes());
+    Some(appdirs::user_cache_dir()?.join(format!("scan-{key:016x}.tsv")))
+}
+
+/// Stable 64-bit FNV-1a, used to derive file names from paths.
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        h ^= u64::from(*b);
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h
+}
@@ -0,0 +1,199 @@
+//! Running ffmpeg on another machine over SSH (`--remote ssh://user@host`): each clip's
+//! segments are copied there with rsync, converted by that machine's ffmpeg and the output
+//! copied back, for devices too slow to transcode themselves (a Steam Deck, a laptop).
+//!
+//! Needs `ssh` and `rsync` here and `rsync` and `ffmpeg` there, with a login that doesn't
+//! ask for a password (keys or an agent; runs are unattended). Clips are worked on in a
+//! folder under the URL's path, by default `.cache/steamclipconverter-remote` in the remote
+//! account's home, which is removed again after each clip.
+
+use crate::Error;
+use std::{
+    path::Path,
+    process::{Command, Output},
+};
+
+/// Work folder used when the URL has no path, relative to the remote home.
+const DEFAULT_DIR: &str = ".cache/steamclipconverter-remote";
+
+/// An `ssh://[user@]host[:port][/path]` URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SshRemote {
+    /// `user@host` or `host`, as given to ssh.
+    pub host: String,
+    pub port: Option<u16>,
+    /// Where clips are worked on there.
+    pub dir: String,
+}
+
+impl SshRemote {
+    /// Parse `ssh://[user@]host[:port][/path]`; used as the clap value parser of `--remote`.
+    pub fn parse(s: &str) -> Result<SshRemote, String> {
+        let rest = s
+            .strip_prefix("ssh://")
+            .ok_or_else(|| format!("expected ssh://[user@]host[:port][/path], got '{s}'"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                Some(
+                    port.parse::<u16>()
+                        .map_err(|_| format!("bad port '{port}' in '{s}'"))?,
+                ),
+            ),
+            None => (authority, None),
+        };
+        if host.is_empty() || host.ends_with('@') || host.starts_with('-') {
+            return Err(format!("no host in '{s}'"));
+        }
+        let dir = path.trim_end_matches('/');
+        Ok(SshRemote {
+            host: host.to_string(),
+            port,
+            dir: if dir.is_empty() {
+                DEFAULT_DIR.to_string()
+            } else {
+                dir.to_string()
+            },
+        })
+    }
+
+    /// The ssh options: never prompt, and the port.
+    fn ssh_options(&self) -> Vec<String> {
+        let mut args = vec!["-o".to_string(), "BatchMode=yes".to_string()];
+        if let Some(port) = self.port {
+            args.extend(["-p".to_string(), port.to_string()]);
+        }
+        args
+    }
+
+    /// `ssh <host> <script>`; the script is run by the remote user's shell.
+    fn ssh(&self, script: &str) -> Command {
+        let mut cmd = Command::new("ssh");
+        cmd.args(self.ssh_options()).arg(&self.host).arg(script);
+        cmd
+    }
+
+    fn rsync(&self) -> Command {
+        let mut cmd = Command::new("rsync");
+        cmd.args(["-a", "--partial", "-e"])
+            .arg(format!("ssh {}", self.ssh_options().join(" ")));
+        cmd
+    }
+
+    /// Check that the machine can be logged in to and has rsync and ffmpeg, as an error
+    /// message.
+    pub fn check(&self) -> Result<(), String> {
+        let out = run(self.ssh("command -v rsync >/dev/null && ffmpeg -hide_banner -version"))
+            .map_err(|e| e.to_string())?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} has no working rsync and ffmpeg, or doesn't accept a login without a \
+                 password: {}",
+                self.host,
+                last_line(&out)
+            ))
+        }
+    }
+
+    /// Copy the clip folder to a new work folder there.
+    pub(crate) fn upload(&self, clip_dir: &Path) -> Result<WorkDir<'_>, Error> {
+        let name: String = clip_dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let work = WorkDir {
+            remote: self,
+            path: format!("{}/{}-{}", self.dir, name, std::process::id()),
+        };
+        self.checked(
+            self.ssh(&format!("mkdir -p {}", quote(&work.path))),
+            "mkdir",
+        )?;
+        let mut src = clip_dir.as_os_str().to_owned();
+        src.push("/");
+        let mut rsync = self.rsync();
+        rsync.arg(src).arg(format!("{}:{}/", self.host, work.path));
+        self.checked(rsync, "rsync")?;
+        Ok(work)
+    }
+
+    /// `cmd`, built for the work folder, run there by `ssh` with its stdout and stderr
+    /// passed through.
+    pub(crate) fn command(&self, work: &WorkDir, cmd: &Command) -> Command {
+        let mut script = format!("cd {} && exec", quote(&work.path));
+        script.push(' ');
+        script.push_str(&quote(&cmd.get_program().to_string_lossy()));
+        for arg in cmd.get_args() {
+            script.push(' ');
+            script.push_str(&quote(&arg.to_string_lossy()));
+        }
+        self.ssh(&script)
+    }
+
+    /// Copy `file` of the work folder back to `local`.
+    pub(crate) fn download(&self, work: &WorkDir, file: &str, local: &Path) -> Result<(), Error> {
+        let mut rsync = self.rsync();
+        rsync
+            .arg(format!("{}:{}/{}", self.host, work.path, file))
+            .arg(local);
+        self.checked(rsync, "rsync")
+    }
+
+    fn checked(&self, cmd: Command, what: &str) -> Result<(), Error> {
+        let out = run(cmd)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::Remote {
+                host: self.host.clone(),
+                reason: format!("{what} failed: {}", last_line(&out)),
+            })
+        }
+    }
+}
+
+/// A clip's work folder on the remote machine, removed when dropped.
+pub(crate) struct WorkDir<'a> {
+    remote: &'a SshRemote,
+    pub path: String,
+}
+
+impl Drop for WorkDir<'_> {
+    fn drop(&mut self) {
+        let _ = run(self.remote.ssh(&format!("rm -rf {}", quote(&self.path))));
+    }
+}
+
+fn run(mut cmd: Command) -> Result<Output, Error> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    cmd.output()
+        .map_err(|source| Error::Spawn { program, source })
+}
+
+fn last_line(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr)
+        .lines()
+        .rfind(|l| !l.trim().is_empty())
+        .unwrap_or("no output")
+        .to_string()
+}
+
+/// `s` as one word for a POSIX shell.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
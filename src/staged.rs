@@ -0,0 +1,37 @@
+//! Inputs whose clips aren't in a local folder (a backup archive, a remote agent): each clip
+//! is fetched into a staging folder just before it converts and removed afterwards.
+
+use crate::sha256::TreeDigest;
+use std::{io, path::PathBuf};
+use steamclipconverter::ClipDir;
+
+pub trait StagedInput: Sync {
+    /// What the run converts from, as shown in the plan and used to key the run queue.
+    fn origin(&self) -> PathBuf;
+
+    /// Every clip, located where [`StagedInput::fetch`] puts it.
+    fn clips(&self) -> Vec<ClipDir>;
+
+    /// Make the clip's folder exist locally.
+    fn fetch(&self, clip: &ClipDir) -> io::Result<()>;
+
+    /// The clip as it should be recorded in the catalog, at its real source.
+    fn source_of(&self, clip: &ClipDir) -> ClipDir;
+
+    /// Game name known at the source, when this machine can't resolve the appid.
+    fn game_name(&self, _clip: &ClipDir) -> Option<String> {
+        None
+    }
+
+    /// Whether converted clips are removed at the source; [`StagedInput::finished`] then
+    /// gets the digest of the fetched copy to prove it arrived intact.
+    fn deletes_source(&self) -> bool {
+        false
+    }
+
+    /// The clip was converted and recorded.
+    fn finished(&self, _clip: &ClipDir, _digest: &TreeDigest) {}
+
+    /// Remove the clip's local copy.
+    fn release(&self, clip: &ClipDir);
+}
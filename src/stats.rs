@@ -0,0 +1,179 @@
+//! `stats` subcommand: how big the converted library is, per game and over time.
+//!
+//! The same aggregates are served as JSON at the agent's `/stats`, for dashboard panels.
+
+use crate::{
+    catalog::{Catalog, CatalogEntry},
+    json::{self, Value},
+    quota::format_size,
+};
+use clap::{ArgAction, Args};
+use std::{collections::BTreeMap, path::PathBuf};
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    /// Catalog to summarize (defaults to the user catalog).
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+
+    /// Print the aggregates as one JSON document instead of a table.
+    #[arg(long, action = ArgAction::SetTrue)]
+    json: bool,
+}
+
+/// Clip count, bytes and seconds of a group of outputs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Totals {
+    pub clips: u64,
+    pub bytes: u64,
+    pub seconds: f64,
+    /// Clips whose record has no duration (catalogued before durations were kept).
+    pub unknown_duration: u64,
+}
+
+impl Totals {
+    fn add(&mut self, e: &CatalogEntry) {
+        self.clips += 1;
+        self.bytes += e.size;
+        match e.duration {
+            Some(d) => self.seconds += d,
+            None => self.unknown_duration += 1,
+        }
+    }
+}
+
+/// Aggregates of every catalogued output that is still on disk and wasn't deleted in review.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub total: Totals,
+    /// By appid: the best known name and the totals.
+    pub games: BTreeMap<u32, (String, Totals)>,
+    /// By conversion day (`YYYY-MM-DD`, UTC): what was added to the library that day.
+    pub days: BTreeMap<String, Totals>,
+}
+
+impl Stats {
+    pub fn collect(catalog: &Catalog) -> Stats {
+        let mut stats = Stats::default();
+        for e in catalog.entries() {
+            if e.review_deleted() || !e.output.is_file() {
+                continue;
+            }
+            stats.total.add(e);
+            let (name, totals) = stats.games.entry(e.appid).or_default();
+            // Prefer a resolved name over the appid fallback used when lookup failed.
+            if name.is_empty() || *name == e.appid.to_string() {
+                *name = e.game.clone();
+            }
+            totals.add(e);
+            let day = e.converted_at.get(..10).unwrap_or("unknown");
+            stats.days.entry(day.to_string()).or_default().add(e);
+        }
+        stats
+    }
+
+    /// `days` carries running totals as well, so a panel can graph growth directly.
+    pub fn to_json(&self) -> Value {
+        let games = self
+            .games
+            .iter()
+            .map(|(appid, (name, t))| {
+                json::obj([
+                    ("appid", (*appid).into()),
+                    ("game", name.as_str().into()),
+                    ("clips", t.clips.into()),
+                    ("bytes", t.bytes.into()),
+                    ("seconds", t.seconds.round().into()),
+                    ("unknown_duration", t.unknown_duration.into()),
+                ])
+            })
+            .collect();
+        let mut running = Totals::default();
+        let days = self
+            .days
+            .iter()
+            .map(|(day, t)| {
+                running.clips += t.clips;
+                running.bytes += t.bytes;
+                running.seconds += t.seconds;
+                json::obj([
+                    ("date", day.as_str().into()),
+                    ("clips", t.clips.into()),
+                    ("bytes", t.bytes.into()),
+                    ("seconds", t.seconds.round().into()),
+                    ("total_clips", running.clips.into()),
+                    ("total_bytes", running.bytes.into()),
+                    ("total_seconds", running.seconds.round().into()),
+                ])
+            })
+            .collect();
+        let t = self.total;
+        json::obj([
+            ("generator", "steamclipconverter".into()),
+            ("version", 1u32.into()),
+            (
+                "generated_at",
+                chrono::Utc::now()
+                    .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+                    .into(),
+            ),
+            ("clips", t.clips.into()),
+            ("bytes", t.bytes.into()),
+            ("seconds", t.seconds.round().into()),
+            ("unknown_duration", t.unknown_duration.into()),
+            ("games", Value::Arr(games)),
+            ("days", Value::Arr(days)),
+        ])
+    }
+}
+
+/// `H:MM:SS`.
+fn hms(seconds: f64) -> String {
+    let s = seconds.round() as u64;
+    format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+}
+
+pub fn run(args: StatsArgs) -> i32 {
+    let Some(path) = args.catalog.clone().or_else(Catalog::default_path) else {
+        eprintln!("ERROR: no catalog location; pass --catalog");
+        return 2;
+    };
+    let catalog = match Catalog::open(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ERROR: cannot read catalog {}: {}", path.display(), e);
+            return 2;
+        }
+    };
+    let stats = Stats::collect(&catalog);
+    if args.json {
+        println!("{}", stats.to_json());
+        return 0;
+    }
+
+    let mut games: Vec<_> = stats.games.values().collect();
+    games.sort_by_key(|(_, t)| std::cmp::Reverse(t.bytes));
+    for (name, t) in games {
+        println!(
+            "{:<32} {:>5} clip(s) {:>10} {:>10}",
+            name,
+            t.clips,
+            format_size(t.bytes),
+            hms(t.seconds)
+        );
+    }
+    let t = stats.total;
+    println!(
+        "{} clip(s) from {} game(s), {}, {}{}",
+        t.clips,
+        stats.games.len(),
+        format_size(t.bytes),
+        hms(t.seconds),
+        if t.unknown_duration > 0 {
+            format!(" ({} without a recorded duration)", t.unknown_duration)
+        } else {
+            String::new()
+        }
+    );
+    0
+}
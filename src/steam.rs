@@ -0,0 +1,405 @@
+//! Where Steam keeps things: its install roots, the game libraries listed in
+//! `libraryfolders.vdf`, game names from `appmanifest_<appid>.acf`, and the `clip_*`
+//! folders around saved clips.
+//!
+//! Clips copied from another machine name games that may not be installed here (or no
+//! Steam at all), so [`AppNames`] also reads plain name lists, and nothing here fails when
+//! Steam's folders are missing: the name just falls back to the appid. Games that were
+//! uninstalled are still named in Steam's appinfo cache (see [`crate::appinfo`]), and as a
+//! last resort the store can be asked, with the answers kept in a cache file.
+
+use crate::{ClipDir, UNKNOWN_APPID, appdirs, appinfo, json};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// Name list read from the config directory when present.
+pub const APP_NAMES_FILE: &str = "appnames.txt";
+
+/// Names the store returned for `--online-names`, in the cache directory, same format as a
+/// name list.
+pub const NAME_CACHE_FILE: &str = "appnames-cache.txt";
+
+/// Names of common games built into the binary (`bundled-appnames` feature).
+#[cfg(feature = "bundled-appnames")]
+const BUNDLED_APP_NAMES: &str = include_str!("../data/appnames.txt");
+
+/// OS-specific default Steam root candidates (NOT steamapps; that's added later).
+pub fn default_roots() -> Vec<PathBuf> {
+    let mut v: Vec<PathBuf> = Vec::new();
+    #[cfg(target_os = "macos")]
+    {
+        if let Ok(home) = std::env::var("HOME") {
+            v.push(PathBuf::from(format!(
+                "{home}/Library/Application Support/Steam"
+            )));
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(home) = std::env::var("HOME") {
+            v.push(PathBuf::from(format!("{home}/.local/share/Steam")));
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        if let Ok(pf86) = std::env::var("PROGRAMFILES(X86)") {
+            v.push(PathBuf::from(format!(r"{pf86}\Steam")));
+        } else {
+            v.push(PathBuf::from(r"C:\Program Files (x86)\Steam"));
+        }
+    }
+    v
+}
+
+/// Discover steamapps roots across OSes:
+/// - default Steam roots
+/// - plus any additional libraries from libraryfolders.vdf (under <root>/config/ or <root>/steamapps/)
+pub fn discover_steamapps_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+
+    for root in default_roots() {
+        let sa = root.join("steamapps");
+        if sa.is_dir() {
+            roots.push(sa.clone());
+        }
+
+        let vdf1 = root.join("config").join("libraryfolders.vdf");
+        let vdf2 = root.join("steamapps").join("libraryfolders.vdf");
+
+        for vdf in [vdf1, vdf2] {
+            if vdf.is_file()
+                && let Ok(txt) = fs::read_to_string(&vdf)
+            {
+                for path in parse_libraryfolders_paths(&txt) {
+                    let sp = Path::new(&path).join("steamapps");
+                    if sp.is_dir() {
+                        roots.push(sp);
+                    }
+                }
+            }
+        }
+    }
+
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Extract library "path" values from libraryfolders.vdf
+fn parse_libraryfolders_paths(vdf_text: &str) -> Vec<String> {
+    // Accept lines like: "path" "/Volumes/External/SteamLibrary" or "path" "D:\\SteamLibrary"
+    let path_re = Regex::new(r#""path"\s*"([^"]+)""#).unwrap();
+    path_re
+        .captures_iter(vdf_text)
+        .map(|c| c[1].to_string())
+        .collect()
+}
+
+/// Read appmanifest_<appid>.acf from any steamapps root and extract "name"
+/// (clips without a game are "Unknown").
+pub fn resolve_app_name(appid: u32, steamapps_roots: &[PathBuf]) -> Option<String> {
+    if appid == UNKNOWN_APPID {
+        return Some("Unknown".into());
+    }
+    let manifest = format!("appmanifest_{}.acf", appid);
+    for root in steamapps_roots {
+        let p = root.join(&manifest);
+        if p.is_file()
+            && let Ok(txt) = fs::read_to_string(&p)
+            && let Some(name) = parse_acf_name(&txt)
+        {
+            return Some(name);
+        }
+    }
+    None
+}
+
+/// Game names by appid: the appmanifests of the steamapps roots first, then name lists,
+/// then Steam's appinfo cache, then (when enabled) the store.
+#[derive(Clone, Debug, Default)]
+pub struct AppNames {
+    roots: Vec<PathBuf>,
+    table: HashMap<u32, String>,
+    /// The appinfo.vdf names, read the first time a name isn't found elsewhere.
+    appinfo: OnceLock<HashMap<u32, String>>,
+    online: bool,
+    /// Store answers of this run, misses included, so each appid is asked once.
+    asked: Arc<Mutex<HashMap<u32, Option<String>>>>,
+}
+
+impl AppNames {
+    pub fn new(steamapps_roots: Vec<PathBuf>) -> Self {
+        AppNames {
+            roots: steamapps_roots,
+            ..AppNames::default()
+        }
+    }
+
+    /// This machine's Steam libraries, plus `appnames.txt` in the config directory if it
+    /// can be read, over the names cached from the store and the bundled names.
+    pub fn discover() -> Self {
+        let mut names = AppNames::new(discover_steamapps_roots());
+        #[cfg(feature = "bundled-appnames")]
+        names.table.extend(parse_app_names(BUNDLED_APP_NAMES));
+        if let Some(path) = name_cache_path()
+            && path.is_file()
+        {
+            let _ = names.load(&path);
+        }
+        if let Some(path) = appdirs::user_config_dir().map(|d| d.join(APP_NAMES_FILE))
+            && path.is_file()
+        {
+            let _ = names.load(&path);
+        }
+        names
+    }
+
+    /// Also read the appmanifests of the Steam folder a copied `userdata` tree under
+    /// `input` came with, if it came with one.
+    pub fn add_roots_near(&mut self, input: &Path) {
+        let Ok(input) = std::path::absolute(input) else {
+            return;
+        };
+        for dir in input.ancestors() {
+            if dir.file_name().is_some_and(|n| n == "userdata")
+                && let Some(steamapps) = dir.parent().map(|root| root.join("steamapps"))
+                && steamapps.is_dir()
+                && !self.roots.contains(&steamapps)
+            {
+                self.roots.push(steamapps);
+            }
+        }
+    }
+
+    /// Add the names in the list at `path` (see [`parse_app_names`]); returns how many.
+    pub fn load(&mut self, path: &Path) -> io::Result<usize> {
+        let names = parse_app_names(&fs::read_to_string(path)?);
+        let n = names.len();
+        self.table.extend(names);
+        Ok(n)
+    }
+
+    /// Ask the Steam store for names found nowhere else (needs `curl`), caching them in
+    /// [`NAME_CACHE_FILE`].
+    pub fn set_online(&mut self, online: bool) {
+        self.online = online;
+    }
+
+    /// True when there is nowhere to look names up.
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty() && self.table.is_empty() && !self.online
+    }
+
+    /// The game's name, if any source knows it.
+    pub fn resolve(&self, appid: u32) -> Option<String> {
+        if appid == UNKNOWN_APPID {
+            return None;
+        }
+        resolve_app_name(appid, &self.roots)
+            .or_else(|| self.table.get(&appid).cloned())
+            .or_else(|| self.appinfo().get(&appid).cloned())
+            .or_else(|| self.online.then(|| self.ask_store(appid)).flatten())
+    }
+
+    fn appinfo(&self) -> &HashMap<u32, String> {
+        self.appinfo.get_or_init(|| {
+            let mut names = HashMap::new();
+            let mut files: Vec<PathBuf> = self
+                .roots
+                .iter()
+                .filter_map(|sa| Some(sa.parent()?.join("appcache").join("appinfo.vdf")))
+                .filter(|p| p.is_file())
+                .collect();
+            files.dedup();
+            for file in files {
+                if let Ok(found) = appinfo::read_names(&file) {
+                    for (id, name) in found {
+                        names.entry(id).or_insert(name);
+                    }
+                }
+            }
+            names
+        })
+    }
+
+    fn ask_store(&self, appid: u32) -> Option<String> {
+        let mut asked = self.asked.lock().unwrap();
+        if let Some(known) = asked.get(&appid) {
+            return known.clone();
+        }
+        let name = store_name(appid);
+        if let Some(name) = &name
+            && let Some(path) = name_cache_path()
+        {
+            let _ = append_cache(&path, appid, name);
+        }
+        asked.insert(appid, name.clone());
+        name
+    }
+}
+
+fn name_cache_path() -> Option<PathBuf> {
+    appdirs::user_cache_dir().map(|d| d.join(NAME_CACHE_FILE))
+}
+
+fn append_cache(path: &Path, appid: u32, name: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(f, "{appid}\t{name}")
+}
+
+/// The store's name for `appid`, from the public appdetails endpoint.
+fn store_name(appid: u32) -> Option<String> {
+    let url = format!("https://store.steampowered.com/api/appdetails?appids={appid}&filters=basic");
+    let out = Command::new("curl")
+        .args(["-fsS", "--max-time", "10", &url])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let doc = json::parse(&String::from_utf8_lossy(&out.stdout)).ok()?;
+    let name = doc
+        .get(&appid.to_string())?
+        .get("data")?
+        .get("name")?
+        .as_str()?
+        .trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Name list contents: either the JSON of the Steam Web API's app list
+/// (`ISteamApps/GetAppList`, `{"applist":{"apps":[{"appid":..,"name":..}]}}`), or lines
+/// of `<appid> <name>` (separated by a tab, comma, `=` or spaces; `#` starts a comment).
+pub fn parse_app_names(text: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    if text.trim_start().starts_with('{') {
+        let doc = json::parse(text).unwrap_or(json::Value::Null);
+        let apps = doc
+            .get("applist")
+            .and_then(|l| l.get("apps"))
+            .or_else(|| doc.get("apps"))
+            .and_then(json::Value::as_arr)
+            .unwrap_or_default();
+        for app in apps {
+            if let (Some(id), Some(name)) = (
+                app.get("appid").and_then(json::Value::as_u64),
+                app.get("name").and_then(json::Value::as_str),
+            ) && !name.trim().is_empty()
+            {
+                names.insert(id as u32, name.trim().to_string());
+            }
+        }
+        return names;
+    }
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let sep = |c: char| c == '\t' || c == ',' || c == '=' || c.is_whitespace();
+        let Some((id, name)) = line.split_once(sep) else {
+            continue;
+        };
+        let name = name.trim_start_matches(sep).trim_matches('"').trim();
+        if let Ok(id) = id.trim().parse::<u32>()
+            && !name.is_empty()
+        {
+            names.insert(id, name.to_string());
+        }
+    }
+    names
+}
+
+/// Minimal ACF parser: `"name"   "Some Game"`
+fn parse_acf_name(acf_text: &str) -> Option<String> {
+    let re = Regex::new(r#""name"\s*"([^"]+)""#).unwrap();
+    re.captures(acf_text).map(|c| c[1].to_string())
+}
+
+/// Why a converted clip folder is kept instead of deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeepReason {
+    /// Not inside one of Steam's `gamerecordings` folders or an allowed path: it may be an
+    /// unrelated video folder the input was pointed at by mistake.
+    OutsideRecordings,
+    /// Its segments couldn't be hashed, or the hash wasn't recorded, so nothing would show
+    /// later what the output was made from.
+    NotHashed,
+}
+
+impl KeepReason {
+    /// Stable snake_case name.
+    pub fn code(self) -> &'static str {
+        match self {
+            KeepReason::OutsideRecordings => "outside_recordings",
+            KeepReason::NotHashed => "not_hashed",
+        }
+    }
+}
+
+/// Whether deleting `dir` is allowed: it is inside one of Steam's `gamerecordings` folders
+/// or under one of the `allow` paths.
+pub fn may_delete(dir: &Path, allow: &[PathBuf]) -> bool {
+    let canonical = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let dir = canonical(dir);
+    dir.ancestors()
+        .any(|a| a.file_name().is_some_and(|n| n == "gamerecordings"))
+        || allow.iter().any(|a| dir.starts_with(canonical(a)))
+}
+
+/// The checks before a converted clip folder `dir` is deleted: it must pass
+/// [`may_delete`], and where its segment hash is kept (`hashed` is Some), the hash must
+/// have been taken and recorded.
+pub fn delete_check(dir: &Path, allow: &[PathBuf], hashed: Option<bool>) -> Result<(), KeepReason> {
+    if !may_delete(dir, allow) {
+        Err(KeepReason::OutsideRecordings)
+    } else if hashed == Some(false) {
+        Err(KeepReason::NotHashed)
+    } else {
+        Ok(())
+    }
+}
+
+/// After `clip`'s fg_* folder was deleted: if it was the only folder in its parent
+/// `video/`, remove the `clip_<appid>_<date>_<time>` grandparent too. Returns what was
+/// removed.
+pub fn remove_clip_parent(clip: &ClipDir) -> io::Result<Option<PathBuf>> {
+    // parent should be .../video/
+    let Some(video_dir) = clip.dir.parent() else {
+        return Ok(None);
+    };
+    if video_dir.file_name().and_then(|s| s.to_str()) != Some("video") {
+        return Ok(None);
+    }
+
+    // Are there any subdirectories left in video/ ?
+    let any_left = fs::read_dir(video_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|ent| ent.path().is_dir());
+    if any_left {
+        return Ok(None); // not the only one
+    }
+
+    // grandparent expected to be clip_<appid>_<date>_<time>
+    let Some(clip_parent) = video_dir.parent() else {
+        return Ok(None);
+    };
+    let re = Regex::new(r"^clip_\d+_\d{8}_\d{6}$").unwrap();
+    match clip_parent.file_name().and_then(|s| s.to_str()) {
+        Some(name) if re.is_match(name) => {
+            fs::remove_dir_all(clip_parent)?;
+            Ok(Some(clip_parent.to_path_buf()))
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,86 @@
+//! Spotting recordings that live in a folder a sync client is mirroring (Syncthing,
+//! OneDrive, Dropbox, Nextcloud, iCloud, Steam Cloud).
+//!
+//! Deleting a clip while its chunks are still being uploaded or downloaded can leave the
+//! other side with half a folder, so `--delete-after` is turned off when one is found.
+//! Detection goes by the marker files the clients leave, and by well-known folder names.
+
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+use steamclipconverter::ClipDir;
+
+/// The sync service and the folder it was detected at.
+pub type Synced = (&'static str, PathBuf);
+
+/// Files or folders a sync client keeps at the root of (or inside) what it syncs, and
+/// whether only a file counts. Dropbox's `.dropbox` is a file at the synced folder's root,
+/// but also the name of its config folder in the home directory, above every clip.
+const MARKERS: &[(&str, &str, bool)] = &[
+    (".stfolder", "Syncthing", false),
+    (".dropbox", "Dropbox", true),
+    (".dropbox.cache", "Dropbox", false),
+    (".owncloudsync.log", "Nextcloud", false),
+    (".nextcloudsync.log", "Nextcloud", false),
+    ("remotecache.vdf", "Steam Cloud", false),
+];
+
+/// Whether `dir` itself looks synced.
+fn at(dir: &Path) -> Option<&'static str> {
+    let marked = |&&(marker, _, file_only): &&(&str, &str, bool)| {
+        let p = dir.join(marker);
+        if file_only { p.is_file() } else { p.exists() }
+    };
+    if let Some((_, service, _)) = MARKERS.iter().find(marked) {
+        return Some(service);
+    }
+    let name = dir.file_name()?.to_string_lossy();
+    if name == "OneDrive" || name.starts_with("OneDrive - ") {
+        return Some("OneDrive");
+    }
+    if name == "Mobile Documents" || name == "iCloud Drive" {
+        return Some("iCloud");
+    }
+    // Nextcloud and ownCloud keep `.sync_<id>.db` journals in the synced root.
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .any(|e| {
+            let n = e.file_name();
+            let n = n.to_string_lossy();
+            n.starts_with(".sync_") && n.ends_with(".db")
+        })
+        .then_some("Nextcloud")
+}
+
+/// OneDrive roots Windows announces in the environment.
+fn onedrive_roots() -> Vec<PathBuf> {
+    ["OneDrive", "OneDriveConsumer", "OneDriveCommercial"]
+        .iter()
+        .filter_map(env::var_os)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// The first clip folder found to be inside a synced tree, checking each folder from the
+/// clip up to the root once.
+pub fn detect(clips: &[ClipDir]) -> Option<Synced> {
+    let onedrive = onedrive_roots();
+    let mut seen: HashMap<PathBuf, Option<&'static str>> = HashMap::new();
+    for clip in clips {
+        let dir = std::path::absolute(&clip.dir).unwrap_or_else(|_| clip.dir.clone());
+        if let Some(root) = onedrive.iter().find(|r| dir.starts_with(r)) {
+            return Some(("OneDrive", root.clone()));
+        }
+        for anc in dir.ancestors() {
+            let found = *seen.entry(anc.to_path_buf()).or_insert_with(|| at(anc));
+            if let Some(service) = found {
+                return Some((service, anc.to_path_buf()));
+            }
+        }
+    }
+    None
+}
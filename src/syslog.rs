@@ -0,0 +1,182 @@
+//! `--system-log`: report run events to the platform's own log, for unattended runs
+//! (scheduled tasks, services) that nobody watches the console of.
+//!
+//! - Linux: the syslog socket `/dev/log` (read by journald and rsyslog).
+//! - macOS: the syslog socket `/var/run/syslog`, which lands in the unified log.
+//! - Windows: the Application event log, source `steamclipconverter`.
+
+use std::io;
+
+const IDENT: &str = "steamclipconverter";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+pub struct SystemLog {
+    sink: imp::Sink,
+}
+
+impl SystemLog {
+    pub fn open() -> io::Result<SystemLog> {
+        Ok(SystemLog {
+            sink: imp::Sink::open()?,
+        })
+    }
+
+    /// Write one event. A log that stops accepting messages doesn't stop the run.
+    pub fn event(&self, level: Level, msg: &str) {
+        if let Err(e) = self.sink.write(level, msg) {
+            eprintln!("[warn] cannot write to the system log: {}", e);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::{IDENT, Level};
+    use std::{io, os::unix::net::UnixDatagram};
+
+    #[cfg(target_os = "macos")]
+    const SOCKET: &str = "/var/run/syslog";
+    #[cfg(not(target_os = "macos"))]
+    const SOCKET: &str = "/dev/log";
+
+    /// Facility `user` (1); priority is facility * 8 + severity.
+    const FACILITY: u8 = 1;
+
+    pub struct Sink(UnixDatagram);
+
+    impl Sink {
+        pub fn open() -> io::Result<Sink> {
+            let sock = UnixDatagram::unbound()?;
+            sock.connect(SOCKET)
+                .map_err(|e| io::Error::new(e.kind(), format!("{SOCKET}: {e}")))?;
+            Ok(Sink(sock))
+        }
+
+        pub fn write(&self, level: Level, msg: &str) -> io::Result<()> {
+            let severity = match level {
+                Level::Error => 3,
+                Level::Warning => 4,
+                Level::Info => 6,
+            };
+            // RFC 3164 without the timestamp, which the daemon fills in.
+            let line = format!(
+                "<{}>{}[{}]: {}",
+                FACILITY * 8 + severity,
+                IDENT,
+                std::process::id(),
+                msg
+            );
+            self.0.send(line.as_bytes()).map(|_| ())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::{IDENT, Level};
+    use std::{ffi::c_void, io, ptr};
+
+    const EVENTLOG_ERROR_TYPE: u16 = 0x0001;
+    const EVENTLOG_WARNING_TYPE: u16 = 0x0002;
+    const EVENTLOG_INFORMATION_TYPE: u16 = 0x0004;
+
+    #[link(name = "advapi32")]
+    unsafe extern "system" {
+        fn RegisterEventSourceW(server: *const u16, source: *const u16) -> *mut c_void;
+        fn DeregisterEventSource(log: *mut c_void) -> i32;
+        fn ReportEventW(
+            log: *mut c_void,
+            kind: u16,
+            category: u16,
+            event_id: u32,
+            user_sid: *mut c_void,
+            num_strings: u16,
+            data_size: u32,
+            strings: *const *const u16,
+            raw_data: *mut c_void,
+        ) -> i32;
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(Some(0)).collect()
+    }
+
+    /// Without a registered message file, Event Viewer shows the text under "the following
+    /// information was included with the event"; it is still searchable and forwardable.
+    pub struct Sink(*mut c_void);
+
+    // The event log handle may be used from any thread.
+    unsafe impl Send for Sink {}
+    unsafe impl Sync for Sink {}
+
+    impl Sink {
+        pub fn open() -> io::Result<Sink> {
+            let source = wide(IDENT);
+            let h = unsafe { RegisterEventSourceW(ptr::null(), source.as_ptr()) };
+            if h.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Sink(h))
+        }
+
+        pub fn write(&self, level: Level, msg: &str) -> io::Result<()> {
+            let (kind, id) = match level {
+                Level::Error => (EVENTLOG_ERROR_TYPE, 3),
+                Level::Warning => (EVENTLOG_WARNING_TYPE, 2),
+                Level::Info => (EVENTLOG_INFORMATION_TYPE, 1),
+            };
+            let text = wide(msg);
+            let strings = [text.as_ptr()];
+            let ok = unsafe {
+                ReportEventW(
+                    self.0,
+                    kind,
+                    0,
+                    id,
+                    ptr::null_mut(),
+                    1,
+                    0,
+                    strings.as_ptr(),
+                    ptr::null_mut(),
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Sink {
+        fn drop(&mut self) {
+            unsafe { DeregisterEventSource(self.0) };
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use super::Level;
+    use std::io;
+
+    pub struct Sink;
+
+    impl Sink {
+        pub fn open() -> io::Result<Sink> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "no system log on this platform",
+            ))
+        }
+
+        pub fn write(&self, _: Level, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
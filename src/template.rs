@@ -0,0 +1,476 @@
+//! `--name-template`: output names built from a clip's metadata, e.g.
+//! `{game|slug}/{date|fmt:%Y/%m}/{time}-{title|truncate:40}.mp4`.
+//!
+//! Variables: `game`, `appid`, `date` (YYYYMMDD), `time` (HHMMSS), `title`, `host`,
+//! `duration` (whole seconds), `user` (Steam persona name), `seq` (take number of the game
+//! on the recording day, `001`, `002`, ..., kept in the catalog), `datetime_local` (record
+//! start in local time, `YYYYMMDD-HHMMSS`), `index` (position in this run, `001`, ...).
+//! Filters, applied left to right after `|`:
+//! - `slug`: lowercase ASCII words joined by `-`.
+//! - `fmt:<strftime>`: `date`/`time` as the record start (UTC), or `datetime_local` in local
+//!   time, in any chrono format.
+//! - `truncate:<n>`: at most n characters.
+//! - `hms`: `duration` as `1h02m03s` (or `2m03s`).
+//!
+//! Optional values: `{title?title:game}` is the title if the clip has one, else the game
+//! (either side may have filters; the else side may be left out), and `{?user}...{/user}`
+//! keeps what is between only when there is a user.
+//!
+//! `{{` and `}}` are literal braces. A `/` in the template (or from `fmt`) makes folders;
+//! every folder and file name is sanitized on its own.
+
+use crate::library::slug;
+use chrono::{
+    DateTime, Local, Utc,
+    format::{Item, StrftimeItems},
+};
+use regex::Regex;
+use sanitize_filename::sanitize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const VARIABLES: &[&str] = &[
+    "game",
+    "appid",
+    "date",
+    "time",
+    "title",
+    "host",
+    "duration",
+    "user",
+    "seq",
+    "datetime_local",
+    "index",
+];
+
+/// `{datetime_local}` without a `fmt` filter.
+const LOCAL_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+#[derive(Clone, Debug)]
+enum Filter {
+    Slug,
+    Fmt(String),
+    Truncate(usize),
+    Hms,
+}
+
+#[derive(Clone, Debug)]
+struct Var {
+    name: String,
+    filters: Vec<Filter>,
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Lit(String),
+    Var(Var),
+    /// `{test?then:else}`
+    Choice {
+        test: String,
+        then: Var,
+        otherwise: Option<Var>,
+    },
+    /// `{?test}...{/test}`
+    Block {
+        test: String,
+        body: Vec<Part>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+/// What a template can refer to for one clip. Text values are already sanitized, so a
+/// `/` in a game name can't make a folder.
+pub struct Vars {
+    pub game: String,
+    pub appid: u32,
+    pub date: String,
+    pub time: String,
+    pub title: Option<String>,
+    pub host: String,
+    pub start: Option<DateTime<Utc>>,
+    pub duration: Option<f64>,
+    pub user: Option<String>,
+    pub seq: Option<u32>,
+    /// Position of the clip in this run, from 1.
+    pub index: Option<usize>,
+}
+
+impl Vars {
+    /// The unfiltered value of a variable; None when the clip doesn't have it.
+    fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "game" => Some(self.game.clone()),
+            "appid" => Some(self.appid.to_string()),
+            "date" => Some(self.date.clone()),
+            "time" => Some(self.time.clone()),
+            "title" => self.title.clone(),
+            "host" => Some(self.host.clone()),
+            "duration" => self.duration.map(|d| format!("{:.0}", d)),
+            "user" => self.user.clone(),
+            "seq" => self.seq.map(|n| format!("{n:03}")),
+            "datetime_local" => self.local_start(LOCAL_FORMAT),
+            "index" => self.index.map(|n| format!("{n:03}")),
+            _ => None,
+        }
+        .filter(|v| !v.is_empty())
+    }
+
+    fn local_start(&self, fmt: &str) -> Option<String> {
+        self.start
+            .map(|t| t.with_timezone(&Local).format(fmt).to_string())
+    }
+}
+
+impl Template {
+    /// Parse and check a template; used as the clap value parser of `--name-template`.
+    pub fn parse(s: &str) -> Result<Template, String> {
+        // Open `{?test}` blocks, innermost last, each with the parts inside it so far.
+        let mut open: Vec<(String, Vec<Part>)> = Vec::new();
+        let mut parts = Vec::new();
+        let mut lit = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    lit.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    lit.push('}');
+                }
+                '{' => {
+                    let mut body = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => body.push(c),
+                            None => return Err(format!("unclosed '{{{body}'")),
+                        }
+                    }
+                    if !lit.is_empty() {
+                        parts.push(Part::Lit(std::mem::take(&mut lit)));
+                    }
+                    if let Some(test) = body.strip_prefix('?') {
+                        let test = variable(test)?;
+                        open.push((test, std::mem::take(&mut parts)));
+                    } else if let Some(end) = body.strip_prefix('/') {
+                        let Some((test, outer)) = open.pop() else {
+                            return Err(format!("'{{/{end}}}' without '{{?{end}}}'"));
+                        };
+                        if end.trim() != test {
+                            return Err(format!("'{{/{end}}}' closes '{{?{test}}}'"));
+                        }
+                        let body = std::mem::replace(&mut parts, outer);
+                        parts.push(Part::Block { test, body });
+                    } else {
+                        parts.push(parse_expr(&body)?);
+                    }
+                }
+                '}' => return Err("unmatched '}' (write '}}' for a literal brace)".into()),
+                c => lit.push(c),
+            }
+        }
+        if let Some((test, _)) = open.last() {
+            return Err(format!("'{{?{test}}}' is never closed with '{{/{test}}}'"));
+        }
+        if !lit.is_empty() {
+            parts.push(Part::Lit(lit));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Whether `{name...}` appears, so costly values are only looked up when needed.
+    pub fn uses(&self, name: &str) -> bool {
+        fn any(parts: &[Part], name: &str) -> bool {
+            parts.iter().any(|p| match p {
+                Part::Lit(_) => false,
+                Part::Var(v) => v.name == name,
+                Part::Choice {
+                    test,
+                    then,
+                    otherwise,
+                } => {
+                    test == name
+                        || then.name == name
+                        || otherwise.as_ref().is_some_and(|v| v.name == name)
+                }
+                Part::Block { test, body } => test == name || any(body, name),
+            })
+        }
+        any(&self.parts, name)
+    }
+
+    /// The relative output path for `vars`, ending in `.<ext>` unless the template already
+    /// gives that extension. Folder names that sanitize to nothing are dropped.
+    pub fn render(&self, vars: &Vars, ext: &str) -> PathBuf {
+        let mut out = String::new();
+        render_parts(&self.parts, vars, &mut out);
+        if !out
+            .to_ascii_lowercase()
+            .ends_with(&format!(".{}", ext.to_ascii_lowercase()))
+        {
+            out.push('.');
+            out.push_str(ext);
+        }
+        out.split(['/', '\\'])
+            .map(|c| sanitize(c.trim()))
+            .filter(|c| !c.is_empty())
+            .collect()
+    }
+}
+
+/// A known variable name.
+fn variable(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if VARIABLES.contains(&name) {
+        Ok(name.to_string())
+    } else {
+        Err(format!(
+            "unknown variable '{{{name}}}' ({})",
+            VARIABLES.join(", ")
+        ))
+    }
+}
+
+/// `var`, `var?then`, or `var?then:otherwise`, each an expression with filters.
+fn parse_expr(body: &str) -> Result<Part, String> {
+    let Some((test, rest)) = body.split_once('?') else {
+        return parse_var(body).map(Part::Var);
+    };
+    let test = variable(test)?;
+    // Filters have `:` too (`truncate:40`), so the else side starts at the first `:`
+    // followed by a variable name.
+    let split = rest.match_indices(':').map(|(i, _)| i).find(|&i| {
+        let after = &rest[i + 1..];
+        let name = after.split('|').next().unwrap_or("").trim();
+        VARIABLES.contains(&name)
+    });
+    let (then, otherwise) = match split {
+        Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+        None => (rest, None),
+    };
+    Ok(Part::Choice {
+        test,
+        then: parse_var(then)?,
+        otherwise: otherwise.map(parse_var).transpose()?,
+    })
+}
+
+fn parse_var(body: &str) -> Result<Var, String> {
+    let mut pieces = body.split('|');
+    let name = variable(pieces.next().unwrap_or(""))?;
+    let mut filters = Vec::new();
+    for f in pieces {
+        let (fname, arg) = match f.split_once(':') {
+            Some((n, a)) => (n.trim(), Some(a)),
+            None => (f.trim(), None),
+        };
+        let filter = match (fname, arg) {
+            ("slug", None) => Filter::Slug,
+            ("fmt", Some(a)) if matches!(name.as_str(), "date" | "time" | "datetime_local") => {
+                // chrono panics on a bad specifier when formatting, so refuse it here.
+                if StrftimeItems::new(a).any(|i| matches!(i, Item::Error)) {
+                    return Err(format!("bad date format '{a}'"));
+                }
+                Filter::Fmt(a.to_string())
+            }
+            ("fmt", _) => {
+                return Err(format!(
+                    "'fmt:<format>' needs date, time or datetime_local, not {name}"
+                ));
+            }
+            ("truncate", Some(a)) => Filter::Truncate(
+                a.trim()
+                    .parse()
+                    .map_err(|_| format!("'truncate:{a}' needs a number"))?,
+            ),
+            ("hms", None) if name == "duration" => Filter::Hms,
+            ("hms", _) => return Err(format!("'hms' needs duration, not {name}")),
+            _ => {
+                return Err(format!(
+                    "unknown filter '{f}' (slug, fmt:<format>, truncate:<n>, hms)"
+                ));
+            }
+        };
+        filters.push(filter);
+    }
+    Ok(Var { name, filters })
+}
+
+fn render_parts(parts: &[Part], vars: &Vars, out: &mut String) {
+    for part in parts {
+        match part {
+            Part::Lit(s) => out.push_str(s),
+            Part::Var(v) => out.push_str(&render_var(v, vars)),
+            Part::Choice {
+                test,
+                then,
+                otherwise,
+            } => match (vars.get(test), otherwise) {
+                (Some(_), _) => out.push_str(&render_var(then, vars)),
+                (None, Some(v)) => out.push_str(&render_var(v, vars)),
+                (None, None) => {}
+            },
+            Part::Block { test, body } => {
+                if vars.get(test).is_some() {
+                    render_parts(body, vars, out);
+                }
+            }
+        }
+    }
+}
+
+fn render_var(var: &Var, vars: &Vars) -> String {
+    let mut value = vars.get(&var.name).unwrap_or_default();
+    for f in &var.filters {
+        value = match f {
+            Filter::Slug => slug(&value),
+            // Checked at parse time that the variable is date, time or datetime_local.
+            Filter::Fmt(fmt) if var.name == "datetime_local" => {
+                vars.local_start(fmt).unwrap_or(value)
+            }
+            Filter::Fmt(fmt) => vars
+                .start
+                .map(|t| t.format(fmt).to_string())
+                .unwrap_or(value),
+            Filter::Truncate(n) => value.chars().take(*n).collect::<String>().trim().into(),
+            Filter::Hms => vars.duration.map(hms).unwrap_or(value),
+        };
+    }
+    value
+}
+
+/// Persona name of the Steam account whose `userdata/<id>` folder holds `dir`, from that
+/// account's `config/localconfig.vdf`.
+pub fn persona_name(dir: &Path) -> Option<String> {
+    let account = dir
+        .ancestors()
+        .find(|a| a.parent().and_then(Path::file_name) == Some("userdata".as_ref()))?;
+    let text = fs::read_to_string(account.join("config").join("localconfig.vdf")).ok()?;
+    let re = Regex::new(r#""PersonaName"\s+"([^"]*)""#).unwrap();
+    re.captures(&text)
+        .map(|c| c[1].trim().to_string())
+        .filter(|n| !n.is_empty())
+}
+
+/// `1h02m03s`, or `2m03s` under an hour.
+fn hms(seconds: f64) -> String {
+    let s = seconds.round() as u64;
+    if s >= 3600 {
+        format!("{}h{:02}m{:02}s", s / 3600, s / 60 % 60, s % 60)
+    } else {
+        format!("{}m{:02}s", s / 60, s % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn vars() -> Vars {
+        Vars {
+            game: "Baldur's Gate 3: Act I".into(),
+            appid: 1086940,
+            date: "20250314".into(),
+            time: "213005".into(),
+            title: None,
+            host: "deck".into(),
+            start: Some(Utc.with_ymd_and_hms(2025, 3, 14, 21, 30, 5).unwrap()),
+            duration: Some(3723.4),
+            user: None,
+            seq: Some(2),
+            index: Some(7),
+        }
+    }
+
+    fn render(template: &str, vars: &Vars) -> String {
+        let path = Template::parse(template).unwrap().render(vars, "mp4");
+        path.to_string_lossy().replace('\\', "/")
+    }
+
+    #[test]
+    fn filters() {
+        let v = vars();
+        assert_eq!(render("{game|slug}", &v), "baldurs-gate-3-act-i.mp4");
+        assert_eq!(render("{date|fmt:%Y/%m}/{time}", &v), "2025/03/213005.mp4");
+        assert_eq!(render("{time|fmt:%H.%M}", &v), "21.30.mp4");
+        assert_eq!(render("{game|truncate:12}", &v), "Baldur's Gat.mp4");
+        assert_eq!(render("{game|slug|truncate:7}", &v), "baldurs.mp4");
+        assert_eq!(render("{duration|hms}", &v), "1h02m03s.mp4");
+        let short = Vars {
+            duration: Some(123.0),
+            ..vars()
+        };
+        assert_eq!(render("{duration|hms}-{duration}", &short), "2m03s-123.mp4");
+        assert_eq!(render("{appid}-{seq}-{index}", &v), "1086940-002-007.mp4");
+    }
+
+    #[test]
+    fn choices_and_blocks() {
+        let v = vars();
+        assert_eq!(
+            render("{title?title:game|slug}", &v),
+            "baldurs-gate-3-act-i.mp4"
+        );
+        assert_eq!(render("x{title?title}", &v), "x.mp4");
+        assert_eq!(render("{appid}{?user} by {user}{/user}", &v), "1086940.mp4");
+        let named = Vars {
+            title: Some("Ace".into()),
+            user: Some("sam".into()),
+            ..vars()
+        };
+        assert_eq!(render("{title?title|truncate:2:game}", &named), "Ac.mp4");
+        assert_eq!(
+            render("{appid}{?user} by {user}{/user}", &named),
+            "1086940 by sam.mp4"
+        );
+    }
+
+    #[test]
+    fn literal_braces_extension_and_empty_folders() {
+        let v = vars();
+        assert_eq!(render("{{{appid}}}", &v), "{1086940}.mp4");
+        assert_eq!(render("{appid}.MP4", &v), "1086940.MP4");
+        // A folder that is empty once rendered is dropped.
+        assert_eq!(render("{title?title}/{appid}", &v), "1086940.mp4");
+    }
+
+    #[test]
+    fn bad_templates_are_errors() {
+        let err = |t: &str| Template::parse(t).unwrap_err();
+        assert_eq!(err("{game"), "unclosed '{game'");
+        assert_eq!(
+            err("game}"),
+            "unmatched '}' (write '}}' for a literal brace)"
+        );
+        assert!(err("{gmae}").starts_with("unknown variable '{gmae}'"));
+        assert!(err("{?user}x").contains("never closed"));
+        assert!(err("{?user}x{/title}").contains("closes '{?user}'"));
+        assert!(err("x{/user}").contains("without '{?user}'"));
+        assert!(err("{game|upper}").starts_with("unknown filter 'upper'"));
+        assert!(err("{game|fmt:%Y}").contains("needs date, time or datetime_local"));
+        assert!(err("{game|hms}").contains("'hms' needs duration"));
+        assert!(err("{game|truncate:x}").contains("needs a number"));
+    }
+
+    #[test]
+    fn bad_strftime_is_refused_up_front() {
+        for spec in ["%Q", "%", "%Y-%"] {
+            let t = format!("{{date|fmt:{spec}}}");
+            assert_eq!(
+                Template::parse(&t).unwrap_err(),
+                format!("bad date format '{spec}'")
+            );
+        }
+        // Valid ones render without touching chrono's panicking path.
+        assert_eq!(render("{date|fmt:%%%Y}", &vars()), "%2025.mp4");
+    }
+}
@@ -0,0 +1,93 @@
+//! `--max-temp`: pause between clips while the CPU is too hot.
+
+use crate::report::say;
+use std::{thread, time::Duration};
+
+/// Resume only once the CPU is this far below the limit, so we don't flap around it.
+const HYSTERESIS_C: f32 = 5.0;
+const POLL: Duration = Duration::from_secs(10);
+
+/// Hottest CPU sensor reading in °C, where the platform exposes one.
+pub fn cpu_temp_celsius() -> Option<f32> {
+    #[cfg(target_os = "linux")]
+    {
+        linux_cpu_temp()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn linux_cpu_temp() -> Option<f32> {
+    use std::fs;
+
+    const CPU_SENSORS: [&str; 5] = ["coretemp", "k10temp", "zenpower", "cpu_thermal", "acpitz"];
+    let millideg = |p: std::path::PathBuf| -> Option<f32> {
+        let v: f32 = fs::read_to_string(p).ok()?.trim().parse().ok()?;
+        Some(v / 1000.0)
+    };
+
+    let mut hottest: Option<f32> = None;
+    for ent in fs::read_dir("/sys/class/hwmon").ok()?.flatten() {
+        let dir = ent.path();
+        let name = fs::read_to_string(dir.join("name")).unwrap_or_default();
+        if !CPU_SENSORS.contains(&name.trim()) {
+            continue;
+        }
+        for f in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let fname = f.file_name();
+            let fname = fname.to_string_lossy();
+            if fname.starts_with("temp")
+                && fname.ends_with("_input")
+                && let Some(t) = millideg(f.path())
+            {
+                hottest = Some(hottest.map_or(t, |h| h.max(t)));
+            }
+        }
+    }
+    if hottest.is_some() {
+        return hottest;
+    }
+    // No hwmon driver: fall back to thermal zones that describe the CPU package.
+    for ent in fs::read_dir("/sys/class/thermal").ok()?.flatten() {
+        let dir = ent.path();
+        let kind = fs::read_to_string(dir.join("type")).unwrap_or_default();
+        let kind = kind.trim();
+        if (kind == "x86_pkg_temp" || kind.contains("cpu"))
+            && let Some(t) = millideg(dir.join("temp"))
+        {
+            hottest = Some(hottest.map_or(t, |h| h.max(t)));
+        }
+    }
+    hottest
+}
+
+/// Block while the CPU is above `max_c`, until it has cooled below `max_c - 5°C`.
+/// Returns immediately when no temperature can be read.
+pub fn cool_down(max_c: f32) {
+    let Some(t) = cpu_temp_celsius() else {
+        return;
+    };
+    if t <= max_c {
+        return;
+    }
+    say!(
+        "[thermal] CPU at {:.0}°C (limit {:.0}°C), pausing until it drops below {:.0}°C",
+        t,
+        max_c,
+        max_c - HYSTERESIS_C
+    );
+    loop {
+        thread::sleep(POLL);
+        match cpu_temp_celsius() {
+            Some(t) if t > max_c - HYSTERESIS_C => continue,
+            Some(t) => {
+                say!("[thermal] cooled to {:.0}°C, resuming", t);
+                return;
+            }
+            None => return,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Markers from Steam's timeline data (game events, achievements, user bookmarks), as
+//! offsets into the clip they fall in.
+//!
+//! Timelines live next to the recordings as `timelines/timeline_<appid>_<date>_<time>.json`
+//! (in `gamerecordings/` for background recordings, in the `clip_*` folder for saved clips).
+//! The format is undocumented, so any object with a millisecond `time` and a title counts.
+
+use crate::json::{self, Value};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use steamclipconverter::ClipDir;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Marker {
+    /// Seconds from the start of the clip.
+    pub offset: f64,
+    pub title: String,
+}
+
+/// Timeline files that may cover `clip`, with the time each one starts at.
+fn timeline_files(clip: &ClipDir) -> Vec<(PathBuf, DateTime<Utc>)> {
+    let re = Regex::new(r"^timeline_(\d+)_(\d{8})_(\d{6})\.json$").unwrap();
+    // fg_* -> video -> (gamerecordings | clip_*)
+    let Some(root) = clip.dir.parent().and_then(Path::parent) else {
+        return Vec::new();
+    };
+    let mut out: Vec<_> = fs::read_dir(root.join("timelines"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            let caps = re.captures(&name)?;
+            if caps[1].parse::<u32>().ok() != Some(clip.appid) {
+                return None;
+            }
+            // Same convention as the folder names (see timestamp::folder_time).
+            let naive =
+                NaiveDateTime::parse_from_str(&format!("{}{}", &caps[2], &caps[3]), "%Y%m%d%H%M%S")
+                    .ok()?;
+            Some((e.path(), Utc.from_utc_datetime(&naive)))
+        })
+        .collect();
+    out.sort();
+    out
+}
+
+fn millis(v: &Value) -> Option<f64> {
+    match v {
+        Value::Num(n) => Some(*n),
+        Value::Str(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Every `(ms, title)` entry in a timeline document, at any depth.
+fn entries(v: &Value, out: &mut Vec<(f64, String)>) {
+    match v {
+        Value::Obj(fields) => {
+            let time = ["time", "time_ms", "offset_ms"]
+                .iter()
+                .find_map(|k| v.get(k).and_then(millis));
+            let title = ["title", "name", "description"]
+                .iter()
+                .map(|k| v.str_field(k))
+                .find(|s| !s.is_empty());
+            if let (Some(t), Some(title)) = (time, title) {
+                out.push((t, title.to_string()));
+            }
+            for (_, val) in fields {
+                entries(val, out);
+            }
+        }
+        Value::Arr(items) => items.iter().for_each(|i| entries(i, out)),
+        _ => {}
+    }
+}
+
+/// Markers inside `clip` (within `duration` seconds of its start when known), in time order.
+pub fn markers(clip: &ClipDir, duration: Option<f64>) -> Vec<Marker> {
+    let Some(clip_start) = crate::timestamp::folder_time(clip) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    for (path, tl_start) in timeline_files(clip) {
+        let Some(doc) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|t| json::parse(&t).ok())
+        else {
+            continue;
+        };
+        let mut found = Vec::new();
+        entries(&doc, &mut found);
+        let shift = (tl_start - clip_start).num_milliseconds() as f64 / 1000.0;
+        for (ms, title) in found {
+            let offset = shift + ms / 1000.0;
+            if offset >= 0.0 && duration.is_none_or(|d| offset <= d) {
+                out.push(Marker { offset, title });
+            }
+        }
+    }
+    out.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+    out.dedup();
+    out
+}
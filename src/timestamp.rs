@@ -0,0 +1,117 @@
+//! `--timestamp-source`: where a clip's record start time comes from.
+//!
+//! The folder name is normally right, but clock skew on the recording machine or folders
+//! copied around can make it obviously wrong; the MPD's `availabilityStartTime` and the
+//! chunk files' mtimes are independent witnesses.
+
+use crate::report::say;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use clap::ValueEnum;
+use regex::Regex;
+use std::{fs, time::SystemTime};
+use steamclipconverter::ClipDir;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimestampSource {
+    /// `fg_<appid>_<YYYYMMDD>_<HHMMSS>` (UTC).
+    Folder,
+    /// `availabilityStartTime` in session.mpd.
+    Mpd,
+    /// Oldest chunk file's modification time.
+    ChunkMtime,
+    /// The folder name, unless it is implausible; then the MPD, then chunk mtimes.
+    #[default]
+    Auto,
+}
+
+/// Clip folder names carry the record start in UTC.
+pub fn folder_time(clip: &ClipDir) -> Option<DateTime<Utc>> {
+    let s = format!("{}{}", clip.date, clip.time);
+    let naive = NaiveDateTime::parse_from_str(&s, "%Y%m%d%H%M%S").ok()?;
+    Some(Utc.from_utc_datetime(&naive))
+}
+
+/// `--since`: a local date (from its midnight) or date and time.
+pub fn parse_since(s: &str) -> Result<DateTime<Utc>, String> {
+    parse_bound(s, false)
+}
+
+/// `--until`: a local date (to the end of that day) or date and time, inclusive.
+pub fn parse_until(s: &str) -> Result<DateTime<Utc>, String> {
+    parse_bound(s, true)
+}
+
+fn parse_bound(s: &str, end_of_day: bool) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    let naive = match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(d) if end_of_day => d.and_hms_opt(23, 59, 59),
+        Ok(d) => d.and_hms_opt(0, 0, 0),
+        Err(_) => [
+            "%Y-%m-%d %H:%M:%S",
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%dT%H:%M:%S",
+            "%Y-%m-%dT%H:%M",
+        ]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok()),
+    }
+    .ok_or_else(|| format!("expected YYYY-MM-DD or \"YYYY-MM-DD HH:MM[:SS]\", got '{s}'"))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| format!("'{s}' doesn't exist in the local time zone"))
+}
+
+fn mpd_time(clip: &ClipDir) -> Option<DateTime<Utc>> {
+    let mpd = fs::read_to_string(clip.dir.join("session.mpd")).ok()?;
+    let re = Regex::new(r#"availabilityStartTime\s*=\s*"([^"]+)""#).unwrap();
+    let raw = re.captures(&mpd)?.get(1)?.as_str().trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(raw) {
+        return Some(t.with_timezone(&Utc));
+    }
+    // xs:dateTime without a zone; DASH says to read it as UTC.
+    let naive = NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    Some(Utc.from_utc_datetime(&naive))
+}
+
+fn chunk_mtime(clip: &ClipDir) -> Option<DateTime<Utc>> {
+    let oldest: Option<SystemTime> = fs::read_dir(&clip.dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("chunk-"))
+        .filter_map(|e| e.metadata().ok()?.modified().ok())
+        .min();
+    oldest.map(DateTime::<Utc>::from)
+}
+
+/// Steam game recording didn't exist before this, and clips can't come from the future.
+fn plausible(t: &DateTime<Utc>) -> bool {
+    let earliest = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+    *t >= earliest && *t <= Utc::now() + chrono::Duration::days(1)
+}
+
+/// The clip's record start according to `source`.
+pub fn record_start(clip: &ClipDir, source: TimestampSource) -> Option<DateTime<Utc>> {
+    match source {
+        TimestampSource::Folder => folder_time(clip),
+        TimestampSource::Mpd => mpd_time(clip),
+        TimestampSource::ChunkMtime => chunk_mtime(clip),
+        TimestampSource::Auto => {
+            let folder = folder_time(clip);
+            if folder.as_ref().is_some_and(plausible) {
+                return folder;
+            }
+            let fallback = [("mpd", mpd_time(clip)), ("chunk-mtime", chunk_mtime(clip))]
+                .into_iter()
+                .find(|(_, t)| t.as_ref().is_some_and(plausible));
+            match fallback {
+                Some((name, t)) => {
+                    say!("[time] folder name time looks wrong; using {}", name);
+                    t
+                }
+                None => folder,
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! A small TOML reader for the files users write by hand (`convert.toml`, ...).
+//!
+//! Supports tables (`[a]`, `[a.b]`), dotted and quoted keys, basic and literal strings,
+//! integers, floats, booleans, arrays, inline tables and arrays of tables (`[[a]]`). Dates
+//! and multi-line strings are not supported. Documents are returned as `json::Value` objects.
+
+use crate::json::Value;
+
+/// Parse a TOML document into a `Value::Obj`.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut p = Parser {
+        s: text.as_bytes(),
+        i: 0,
+        line: 1,
+    };
+    let mut root = Value::Obj(Vec::new());
+    let mut table: Vec<String> = Vec::new();
+    loop {
+        p.skip_ws_comments_newlines();
+        if p.i >= p.s.len() {
+            return Ok(root);
+        }
+        if p.s[p.i] == b'[' {
+            p.i += 1;
+            let array = p.eat(b'[');
+            table = p.key_path()?;
+            p.ws();
+            if !p.eat(b']') || array && !p.eat(b']') {
+                return p.err(if array {
+                    "expected ']]'"
+                } else {
+                    "expected ']'"
+                });
+            }
+            if array {
+                push_table(&mut root, &table).map_err(|e| p.msg(&e))?;
+            } else {
+                // Make sure the table exists even when empty.
+                insert(&mut root, &table, None).map_err(|e| p.msg(&e))?;
+            }
+        } else {
+            let mut key = table.clone();
+            key.extend(p.key_path()?);
+            p.ws();
+            if !p.eat(b'=') {
+                return p.err("expected '='");
+            }
+            p.ws();
+            let v = p.value()?;
+            insert(&mut root, &key, Some(v)).map_err(|e| p.msg(&e))?;
+        }
+        p.ws();
+        p.skip_comment();
+        match p.peek() {
+            None | Some(b'\n') => {}
+            Some(b'\r') if p.s.get(p.i + 1) == Some(&b'\n') => {}
+            _ => return p.err("expected end of line"),
+        }
+    }
+}
+
+/// Set `path` in `root` to `value`, or just create the table at `path` when `value` is None.
+fn insert(root: &mut Value, path: &[String], value: Option<Value>) -> Result<(), String> {
+    let mut cur = root;
+    for (n, key) in path.iter().enumerate() {
+        let Value::Obj(fields) = cur else {
+            return Err(format!("'{}' is not a table", path[..n].join(".")));
+        };
+        let last = n + 1 == path.len();
+        let pos = fields.iter().position(|(k, _)| k == key);
+        let idx = match (pos, last, &value) {
+            (Some(_), true, Some(_)) => return Err(format!("duplicate key '{}'", path.join("."))),
+            (Some(i), _, _) => i,
+            (None, true, Some(v)) => {
+                fields.push((key.clone(), v.clone()));
+                return Ok(());
+            }
+            (None, _, _) => {
+                fields.push((key.clone(), Value::Obj(Vec::new())));
+                fields.len() - 1
+            }
+        };
+        cur = &mut fields[idx].1;
+        // Keys below an array of tables go into its latest table.
+        if !last && let Value::Arr(items) = cur {
+            match items.last_mut() {
+                Some(t) => cur = t,
+                None => return Err(format!("'{}' is not a table", path[..=n].join("."))),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `[[path]]`: append a new table to the array at `path`, creating the array if needed.
+fn push_table(root: &mut Value, path: &[String]) -> Result<(), String> {
+    let (last, parent) = path.split_last().ok_or("empty table name")?;
+    insert(root, parent, None)?;
+    let mut cur = root;
+    for key in parent {
+        let Value::Obj(fields) = cur else {
+            unreachable!("insert created the parent tables")
+        };
+        let i = fields.iter().position(|(k, _)| k == key).unwrap();
+        cur = &mut fields[i].1;
+        if let Value::Arr(items) = cur {
+            cur = items.last_mut().ok_or("empty array")?;
+        }
+    }
+    let Value::Obj(fields) = cur else {
+        return Err(format!("'{}' is not a table", parent.join(".")));
+    };
+    match fields.iter_mut().find(|(k, _)| k == last) {
+        Some((_, Value::Arr(items))) => items.push(Value::Obj(Vec::new())),
+        Some(_) => return Err(format!("'{}' is not an array of tables", path.join("."))),
+        None => fields.push((last.clone(), Value::Arr(vec![Value::Obj(Vec::new())]))),
+    }
+    Ok(())
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.i).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.i += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn msg(&self, what: &str) -> String {
+        format!("line {}: {what}", self.line)
+    }
+
+    fn err<T>(&self, what: &str) -> Result<T, String> {
+        Err(self.msg(what))
+    }
+
+    fn ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.i += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some(b'#') {
+            while !matches!(self.peek(), None | Some(b'\n')) {
+                self.i += 1;
+            }
+        }
+    }
+
+    fn skip_ws_comments_newlines(&mut self) {
+        loop {
+            self.ws();
+            self.skip_comment();
+            match self.peek() {
+                Some(b'\n') => {
+                    self.line += 1;
+                    self.i += 1;
+                }
+                Some(b'\r') => self.i += 1,
+                _ => return,
+            }
+        }
+    }
+
+    fn key_path(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            self.ws();
+            let part = match self.peek() {
+                Some(b'"') => self.basic_string()?,
+                Some(b'\'') => self.literal_string()?,
+                _ => {
+                    let start = self.i;
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_' || c == b'-')
+                    {
+                        self.i += 1;
+                    }
+                    if start == self.i {
+                        return self.err("expected a key");
+                    }
+                    String::from_utf8_lossy(&self.s[start..self.i]).into_owned()
+                }
+            };
+            parts.push(part);
+            self.ws();
+            if !self.eat(b'.') {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some(b'"') => self.basic_string().map(Value::Str),
+            Some(b'\'') => self.literal_string().map(Value::Str),
+            Some(b'[') => self.array(),
+            Some(b'{') => self.inline_table(),
+            Some(b't') if self.s[self.i..].starts_with(b"true") => {
+                self.i += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(b'f') if self.s[self.i..].starts_with(b"false") => {
+                self.i += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(c) if c == b'-' || c == b'+' || c.is_ascii_digit() => self.number(),
+            _ => self.err("expected a value"),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.i += 1;
+        let mut out = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => return self.err("unterminated string"),
+                Some(b'"') => {
+                    self.i += 1;
+                    return String::from_utf8(out).or_else(|_| self.err("invalid UTF-8"));
+                }
+                Some(b'\\') => {
+                    self.i += 1;
+                    let e = self.peek();
+                    self.i += 1;
+                    match e {
+                        Some(b'"') => out.push(b'"'),
+                        Some(b'\\') => out.push(b'\\'),
+                        Some(b'n') => out.push(b'\n'),
+                        Some(b't') => out.push(b'\t'),
+                        Some(b'r') => out.push(b'\r'),
+                        Some(b'u') => {
+                            let hex = self.s.get(self.i..self.i + 4).unwrap_or_default();
+                            let c = std::str::from_utf8(hex)
+                                .ok()
+                                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                                .and_then(char::from_u32);
+                            let Some(c) = c else {
+                                return self.err("bad \\u escape");
+                            };
+                            self.i += 4;
+                            out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        _ => return self.err("bad escape"),
+                    }
+                }
+                Some(c) => {
+                    out.push(c);
+                    self.i += 1;
+                }
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.i += 1;
+        let start = self.i;
+        while !matches!(self.peek(), None | Some(b'\n' | b'\'')) {
+            self.i += 1;
+        }
+        if !self.eat(b'\'') {
+            return self.err("unterminated string");
+        }
+        String::from_utf8(self.s[start..self.i - 1].to_vec()).or_else(|_| self.err("invalid UTF-8"))
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.i;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || matches!(c, b'-' | b'+' | b'.' | b'_'))
+        {
+            self.i += 1;
+        }
+        let txt: String = String::from_utf8_lossy(&self.s[start..self.i]).replace('_', "");
+        txt.parse::<f64>()
+            .map(Value::Num)
+            .or_else(|_| self.err(&format!("bad number '{txt}'")))
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.i += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws_comments_newlines();
+            if self.eat(b']') {
+                return Ok(Value::Arr(items));
+            }
+            items.push(self.value()?);
+            self.skip_ws_comments_newlines();
+            if self.eat(b',') {
+                continue;
+            }
+            if self.eat(b']') {
+                return Ok(Value::Arr(items));
+            }
+            return self.err("expected ',' or ']'");
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.i += 1;
+        let mut t = Value::Obj(Vec::new());
+        self.ws();
+        if self.eat(b'}') {
+            return Ok(t);
+        }
+        loop {
+            let key = self.key_path()?;
+            self.ws();
+            if !self.eat(b'=') {
+                return self.err("expected '='");
+            }
+            self.ws();
+            let v = self.value()?;
+            insert(&mut t, &key, Some(v)).map_err(|e| self.msg(&e))?;
+            self.ws();
+            if self.eat(b',') {
+                continue;
+            }
+            if self.eat(b'}') {
+                return Ok(t);
+            }
+            return self.err("expected ',' or '}'");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obj<const N: usize>(fields: [(&str, Value); N]) -> Value {
+        Value::Obj(fields.map(|(k, v)| (k.to_string(), v)).to_vec())
+    }
+
+    fn s(v: &str) -> Value {
+        Value::Str(v.into())
+    }
+
+    #[test]
+    fn keys_after_a_table_header_belong_to_it() {
+        let doc = parse("title = \"x\"\n[trim]\nstart = 5\nvisibility = \"private\"\n").unwrap();
+        assert_eq!(
+            doc,
+            obj([
+                ("title", s("x")),
+                (
+                    "trim",
+                    obj([("start", Value::Num(5.0)), ("visibility", s("private"))])
+                ),
+            ])
+        );
+        assert_eq!(doc.get("visibility"), None);
+    }
+
+    #[test]
+    fn nested_tables_and_arrays_of_tables() {
+        let doc = parse("[a.b]\nc = 1\n[[step]]\ntype = \"x\"\n[[step]]\ntype = \"y\"\n").unwrap();
+        assert_eq!(
+            doc,
+            obj([
+                ("a", obj([("b", obj([("c", Value::Num(1.0))]))])),
+                (
+                    "step",
+                    Value::Arr(vec![obj([("type", s("x"))]), obj([("type", s("y"))])])
+                ),
+            ])
+        );
+        // An empty table still exists.
+        assert_eq!(parse("[empty]").unwrap(), obj([("empty", obj([]))]));
+    }
+
+    #[test]
+    fn dotted_and_quoted_keys() {
+        let doc = parse("a.b = 1\n\"a\".'c d' = 2\nx = { y.z = true }\n").unwrap();
+        assert_eq!(
+            doc,
+            obj([
+                ("a", obj([("b", Value::Num(1.0)), ("c d", Value::Num(2.0))])),
+                ("x", obj([("y", obj([("z", Value::Bool(true))]))])),
+            ])
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        let doc = parse(r#"a = "q\"b\\n\n\t\u00e9" "#).unwrap();
+        assert_eq!(doc.get("a"), Some(&s("q\"b\\n\n\té")));
+        // Literal strings take backslashes as they are.
+        assert_eq!(
+            parse(r"a = 'C:\dir'").unwrap().get("a"),
+            Some(&s(r"C:\dir"))
+        );
+        assert!(parse(r#"a = "\x""#).unwrap_err().contains("bad escape"));
+        assert!(
+            parse(r#"a = "\u12""#)
+                .unwrap_err()
+                .contains("bad \\u escape")
+        );
+        assert!(
+            parse("a = \"open\nb = 1")
+                .unwrap_err()
+                .contains("unterminated string")
+        );
+    }
+
+    #[test]
+    fn comments_after_values_and_headers() {
+        let doc = parse(
+            "# top\na = \"#not a comment\" # comment\n[t] # header comment\nb = [1, # one\n 2]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            doc,
+            obj([
+                ("a", s("#not a comment")),
+                (
+                    "t",
+                    obj([("b", Value::Arr(vec![Value::Num(1.0), Value::Num(2.0)]))])
+                ),
+            ])
+        );
+        assert_eq!(
+            parse("a = 1 2").unwrap_err(),
+            "line 1: expected end of line"
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        assert_eq!(
+            parse("a = 1\nb = 2\na = 3").unwrap_err(),
+            "line 3: duplicate key 'a'"
+        );
+        assert_eq!(
+            parse("[t]\nx = 1\n[u]\n[t]\nx = 2").unwrap_err(),
+            "line 5: duplicate key 't.x'"
+        );
+        assert_eq!(
+            parse("a = 1\na.b = 2").unwrap_err(),
+            "line 2: 'a' is not a table"
+        );
+    }
+
+    #[test]
+    fn numbers_and_bad_values() {
+        let doc = parse("a = -1_000\nb = 2.5\nc = +3").unwrap();
+        assert_eq!(doc.get("a"), Some(&Value::Num(-1000.0)));
+        assert_eq!(doc.get("b"), Some(&Value::Num(2.5)));
+        assert_eq!(doc.get("c"), Some(&Value::Num(3.0)));
+        assert!(parse("a = 1.2.3").unwrap_err().contains("bad number"));
+        assert!(parse("a = yes").unwrap_err().contains("expected a value"));
+        assert!(parse("[a").unwrap_err().contains("expected ']'"));
+    }
+}
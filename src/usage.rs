@@ -0,0 +1,139 @@
+//! CPU time used by a child process, read when it is waited for: `wait4` on Unix,
+//! `GetProcessTimes` on Windows. Each child's own figure, so clips converting side by side
+//! don't count each other's work.
+
+use std::{
+    io,
+    process::{Child, ExitStatus},
+    time::Duration,
+};
+
+/// Wait for `child` to exit, returning its status and the CPU time (user + system) it used
+/// where the platform can tell.
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Duration>)> {
+    imp::wait(child)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{
+        ffi::{c_int, c_long},
+        io,
+        os::unix::process::ExitStatusExt,
+        process::{Child, ExitStatus},
+        time::Duration,
+    };
+
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: c_long,
+        #[cfg(target_vendor = "apple")]
+        tv_usec: i32,
+        #[cfg(not(target_vendor = "apple"))]
+        tv_usec: c_long,
+    }
+
+    #[repr(C)]
+    struct Rusage {
+        ru_utime: Timeval,
+        ru_stime: Timeval,
+        /// maxrss .. nivcsw, not needed here.
+        _rest: [c_long; 14],
+    }
+
+    unsafe extern "C" {
+        fn wait4(pid: c_int, status: *mut c_int, options: c_int, rusage: *mut Rusage) -> c_int;
+    }
+
+    fn duration(t: &Timeval) -> Duration {
+        Duration::from_secs(t.tv_sec.max(0) as u64) + Duration::from_micros(t.tv_usec.max(0) as u64)
+    }
+
+    pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Duration>)> {
+        let mut status: c_int = 0;
+        // SAFETY: an all-zero rusage is valid, and wait4 only writes into the two out-params.
+        let mut usage: Rusage = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: both pointers are to live locals of the right types.
+            let pid = unsafe { wait4(child.id() as c_int, &mut status, 0, &mut usage) };
+            if pid >= 0 {
+                break;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                // E.g. already reaped: the status is still known to Child, the time isn't.
+                return child.wait().map(|s| (s, None));
+            }
+        }
+        Ok((
+            ExitStatus::from_raw(status),
+            Some(duration(&usage.ru_utime) + duration(&usage.ru_stime)),
+        ))
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{
+        ffi::c_void,
+        io,
+        os::windows::io::AsRawHandle,
+        process::{Child, ExitStatus},
+        time::Duration,
+    };
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetProcessTimes(
+            process: *mut c_void,
+            creation: *mut FileTime,
+            exit: *mut FileTime,
+            kernel: *mut FileTime,
+            user: *mut FileTime,
+        ) -> i32;
+    }
+
+    /// FILETIMEs count 100 ns.
+    fn duration(t: &FileTime) -> Duration {
+        Duration::from_nanos(((t.high as u64) << 32 | t.low as u64) * 100)
+    }
+
+    pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Duration>)> {
+        let status = child.wait()?;
+        let (mut creation, mut exit, mut kernel, mut user) = Default::default();
+        // SAFETY: the handle stays open until `child` is dropped; the rest are live locals.
+        let ok = unsafe {
+            GetProcessTimes(
+                child.as_raw_handle(),
+                &mut creation,
+                &mut exit,
+                &mut kernel,
+                &mut user,
+            )
+        };
+        Ok((
+            status,
+            (ok != 0).then(|| duration(&kernel) + duration(&user)),
+        ))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::{
+        io,
+        process::{Child, ExitStatus},
+        time::Duration,
+    };
+
+    pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Duration>)> {
+        child.wait().map(|s| (s, None))
+    }
+}
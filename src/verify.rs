@@ -0,0 +1,171 @@
+//! `verify` subcommand: check converted outputs for truncation and bit-rot.
+
+use crate::{catalog::Catalog, sha256::file_sha256};
+use clap::{ArgAction, Args};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use steamclipconverter::{ffmpeg::file_arg, probe::probe_duration};
+
+/// Extensions treated as converted outputs when walking the directory.
+const VIDEO_EXTS: &[&str] = &["mp4", "mkv", "webm", "mov"];
+
+/// `--encrypt`ed outputs: only their checksums can be checked.
+const ENCRYPTED_EXT: &str = "age";
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Directory of converted files to check (searched recursively).
+    dir: PathBuf,
+
+    /// Catalog to compare checksums against (defaults to the user catalog).
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+
+    /// Skip the ffmpeg decode checks and only compare sizes/checksums.
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_decode: bool,
+}
+
+pub fn run(args: VerifyArgs) -> i32 {
+    if !args.dir.is_dir() {
+        eprintln!("ERROR: not a directory: {}", args.dir.display());
+        return 2;
+    }
+    let catalog = args
+        .catalog
+        .clone()
+        .or_else(Catalog::default_path)
+        .and_then(|p| match Catalog::open(&p) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                eprintln!("[warn] cannot read catalog {}: {}", p.display(), e);
+                None
+            }
+        });
+
+    let mut files = find_videos(&args.dir);
+    files.sort();
+    println!(
+        "Verifying {} file(s) under {}",
+        files.len(),
+        args.dir.display()
+    );
+
+    let (mut good, mut bad, mut untracked) = (0, 0, 0);
+    for f in &files {
+        let mut problems = Vec::new();
+
+        match catalog.as_ref().and_then(|c| c.find_output(f)) {
+            Some(entry) => problems.extend(checksum_problem(f, entry.size, &entry.sha256)),
+            None => untracked += 1,
+        }
+        let encrypted = f.extension().is_some_and(|e| e == ENCRYPTED_EXT);
+        if !args.no_decode && !encrypted {
+            problems.extend(decode_problems(f));
+        }
+
+        if problems.is_empty() {
+            good += 1;
+            println!("[ok] {}", f.display());
+        } else {
+            bad += 1;
+            println!("[bad] {}: {}", f.display(), problems.join("; "));
+        }
+    }
+
+    println!(
+        "\n{} ok, {} bad, {} not in catalog (checksum not checked).",
+        good, bad, untracked
+    );
+    if bad > 0 { 1 } else { 0 }
+}
+
+fn find_videos(dir: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        let Ok(rd) = fs::read_dir(&d) else { continue };
+        for ent in rd.flatten() {
+            let p = ent.path();
+            if p.is_dir() {
+                stack.push(p);
+            } else if p.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+                let e = e.to_ascii_lowercase();
+                VIDEO_EXTS.contains(&e.as_str()) || e == ENCRYPTED_EXT
+            }) {
+                out.push(p);
+            }
+        }
+    }
+    out
+}
+
+fn checksum_problem(f: &Path, size: u64, sha256: &str) -> Option<String> {
+    let actual_size = match fs::metadata(f) {
+        Ok(m) => m.len(),
+        Err(e) => return Some(format!("unreadable: {e}")),
+    };
+    if actual_size != size {
+        return Some(format!(
+            "size {} differs from catalog ({}), truncated?",
+            actual_size, size
+        ));
+    }
+    if sha256.is_empty() {
+        return None;
+    }
+    match file_sha256(f) {
+        Ok(h) if h == sha256 => None,
+        Ok(_) => Some("checksum mismatch (bit-rot or modified)".to_string()),
+        Err(e) => Some(format!("read failed: {e}")),
+    }
+}
+
+/// Duration sanity plus a decode of the first and last couple of seconds (first/last GOP).
+fn decode_problems(f: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+    match probe_duration(f) {
+        Ok(d) if d.is_finite() && d > 0.0 => {}
+        Ok(d) => problems.push(format!("implausible duration {d}s")),
+        Err(e) => {
+            problems.push(format!("unreadable container: {e}"));
+            return problems;
+        }
+    }
+    // Input options seek before opening; output options stop after the first seconds.
+    problems.extend(decode_window(f, "start", &[], &["-t", "2"]));
+    problems.extend(decode_window(f, "end", &["-sseof", "-2"], &[]));
+    problems
+}
+
+fn decode_window(
+    f: &Path,
+    what: &str,
+    input_opts: &[&str],
+    output_opts: &[&str],
+) -> Option<String> {
+    let out = Command::new("ffmpeg")
+        .args(["-hide_banner", "-v", "error", "-xerror"])
+        .args(input_opts)
+        .arg("-i")
+        .arg(file_arg(f))
+        .args(output_opts)
+        .args(["-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output();
+    match out {
+        Ok(o) if o.status.success() && o.stderr.is_empty() => None,
+        Ok(o) => Some(format!(
+            "decode errors at {}: {}",
+            what,
+            String::from_utf8_lossy(&o.stderr)
+                .lines()
+                .next()
+                .unwrap_or("ffmpeg failed")
+        )),
+        Err(e) => Some(format!("launching ffmpeg: {e}")),
+    }
+}
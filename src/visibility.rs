@@ -0,0 +1,92 @@
+//! Steam's private/unlisted marking of a clip, carried into our metadata so nothing that
+//! publishes outputs later can make a private recording public by accident.
+
+use crate::{
+    json::{self, Value},
+    toml,
+};
+use std::{fs, path::Path};
+use steamclipconverter::ClipDir;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Unlisted,
+    Private,
+}
+
+impl Visibility {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "public" => Some(Visibility::Public),
+            // Steam's "friends only" is the closest thing to unlisted elsewhere.
+            "unlisted" | "friends" | "friendsonly" | "friends_only" => Some(Visibility::Unlisted),
+            "private" | "hidden" => Some(Visibility::Private),
+            _ => None,
+        }
+    }
+}
+
+/// Look for a `visibility` / `private` marker in a JSON document, at any depth.
+fn find_marker(v: &Value) -> Option<Visibility> {
+    match v {
+        Value::Obj(fields) => {
+            for (k, val) in fields {
+                match (k.as_str(), val) {
+                    ("visibility" | "privacy", Value::Str(s)) => {
+                        if let Some(vis) = Visibility::parse(s) {
+                            return Some(vis);
+                        }
+                    }
+                    ("private" | "is_private" | "hidden", Value::Bool(true)) => {
+                        return Some(Visibility::Private);
+                    }
+                    _ => {}
+                }
+            }
+            fields.iter().find_map(|(_, val)| find_marker(val))
+        }
+        Value::Arr(items) => items.iter().find_map(find_marker),
+        _ => None,
+    }
+}
+
+fn json_files(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut out: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "json") && p.is_file())
+        .collect();
+    out.sort();
+    out
+}
+
+/// What the clip is marked as: `visibility = "..."` in its convert.toml wins, then any
+/// marker in the JSON metadata Steam keeps in the clip_* folder (top level or
+/// `timelines/`). `None` when nothing says either way.
+pub fn detect(clip: &ClipDir) -> Option<Visibility> {
+    if let Ok(txt) = fs::read_to_string(clip.dir.join(crate::overrides::FILE_NAME))
+        && let Ok(doc) = toml::parse(&txt)
+        && let Some(vis) = doc.get("visibility").and_then(Value::as_str)
+    {
+        return Visibility::parse(vis);
+    }
+
+    let clip_root = clip.dir.parent().and_then(Path::parent)?;
+    let mut files = json_files(clip_root);
+    files.extend(json_files(&clip_root.join("timelines")));
+    files.into_iter().find_map(|f| {
+        let txt = fs::read_to_string(f).ok()?;
+        find_marker(&json::parse(&txt).ok()?)
+    })
+}
@@ -0,0 +1,120 @@
+//! `--watch`: keep running and convert clips as Steam finishes writing them.
+//!
+//! The input tree is polled (with the scan snapshot, so unchanged folders aren't listed
+//! again) rather than subscribed to with OS file notifications: Steam's recording folders
+//! are often on other drives or network shares where those are unreliable, and a poll every
+//! few seconds is cheap. A clip is handed to the conversion pipeline once its `session.mpd`
+//! exists and it has settled (`--settle-time`, `--settle-growth`), and only once per run.
+
+use crate::{
+    Cli,
+    batch::Batch,
+    catalog::Catalog,
+    folder_match, game_filter, guard_synced_deletes, order, plan,
+    postprocess::Chain,
+    range_filter,
+    report::say,
+    skip::{self, RunSummary, SkipReason},
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+};
+use steamclipconverter::{find_fg_clip_dirs, settle::SettleRules, snapshot::ScanSnapshot};
+
+/// Convert what is already there, then every clip that appears later, until interrupted.
+pub fn run(
+    cli: &mut Cli,
+    input_dir: &Path,
+    output_dir: PathBuf,
+    catalog: Option<Catalog>,
+    chain: Chain,
+) -> RunSummary {
+    let mut snapshot = if cli.full_rescan {
+        ScanSnapshot::default()
+    } else {
+        ScanSnapshot::load(input_dir)
+    };
+    let mut existing = find_fg_clip_dirs(input_dir, &mut snapshot).unwrap_or_default();
+    existing.retain(|c| cli.kind.includes(c.kind()));
+    guard_synced_deletes(cli, &existing);
+    if !plan::confirm(cli, input_dir, &output_dir, &existing, false) {
+        std::process::exit(2);
+    }
+    say!(
+        "[watch] watching {} for finished clips every {}s (Ctrl-C to stop)",
+        input_dir.display(),
+        cli.watch_interval.as_secs().max(1)
+    );
+
+    let (tx, rx) = mpsc::channel();
+    let rules = SettleRules {
+        settle_time: cli.settle_time,
+        growth_window: cli.settle_growth,
+    };
+    let (wanted, in_range) = (game_filter(cli), range_filter(cli));
+    let (input, interval, clip_order, kind, patterns) = (
+        input_dir.to_path_buf(),
+        cli.watch_interval,
+        cli.order,
+        cli.kind,
+        cli.matches.clone(),
+    );
+    thread::spawn(move || {
+        let mut sent: HashSet<PathBuf> = HashSet::new();
+        let mut waiting: HashSet<PathBuf> = HashSet::new();
+        loop {
+            let mut clips = match find_fg_clip_dirs(&input, &mut snapshot) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("[warn] cannot scan {}: {}", input.display(), e);
+                    Vec::new()
+                }
+            };
+            if let Err(e) = snapshot.save(&input) {
+                eprintln!("[warn] could not save scan snapshot: {}", e);
+            }
+            clips.retain(|c| {
+                !sent.contains(&c.dir)
+                    && kind.includes(c.kind())
+                    && wanted(c)
+                    && in_range(c)
+                    && folder_match::matches(&patterns, c)
+            });
+            order::sort_clips(&mut clips, clip_order);
+            for clip in clips {
+                // A folder without session.mpd yet is still being recorded, like an unsettled
+                // one; anything else (ready, or skipped for good) goes on and the pipeline
+                // reports it.
+                if let Err(SkipReason::InProgress(_) | SkipReason::MissingMpd) =
+                    skip::assess(&clip, &rules)
+                {
+                    if waiting.insert(clip.dir.clone()) {
+                        say!("[watch] {} is being recorded", clip.dir.display());
+                    }
+                    continue;
+                }
+                waiting.remove(&clip.dir);
+                sent.insert(clip.dir.clone());
+                if tx.send(clip).is_err() {
+                    return;
+                }
+            }
+            thread::sleep(interval);
+        }
+    });
+
+    let batch = Batch::new(
+        cli,
+        output_dir,
+        None,
+        catalog,
+        RunSummary::default(),
+        None,
+        None,
+    )
+    .postprocess(chain);
+    batch.watch(rx, &cli.stages)
+}
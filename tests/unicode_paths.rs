@@ -0,0 +1,104 @@
+//! Clip folders and outputs under non-ASCII (and, on Unix, non-UTF-8) paths.
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+use steamclipconverter::{
+    ClipDir,
+    ffmpeg::{RemuxOptions, file_arg, remux_command},
+    find_fg_clip_dirs,
+    snapshot::ScanSnapshot,
+};
+
+/// Fresh scratch directory under the system temp dir.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("scc-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn make_clip(root: &Path, parents: &[&str], name: &str) -> PathBuf {
+    let mut dir = root.to_path_buf();
+    for p in parents {
+        dir.push(p);
+    }
+    dir.push(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("session.mpd"), "<MPD/>").unwrap();
+    dir
+}
+
+#[test]
+fn finds_clips_under_cjk_and_emoji_dirs() {
+    let root = scratch("cjk");
+    let a = make_clip(&root, &["ユーザー", "录像 🎮"], "fg_570_20250828_124021");
+    let b = make_clip(&root, &["Ünïcödé ✨"], "fg_294100_20250101_235959");
+
+    let mut clips = find_fg_clip_dirs(&root, &mut ScanSnapshot::default()).unwrap();
+    clips.sort_by_key(|c| c.appid);
+    assert_eq!(clips.len(), 2);
+    assert_eq!(clips[0].dir, a);
+    assert_eq!(clips[0].appid, 570);
+    assert_eq!(clips[1].dir, b);
+    assert_eq!(clips[1].date, "20250101");
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn clip_dir_from_path_ignores_parent_names() {
+    let clip = ClipDir::from_path(Path::new("/存档/🎬 clips/fg_570_20250828_124021")).unwrap();
+    assert_eq!(clip.appid, 570);
+    assert_eq!(clip.time, "124021");
+    assert!(ClipDir::from_path(Path::new("/存档/fg_录像_20250828_124021")).is_none());
+}
+
+#[test]
+fn output_path_reaches_ffmpeg_unchanged() {
+    let out = Path::new("/tmp/ゲーム 🎮/原神-20250828-124021.mp4");
+    let cmd = remux_command(Path::new("/clips"), out, &RemuxOptions::default());
+    let last = cmd.get_args().last().unwrap();
+    assert_eq!(last, file_arg(out).as_os_str());
+    assert_eq!(last, "file:/tmp/ゲーム 🎮/原神-20250828-124021.mp4");
+}
+
+#[test]
+fn colon_in_output_name_is_not_a_protocol() {
+    let out = Path::new("rtmp:not-a-stream.mp4");
+    assert_eq!(file_arg(out), OsString::from("file:rtmp:not-a-stream.mp4"));
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_paths_do_not_panic() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let root = scratch("latin1");
+    // "caf\xe9" is Latin-1 "café", not valid UTF-8.
+    let bad = OsStr::from_bytes(b"caf\xe9");
+    let clip_dir = root.join(bad).join("fg_570_20250828_124021");
+    if fs::create_dir_all(&clip_dir).is_err() {
+        // Some filesystems (e.g. on macOS) reject non-UTF-8 names outright.
+        let _ = fs::remove_dir_all(&root);
+        return;
+    }
+
+    let clips = find_fg_clip_dirs(&root, &mut ScanSnapshot::default()).unwrap();
+    assert_eq!(clips.len(), 1);
+    assert_eq!(clips[0].dir, clip_dir);
+
+    let out = root.join(bad).join("out.mp4");
+    let cmd = remux_command(&clip_dir, &out, &RemuxOptions::default());
+    assert!(
+        cmd.get_args()
+            .last()
+            .unwrap()
+            .as_bytes()
+            .ends_with(b"caf\xe9/out.mp4")
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}